@@ -0,0 +1,65 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+// Embeds the application icon as icon resource 1 of the executable, which the window
+// class loads for the caption, the taskbar and Alt+Tab. Explorer shows it for the exe
+// too. WINDOWSGUI_ICON points to another .ico than assets/app.ico.
+//
+// Needs rc.exe from the Windows SDK for MSVC targets or windres from binutils for GNU
+// targets. Without them the build goes on with a warning and the generic icon.
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=WINDOWSGUI_ICON");
+
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("windows") {
+        return;
+    }
+
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let icon = env::var_os("WINDOWSGUI_ICON")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| manifest_dir.join("assets").join("app.ico"));
+    println!("cargo:rerun-if-changed={}", icon.display());
+    if !icon.exists() {
+        println!("cargo:warning=No icon at {}", icon.display());
+        return;
+    }
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let script = out_dir.join("app.rc");
+    // Forward slashes need no escaping in the resource script
+    let icon_path = icon.display().to_string().replace('\\', "/");
+    if let Err(e) = fs::write(&script, format!("1 ICON \"{}\"\n", icon_path)) {
+        println!("cargo:warning=Failed to write resource script: {}", e);
+        return;
+    }
+
+    let msvc = env::var("CARGO_CFG_TARGET_ENV").as_deref() == Ok("msvc");
+    let (resource, mut command) = if msvc {
+        let resource = out_dir.join("app.res");
+        let mut command = Command::new("rc.exe");
+        command
+            .arg("/nologo")
+            .arg("/fo")
+            .arg(&resource)
+            .arg(&script);
+        (resource, command)
+    } else {
+        let resource = out_dir.join("app.res.o");
+        let mut command = Command::new("windres");
+        command
+            .arg(&script)
+            .arg("-O")
+            .arg("coff")
+            .arg("-o")
+            .arg(&resource);
+        (resource, command)
+    };
+
+    match command.status() {
+        Ok(status) if status.success() => {
+            println!("cargo:rustc-link-arg-bins={}", resource.display());
+        }
+        Ok(status) => println!("cargo:warning=Resource compiler failed with {}", status),
+        Err(e) => println!("cargo:warning=Failed to run the resource compiler: {}", e),
+    }
+}
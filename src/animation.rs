@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+// How often running animations repaint, ~60 frames per second
+pub(crate) const ANIMATION_FRAME_INTERVAL_MS: u32 = 16;
+
+// A value moving from one end to the other over a fixed duration. Nothing is
+// stored per frame, the value is sampled from the clock whenever we paint.
+#[derive(Clone, Copy)]
+pub(crate) struct Transition {
+    from: f32,
+    to: f32,
+    start: Instant,
+    duration: Duration,
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self::settled(0.0)
+    }
+}
+
+impl Transition {
+    pub(crate) fn settled(value: f32) -> Self {
+        Self {
+            from: value,
+            to: value,
+            start: Instant::now(),
+            duration: Duration::ZERO,
+        }
+    }
+
+    // Starts from wherever the value currently is, so reversing halfway through
+    // doesn't jump
+    pub(crate) fn animate_to(&mut self, to: f32, duration: Duration) {
+        if self.to == to {
+            return;
+        }
+
+        *self = Self {
+            from: self.value(),
+            to,
+            start: Instant::now(),
+            duration,
+        };
+    }
+
+    // Skips to the end, e.g. when animations get throttled
+    pub(crate) fn finish(&mut self) {
+        *self = Self::settled(self.to);
+    }
+
+    // Where the value is heading, or resting
+    pub(crate) fn target(&self) -> f32 {
+        self.to
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.start.elapsed() < self.duration
+    }
+
+    pub(crate) fn value(&self) -> f32 {
+        if !self.is_running() {
+            return self.to;
+        }
+
+        let progress = self.start.elapsed().as_secs_f32() / self.duration.as_secs_f32();
+        // Ease out, fast at first and settling gently
+        let eased = 1.0 - (1.0 - progress) * (1.0 - progress);
+        self.from + (self.to - self.from) * eased
+    }
+}
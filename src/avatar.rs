@@ -0,0 +1,141 @@
+use crate::{
+    caption_buttons::{win32_caption_button_layout, CaptionButtonLayout},
+    dpi::win32_window_dpi,
+    images::win32_load_round_bitmap,
+    win32_client_rect_to_screen, win32_dpi_scale, win32_titlebar_rect, win32_with_window_state,
+    CaptionButtonId,
+};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use windows::Win32::{
+    Foundation::{HWND, RECT},
+    Graphics::Gdi::{
+        AlphaBlend, CreateCompatibleDC, DeleteDC, DeleteObject, SelectObject, AC_SRC_ALPHA,
+        AC_SRC_OVER, BLENDFUNCTION, HBITMAP, HDC,
+    },
+};
+
+// Diameter of the picture at 96 dpi, the button around it is as big as the others
+const WIN32_AVATAR_SIZE: i32 = 24;
+
+// The user picture button of Window::set_avatar_button
+pub(crate) struct AvatarButton {
+    pub(crate) id: CaptionButtonId,
+    pub(crate) path: PathBuf,
+    // Cut round at the size for the window's dpi, loaded again when it changes
+    picture: HBITMAP,
+    size: i32,
+    // Taken out while it runs
+    pub(crate) on_open: Option<Box<dyn FnMut(RECT)>>,
+}
+
+impl AvatarButton {
+    pub(crate) fn win32_load(
+        handle: HWND,
+        id: CaptionButtonId,
+        path: &Path,
+        on_open: Box<dyn FnMut(RECT)>,
+    ) -> Result<Self> {
+        let size = win32_dpi_scale(WIN32_AVATAR_SIZE, win32_window_dpi(handle));
+        Ok(Self {
+            id,
+            path: path.to_owned(),
+            picture: win32_load_round_bitmap(path, size)?,
+            size,
+            on_open: Some(on_open),
+        })
+    }
+
+    // For a new picture or a new dpi, the old picture stays when it fails to load
+    pub(crate) fn win32_reload(&mut self, handle: HWND, path: &Path) -> Result<()> {
+        let size = win32_dpi_scale(WIN32_AVATAR_SIZE, win32_window_dpi(handle));
+        let picture = win32_load_round_bitmap(path, size)?;
+        unsafe { DeleteObject(self.picture) };
+        self.picture = picture;
+        self.size = size;
+        self.path = path.to_owned();
+        Ok(())
+    }
+}
+
+impl Drop for AvatarButton {
+    fn drop(&mut self) {
+        unsafe { DeleteObject(self.picture) };
+    }
+}
+
+fn win32_avatar_rect(layout: &CaptionButtonLayout, id: CaptionButtonId) -> Option<RECT> {
+    layout
+        .buttons
+        .iter()
+        .find(|&&(button, _)| button == id)
+        .map(|&(_, rect)| rect)
+}
+
+// Over the hover and press background the caption button painted already
+pub(crate) fn win32_paint_avatar(hdc: HDC, avatar: &AvatarButton, layout: &CaptionButtonLayout) {
+    let Some(button_rect) = win32_avatar_rect(layout, avatar.id) else {
+        return;
+    };
+    if button_rect.right - button_rect.left < avatar.size {
+        return;
+    }
+
+    let left = (button_rect.left + button_rect.right - avatar.size) / 2;
+    let top = (button_rect.top + button_rect.bottom - avatar.size) / 2;
+    let blend = BLENDFUNCTION {
+        BlendOp: AC_SRC_OVER as u8,
+        SourceConstantAlpha: 255,
+        AlphaFormat: AC_SRC_ALPHA as u8,
+        ..Default::default()
+    };
+
+    unsafe {
+        let picture_hdc = CreateCompatibleDC(hdc);
+        let old_bitmap = SelectObject(picture_hdc, avatar.picture);
+        if !AlphaBlend(
+            hdc,
+            left,
+            top,
+            avatar.size,
+            avatar.size,
+            picture_hdc,
+            0,
+            0,
+            avatar.size,
+            avatar.size,
+            blend,
+        )
+        .as_bool()
+        {
+            eprintln!("Failed to draw avatar");
+        }
+        SelectObject(picture_hdc, old_bitmap);
+        DeleteDC(picture_hdc);
+    }
+}
+
+// The click handler of the avatar button, hands on_open the screen rect of the button
+pub(crate) fn win32_open_avatar_flyout(handle: HWND) {
+    let Some((id, button_rect, mut on_open)) = win32_with_window_state(handle, |state| {
+        let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+        let layout = win32_caption_button_layout(handle, state, &title_bar_rect);
+        let avatar = state.avatar.as_mut()?;
+        let button_rect = win32_avatar_rect(&layout, avatar.id)?;
+        Some((avatar.id, button_rect, avatar.on_open.take()?))
+    })
+    .flatten() else {
+        return;
+    };
+
+    on_open(win32_client_rect_to_screen(handle, &button_rect));
+
+    // Unless the flyout replaced or removed the button meanwhile
+    win32_with_window_state(handle, |state| {
+        if let Some(avatar) = &mut state.avatar {
+            if avatar.id == id && avatar.on_open.is_none() {
+                avatar.on_open = Some(on_open);
+            }
+        }
+    });
+}
@@ -0,0 +1,291 @@
+use crate::{
+    caption_buttons::CaptionButtonLayout,
+    deferred::win32_defer,
+    dpi::win32_window_dpi,
+    fonts::{win32_measure_text, win32_subtitle_font},
+    get_x_param, get_y_param, rgb, win32_dpi_scale, win32_with_window_state, CaptionButtonId,
+    WindowState,
+};
+use windows::Win32::{
+    Foundation::{COLORREF, HWND, LPARAM, LRESULT, POINT, RECT, SIZE, WPARAM},
+    Graphics::Gdi::{
+        CreateSolidBrush, DeleteObject, DrawTextW, GetStockObject, PtInRect, RoundRect,
+        SelectObject, SetBkMode, SetTextColor, DT_CENTER, DT_NOPREFIX, DT_SINGLELINE, DT_VCENTER,
+        HDC, HFONT, NULL_PEN, TRANSPARENT,
+    },
+    UI::WindowsAndMessaging::WM_LBUTTONDOWN,
+};
+
+// Where a badge of Window::set_badge sits
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BadgeAnchor {
+    // Right after the title, only while the title is shown
+    Title,
+    // On the top right corner of a button added with Window::add_caption_button
+    Button(CaptionButtonId),
+}
+
+// A count in a small pill in the caption, e.g. of unread messages. Above 99 it reads
+// 99+.
+pub struct Badge {
+    count: u32,
+    background: COLORREF,
+    text_color: COLORREF,
+    // Taken out while it runs
+    on_click: Option<Box<dyn FnMut()>>,
+}
+
+const WIN32_BADGE_BACKGROUND: u32 = rgb(196, 43, 28);
+const WIN32_BADGE_TEXT_COLOR: u32 = rgb(255, 255, 255);
+// Height and the padding left and right of the count at 96 dpi, as wide as high at
+// least
+const WIN32_BADGE_HEIGHT: i32 = 16;
+const WIN32_BADGE_PADDING: i32 = 5;
+// Gap after the title, and inset from the corner of a button
+const WIN32_BADGE_GAP: i32 = 6;
+const WIN32_BADGE_BUTTON_INSET: i32 = 4;
+
+impl Badge {
+    // White on red by default, clicks go to whatever is under it
+    pub fn new(count: u32) -> Self {
+        Self {
+            count,
+            background: COLORREF(WIN32_BADGE_BACKGROUND),
+            text_color: COLORREF(WIN32_BADGE_TEXT_COLOR),
+            on_click: None,
+        }
+    }
+
+    pub fn colors(mut self, background: COLORREF, text_color: COLORREF) -> Self {
+        self.background = background;
+        self.text_color = text_color;
+        self
+    }
+
+    // Clicks on the badge call this instead of reaching the title or button below
+    pub fn on_click(mut self, on_click: impl FnMut() + 'static) -> Self {
+        self.on_click = Some(Box::new(on_click));
+        self
+    }
+
+    fn text(&self) -> Vec<u16> {
+        let text = if self.count > 99 {
+            "99+".to_owned()
+        } else {
+            self.count.to_string()
+        };
+        text.encode_utf16().collect()
+    }
+}
+
+// The badges of Window::set_badge, one for each anchor
+#[derive(Default)]
+pub(crate) struct Badges {
+    badges: Vec<(BadgeAnchor, Badge)>,
+    // Where each was painted last, empty when it wasn't. The title badge goes where the
+    // title ended, which only painting knows.
+    rects: Vec<RECT>,
+}
+
+impl Badges {
+    // False when nothing changed
+    pub(crate) fn set(&mut self, anchor: BadgeAnchor, badge: Option<Badge>) -> bool {
+        let index = self.badges.iter().position(|&(other, _)| other == anchor);
+        match (index, badge) {
+            (Some(index), Some(badge)) => self.badges[index].1 = badge,
+            (None, Some(badge)) => {
+                self.badges.push((anchor, badge));
+                self.rects.push(RECT::default());
+            }
+            (Some(index), None) => {
+                self.badges.remove(index);
+                self.rects.remove(index);
+            }
+            (None, None) => return false,
+        }
+        true
+    }
+
+    // False when there is no badge at anchor or it already shows count
+    pub(crate) fn set_count(&mut self, anchor: BadgeAnchor, count: u32) -> bool {
+        match self.badges.iter_mut().find(|(other, _)| *other == anchor) {
+            Some((_, badge)) if badge.count != count => {
+                badge.count = count;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Room the title leaves for its badge, None without one
+    pub(crate) fn title_badge_width(&self, dpi: u32) -> Option<i32> {
+        let (_, badge) = self
+            .badges
+            .iter()
+            .find(|(anchor, _)| *anchor == BadgeAnchor::Title)?;
+        Some(win32_badge_size(badge, dpi).cx + win32_dpi_scale(WIN32_BADGE_GAP, dpi))
+    }
+}
+
+fn win32_badge_size(badge: &Badge, dpi: u32) -> SIZE {
+    let height = win32_dpi_scale(WIN32_BADGE_HEIGHT, dpi);
+    let padding = win32_dpi_scale(WIN32_BADGE_PADDING, dpi);
+    let text = badge.text();
+    let text_size = win32_measure_text(win32_subtitle_font(dpi), &[&text], DT_NOPREFIX)[0];
+    SIZE {
+        cx: (text_size.cx + 2 * padding).max(height),
+        cy: height,
+    }
+}
+
+// title_end is where the title text ended, vertically the line it is on. None while
+// there is no title.
+pub(crate) fn win32_paint_badges(
+    hdc: HDC,
+    handle: HWND,
+    badges: &mut Badges,
+    layout: &CaptionButtonLayout,
+    title_end: Option<RECT>,
+) {
+    if badges.badges.is_empty() {
+        return;
+    }
+
+    let dpi = win32_window_dpi(handle);
+    let font = win32_subtitle_font(dpi);
+
+    for (index, (anchor, badge)) in badges.badges.iter().enumerate() {
+        let size = win32_badge_size(badge, dpi);
+        let rect = match anchor {
+            BadgeAnchor::Title => title_end.map(|title_end| {
+                let left = title_end.left + win32_dpi_scale(WIN32_BADGE_GAP, dpi);
+                let top = (title_end.top + title_end.bottom - size.cy) / 2;
+                RECT {
+                    left,
+                    top,
+                    right: left + size.cx,
+                    bottom: top + size.cy,
+                }
+            }),
+            BadgeAnchor::Button(id) => layout
+                .buttons
+                .iter()
+                .find(|&&(button, rect)| button == *id && rect.right > rect.left)
+                .map(|(_, button_rect)| {
+                    let inset = win32_dpi_scale(WIN32_BADGE_BUTTON_INSET, dpi);
+                    let right = button_rect.right - inset;
+                    let top = button_rect.top + inset;
+                    RECT {
+                        left: right - size.cx,
+                        top,
+                        right,
+                        bottom: top + size.cy,
+                    }
+                }),
+        };
+        badges.rects[index] = rect.unwrap_or_default();
+        let Some(mut rect) = rect else {
+            continue;
+        };
+
+        unsafe {
+            let brush = CreateSolidBrush(badge.background);
+            let old_brush = SelectObject(hdc, brush);
+            let old_pen = SelectObject(hdc, GetStockObject(NULL_PEN));
+            // The null pen leaves out the right and bottom edge
+            RoundRect(
+                hdc,
+                rect.left,
+                rect.top,
+                rect.right + 1,
+                rect.bottom + 1,
+                size.cy,
+                size.cy,
+            );
+            SelectObject(hdc, old_pen);
+            SelectObject(hdc, old_brush);
+            DeleteObject(brush);
+
+            let old_font = (font != HFONT(0)).then(|| SelectObject(hdc, font));
+            SetBkMode(hdc, TRANSPARENT);
+            SetTextColor(hdc, badge.text_color);
+            let mut text = badge.text();
+            DrawTextW(
+                hdc,
+                &mut text,
+                &mut rect,
+                DT_CENTER | DT_VCENTER | DT_SINGLELINE | DT_NOPREFIX,
+            );
+            if let Some(old_font) = old_font {
+                SelectObject(hdc, old_font);
+            }
+        }
+    }
+}
+
+// The badge with a click handler under point, its index
+fn win32_clickable_badge_at(badges: &Badges, point: POINT) -> Option<usize> {
+    badges
+        .badges
+        .iter()
+        .zip(&badges.rects)
+        .position(|((_, badge), rect)| {
+            badge.on_click.is_some() && unsafe { PtInRect(rect, point) }.as_bool()
+        })
+}
+
+pub(crate) fn win32_is_over_badge(badges: &Badges, point: POINT) -> bool {
+    win32_clickable_badge_at(badges, point).is_some()
+}
+
+pub(crate) fn win32_badges_input(
+    handle: HWND,
+    state: &mut WindowState,
+    message: u32,
+    _w_param: WPARAM,
+    l_param: LPARAM,
+) -> Option<LRESULT> {
+    if message != WM_LBUTTONDOWN {
+        return None;
+    }
+
+    let client_point = POINT {
+        x: get_x_param(l_param),
+        y: get_y_param(l_param),
+    };
+    let index = win32_clickable_badge_at(&state.badges, client_point)?;
+    let anchor = state.badges.badges[index].0;
+    win32_defer(move || win32_click_badge(handle, anchor));
+    Some(LRESULT(0))
+}
+
+// Deferred from the click, see win32_defer
+fn win32_click_badge(handle: HWND, anchor: BadgeAnchor) {
+    let Some(mut on_click) = win32_with_window_state(handle, |state| {
+        state
+            .badges
+            .badges
+            .iter_mut()
+            .find(|(other, _)| *other == anchor)
+            .and_then(|(_, badge)| badge.on_click.take())
+    })
+    .flatten() else {
+        return;
+    };
+
+    on_click();
+
+    // Unless the badge was replaced or removed meanwhile
+    win32_with_window_state(handle, |state| {
+        if let Some((_, badge)) = state
+            .badges
+            .badges
+            .iter_mut()
+            .find(|(other, _)| *other == anchor)
+        {
+            if badge.on_click.is_none() {
+                badge.on_click = Some(on_click);
+            }
+        }
+    });
+}
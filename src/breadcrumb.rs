@@ -0,0 +1,466 @@
+use crate::{
+    caption_buttons::win32_caption_button_layout,
+    dpi::win32_window_dpi,
+    fonts::win32_measure_text,
+    get_x_param, get_y_param,
+    menu_bar::{win32_menu_bar_right, win32_track_client_leave},
+    rgb, win32_caption_font, win32_caption_icon_rect, win32_dispatch_event, win32_dpi_scale,
+    win32_invalidate_rect, win32_once_released, win32_release_capture, win32_set_capture,
+    win32_titlebar_rect, WindowEvent, WindowState,
+};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{COLORREF, HWND, LPARAM, LRESULT, POINT, RECT, WPARAM},
+        Graphics::Gdi::{
+            ClientToScreen, CreatePen, CreateSolidBrush, DeleteObject, DrawTextW, FillRect, LineTo,
+            MoveToEx, PtInRect, SelectObject, SetBkMode, SetTextColor, DT_END_ELLIPSIS,
+            DT_NOPREFIX, DT_SINGLELINE, DT_VCENTER, HDC, HFONT, PS_SOLID, TRANSPARENT,
+        },
+        UI::{
+            Controls::WM_MOUSELEAVE,
+            WindowsAndMessaging::{
+                AppendMenuW, CreatePopupMenu, DestroyMenu, TrackPopupMenu, HMENU, MF_STRING,
+                TPM_RETURNCMD, TPM_RIGHTBUTTON, WM_CAPTURECHANGED, WM_LBUTTONDOWN, WM_LBUTTONUP,
+                WM_MOUSEMOVE,
+            },
+        },
+    },
+};
+
+// Path segments drawn in the caption in place of the title, see Window::set_breadcrumb
+#[derive(Default)]
+pub(crate) struct Breadcrumb {
+    segments: Vec<Vec<u16>>,
+    hovered: Option<BreadcrumbPart>,
+    // Segments act on release, the mouse is captured until then
+    pressed: Option<BreadcrumbPart>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum BreadcrumbPart {
+    Segment(usize),
+    // The chevron standing in for the segments that don't fit
+    Overflow,
+}
+
+impl Breadcrumb {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub(crate) fn set(&mut self, segments: &[&str]) {
+        self.segments = segments
+            .iter()
+            .map(|segment| segment.encode_utf16().collect())
+            .collect();
+        self.hovered = None;
+        self.pressed = None;
+    }
+}
+
+// Sizes at 96 dpi
+const WIN32_BREADCRUMB_TOP_MARGIN: i32 = 6;
+const WIN32_BREADCRUMB_TEXT_PADDING: i32 = 6;
+const WIN32_BREADCRUMB_SEPARATOR_WIDTH: i32 = 14;
+const WIN32_BREADCRUMB_OVERFLOW_WIDTH: i32 = 24;
+const WIN32_BREADCRUMB_GLYPH_SIZE: i32 = 4;
+// Gap to the icon or the window edge on the left and the caption buttons on the right
+const WIN32_BREADCRUMB_PADDING: i32 = 10;
+
+struct BreadcrumbLayout {
+    overflow: Option<RECT>,
+    // The segments that fit, the last ones of the path
+    segments: Vec<(usize, RECT)>,
+    // Chevrons after the overflow and between segments
+    separators: Vec<RECT>,
+}
+
+// Segments are laid out from the end of the path, the ones that don't fit any more
+// go behind the overflow chevron. The last one is always shown, cut off if it has to.
+fn win32_breadcrumb_layout(
+    handle: HWND,
+    state: &WindowState,
+    title_bar_rect: &RECT,
+) -> BreadcrumbLayout {
+    let dpi = win32_window_dpi(handle);
+    let padding = win32_dpi_scale(WIN32_BREADCRUMB_PADDING, dpi);
+    let text_padding = win32_dpi_scale(WIN32_BREADCRUMB_TEXT_PADDING, dpi);
+    let separator_width = win32_dpi_scale(WIN32_BREADCRUMB_SEPARATOR_WIDTH, dpi);
+    let overflow_width = win32_dpi_scale(WIN32_BREADCRUMB_OVERFLOW_WIDTH, dpi);
+    let caption_button_layout = win32_caption_button_layout(handle, state, title_bar_rect);
+
+    let left = win32_menu_bar_right(handle, state, title_bar_rect).unwrap_or_else(|| {
+        win32_caption_icon_rect(handle, state, title_bar_rect)
+            .map_or(caption_button_layout.leading_right, |icon_rect| {
+                icon_rect.right
+            })
+    }) + padding;
+    let right = (caption_button_layout.content_right - padding).max(left);
+    let top = title_bar_rect.top + win32_dpi_scale(WIN32_BREADCRUMB_TOP_MARGIN, dpi);
+    let bottom = title_bar_rect.bottom - win32_dpi_scale(WIN32_BREADCRUMB_TOP_MARGIN, dpi) / 2;
+
+    let segments: Vec<&[u16]> = state
+        .breadcrumb
+        .segments
+        .iter()
+        .map(Vec::as_slice)
+        .collect();
+    let widths: Vec<i32> = win32_measure_text(win32_caption_font(dpi), &segments, DT_NOPREFIX)
+        .into_iter()
+        .map(|size| size.cx + text_padding * 2)
+        .collect();
+    let count = widths.len();
+    let mut layout = BreadcrumbLayout {
+        overflow: None,
+        segments: Vec::new(),
+        separators: Vec::new(),
+    };
+    if count == 0 {
+        return layout;
+    }
+
+    let available = right - left;
+    let mut first = count - 1;
+    let mut used = widths[first];
+    while first > 0 {
+        let needed = used + separator_width + widths[first - 1];
+        let overflow = if first > 1 {
+            overflow_width + separator_width
+        } else {
+            0
+        };
+        if needed + overflow > available {
+            break;
+        }
+
+        used = needed;
+        first -= 1;
+    }
+
+    let mut x = left;
+    let separator = |x: i32| RECT {
+        left: x,
+        top,
+        right: (x + separator_width).min(right),
+        bottom,
+    };
+    if first > 0 {
+        layout.overflow = Some(RECT {
+            left: x,
+            top,
+            right: (x + overflow_width).min(right),
+            bottom,
+        });
+        x += overflow_width;
+        layout.separators.push(separator(x));
+        x += separator_width;
+    }
+
+    for (index, &width) in widths.iter().enumerate().skip(first) {
+        if index > first {
+            layout.separators.push(separator(x));
+            x += separator_width;
+        }
+
+        layout.segments.push((
+            index,
+            RECT {
+                left: x.min(right),
+                top,
+                right: (x + width).min(right),
+                bottom,
+            },
+        ));
+        x += width;
+    }
+
+    layout
+}
+
+fn win32_breadcrumb_part_at(
+    handle: HWND,
+    state: &WindowState,
+    point: POINT,
+) -> Option<BreadcrumbPart> {
+    if state.breadcrumb.is_empty() {
+        return None;
+    }
+
+    let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+    let layout = win32_breadcrumb_layout(handle, state, &title_bar_rect);
+    let contains = |rect: &RECT| unsafe { PtInRect(rect, point) }.as_bool();
+    if layout.overflow.as_ref().is_some_and(contains) {
+        return Some(BreadcrumbPart::Overflow);
+    }
+
+    layout
+        .segments
+        .iter()
+        .find(|(_, rect)| contains(rect))
+        .map(|&(index, _)| BreadcrumbPart::Segment(index))
+}
+
+// For WM_NCHITTEST, the segments take mouse input like the content while the caption
+// around them still drags the window
+pub(crate) fn win32_is_over_breadcrumb(handle: HWND, state: &WindowState, point: POINT) -> bool {
+    win32_breadcrumb_part_at(handle, state, point).is_some()
+}
+
+// The same hover colors as the caption tabs
+const WIN32_BREADCRUMB_HOVER_COLOR: u32 = rgb(170, 220, 200);
+const WIN32_BREADCRUMB_PRESSED_COLOR: u32 = rgb(130, 180, 160);
+
+pub(crate) fn win32_paint_breadcrumb(
+    hdc: HDC,
+    handle: HWND,
+    state: &WindowState,
+    title_bar_rect: &RECT,
+    item_color: COLORREF,
+) {
+    let breadcrumb = &state.breadcrumb;
+    if breadcrumb.is_empty() {
+        return;
+    }
+
+    let dpi = win32_window_dpi(handle);
+    let layout = win32_breadcrumb_layout(handle, state, title_bar_rect);
+    let text_padding = win32_dpi_scale(WIN32_BREADCRUMB_TEXT_PADDING, dpi);
+    let glyph_size = win32_dpi_scale(WIN32_BREADCRUMB_GLYPH_SIZE, dpi);
+    let font = win32_caption_font(dpi);
+
+    let fill_hovered = |rect: &RECT, part: BreadcrumbPart| unsafe {
+        let color = if breadcrumb.pressed == Some(part) && breadcrumb.hovered == Some(part) {
+            WIN32_BREADCRUMB_PRESSED_COLOR
+        } else if breadcrumb.hovered == Some(part) {
+            WIN32_BREADCRUMB_HOVER_COLOR
+        } else {
+            return;
+        };
+
+        let brush = CreateSolidBrush(COLORREF(color));
+        FillRect(hdc, rect, brush);
+        DeleteObject(brush);
+    };
+    // A chevron pointing right, or left for the overflow
+    let chevron = |center_x: i32, center_y: i32, direction: i32| unsafe {
+        MoveToEx(
+            hdc,
+            center_x - glyph_size / 2 * direction,
+            center_y - glyph_size,
+            None,
+        );
+        LineTo(hdc, center_x + glyph_size / 2 * direction, center_y);
+        LineTo(
+            hdc,
+            center_x - glyph_size / 2 * direction,
+            center_y + glyph_size + 1,
+        );
+    };
+
+    unsafe {
+        let old_font = if font != HFONT(0) {
+            HFONT(SelectObject(hdc, font).0)
+        } else {
+            HFONT(0)
+        };
+        let glyph_pen = CreatePen(PS_SOLID, win32_dpi_scale(1, dpi).max(1), item_color);
+        let old_pen = SelectObject(hdc, glyph_pen);
+        SetBkMode(hdc, TRANSPARENT);
+        SetTextColor(hdc, item_color);
+
+        if let Some(overflow) = &layout.overflow {
+            fill_hovered(overflow, BreadcrumbPart::Overflow);
+            let center_y = (overflow.top + overflow.bottom) / 2;
+            let center_x = (overflow.left + overflow.right) / 2;
+            chevron(center_x - glyph_size / 2, center_y, -1);
+            chevron(center_x + glyph_size / 2, center_y, -1);
+        }
+
+        for separator in &layout.separators {
+            if separator.right > separator.left {
+                chevron(
+                    (separator.left + separator.right) / 2,
+                    (separator.top + separator.bottom) / 2,
+                    1,
+                );
+            }
+        }
+
+        for (index, segment_rect) in &layout.segments {
+            if segment_rect.right <= segment_rect.left {
+                continue;
+            }
+
+            fill_hovered(segment_rect, BreadcrumbPart::Segment(*index));
+            let mut text_rect = RECT {
+                left: segment_rect.left + text_padding,
+                right: (segment_rect.right - text_padding).max(segment_rect.left + text_padding),
+                ..*segment_rect
+            };
+            let mut text = breadcrumb.segments[*index].clone();
+            DrawTextW(
+                hdc,
+                &mut text,
+                &mut text_rect,
+                DT_VCENTER | DT_SINGLELINE | DT_END_ELLIPSIS | DT_NOPREFIX,
+            );
+        }
+
+        SelectObject(hdc, old_pen);
+        DeleteObject(glyph_pen);
+        if font != HFONT(0) {
+            SelectObject(hdc, old_font);
+        }
+    }
+}
+
+pub(crate) fn win32_invalidate_breadcrumb(handle: HWND, state: &mut WindowState) {
+    if let Ok(title_bar_rect) = win32_titlebar_rect(handle, state) {
+        win32_invalidate_rect(handle, state, &title_bar_rect);
+    }
+}
+
+fn win32_set_hovered_breadcrumb_part(
+    handle: HWND,
+    state: &mut WindowState,
+    part: Option<BreadcrumbPart>,
+) {
+    if state.breadcrumb.hovered != part {
+        state.breadcrumb.hovered = part;
+        win32_invalidate_breadcrumb(handle, state);
+    }
+}
+
+// The segments behind the overflow chevron as a menu, and the screen point below it to
+// show that at
+fn win32_overflow_menu(handle: HWND, state: &WindowState) -> Option<(HMENU, POINT)> {
+    let breadcrumb = &state.breadcrumb;
+    let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+    let layout = win32_breadcrumb_layout(handle, state, &title_bar_rect);
+    let overflow = layout.overflow?;
+    let hidden = layout.segments.first().map_or(0, |&(index, _)| index);
+
+    let menu = match unsafe { CreatePopupMenu() } {
+        Ok(menu) => menu,
+        Err(e) => {
+            eprintln!("Failed to create breadcrumb menu: {}", e.message());
+            return None;
+        }
+    };
+
+    // Commands start at 1, 0 is what TrackPopupMenu returns when nothing was picked
+    for (index, segment) in breadcrumb.segments[..hidden].iter().enumerate() {
+        let text: Vec<u16> = segment.iter().copied().chain([0]).collect();
+        if let Err(e) = unsafe { AppendMenuW(menu, MF_STRING, index + 1, PCWSTR(text.as_ptr())) } {
+            eprintln!("Failed to add breadcrumb menu item: {}", e.message());
+        }
+    }
+
+    let mut point = POINT {
+        x: overflow.left,
+        y: overflow.bottom,
+    };
+    unsafe { ClientToScreen(handle, &mut point) };
+    Some((menu, point))
+}
+
+// Shows the overflow menu and destroys it, returns the segment picked
+fn win32_track_overflow_menu(handle: HWND, menu: HMENU, point: POINT) -> Option<usize> {
+    let command = unsafe {
+        TrackPopupMenu(
+            menu,
+            TPM_RETURNCMD | TPM_RIGHTBUTTON,
+            point.x,
+            point.y,
+            0,
+            handle,
+            None,
+        )
+    };
+
+    if let Err(e) = unsafe { DestroyMenu(menu) } {
+        eprintln!("Failed to destroy breadcrumb menu: {}", e.message());
+    }
+
+    (command.0 as usize).checked_sub(1)
+}
+
+// Mouse input over the segments, which WM_NCHITTEST makes client area. None leaves the
+// message to the window.
+pub(crate) fn win32_breadcrumb_input(
+    handle: HWND,
+    state: &mut WindowState,
+    message: u32,
+    _w_param: WPARAM,
+    l_param: LPARAM,
+) -> Option<LRESULT> {
+    if state.breadcrumb.is_empty() {
+        return None;
+    }
+
+    let client_point = POINT {
+        x: get_x_param(l_param),
+        y: get_y_param(l_param),
+    };
+
+    match message {
+        WM_MOUSEMOVE => {
+            let part = win32_breadcrumb_part_at(handle, state, client_point);
+            win32_set_hovered_breadcrumb_part(handle, state, part);
+
+            // Moving on to the caption around the segments is a WM_MOUSELEAVE
+            if part.is_some() {
+                win32_track_client_leave(handle, state);
+            }
+
+            None
+        }
+        WM_MOUSELEAVE if state.breadcrumb.pressed.is_none() => {
+            win32_set_hovered_breadcrumb_part(handle, state, None);
+            None
+        }
+        WM_LBUTTONDOWN => {
+            let part = win32_breadcrumb_part_at(handle, state, client_point)?;
+            state.breadcrumb.pressed = Some(part);
+            win32_set_capture(handle, state);
+            win32_invalidate_breadcrumb(handle, state);
+            Some(LRESULT(0))
+        }
+        WM_LBUTTONUP => {
+            let pressed = state.breadcrumb.pressed.take()?;
+            win32_release_capture(state);
+
+            win32_invalidate_breadcrumb(handle, state);
+            if win32_breadcrumb_part_at(handle, state, client_point) != Some(pressed) {
+                return Some(LRESULT(0));
+            }
+
+            match pressed {
+                BreadcrumbPart::Segment(index) => {
+                    win32_dispatch_event(handle, WindowEvent::BreadcrumbSelected(index));
+                }
+                BreadcrumbPart::Overflow => {
+                    if let Some((menu, point)) = win32_overflow_menu(handle, state) {
+                        win32_once_released(state, move || {
+                            if let Some(index) = win32_track_overflow_menu(handle, menu, point) {
+                                win32_dispatch_event(
+                                    handle,
+                                    WindowEvent::BreadcrumbSelected(index),
+                                );
+                            }
+                        });
+                    }
+                }
+            }
+
+            Some(LRESULT(0))
+        }
+        WM_CAPTURECHANGED if state.breadcrumb.pressed.is_some() => {
+            state.breadcrumb.pressed = None;
+            win32_invalidate_breadcrumb(handle, state);
+            Some(LRESULT(0))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,904 @@
+use crate::{
+    animation::Transition,
+    caption_label::win32_caption_label_item,
+    caption_layout::{win32_layout_caption_items, CaptionAlign, CaptionItem},
+    deferred::win32_defer,
+    dpi::win32_window_dpi,
+    get_x_param, get_y_param,
+    menu_bar::win32_track_client_leave,
+    names::CaptionButtonNames,
+    search_box::{win32_layout_search_box, win32_search_field_item, win32_search_field_rect},
+    status_led::WIN32_STATUS_LED_WIDTH,
+    tooltip::{win32_relay_mouse_move, win32_set_tool_rect},
+    win32_blend_color, win32_button_width, win32_client_rect_to_screen, win32_dispatch_event,
+    win32_dpi_scale, win32_draw_focus_ring, win32_invalidate_rect, win32_release_capture,
+    win32_set_capture, win32_start_animation_timer, win32_titlebar_rect,
+    win32_update_caption_overlay, win32_with_window_state, CaptionButtonName,
+    CustomTitleBarButtonRects, VectorIcon, WindowEvent, WindowState, WIN32_HOVER_FADE_DURATION,
+};
+use std::time::Duration;
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{COLORREF, HWND, LPARAM, LRESULT, POINT, RECT, WPARAM},
+        Graphics::Gdi::{CreateSolidBrush, DeleteObject, FillRect, PtInRect, HDC},
+        UI::{
+            Controls::WM_MOUSELEAVE,
+            Input::KeyboardAndMouse::GetFocus,
+            WindowsAndMessaging::{
+                AppendMenuW, CreatePopupMenu, DestroyMenu, TrackPopupMenu, HMENU, MF_GRAYED,
+                MF_STRING, TPM_RETURNCMD, TPM_RIGHTBUTTON, WM_CAPTURECHANGED, WM_LBUTTONDOWN,
+                WM_LBUTTONUP, WM_MOUSEMOVE,
+            },
+        },
+    },
+};
+
+// Where a button added with Window::add_caption_button goes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CaptionButtonPlacement {
+    // Left of the system buttons, the first one added right next to them
+    #[default]
+    BeforeSystemButtons,
+    // At the left edge of the caption before the icon, in the order they were added
+    Leading,
+}
+
+// Returned by Window::add_caption_button to change or remove the button later
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CaptionButtonId(usize);
+
+// A button of the application's own in the caption, as big as the system buttons and
+// drawn the same way. The window takes care of hovering, pressing and the tooltip,
+// on_click runs when the button is released over it.
+pub struct CaptionButton {
+    icon: VectorIcon,
+    tooltip: String,
+    name: Option<CaptionButtonName>,
+    placement: CaptionButtonPlacement,
+    on_click: Box<dyn FnMut()>,
+}
+
+impl CaptionButton {
+    pub fn new(icon: VectorIcon, tooltip: &str, on_click: impl FnMut() + 'static) -> Self {
+        Self {
+            icon,
+            tooltip: tooltip.to_owned(),
+            name: None,
+            placement: CaptionButtonPlacement::default(),
+            on_click: Box::new(on_click),
+        }
+    }
+
+    pub fn placement(mut self, placement: CaptionButtonPlacement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    // Does what one of the window's own buttons would: the tooltip is name's text in
+    // place of tooltip, see Window::set_caption_button_name, and glyph painters and
+    // context help are told it is that button
+    pub fn name(mut self, name: CaptionButtonName) -> Self {
+        self.name = Some(name);
+        self
+    }
+}
+
+// Which button a glyph painter is asked to draw, see Window::set_caption_glyph_painter
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaptionGlyphButton {
+    // One of the window's own, by what it is called right now
+    System(CaptionButtonName),
+    Added(CaptionButtonId),
+}
+
+// What a glyph painter draws with. The window has painted the hover or press background
+// of the button already, the glyph goes on top.
+#[derive(Clone, Copy, Debug)]
+pub struct CaptionGlyph {
+    pub button: CaptionGlyphButton,
+    pub hdc: HDC,
+    pub rect: RECT,
+    pub dpi: u32,
+    // The color the window's own glyph would have, dimmed while inactive or disabled
+    pub color: COLORREF,
+    pub hovered: bool,
+    pub pressed: bool,
+    pub enabled: bool,
+}
+
+// True when it drew the glyph, false leaves it to the window
+pub(crate) type Win32GlyphPainter = Box<dyn FnMut(&CaptionGlyph) -> bool>;
+
+struct AddedCaptionButton {
+    id: CaptionButtonId,
+    icon: VectorIcon,
+    // Null terminated for TTN_GETDISPINFOW
+    tooltip: Vec<u16>,
+    name: Option<CaptionButtonName>,
+    placement: CaptionButtonPlacement,
+    // Disabled buttons are dimmed and neither hover nor click
+    enabled: bool,
+    // Fades the hover highlight in and out like the system buttons have it
+    hover: Transition,
+    // Taken out while it runs, it may well add or remove buttons itself
+    on_click: Option<Box<dyn FnMut()>>,
+}
+
+// The buttons added with Window::add_caption_button
+#[derive(Default)]
+pub(crate) struct CaptionButtons {
+    buttons: Vec<AddedCaptionButton>,
+    next_id: usize,
+    hovered: Option<CaptionButtonId>,
+    // Buttons act on release, the mouse is captured until then
+    pressed: Option<CaptionButtonId>,
+    // Has the focus ring while the caption region has the keyboard focus, see
+    // win32_set_focused_caption_button
+    focused: Option<CaptionButtonId>,
+    overflow_hover: Transition,
+}
+
+// The title gives way before anything else in the caption: it is cut off with a fade
+// down to its minimum width, below that it goes altogether so the buttons and items
+// keep their size
+pub(crate) const WIN32_TITLE_SHRINK: u32 = u32::MAX;
+pub(crate) const WIN32_TITLE_MIN_WIDTH: i32 = 48;
+
+// Tooltip tool ids of the added buttons start here, below it are the system buttons
+pub(crate) const WIN32_CAPTION_BUTTON_TOOL_ID_BASE: usize = 0x100;
+
+// The » button the layout adds for buttons that don't fit, see
+// win32_open_caption_overflow. Never handed out to the application.
+pub(crate) const WIN32_OVERFLOW_BUTTON: CaptionButtonId = CaptionButtonId(usize::MAX);
+const WIN32_OVERFLOW_GLYPH: &str = "M4 4.5 L7.5 8 L4 11.5 M8.5 4.5 L12 8 L8.5 11.5";
+
+impl CaptionButtonId {
+    pub(crate) fn tool_id(self) -> usize {
+        WIN32_CAPTION_BUTTON_TOOL_ID_BASE + self.0
+    }
+}
+
+impl CaptionButtons {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.buttons.is_empty()
+    }
+
+    pub(crate) fn add(&mut self, button: CaptionButton) -> CaptionButtonId {
+        self.insert(self.buttons.len(), button)
+    }
+
+    // Ahead of the ones added before, i.e. first from the edge it is placed at
+    pub(crate) fn add_first(&mut self, button: CaptionButton) -> CaptionButtonId {
+        self.insert(0, button)
+    }
+
+    fn insert(&mut self, index: usize, button: CaptionButton) -> CaptionButtonId {
+        let id = CaptionButtonId(self.next_id);
+        self.next_id += 1;
+        self.buttons.insert(
+            index,
+            AddedCaptionButton {
+                id,
+                icon: button.icon,
+                tooltip: button.tooltip.encode_utf16().chain([0]).collect(),
+                name: button.name,
+                placement: button.placement,
+                enabled: true,
+                hover: Transition::default(),
+                on_click: Some(button.on_click),
+            },
+        );
+        id
+    }
+
+    // False when there is no such button
+    pub(crate) fn remove(&mut self, id: CaptionButtonId) -> bool {
+        let count = self.buttons.len();
+        self.buttons.retain(|button| button.id != id);
+        if self.hovered == Some(id) {
+            self.hovered = None;
+        }
+        if self.pressed == Some(id) {
+            self.pressed = None;
+        }
+        if self.focused == Some(id) {
+            self.focused = None;
+        }
+        self.buttons.len() != count
+    }
+
+    pub(crate) fn set_icon(&mut self, id: CaptionButtonId, icon: VectorIcon) -> bool {
+        match self.buttons.iter_mut().find(|button| button.id == id) {
+            Some(button) => {
+                button.icon = icon;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn set_enabled(&mut self, id: CaptionButtonId, enabled: bool) -> bool {
+        match self.buttons.iter_mut().find(|button| button.id == id) {
+            Some(button) if button.enabled != enabled => {
+                button.enabled = enabled;
+                if !enabled && self.pressed == Some(id) {
+                    self.pressed = None;
+                }
+                // Like the system buttons, disabled ones take no keyboard focus
+                if !enabled && self.focused == Some(id) {
+                    self.focused = None;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self, id: CaptionButtonId) -> bool {
+        id == WIN32_OVERFLOW_BUTTON
+            || self
+                .buttons
+                .iter()
+                .any(|button| button.id == id && button.enabled)
+    }
+
+    pub(crate) fn ids(&self) -> impl Iterator<Item = CaptionButtonId> + '_ {
+        self.buttons.iter().map(|button| button.id)
+    }
+
+    // See CaptionButton::name
+    pub(crate) fn name(&self, id: CaptionButtonId) -> Option<CaptionButtonName> {
+        self.buttons
+            .iter()
+            .find(|button| button.id == id)
+            .and_then(|button| button.name)
+    }
+
+    pub(crate) fn focused(&self) -> Option<CaptionButtonId> {
+        self.focused
+    }
+
+    // For the tooltip tool id, None for the ids of the system buttons
+    pub(crate) fn tooltip<'a>(
+        &'a self,
+        tool_id: usize,
+        names: &'a CaptionButtonNames,
+    ) -> Option<&'a [u16]> {
+        let id = CaptionButtonId(tool_id.checked_sub(WIN32_CAPTION_BUTTON_TOOL_ID_BASE)?);
+        self.buttons
+            .iter()
+            .find(|button| button.id == id)
+            .map(|button| button.tooltip(names))
+    }
+
+    fn hover_mut(&mut self, id: CaptionButtonId) -> Option<&mut Transition> {
+        if id == WIN32_OVERFLOW_BUTTON {
+            return Some(&mut self.overflow_hover);
+        }
+        self.buttons
+            .iter_mut()
+            .find(|button| button.id == id)
+            .map(|button| &mut button.hover)
+    }
+
+    fn hover_amount(&self, id: CaptionButtonId) -> f32 {
+        if id == WIN32_OVERFLOW_BUTTON {
+            return self.overflow_hover.value();
+        }
+        self.buttons
+            .iter()
+            .find(|button| button.id == id)
+            .map_or(0.0, |button| button.hover.value())
+    }
+
+    pub(crate) fn is_animating(&self) -> bool {
+        self.overflow_hover.is_running()
+            || self.buttons.iter().any(|button| button.hover.is_running())
+    }
+
+    // Skips to the end of the hover fades, e.g. when animations get throttled
+    pub(crate) fn finish_animations(&mut self) {
+        self.overflow_hover.finish();
+        for button in &mut self.buttons {
+            button.hover.finish();
+        }
+    }
+}
+
+impl AddedCaptionButton {
+    // Null terminated
+    fn tooltip<'a>(&'a self, names: &'a CaptionButtonNames) -> &'a [u16] {
+        match self.name {
+            Some(name) => names.text(name),
+            None => &self.tooltip,
+        }
+    }
+}
+
+pub(crate) struct CaptionButtonLayout {
+    pub(crate) buttons: Vec<(CaptionButtonId, RECT)>,
+    // Right edge of the leading buttons and items, the left edge of the caption without
+    // any
+    pub(crate) leading_right: i32,
+    // Left edge of the system buttons and the buttons and items before them
+    pub(crate) trailing_left: i32,
+    // See Window::set_search_box, None when there is none or it doesn't fit
+    pub(crate) search_field: Option<RECT>,
+    // Where the title, tabs or breadcrumb have to end, before the centered items
+    pub(crate) content_right: i32,
+    // See Window::set_caption_items, empty for the ones that had to go
+    pub(crate) items: Vec<RECT>,
+    // False once the title had to go to make room, see WIN32_TITLE_SHRINK
+    pub(crate) title_fits: bool,
+    // See Window::set_status_led, None when there is none or it doesn't fit
+    pub(crate) status_led: Option<RECT>,
+    // See Window::set_caption_label, None when there is none or it had to go
+    pub(crate) caption_label: Option<RECT>,
+}
+
+// What the caption layout is made of, besides the application's items
+#[derive(Clone, Copy, PartialEq)]
+enum CaptionPiece {
+    Button(CaptionButtonId),
+    // Room kept for the caption icon so centered items don't cover it
+    IconSpace,
+    // Room kept for the title, it is drawn in whatever is left after the leading pieces
+    Title,
+    Item(usize),
+    SearchField,
+    StatusLed,
+    Label,
+}
+
+// Everything between the left edge of the caption and the system buttons goes through
+// the caption layout: leading buttons and items from the left, trailing items and
+// buttons up to the system buttons, the search field and centered items in the middle
+pub(crate) fn win32_caption_button_layout(
+    handle: HWND,
+    state: &WindowState,
+    title_bar_rect: &RECT,
+) -> CaptionButtonLayout {
+    let button_rects =
+        CustomTitleBarButtonRects::win32_get_title_bar_button_rects(handle, state, title_bar_rect);
+    let mut layout = CaptionButtonLayout {
+        buttons: Vec::new(),
+        leading_right: title_bar_rect.left,
+        trailing_left: button_rects.shade.left,
+        search_field: None,
+        content_right: button_rects.shade.left,
+        items: Vec::new(),
+        title_fits: true,
+        status_led: None,
+        caption_label: None,
+    };
+
+    let dpi = win32_window_dpi(handle);
+    let button_width = win32_button_width(handle, state);
+    let buttons = &state.caption_buttons.buttons;
+    let placed = |placement| {
+        buttons
+            .iter()
+            .filter(move |button| button.placement == placement)
+    };
+    let item_at =
+        |index: usize| state.caption_items[index].scaled(|value| win32_dpi_scale(value, dpi));
+    let app_items = |align| {
+        (0..state.caption_items.len())
+            .filter(move |&index| state.caption_items[index].align() == align)
+    };
+    // After the application's items of the same alignment
+    let label = |align| {
+        state
+            .caption_label
+            .as_ref()
+            .filter(|label| label.alignment() == align)
+            .map(|label| (CaptionPiece::Label, win32_caption_label_item(label, dpi)))
+    };
+
+    // On a narrow window the leading buttons win, the others are squeezed to nothing
+    let mut pieces = Vec::new();
+    for button in placed(CaptionButtonPlacement::Leading) {
+        let item = CaptionItem::new(CaptionAlign::Leading, button_width).shrink(0);
+        pieces.push((CaptionPiece::Button(button.id), item));
+    }
+    for index in app_items(CaptionAlign::Leading) {
+        pieces.push((CaptionPiece::Item(index), item_at(index)));
+    }
+    pieces.extend(label(CaptionAlign::Leading));
+
+    // Not in the caption that slides in over fullscreen and picture in picture
+    let has_search_field = state.search_box.is_some() && !state.hides_caption();
+    let has_center = has_search_field || app_items(CaptionAlign::Center).next().is_some();
+    if has_center {
+        let item = CaptionItem::new(CaptionAlign::Leading, button_width).shrink(0);
+        pieces.push((CaptionPiece::IconSpace, item));
+    }
+    // Tabs and the breadcrumb take the title's place
+    if state.tabs.is_empty() && state.breadcrumb.is_empty() {
+        let min_width = win32_dpi_scale(WIN32_TITLE_MIN_WIDTH, dpi);
+        let item = CaptionItem::new(CaptionAlign::Leading, min_width).shrink(WIN32_TITLE_SHRINK);
+        pieces.push((CaptionPiece::Title, item));
+    }
+    for index in app_items(CaptionAlign::Center) {
+        pieces.push((CaptionPiece::Item(index), item_at(index)));
+    }
+    pieces.extend(label(CaptionAlign::Center));
+    if has_search_field {
+        pieces.push((CaptionPiece::SearchField, win32_search_field_item(dpi)));
+    }
+
+    for index in app_items(CaptionAlign::Trailing) {
+        pieces.push((CaptionPiece::Item(index), item_at(index)));
+    }
+    pieces.extend(label(CaptionAlign::Trailing));
+    if state.status_led.is_some() {
+        let width = win32_dpi_scale(WIN32_STATUS_LED_WIDTH, dpi);
+        let item = CaptionItem::new(CaptionAlign::Trailing, width);
+        pieces.push((CaptionPiece::StatusLed, item));
+    }
+    // The first one added is right next to the system buttons
+    let trailing_buttons: Vec<_> = placed(CaptionButtonPlacement::BeforeSystemButtons).collect();
+    for button in trailing_buttons.into_iter().rev() {
+        let item = CaptionItem::new(CaptionAlign::Trailing, button_width);
+        pieces.push((CaptionPiece::Button(button.id), item));
+    }
+
+    let spans_of = |pieces: &[(CaptionPiece, CaptionItem)]| {
+        let items: Vec<CaptionItem> = pieces.iter().map(|&(_, item)| item).collect();
+        win32_layout_caption_items(title_bar_rect.left, button_rects.shade.left, &items)
+    };
+    let mut spans = spans_of(&pieces);
+
+    // Trailing buttons that don't fit go behind a » button where the first of them
+    // would be, which may push out one more
+    let is_trailing_button = |&(piece, item): &(CaptionPiece, CaptionItem)| {
+        matches!(piece, CaptionPiece::Button(_)) && item.align() == CaptionAlign::Trailing
+    };
+    let overflows = pieces
+        .iter()
+        .zip(&spans)
+        .any(|(piece, &(left, right))| is_trailing_button(piece) && right <= left);
+    if let (true, Some(position)) = (overflows, pieces.iter().position(is_trailing_button)) {
+        let item = CaptionItem::new(CaptionAlign::Trailing, button_width).shrink(0);
+        pieces.insert(
+            position,
+            (CaptionPiece::Button(WIN32_OVERFLOW_BUTTON), item),
+        );
+        spans = spans_of(&pieces);
+    }
+    layout.items = vec![RECT::default(); state.caption_items.len()];
+    for (&(piece, item), &(left, right)) in pieces.iter().zip(&spans) {
+        let rect = RECT {
+            left,
+            right,
+            ..button_rects.close
+        };
+        match item.align() {
+            CaptionAlign::Leading
+                if piece != CaptionPiece::IconSpace && piece != CaptionPiece::Title =>
+            {
+                layout.leading_right = layout.leading_right.max(right)
+            }
+            CaptionAlign::Trailing => layout.trailing_left = layout.trailing_left.min(left),
+            _ => {}
+        }
+
+        match piece {
+            CaptionPiece::Button(id) => layout.buttons.push((id, rect)),
+            CaptionPiece::IconSpace => {}
+            CaptionPiece::Title => layout.title_fits = right > left,
+            CaptionPiece::Item(index) if right > left => {
+                layout.items[index] = RECT {
+                    left,
+                    right,
+                    ..*title_bar_rect
+                }
+            }
+            CaptionPiece::Item(_) => {}
+            CaptionPiece::SearchField => {
+                layout.search_field = win32_search_field_rect(handle, title_bar_rect, (left, right))
+            }
+            CaptionPiece::StatusLed => layout.status_led = (right > left).then_some(rect),
+            CaptionPiece::Label if right > left => {
+                layout.caption_label = Some(RECT {
+                    left,
+                    right,
+                    ..*title_bar_rect
+                })
+            }
+            CaptionPiece::Label => {}
+        }
+    }
+
+    // Leading buttons that don't fit push the rest out of the caption
+    layout.trailing_left = layout.trailing_left.max(layout.leading_right);
+    layout.content_right = layout.trailing_left;
+    for (&(piece, item), &(left, right)) in pieces.iter().zip(&spans) {
+        if item.align() == CaptionAlign::Center && right > left && piece != CaptionPiece::IconSpace
+        {
+            layout.content_right = layout.content_right.min(left);
+        }
+    }
+
+    layout
+}
+
+pub(crate) fn win32_caption_button_at(
+    handle: HWND,
+    state: &WindowState,
+    point: POINT,
+) -> Option<CaptionButtonId> {
+    let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+    win32_caption_button_layout(handle, state, &title_bar_rect)
+        .buttons
+        .into_iter()
+        .find(|(_, rect)| unsafe { PtInRect(rect, point) }.as_bool())
+        .map(|(id, _)| id)
+}
+
+// For WM_NCHITTEST, the buttons take mouse input like the content
+pub(crate) fn win32_is_over_caption_button(layout: &CaptionButtonLayout, point: POINT) -> bool {
+    layout
+        .buttons
+        .iter()
+        .any(|(_, rect)| unsafe { PtInRect(rect, point) }.as_bool())
+}
+
+// Backgrounds and focus ring like the system buttons have, glyphs in item_color or
+// disabled_color
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn win32_paint_caption_buttons(
+    hdc: HDC,
+    handle: HWND,
+    state: &WindowState,
+    layout: &CaptionButtonLayout,
+    titlebar_color: COLORREF,
+    item_color: COLORREF,
+    hover_color: COLORREF,
+    pressed_color: COLORREF,
+    disabled_color: COLORREF,
+    mut glyph_painter: Option<&mut Win32GlyphPainter>,
+) {
+    let buttons = &state.caption_buttons;
+    if buttons.is_empty() {
+        return;
+    }
+
+    let dpi = win32_window_dpi(handle);
+    // Only while the window itself has the focus
+    let focused = buttons.focused.filter(|_| unsafe { GetFocus() } == handle);
+    for (id, rect) in &layout.buttons {
+        if rect.right <= rect.left {
+            continue;
+        }
+
+        let enabled = buttons.is_enabled(*id);
+        let hovered = enabled && buttons.hovered == Some(*id);
+        let pressed = hovered && buttons.pressed == Some(*id);
+        let amount = if enabled {
+            buttons.hover_amount(*id)
+        } else {
+            0.0
+        };
+        let fill_color = if pressed {
+            Some(pressed_color)
+        } else if amount > 0.0 {
+            Some(win32_blend_color(titlebar_color, hover_color, amount))
+        } else {
+            None
+        };
+        if let Some(fill_color) = fill_color {
+            unsafe {
+                let brush = CreateSolidBrush(fill_color);
+                FillRect(hdc, rect, brush);
+                DeleteObject(brush);
+            }
+        }
+
+        if let Some(button) = buttons.buttons.iter().find(|button| button.id == *id) {
+            let color = if enabled { item_color } else { disabled_color };
+            let glyph = CaptionGlyph {
+                button: button
+                    .name
+                    .map_or(CaptionGlyphButton::Added(*id), CaptionGlyphButton::System),
+                hdc,
+                rect: *rect,
+                dpi,
+                color,
+                hovered,
+                pressed,
+                enabled,
+            };
+            let painted = glyph_painter
+                .as_mut()
+                .is_some_and(|glyph_painter| glyph_painter(&glyph));
+            if !painted {
+                button.icon.draw(hdc, rect, color, dpi);
+            }
+        } else if *id == WIN32_OVERFLOW_BUTTON {
+            match VectorIcon::stroked(WIN32_OVERFLOW_GLYPH) {
+                Ok(icon) => icon.draw(hdc, rect, item_color, dpi),
+                Err(e) => eprintln!("Failed to create overflow glyph: {}", e),
+            }
+        }
+
+        if focused == Some(*id) {
+            win32_draw_focus_ring(hdc, rect, dpi, item_color, titlebar_color);
+        }
+    }
+}
+
+// After anything that moves things around in the caption: places the search box and
+// tells the application where its items and the system buttons went
+pub(crate) fn win32_relayout_caption(handle: HWND, state: &mut WindowState) {
+    win32_layout_search_box(handle, state);
+    win32_update_caption_overlay(handle, state);
+    if state.caption_items.is_empty() && state.caption_item_rects.is_empty() {
+        return;
+    }
+
+    let Ok(title_bar_rect) = win32_titlebar_rect(handle, state) else {
+        return;
+    };
+    let rects = win32_caption_button_layout(handle, state, &title_bar_rect).items;
+    if rects != state.caption_item_rects {
+        state.caption_item_rects = rects.clone();
+        win32_dispatch_event(handle, WindowEvent::CaptionItemsMoved(rects));
+    }
+}
+
+// Also lays the caption out again, buttons coming and going move the rest
+pub(crate) fn win32_invalidate_added_caption_buttons(handle: HWND, state: &mut WindowState) {
+    win32_relayout_caption(handle, state);
+    if let Ok(title_bar_rect) = win32_titlebar_rect(handle, state) {
+        win32_invalidate_rect(handle, state, &title_bar_rect);
+    }
+}
+
+// Repaints the buttons where they are, for hovering and focus
+pub(crate) fn win32_invalidate_caption_button_rects(handle: HWND, state: &mut WindowState) {
+    if state.caption_buttons.is_empty() {
+        return;
+    }
+
+    let Ok(title_bar_rect) = win32_titlebar_rect(handle, state) else {
+        return;
+    };
+    for (_, rect) in win32_caption_button_layout(handle, state, &title_bar_rect).buttons {
+        win32_invalidate_rect(handle, state, &rect);
+    }
+}
+
+// Moves the hover highlight, fading it in and out like win32_set_hovered_button
+fn win32_set_hovered_caption_button(
+    handle: HWND,
+    state: &mut WindowState,
+    id: Option<CaptionButtonId>,
+) {
+    if state.caption_buttons.hovered == id {
+        return;
+    }
+
+    let duration = if state.skips_animations() {
+        Duration::ZERO
+    } else {
+        WIN32_HOVER_FADE_DURATION
+    };
+    let buttons = &mut state.caption_buttons;
+    for (button, target) in [(buttons.hovered, 0.0), (id, 1.0)] {
+        if let Some(hover) = button.and_then(|button| buttons.hover_mut(button)) {
+            hover.animate_to(target, duration);
+        }
+    }
+
+    buttons.hovered = id;
+    win32_invalidate_caption_button_rects(handle, state);
+    win32_start_animation_timer(handle, state);
+}
+
+// Moves the keyboard focus ring onto one of the buttons, None takes it off them. See
+// win32_set_caption_focus for the system buttons.
+pub(crate) fn win32_set_focused_caption_button(
+    handle: HWND,
+    state: &mut WindowState,
+    id: Option<CaptionButtonId>,
+) {
+    if state.caption_buttons.focused != id {
+        state.caption_buttons.focused = id;
+        win32_invalidate_caption_button_rects(handle, state);
+    }
+}
+
+// The buttons behind the » button as a menu below it, by their tooltips. Returns the
+// one picked. The state is only borrowed to fill the menu, not while it is open.
+fn win32_open_caption_overflow(handle: HWND) -> Option<CaptionButtonId> {
+    let (menu, hidden, screen_rect) =
+        win32_with_window_state(handle, |state| win32_caption_overflow_menu(handle, state))??;
+    let command = unsafe {
+        TrackPopupMenu(
+            menu,
+            TPM_RETURNCMD | TPM_RIGHTBUTTON,
+            screen_rect.left,
+            screen_rect.bottom,
+            0,
+            handle,
+            None,
+        )
+    };
+
+    if let Err(e) = unsafe { DestroyMenu(menu) } {
+        eprintln!("Failed to destroy caption button menu: {}", e.message());
+    }
+
+    let index = (command.0 as usize).checked_sub(1)?;
+    hidden.get(index).copied()
+}
+
+// The menu of win32_open_caption_overflow, with the buttons its commands stand for and
+// where to open it
+fn win32_caption_overflow_menu(
+    handle: HWND,
+    state: &WindowState,
+) -> Option<(HMENU, Vec<CaptionButtonId>, RECT)> {
+    let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+    let layout = win32_caption_button_layout(handle, state, &title_bar_rect);
+    let (_, overflow_rect) = *layout
+        .buttons
+        .iter()
+        .find(|&&(id, _)| id == WIN32_OVERFLOW_BUTTON)?;
+    let buttons = &state.caption_buttons;
+    let hidden: Vec<&AddedCaptionButton> = layout
+        .buttons
+        .iter()
+        .filter(|(_, rect)| rect.right <= rect.left)
+        .filter_map(|&(id, _)| buttons.buttons.iter().find(|button| button.id == id))
+        .collect();
+
+    let menu = match unsafe { CreatePopupMenu() } {
+        Ok(menu) => menu,
+        Err(e) => {
+            eprintln!("Failed to create caption button menu: {}", e.message());
+            return None;
+        }
+    };
+
+    // Commands start at 1, 0 is what TrackPopupMenu returns when nothing was picked
+    for (index, button) in hidden.iter().enumerate() {
+        let flags = if button.enabled {
+            MF_STRING
+        } else {
+            MF_STRING | MF_GRAYED
+        };
+        let text = PCWSTR(button.tooltip(&state.caption_button_names).as_ptr());
+        if let Err(e) = unsafe { AppendMenuW(menu, flags, index + 1, text) } {
+            eprintln!("Failed to add caption button menu item: {}", e.message());
+        }
+    }
+    let hidden = hidden.iter().map(|button| button.id).collect();
+
+    Some((
+        menu,
+        hidden,
+        win32_client_rect_to_screen(handle, &overflow_rect),
+    ))
+}
+
+// Runs the button's on_click once the state is let go of, then puts the callback back
+// unless the button was removed in the meantime. The » button opens its menu first.
+pub(crate) fn win32_click_caption_button(handle: HWND, id: CaptionButtonId) {
+    win32_defer(move || {
+        let id = if id == WIN32_OVERFLOW_BUTTON {
+            match win32_open_caption_overflow(handle) {
+                Some(id) => id,
+                None => return,
+            }
+        } else {
+            id
+        };
+
+        let Some(mut on_click) = win32_with_window_state(handle, |state| {
+            state
+                .caption_buttons
+                .buttons
+                .iter_mut()
+                .find(|button| button.id == id)
+                .and_then(|button| button.on_click.take())
+        })
+        .flatten() else {
+            return;
+        };
+
+        on_click();
+
+        win32_with_window_state(handle, |state| {
+            if let Some(button) = state
+                .caption_buttons
+                .buttons
+                .iter_mut()
+                .find(|button| button.id == id)
+            {
+                button.on_click = Some(on_click);
+            }
+        });
+    });
+}
+
+// Mouse input over the buttons, which WM_NCHITTEST makes client area. None leaves the
+// message to the window.
+pub(crate) fn win32_caption_buttons_input(
+    handle: HWND,
+    state: &mut WindowState,
+    message: u32,
+    _w_param: WPARAM,
+    l_param: LPARAM,
+) -> Option<LRESULT> {
+    if state.caption_buttons.is_empty() {
+        return None;
+    }
+
+    let client_point = POINT {
+        x: get_x_param(l_param),
+        y: get_y_param(l_param),
+    };
+
+    match message {
+        WM_MOUSEMOVE => {
+            let id = win32_caption_button_at(handle, state, client_point);
+            win32_set_hovered_caption_button(handle, state, id);
+
+            if state.tooltip != HWND(0) {
+                if let Ok(title_bar_rect) = win32_titlebar_rect(handle, state) {
+                    let layout = win32_caption_button_layout(handle, state, &title_bar_rect);
+                    for (id, rect) in layout.buttons {
+                        if id != WIN32_OVERFLOW_BUTTON {
+                            win32_set_tool_rect(state.tooltip, handle, id.tool_id(), rect);
+                        }
+                    }
+                }
+
+                win32_relay_mouse_move(state.tooltip, handle, client_point);
+            }
+
+            // Moving on to the caption around the buttons is a WM_MOUSELEAVE
+            if id.is_some() {
+                win32_track_client_leave(handle, state);
+            }
+
+            None
+        }
+        WM_MOUSELEAVE if state.caption_buttons.pressed.is_none() => {
+            win32_set_hovered_caption_button(handle, state, None);
+            None
+        }
+        WM_LBUTTONDOWN => {
+            let id = win32_caption_button_at(handle, state, client_point)?;
+            // Still swallowed, the caption under a disabled button doesn't drag either
+            if !state.caption_buttons.is_enabled(id) {
+                return Some(LRESULT(0));
+            }
+
+            state.caption_buttons.pressed = Some(id);
+            win32_set_capture(handle, state);
+            win32_invalidate_added_caption_buttons(handle, state);
+            Some(LRESULT(0))
+        }
+        WM_LBUTTONUP => {
+            let pressed = state.caption_buttons.pressed.take()?;
+            win32_release_capture(state);
+
+            win32_invalidate_added_caption_buttons(handle, state);
+            if win32_caption_button_at(handle, state, client_point) == Some(pressed) {
+                win32_click_caption_button(handle, pressed);
+            }
+
+            Some(LRESULT(0))
+        }
+        WM_CAPTURECHANGED if state.caption_buttons.pressed.is_some() => {
+            state.caption_buttons.pressed = None;
+            win32_invalidate_added_caption_buttons(handle, state);
+            Some(LRESULT(0))
+        }
+        _ => None,
+    }
+}
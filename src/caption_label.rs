@@ -0,0 +1,108 @@
+use crate::{
+    caption_layout::{CaptionAlign, CaptionItem},
+    dpi::win32_window_dpi,
+    fonts::{win32_caption_font, win32_measure_text},
+    win32_dpi_scale,
+};
+use windows::Win32::{
+    Foundation::{COLORREF, HWND, RECT},
+    Graphics::Gdi::{
+        DrawTextW, SelectObject, SetBkMode, SetTextColor, DT_CENTER, DT_END_ELLIPSIS, DT_NOPREFIX,
+        DT_SINGLELINE, DT_VCENTER, HDC, HFONT, TRANSPARENT,
+    },
+};
+
+// Text in the caption the user can only read, e.g. a clock or the name of the
+// environment, see Window::set_caption_label. Clicks and drags on it do nothing.
+pub struct CaptionLabel {
+    text: Vec<u16>,
+    align: CaptionAlign,
+    color: Option<COLORREF>,
+}
+
+// Room left and right of the text, and how narrow it gets with an ellipsis before it
+// goes away, at 96 dpi
+const WIN32_CAPTION_LABEL_PADDING: i32 = 8;
+const WIN32_CAPTION_LABEL_MIN_WIDTH: i32 = 40;
+// Gives way after the title and before the application's items and buttons
+const WIN32_CAPTION_LABEL_SHRINK: u32 = 2;
+
+impl CaptionLabel {
+    // Before the trailing items and buttons, in the color of the title by default
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.encode_utf16().collect(),
+            align: CaptionAlign::Trailing,
+            color: None,
+        }
+    }
+
+    pub fn align(mut self, align: CaptionAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn color(mut self, color: COLORREF) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub(crate) fn alignment(&self) -> CaptionAlign {
+        self.align
+    }
+
+    // False when it already reads text
+    pub(crate) fn set_text(&mut self, text: &str) -> bool {
+        let text: Vec<u16> = text.encode_utf16().collect();
+        if self.text == text {
+            return false;
+        }
+
+        self.text = text;
+        true
+    }
+}
+
+// As wide as the text, at the window's dpi
+pub(crate) fn win32_caption_label_item(label: &CaptionLabel, dpi: u32) -> CaptionItem {
+    let text_size = win32_measure_text(win32_caption_font(dpi), &[&label.text], DT_NOPREFIX)[0];
+    let width = text_size.cx + 2 * win32_dpi_scale(WIN32_CAPTION_LABEL_PADDING, dpi);
+    let min_width = win32_dpi_scale(WIN32_CAPTION_LABEL_MIN_WIDTH, dpi);
+    CaptionItem::new(label.align, width)
+        .min_width(min_width)
+        .shrink(WIN32_CAPTION_LABEL_SHRINK)
+}
+
+// Squeezed below its width, the text ends in an ellipsis
+pub(crate) fn win32_paint_caption_label(
+    hdc: HDC,
+    handle: HWND,
+    label: &CaptionLabel,
+    rect: &RECT,
+    item_color: COLORREF,
+) {
+    let dpi = win32_window_dpi(handle);
+    let padding = win32_dpi_scale(WIN32_CAPTION_LABEL_PADDING, dpi);
+    let mut text_rect = RECT {
+        left: rect.left + padding,
+        right: rect.right - padding,
+        ..*rect
+    };
+    let font = win32_caption_font(dpi);
+
+    unsafe {
+        let old_font = (font != HFONT(0)).then(|| SelectObject(hdc, font));
+        SetBkMode(hdc, TRANSPARENT);
+        SetTextColor(hdc, label.color.unwrap_or(item_color));
+        let mut text = label.text.clone();
+        DrawTextW(
+            hdc,
+            &mut text,
+            &mut text_rect,
+            DT_CENTER | DT_VCENTER | DT_SINGLELINE | DT_NOPREFIX | DT_END_ELLIPSIS,
+        );
+        if let Some(old_font) = old_font {
+            SelectObject(hdc, old_font);
+        }
+    }
+}
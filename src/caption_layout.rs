@@ -0,0 +1,273 @@
+// Which end of the caption an item sticks to, see CaptionItem
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CaptionAlign {
+    // After the ones before it, from the left edge on
+    #[default]
+    Leading,
+    // Centered in the caption as a group, as far as the others leave room
+    Center,
+    // Before the ones after it, up to the caption buttons
+    Trailing,
+}
+
+// A widget of the application's in the caption, see Window::set_caption_items. The
+// window works out where it goes along with its own widgets, the application only says
+// how wide it likes to be. Sizes are at 96 dpi.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CaptionItem {
+    align: CaptionAlign,
+    width: i32,
+    min_width: i32,
+    grow: u32,
+    shrink: u32,
+}
+
+impl CaptionItem {
+    pub fn new(align: CaptionAlign, width: i32) -> Self {
+        Self {
+            align,
+            width,
+            min_width: width,
+            grow: 0,
+            shrink: 1,
+        }
+    }
+
+    // Squeezed down to this before it goes away altogether, the full width by default
+    pub fn min_width(mut self, min_width: i32) -> Self {
+        self.min_width = min_width.min(self.width);
+        self
+    }
+
+    // Share of the room left over, none by default
+    pub fn grow(mut self, grow: u32) -> Self {
+        self.grow = grow;
+        self
+    }
+
+    // When the caption is too narrow, items with a higher priority give way first, 0
+    // never does. 1 by default, like the caption buttons added by the application. The
+    // title gives way before any of them.
+    pub fn shrink(mut self, priority: u32) -> Self {
+        self.shrink = priority;
+        self
+    }
+
+    pub(crate) fn align(&self) -> CaptionAlign {
+        self.align
+    }
+
+    // At the window's dpi
+    pub(crate) fn scaled(self, scale: impl Fn(i32) -> i32) -> Self {
+        Self {
+            width: scale(self.width),
+            min_width: scale(self.min_width),
+            ..self
+        }
+    }
+}
+
+// Widths from the items' sizes and priorities. Too wide, the highest shrink priority
+// goes down to its minimum and then to nothing before the next one is touched, in the
+// order the items were given. Room to spare is shared out by grow.
+fn win32_item_widths(available: i32, items: &[CaptionItem]) -> Vec<i32> {
+    // Summed in i64, a few wide items add up past i32
+    let mut widths: Vec<i64> = items.iter().map(|item| item.width.max(0) as i64).collect();
+    let mut excess = widths.iter().sum::<i64>() - available.max(0) as i64;
+
+    if excess > 0 {
+        let mut priorities: Vec<u32> = items
+            .iter()
+            .map(|item| item.shrink)
+            .filter(|&shrink| shrink > 0)
+            .collect();
+        priorities.sort_unstable_by(|a, b| b.cmp(a));
+        priorities.dedup();
+
+        for priority in priorities {
+            let shrinking = || (0..items.len()).filter(|&index| items[index].shrink == priority);
+            for index in shrinking() {
+                let give =
+                    (widths[index] - items[index].min_width.max(0) as i64).clamp(0, excess.max(0));
+                widths[index] -= give;
+                excess -= give;
+            }
+            for index in shrinking() {
+                if excess <= 0 {
+                    break;
+                }
+                excess -= widths[index];
+                widths[index] = 0;
+            }
+        }
+    }
+
+    // Also what dropping an item freed more than it had to
+    let total_grow: i64 = items.iter().map(|item| item.grow as i64).sum();
+    if excess < 0 && total_grow > 0 {
+        let spare = -excess;
+        let mut shared = 0;
+        for (index, item) in items.iter().enumerate() {
+            let share = spare * item.grow as i64 / total_grow;
+            widths[index] += share;
+            shared += share;
+        }
+        // Rounding leftovers to the last one that grows
+        if let Some(index) = items.iter().rposition(|item| item.grow > 0) {
+            widths[index] += spare - shared;
+        }
+    }
+
+    // None is wider than available or than it was given, each fits in i32 again
+    widths.into_iter().map(|width| width as i32).collect()
+}
+
+// Left and right edge of every item between left and right. Items that had to go get
+// an empty span where they would have been, and so do the ones that don't fit after
+// items that never shrink.
+pub(crate) fn win32_layout_caption_items(
+    left: i32,
+    right: i32,
+    items: &[CaptionItem],
+) -> Vec<(i32, i32)> {
+    let widths = win32_item_widths(right.saturating_sub(left), items);
+    let mut spans = vec![(left, left); items.len()];
+    let aligned = |align| (0..items.len()).filter(move |&index| items[index].align == align);
+
+    let mut leading_right = left;
+    for index in aligned(CaptionAlign::Leading) {
+        spans[index] = (leading_right, leading_right.saturating_add(widths[index]));
+        leading_right = spans[index].1;
+    }
+
+    let mut trailing_left = right;
+    let trailing: Vec<usize> = aligned(CaptionAlign::Trailing).collect();
+    for &index in trailing.iter().rev() {
+        let item_left = trailing_left
+            .saturating_sub(widths[index])
+            .max(leading_right);
+        spans[index] = (item_left, trailing_left.max(item_left));
+        trailing_left = item_left;
+    }
+
+    let center_width: i64 = aligned(CaptionAlign::Center)
+        .map(|index| widths[index] as i64)
+        .sum();
+    let mut x = ((left as i64 + right as i64) / 2 - center_width / 2)
+        .min(trailing_left as i64 - center_width)
+        .max(leading_right as i64) as i32;
+    for index in aligned(CaptionAlign::Center) {
+        let item_right = x.saturating_add(widths[index]).min(trailing_left).max(x);
+        spans[index] = (x, item_right);
+        x = item_right;
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use CaptionAlign::{Center, Leading, Trailing};
+
+    #[test]
+    fn items_that_fit_keep_their_widths() {
+        let items = [
+            CaptionItem::new(Leading, 100),
+            CaptionItem::new(Trailing, 50),
+        ];
+        assert_eq!(win32_item_widths(300, &items), [100, 50]);
+    }
+
+    #[test]
+    fn highest_shrink_priority_gives_way_first() {
+        let items = [
+            CaptionItem::new(Leading, 100).min_width(50),
+            CaptionItem::new(Leading, 100).min_width(20).shrink(2),
+            CaptionItem::new(Leading, 100).shrink(0),
+        ];
+        // Down towards its minimum only as far as needed
+        assert_eq!(win32_item_widths(230, &items), [100, 30, 100]);
+        // Gone before the next priority is touched
+        assert_eq!(win32_item_widths(150, &items), [50, 0, 100]);
+        // Shrink 0 never gives way, even if it still doesn't fit
+        assert_eq!(win32_item_widths(50, &items), [0, 0, 100]);
+    }
+
+    #[test]
+    fn spare_room_is_shared_by_grow() {
+        let items = [
+            CaptionItem::new(Leading, 100).grow(1),
+            CaptionItem::new(Leading, 50),
+            CaptionItem::new(Trailing, 10).grow(2),
+        ];
+        // 46.67 and 93.33, the pixel lost to rounding goes to the last one
+        assert_eq!(win32_item_widths(300, &items), [146, 50, 104]);
+    }
+
+    #[test]
+    fn room_freed_by_a_dropped_item_is_shared_by_grow() {
+        let items = [
+            CaptionItem::new(Leading, 100),
+            CaptionItem::new(Leading, 50).grow(1).shrink(0),
+        ];
+        assert_eq!(win32_item_widths(120, &items), [0, 120]);
+    }
+
+    #[test]
+    fn huge_widths_and_grows_do_not_overflow() {
+        let items = [CaptionItem::new(Leading, i32::MAX).grow(u32::MAX); 3];
+        assert_eq!(win32_item_widths(i32::MAX, &items), [0, 0, i32::MAX]);
+
+        let items = [CaptionItem::new(Leading, 10).grow(u32::MAX); 2];
+        assert_eq!(win32_item_widths(100, &items), [50, 50]);
+
+        let items = [CaptionItem::new(Center, i32::MAX).shrink(0); 2];
+        assert_eq!(
+            win32_layout_caption_items(i32::MIN, i32::MAX, &items),
+            [(-i32::MAX, 0), (0, i32::MAX)]
+        );
+    }
+
+    #[test]
+    fn items_line_up_at_their_ends() {
+        let items = [
+            CaptionItem::new(Leading, 50),
+            CaptionItem::new(Trailing, 40),
+            CaptionItem::new(Center, 60),
+            CaptionItem::new(Leading, 30),
+            CaptionItem::new(Trailing, 20),
+        ];
+        assert_eq!(
+            win32_layout_caption_items(0, 300, &items),
+            [(0, 50), (240, 280), (120, 180), (50, 80), (280, 300)]
+        );
+    }
+
+    #[test]
+    fn centered_items_move_aside_for_the_trailing_ones() {
+        let items = [
+            CaptionItem::new(Leading, 20),
+            CaptionItem::new(Center, 60),
+            CaptionItem::new(Trailing, 100),
+        ];
+        assert_eq!(
+            win32_layout_caption_items(0, 200, &items),
+            [(0, 20), (40, 100), (100, 200)]
+        );
+    }
+
+    #[test]
+    fn items_after_ones_that_never_shrink_get_empty_spans() {
+        let items = [
+            CaptionItem::new(Leading, 120).shrink(0),
+            CaptionItem::new(Trailing, 50).shrink(0),
+            CaptionItem::new(Center, 10).shrink(0),
+        ];
+        assert_eq!(
+            win32_layout_caption_items(0, 100, &items),
+            [(0, 120), (120, 120), (120, 120)]
+        );
+    }
+}
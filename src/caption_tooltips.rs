@@ -0,0 +1,213 @@
+use crate::{
+    caption_buttons::{win32_caption_button_layout, CaptionButtonId},
+    get_x_param, get_y_param,
+    status_led::WIN32_STATUS_LED_TOOL_ID,
+    tooltip::{win32_add_tool, win32_relay_mouse_move, win32_remove_tool, win32_set_tool_rect},
+    wide::win32_to_wide,
+    win32_titlebar_rect, win32_with_window_state, WindowState,
+};
+use windows::Win32::{
+    Foundation::{HWND, LPARAM, LRESULT, POINT, RECT, WPARAM},
+    UI::WindowsAndMessaging::WM_MOUSEMOVE,
+};
+
+// A widget in the caption a tooltip can be given to, see Window::set_caption_tooltip
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaptionWidget {
+    // Of Window::set_caption_items, by its index there
+    Item(usize),
+    // See Window::set_caption_label
+    Label,
+    // See Window::set_status_led, instead of the tooltip the led was made with
+    StatusLed,
+    // Added with Window::add_caption_button, instead of the tooltip it was made with
+    Button(CaptionButtonId),
+}
+
+const WIN32_CAPTION_LABEL_TOOL_ID: usize = 0xfe;
+// Far past the ids of the added buttons
+const WIN32_CAPTION_ITEM_TOOL_ID_BASE: usize = 0x1000_0000;
+
+impl CaptionWidget {
+    fn tool_id(self) -> usize {
+        match self {
+            Self::Item(index) => WIN32_CAPTION_ITEM_TOOL_ID_BASE + index,
+            Self::Label => WIN32_CAPTION_LABEL_TOOL_ID,
+            Self::StatusLed => WIN32_STATUS_LED_TOOL_ID,
+            Self::Button(id) => id.tool_id(),
+        }
+    }
+
+    // The led and the buttons have a tool of their own already, items and the label
+    // only get one along with a tooltip
+    fn needs_tool(self) -> bool {
+        matches!(self, Self::Item(_) | Self::Label)
+    }
+}
+
+// What the tooltip of a caption widget reads. It shows after the usual hover delay,
+// like the ones of the caption buttons.
+pub struct CaptionTooltip {
+    text: Win32TooltipText,
+}
+
+enum Win32TooltipText {
+    // Null terminated for TTN_GETDISPINFOW
+    Fixed(Vec<u16>),
+    // Taken out while it runs
+    Computed(Option<Box<dyn FnMut() -> String>>),
+}
+
+impl CaptionTooltip {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: Win32TooltipText::Fixed(win32_to_wide(text)),
+        }
+    }
+
+    // Asked for the text every time the tooltip is about to show, e.g. for how long ago
+    // something was synced
+    pub fn computed(text: impl FnMut() -> String + 'static) -> Self {
+        Self {
+            text: Win32TooltipText::Computed(Some(Box::new(text))),
+        }
+    }
+}
+
+// The tooltips of Window::set_caption_tooltip, one for each widget
+#[derive(Default)]
+pub(crate) struct CaptionTooltips {
+    tooltips: Vec<(CaptionWidget, CaptionTooltip)>,
+    // The text computed last, the tooltip copies it once TTN_GETDISPINFOW returns
+    computed: Vec<u16>,
+}
+
+impl CaptionTooltips {
+    // Items and the label that have a tool for their tooltip
+    pub(crate) fn tool_ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.tooltips
+            .iter()
+            .map(|&(widget, _)| widget)
+            .filter(|widget| widget.needs_tool())
+            .map(CaptionWidget::tool_id)
+    }
+}
+
+// Replaces the tooltip of widget, None takes it away
+pub(crate) fn win32_set_caption_tooltip(
+    handle: HWND,
+    state: &mut WindowState,
+    widget: CaptionWidget,
+    tooltip: Option<CaptionTooltip>,
+) {
+    let tooltips = &mut state.caption_tooltips.tooltips;
+    let index = tooltips.iter().position(|&(other, _)| other == widget);
+    let had_tooltip = index.is_some();
+    let has_tooltip = tooltip.is_some();
+    match (index, tooltip) {
+        (Some(index), Some(tooltip)) => tooltips[index].1 = tooltip,
+        (None, Some(tooltip)) => tooltips.push((widget, tooltip)),
+        (Some(index), None) => {
+            tooltips.remove(index);
+        }
+        (None, None) => {}
+    }
+
+    if state.tooltip != HWND(0) && widget.needs_tool() {
+        match (had_tooltip, has_tooltip) {
+            (false, true) => win32_add_tool(state.tooltip, handle, widget.tool_id()),
+            (true, false) => win32_remove_tool(state.tooltip, handle, widget.tool_id()),
+            _ => {}
+        }
+    }
+}
+
+// For TTN_GETDISPINFOW, None when tool_id belongs to no widget with a tooltip. Called
+// without the state borrowed, a computed text can call back into the Window.
+pub(crate) fn win32_caption_tooltip_text(handle: HWND, tool_id: usize) -> Option<*const u16> {
+    let computed = win32_with_window_state(handle, |state| {
+        let (widget, tooltip) = state
+            .caption_tooltips
+            .tooltips
+            .iter_mut()
+            .find(|(widget, _)| widget.tool_id() == tool_id)?;
+        match &mut tooltip.text {
+            Win32TooltipText::Fixed(text) => Some(Err(text.as_ptr())),
+            Win32TooltipText::Computed(compute) => Some(Ok((*widget, compute.take()?))),
+        }
+    })??;
+    let (widget, mut compute) = match computed {
+        Ok(computed) => computed,
+        Err(text) => return Some(text),
+    };
+
+    let text = compute();
+
+    win32_with_window_state(handle, |state| {
+        // Unless the tooltip was replaced or removed meanwhile
+        if let Some((_, tooltip)) = state
+            .caption_tooltips
+            .tooltips
+            .iter_mut()
+            .find(|(other, _)| *other == widget)
+        {
+            if let Win32TooltipText::Computed(slot @ None) = &mut tooltip.text {
+                *slot = Some(compute);
+            }
+        }
+
+        state.caption_tooltips.computed = win32_to_wide(&text);
+        state.caption_tooltips.computed.as_ptr()
+    })
+}
+
+// Moves the tools of items and the label along with the caption layout. WM_NCHITTEST
+// makes them client area, so the mouse moves over them arrive here.
+pub(crate) fn win32_caption_tooltips_input(
+    handle: HWND,
+    state: &mut WindowState,
+    message: u32,
+    _w_param: WPARAM,
+    l_param: LPARAM,
+) -> Option<LRESULT> {
+    if message != WM_MOUSEMOVE
+        || state.tooltip == HWND(0)
+        || state.caption_tooltips.tool_ids().next().is_none()
+    {
+        return None;
+    }
+
+    let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+    let layout = win32_caption_button_layout(handle, state, &title_bar_rect);
+    for &(widget, _) in &state.caption_tooltips.tooltips {
+        let rect = match widget {
+            CaptionWidget::Item(index) => layout.items.get(index).copied(),
+            CaptionWidget::Label => layout.caption_label,
+            CaptionWidget::StatusLed | CaptionWidget::Button(_) => continue,
+        };
+        win32_set_tool_rect(
+            state.tooltip,
+            handle,
+            widget.tool_id(),
+            rect.unwrap_or_default(),
+        );
+    }
+
+    // The caption buttons relay it themselves once their tools are in place
+    if state.caption_buttons.is_empty() {
+        let client_point = POINT {
+            x: get_x_param(l_param),
+            y: get_y_param(l_param),
+        };
+        win32_relay_mouse_move(state.tooltip, handle, client_point);
+    }
+
+    None
+}
+
+// Their tools would cover the caption at its old size until the next mouse move
+pub(crate) fn win32_clear_caption_tool_rects(handle: HWND, state: &WindowState) {
+    for id in state.caption_tooltips.tool_ids() {
+        win32_set_tool_rect(state.tooltip, handle, id, RECT::default());
+    }
+}
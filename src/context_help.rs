@@ -0,0 +1,191 @@
+use crate::{
+    caption_buttons::{win32_caption_button_at, WIN32_OVERFLOW_BUTTON},
+    deferred::win32_defer,
+    get_x_param, get_y_param, win32_caption_icon_rect, win32_release_capture, win32_set_capture,
+    win32_titlebar_rect, win32_window_is_maximized, win32_with_window_state, CaptionButtonId,
+    CaptionButtonName, CustomTitleBarButtonRects, CustomTitleBarHoveredButton, WindowState,
+};
+use windows::Win32::{
+    Foundation::{HWND, LPARAM, LRESULT, POINT, WPARAM},
+    Graphics::Gdi::{ClientToScreen, PtInRect},
+    UI::{
+        Input::KeyboardAndMouse::VK_ESCAPE,
+        WindowsAndMessaging::{
+            IsChild, LoadCursorW, SetCursor, WindowFromPoint, IDC_HELP, WM_CAPTURECHANGED,
+            WM_KEYDOWN, WM_LBUTTONDOWN, WM_MBUTTONDOWN, WM_MOUSEMOVE, WM_RBUTTONDOWN,
+        },
+    },
+};
+
+// A click in context help mode, see Window::set_help_handler
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HelpRequest {
+    pub target: HelpTarget,
+    // Client coordinates of the click
+    pub point: POINT,
+}
+
+// The part of the window that was clicked for help
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HelpTarget {
+    // One of the window's own caption buttons, by what it is called right now
+    CaptionButton(CaptionButtonName),
+    // A button added with Window::add_caption_button
+    AddedCaptionButton(CaptionButtonId),
+    Icon,
+    // The rest of the caption: title, tabs or breadcrumb
+    Caption,
+    // The child window under the click, or the window itself for its own content
+    Content(HWND),
+}
+
+// Like the ? button of WS_EX_CONTEXTHELP: the help cursor shows until the next click,
+// which asks for help instead of doing what it normally would
+pub(crate) fn win32_enter_context_help(handle: HWND) {
+    win32_with_window_state(handle, |state| {
+        if state.help_handler.is_none() || state.context_help {
+            return;
+        }
+
+        state.context_help = true;
+        win32_set_capture(handle, state);
+        unsafe { win32_set_help_cursor() };
+    });
+}
+
+unsafe fn win32_set_help_cursor() {
+    match LoadCursorW(None, IDC_HELP) {
+        Ok(cursor) => {
+            SetCursor(cursor);
+        }
+        Err(e) => eprintln!("Failed to load help cursor: {}", e.message()),
+    }
+}
+
+fn win32_leave_context_help(state: &mut WindowState) {
+    // Cleared first, releasing the capture sends the WM_CAPTURECHANGED that would
+    // otherwise cancel again
+    state.context_help = false;
+    win32_release_capture(state);
+}
+
+// None for a click outside the window, or on the help button again
+fn win32_help_target(handle: HWND, state: &WindowState, client_point: POINT) -> Option<HelpTarget> {
+    let mut screen_point = client_point;
+    unsafe { ClientToScreen(handle, &mut screen_point) };
+    let window = unsafe { WindowFromPoint(screen_point) };
+    if window != handle && !unsafe { IsChild(handle, window) }.as_bool() {
+        return None;
+    }
+
+    let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+    if window != handle || client_point.y >= title_bar_rect.bottom {
+        return Some(HelpTarget::Content(window));
+    }
+
+    let button_rects =
+        CustomTitleBarButtonRects::win32_get_title_bar_button_rects(handle, state, &title_bar_rect);
+    let button = button_rects.button_at(client_point);
+    if button != CustomTitleBarHoveredButton::None {
+        let name = CaptionButtonName::for_button(
+            button,
+            win32_window_is_maximized(handle),
+            state.shade_restore_height.is_some(),
+        )?;
+        return Some(HelpTarget::CaptionButton(name));
+    }
+
+    // The » button has no help of its own, it is part of the caption
+    if let Some(id) = win32_caption_button_at(handle, state, client_point)
+        .filter(|&id| id != WIN32_OVERFLOW_BUTTON)
+    {
+        if state.help_button == Some(id) {
+            return None;
+        }
+        // Named ones, like the pin button, get the help of the button they stand in for
+        return Some(match state.caption_buttons.name(id) {
+            Some(name) => HelpTarget::CaptionButton(name),
+            None => HelpTarget::AddedCaptionButton(id),
+        });
+    }
+
+    let over_icon = win32_caption_icon_rect(handle, state, &title_bar_rect)
+        .is_some_and(|icon_rect| unsafe { PtInRect(&icon_rect, client_point) }.as_bool());
+    Some(if over_icon {
+        HelpTarget::Icon
+    } else {
+        HelpTarget::Caption
+    })
+}
+
+// Deferred like the event handler, see win32_dispatch_event
+fn win32_request_help(handle: HWND, request: HelpRequest) {
+    win32_defer(move || {
+        let Some(mut handler) =
+            win32_with_window_state(handle, |state| state.help_handler.take()).flatten()
+        else {
+            return;
+        };
+
+        handler(request);
+
+        win32_with_window_state(handle, |state| {
+            if state.help_handler.is_none() {
+                state.help_handler = Some(handler);
+            }
+        });
+    });
+}
+
+// Mouse and keyboard input while in context help mode, which has the mouse captured so
+// even the caption sends client messages. Everything else waits until it's over.
+pub(crate) fn win32_context_help_input(
+    handle: HWND,
+    state: &mut WindowState,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> Option<LRESULT> {
+    if !state.context_help {
+        return None;
+    }
+
+    match message {
+        // Nothing hovers meanwhile, and the cursor stays the help cursor
+        WM_MOUSEMOVE => {
+            unsafe { win32_set_help_cursor() };
+            Some(LRESULT(0))
+        }
+        WM_LBUTTONDOWN => {
+            let client_point = POINT {
+                x: get_x_param(l_param),
+                y: get_y_param(l_param),
+            };
+            let target = win32_help_target(handle, state, client_point);
+            win32_leave_context_help(state);
+            if let Some(target) = target {
+                let request = HelpRequest {
+                    target,
+                    point: client_point,
+                };
+                win32_request_help(handle, request);
+            }
+
+            Some(LRESULT(0))
+        }
+        WM_RBUTTONDOWN | WM_MBUTTONDOWN => {
+            win32_leave_context_help(state);
+            Some(LRESULT(0))
+        }
+        WM_KEYDOWN if w_param.0 == VK_ESCAPE.0 as usize => {
+            win32_leave_context_help(state);
+            Some(LRESULT(0))
+        }
+        // Another window took the mouse, e.g. after Alt+Tab
+        WM_CAPTURECHANGED => {
+            state.context_help = false;
+            None
+        }
+        _ => None,
+    }
+}
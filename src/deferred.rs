@@ -0,0 +1,72 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+};
+
+thread_local! {
+    // Callbacks waiting for the window procedures on this thread to let go of the
+    // window states, see win32_defer
+    static DEFERRED: RefCell<VecDeque<Box<dyn FnOnce()>>> = RefCell::new(VecDeque::new());
+    // Window procedure calls on this thread holding a window's state, nested ones
+    // included
+    static STATE_HOLDERS: Cell<u32> = const { Cell::new(0) };
+}
+
+// Runs callback once no window procedure holds a window's state, right after the
+// current message is handled. The application's callbacks go through here so they can
+// call back into the Window. Deferred outside a window procedure, e.g. from a Window
+// method, it waits for the next message. In the order they were deferred.
+pub(crate) fn win32_defer(callback: impl FnOnce() + 'static) {
+    DEFERRED.with(|deferred| deferred.borrow_mut().push_back(Box::new(callback)));
+}
+
+fn win32_run_deferred() {
+    // One at a time, a callback can defer more or reach a window procedure that runs
+    // the rest
+    while let Some(callback) = DEFERRED.with(|deferred| deferred.borrow_mut().pop_front()) {
+        callback();
+    }
+}
+
+// Kept by the window procedure for as long as it has the state. The last one to go
+// runs the deferred callbacks.
+pub(crate) struct StateHold(());
+
+impl StateHold {
+    pub(crate) fn new() -> Self {
+        STATE_HOLDERS.set(STATE_HOLDERS.get() + 1);
+        Self(())
+    }
+}
+
+impl Drop for StateHold {
+    fn drop(&mut self) {
+        let holders = STATE_HOLDERS.get() - 1;
+        STATE_HOLDERS.set(holders);
+        if holders == 0 {
+            win32_run_deferred();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn deferred_callbacks_wait_for_the_last_hold() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let outer = StateHold::new();
+        let inner = StateHold::new();
+        for call in 0..2 {
+            let calls = Rc::clone(&calls);
+            win32_defer(move || calls.borrow_mut().push(call));
+        }
+
+        drop(inner);
+        assert!(calls.borrow().is_empty());
+        drop(outer);
+        assert_eq!(*calls.borrow(), [0, 1]);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{win32_monitor_dpi, win32_mul_div, DEFAULT_DPI};
+use anyhow::{anyhow, Result};
+use std::{ffi::c_void, mem::size_of, mem::transmute, sync::OnceLock};
+use windows::{
+    core::{s, w, PCSTR},
+    Win32::{
+        Foundation::{BOOL, HWND},
+        Graphics::Gdi::{
+            GetDC, GetDeviceCaps, MonitorFromWindow, ReleaseDC, LOGFONTW, LOGPIXELSX,
+            MONITOR_DEFAULTTONEAREST,
+        },
+        System::LibraryLoader::{GetModuleHandleW, GetProcAddress},
+        UI::{
+            HiDpi::{
+                SetProcessDpiAwareness, DPI_AWARENESS_CONTEXT,
+                DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, PROCESS_PER_MONITOR_DPI_AWARE,
+            },
+            WindowsAndMessaging::{
+                GetSystemMetrics, SystemParametersInfoW, SPI_GETICONTITLELOGFONT,
+                SYSTEM_METRICS_INDEX, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+            },
+        },
+    },
+};
+
+// The per window dpi functions came with the Windows 10 Anniversary Update and
+// per monitor v2 awareness with the Creators Update. Linking them directly would
+// keep the application from starting on anything older, so they are looked up
+// and everything falls back to the monitor dpi of Windows 8.1.
+pub(crate) type Win32Proc = unsafe extern "system" fn() -> isize;
+type Win32SetProcessDpiAwarenessContext = unsafe extern "system" fn(DPI_AWARENESS_CONTEXT) -> BOOL;
+type Win32GetDpiForWindow = unsafe extern "system" fn(HWND) -> u32;
+type Win32GetSystemMetricsForDpi = unsafe extern "system" fn(SYSTEM_METRICS_INDEX, u32) -> i32;
+type Win32SystemParametersInfoForDpi =
+    unsafe extern "system" fn(u32, u32, *mut c_void, u32, u32) -> BOOL;
+
+static SET_PROCESS_DPI_AWARENESS_CONTEXT: OnceLock<Option<Win32SetProcessDpiAwarenessContext>> =
+    OnceLock::new();
+static GET_DPI_FOR_WINDOW: OnceLock<Option<Win32GetDpiForWindow>> = OnceLock::new();
+static GET_SYSTEM_METRICS_FOR_DPI: OnceLock<Option<Win32GetSystemMetricsForDpi>> = OnceLock::new();
+static SYSTEM_PARAMETERS_INFO_FOR_DPI: OnceLock<Option<Win32SystemParametersInfoForDpi>> =
+    OnceLock::new();
+
+// Looked up by name in user32, None where this Windows doesn't have it yet
+pub(crate) fn win32_user32_proc(name: PCSTR) -> Option<Win32Proc> {
+    let module = unsafe { GetModuleHandleW(w!("user32.dll")) }.ok()?;
+    unsafe { GetProcAddress(module, name) }
+}
+
+// Call before the first window is created. Per monitor v2 where there is one, which
+// also scales the system menu and tooltips, otherwise per monitor awareness of 8.1.
+pub fn enable_dpi_awareness() -> Result<()> {
+    let set_context = SET_PROCESS_DPI_AWARENESS_CONTEXT.get_or_init(|| {
+        win32_user32_proc(s!("SetProcessDpiAwarenessContext"))
+            .map(|proc| unsafe { transmute::<Win32Proc, Win32SetProcessDpiAwarenessContext>(proc) })
+    });
+
+    if let Some(set_context) = set_context {
+        if unsafe { set_context(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) }.as_bool() {
+            return Ok(());
+        }
+    }
+
+    unsafe { SetProcessDpiAwareness(PROCESS_PER_MONITOR_DPI_AWARE) }
+        .map_err(|e| anyhow!("Failed to set DPI awareness: {}", e.message()))
+}
+
+// GetDpiForWindow, or the dpi of the monitor the window is on
+pub(crate) fn win32_window_dpi(handle: HWND) -> u32 {
+    let get_dpi = GET_DPI_FOR_WINDOW.get_or_init(|| {
+        win32_user32_proc(s!("GetDpiForWindow"))
+            .map(|proc| unsafe { transmute::<Win32Proc, Win32GetDpiForWindow>(proc) })
+    });
+
+    match get_dpi {
+        Some(get_dpi) => unsafe { get_dpi(handle) },
+        None => win32_monitor_dpi(unsafe { MonitorFromWindow(handle, MONITOR_DEFAULTTONEAREST) }),
+    }
+}
+
+// What GetSystemMetrics returns is for the dpi of the primary monitor at logon
+fn win32_system_dpi() -> u32 {
+    static SYSTEM_DPI: OnceLock<u32> = OnceLock::new();
+    *SYSTEM_DPI.get_or_init(|| unsafe {
+        let hdc = GetDC(None);
+        let dpi = GetDeviceCaps(hdc, LOGPIXELSX);
+        ReleaseDC(None, hdc);
+        if dpi > 0 {
+            dpi as u32
+        } else {
+            DEFAULT_DPI as u32
+        }
+    })
+}
+
+// GetSystemMetricsForDpi, or the system dpi metric scaled to dpi
+pub(crate) fn win32_system_metrics_for_dpi(index: SYSTEM_METRICS_INDEX, dpi: u32) -> i32 {
+    let get_metrics = GET_SYSTEM_METRICS_FOR_DPI.get_or_init(|| {
+        win32_user32_proc(s!("GetSystemMetricsForDpi"))
+            .map(|proc| unsafe { transmute::<Win32Proc, Win32GetSystemMetricsForDpi>(proc) })
+    });
+
+    match get_metrics {
+        Some(get_metrics) => unsafe { get_metrics(index, dpi) },
+        None => {
+            let value = unsafe { GetSystemMetrics(index) };
+            win32_mul_div(value, dpi, win32_system_dpi())
+        }
+    }
+}
+
+// The font of icon titles, which the caption text uses too, with its height for dpi
+pub(crate) fn win32_icon_title_font(dpi: u32) -> Option<LOGFONTW> {
+    let parameters_info = SYSTEM_PARAMETERS_INFO_FOR_DPI.get_or_init(|| {
+        win32_user32_proc(s!("SystemParametersInfoForDpi"))
+            .map(|proc| unsafe { transmute::<Win32Proc, Win32SystemParametersInfoForDpi>(proc) })
+    });
+
+    let mut logical_font = LOGFONTW::default();
+    let logical_font_ptr = &mut logical_font as *mut LOGFONTW as *mut c_void;
+    let queried_dpi = match parameters_info {
+        Some(parameters_info) => {
+            unsafe {
+                parameters_info(
+                    SPI_GETICONTITLELOGFONT.0,
+                    size_of::<LOGFONTW>() as _,
+                    logical_font_ptr,
+                    0,
+                    DEFAULT_DPI as u32,
+                )
+            }
+            .ok()
+            .ok()?;
+            DEFAULT_DPI as u32
+        }
+        None => {
+            unsafe {
+                SystemParametersInfoW(
+                    SPI_GETICONTITLELOGFONT,
+                    size_of::<LOGFONTW>() as _,
+                    Some(logical_font_ptr),
+                    SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+                )
+            }
+            .ok()?;
+            win32_system_dpi()
+        }
+    };
+
+    // Scaled here rather than queried at dpi, so it is exact whichever way it was had
+    logical_font.lfHeight = win32_mul_div(logical_font.lfHeight, dpi, queried_dpi);
+    Some(logical_font)
+}
@@ -0,0 +1,86 @@
+// Parts of the window that keyboard focus cycles through with F6
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FocusRegion {
+    // The application's own content, where focus starts
+    Content,
+    // The caption buttons and widgets
+    Caption,
+    // Only takes part when the window has a menu bar, F10 jumps straight to it
+    MenuBar,
+}
+
+// The regions in F6 order and the one that currently has the focus
+pub(crate) struct FocusRegions {
+    order: Vec<FocusRegion>,
+    current: FocusRegion,
+}
+
+impl Default for FocusRegions {
+    fn default() -> Self {
+        Self {
+            order: vec![FocusRegion::Content, FocusRegion::Caption],
+            current: FocusRegion::Content,
+        }
+    }
+}
+
+impl FocusRegions {
+    // Content is always part of the cycle, added in front when missing
+    pub(crate) fn set_order(&mut self, order: &[FocusRegion]) {
+        self.order.clear();
+        if !order.contains(&FocusRegion::Content) {
+            self.order.push(FocusRegion::Content);
+        }
+
+        for &region in order {
+            if !self.order.contains(&region) {
+                self.order.push(region);
+            }
+        }
+
+        if !self.contains(self.current) {
+            self.current = FocusRegion::Content;
+        }
+    }
+
+    // For the menu bar region coming and going with the menu bar, at the end of the cycle
+    pub(crate) fn add(&mut self, region: FocusRegion) {
+        if !self.contains(region) {
+            self.order.push(region);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, region: FocusRegion) {
+        if region != FocusRegion::Content {
+            self.order.retain(|&other| other != region);
+            if self.current == region {
+                self.current = FocusRegion::Content;
+            }
+        }
+    }
+
+    pub(crate) fn contains(&self, region: FocusRegion) -> bool {
+        self.order.contains(&region)
+    }
+
+    pub(crate) fn current(&self) -> FocusRegion {
+        self.current
+    }
+
+    pub(crate) fn set_current(&mut self, region: FocusRegion) {
+        if self.contains(region) {
+            self.current = region;
+        }
+    }
+
+    // The region after the current one in direction (-1 or 1), wrapping around
+    pub(crate) fn next(&self, direction: isize) -> FocusRegion {
+        let count = self.order.len() as isize;
+        let index = self
+            .order
+            .iter()
+            .position(|&region| region == self.current)
+            .unwrap_or(0) as isize;
+        self.order[(index + direction).rem_euclid(count) as usize]
+    }
+}
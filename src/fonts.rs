@@ -0,0 +1,124 @@
+use crate::{dpi::win32_icon_title_font, win32_mul_div};
+use std::{cell::RefCell, thread::LocalKey};
+use windows::Win32::{
+    Foundation::{RECT, SIZE},
+    Graphics::Gdi::{
+        CreateFontIndirectW, DeleteObject, DrawTextW, GetDC, ReleaseDC, SelectObject,
+        DRAW_TEXT_FORMAT, DT_CALCRECT, DT_SINGLELINE, HFONT,
+    },
+    UI::WindowsAndMessaging::{
+        SPI_SETICONTITLELOGFONT, SPI_SETNONCLIENTMETRICS, SYSTEM_PARAMETERS_INFO_ACTION,
+    },
+};
+
+thread_local! {
+    // One caption font for each dpi a window was painted at, windows are only ever
+    // used from the thread that created them
+    static CAPTION_FONTS: RefCell<Vec<(u32, HFONT)>> = const { RefCell::new(Vec::new()) };
+    static SUBTITLE_FONTS: RefCell<Vec<(u32, HFONT)>> = const { RefCell::new(Vec::new()) };
+}
+
+type Win32FontCache = LocalKey<RefCell<Vec<(u32, HFONT)>>>;
+
+// The subtitle line under the title is this much of the caption font, in sixths
+const WIN32_SUBTITLE_FONT_SIXTHS: u32 = 5;
+
+// The icon title font the caption text is drawn with, created on first use for each
+// dpi. Owned by the cache, don't delete it. HFONT(0) if the system font is unknown.
+pub(crate) fn win32_caption_font(dpi: u32) -> HFONT {
+    win32_cached_font(&CAPTION_FONTS, dpi, win32_create_caption_font)
+}
+
+// The smaller font of the subtitle, see Window::set_subtitle. Owned by the cache too.
+pub(crate) fn win32_subtitle_font(dpi: u32) -> HFONT {
+    win32_cached_font(&SUBTITLE_FONTS, dpi, win32_create_subtitle_font)
+}
+
+fn win32_cached_font(cache: &'static Win32FontCache, dpi: u32, create: fn(u32) -> HFONT) -> HFONT {
+    cache.with(|cell| {
+        let mut fonts = cell.borrow_mut();
+        if let Some(&(_, font)) = fonts.iter().find(|&&(font_dpi, _)| font_dpi == dpi) {
+            return font;
+        }
+
+        let font = create(dpi);
+        if font != HFONT(0) {
+            fonts.push((dpi, font));
+        }
+
+        font
+    })
+}
+
+// The user picked another font or size in the settings, fonts are created again on
+// the next paint
+pub(crate) fn win32_clear_caption_fonts() {
+    for cache in [&CAPTION_FONTS, &SUBTITLE_FONTS] {
+        cache.with(|cell| {
+            for (_, font) in cell.borrow_mut().drain(..) {
+                unsafe { DeleteObject(font) };
+            }
+        });
+    }
+}
+
+// Size of each text in font on one line, the way DrawTextW draws it with format, e.g.
+// DT_NOPREFIX for text without mnemonics. Measured on the screen, the result is the
+// same for every window on it.
+pub(crate) fn win32_measure_text(
+    font: HFONT,
+    texts: &[&[u16]],
+    format: DRAW_TEXT_FORMAT,
+) -> Vec<SIZE> {
+    unsafe {
+        let hdc = GetDC(None);
+        let old_font = (font != HFONT(0)).then(|| SelectObject(hdc, font));
+
+        let sizes = texts
+            .iter()
+            .map(|text| {
+                let mut text = text.to_vec();
+                let mut rect = RECT::default();
+                DrawTextW(
+                    hdc,
+                    &mut text,
+                    &mut rect,
+                    format | DT_CALCRECT | DT_SINGLELINE,
+                );
+                SIZE {
+                    cx: rect.right - rect.left,
+                    cy: rect.bottom - rect.top,
+                }
+            })
+            .collect();
+
+        if let Some(old_font) = old_font {
+            SelectObject(hdc, old_font);
+        }
+        ReleaseDC(None, hdc);
+        sizes
+    }
+}
+
+// Whether a WM_SETTINGCHANGE action can change the caption font
+pub(crate) fn win32_affects_caption_font(action: SYSTEM_PARAMETERS_INFO_ACTION) -> bool {
+    action == SPI_SETICONTITLELOGFONT || action == SPI_SETNONCLIENTMETRICS
+}
+
+fn win32_create_caption_font(dpi: u32) -> HFONT {
+    match win32_icon_title_font(dpi) {
+        Some(logical_font) => unsafe { CreateFontIndirectW(&logical_font) },
+        None => HFONT(0),
+    }
+}
+
+fn win32_create_subtitle_font(dpi: u32) -> HFONT {
+    match win32_icon_title_font(dpi) {
+        Some(mut logical_font) => {
+            logical_font.lfHeight =
+                win32_mul_div(logical_font.lfHeight, WIN32_SUBTITLE_FONT_SIXTHS, 6);
+            unsafe { CreateFontIndirectW(&logical_font) }
+        }
+        None => HFONT(0),
+    }
+}
@@ -0,0 +1,209 @@
+use crate::wide::win32_os_to_wide;
+use anyhow::{anyhow, Result};
+use std::{cell::Cell, mem::size_of, path::Path};
+use windows::{
+    core::{Interface, GUID, PCWSTR},
+    Win32::{
+        Foundation::{BOOL, GENERIC_READ},
+        Graphics::{
+            Gdi::{
+                CreateBitmap, CreateDIBSection, DeleteObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB,
+                DIB_RGB_COLORS, HBITMAP,
+            },
+            Imaging::{
+                CLSID_WICImagingFactory, GUID_WICPixelFormat32bppBGRA,
+                GUID_WICPixelFormat32bppPBGRA, IWICBitmapDecoder, IWICBitmapFrameDecode,
+                IWICBitmapSource, IWICImagingFactory, WICBitmapDitherTypeNone,
+                WICBitmapInterpolationModeFant, WICBitmapPaletteTypeCustom,
+                WICDecodeMetadataCacheOnDemand, WICRect,
+            },
+        },
+        System::Com::{
+            CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
+        },
+        UI::WindowsAndMessaging::{CreateIconIndirect, HICON, ICONINFO},
+    },
+};
+
+thread_local! {
+    // COM stays initialized for the rest of the thread once an image was loaded on it
+    static COM_INITIALIZED: Cell<bool> = const { Cell::new(false) };
+}
+
+fn win32_imaging_factory() -> Result<IWICImagingFactory> {
+    COM_INITIALIZED.with(|initialized| {
+        if !initialized.get() {
+            // S_FALSE or RPC_E_CHANGED_MODE when the application initialized COM itself,
+            // either way WIC can be used
+            let _ = unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) };
+            initialized.set(true);
+        }
+    });
+
+    unsafe { CoCreateInstance(&CLSID_WICImagingFactory, None, CLSCTX_INPROC_SERVER) }
+        .map_err(|e| anyhow!("Failed to create WIC imaging factory: {}", e.message()))
+}
+
+// The frame closest to size, an .ico holds one for each size it was drawn at. The
+// smallest one at least as big scales down best, otherwise the biggest there is.
+fn win32_best_frame(decoder: &IWICBitmapDecoder, size: u32) -> Result<IWICBitmapFrameDecode> {
+    let frame_count = unsafe { decoder.GetFrameCount() }.unwrap_or(1);
+    let mut best: Option<(u32, IWICBitmapFrameDecode)> = None;
+    for index in 0..frame_count {
+        let Ok(frame) = (unsafe { decoder.GetFrame(index) }) else {
+            continue;
+        };
+
+        let (mut width, mut height) = (0, 0);
+        if unsafe { frame.GetSize(&mut width, &mut height) }.is_err() {
+            continue;
+        }
+
+        let frame_size = width.max(height);
+        let better = match &best {
+            None => true,
+            Some((best_size, _)) if *best_size < size => frame_size > *best_size,
+            Some((best_size, _)) => frame_size >= size && frame_size < *best_size,
+        };
+        if better {
+            best = Some((frame_size, frame));
+        }
+    }
+
+    best.map(|(_, frame)| frame)
+        .ok_or_else(|| anyhow!("The image has no frames"))
+}
+
+// 32bpp top-down pixels in format of the image at path scaled to width x height. With
+// crop_to_square only the middle square of a wide or tall image is kept.
+fn win32_decode_image(
+    path: &Path,
+    width: i32,
+    height: i32,
+    format: &GUID,
+    crop_to_square: bool,
+) -> Result<Vec<u8>> {
+    let factory = win32_imaging_factory()?;
+    let file_name = win32_os_to_wide(path.as_os_str());
+    let pixels = unsafe {
+        let decoder = factory.CreateDecoderFromFilename(
+            PCWSTR(file_name.as_ptr()),
+            None,
+            GENERIC_READ,
+            WICDecodeMetadataCacheOnDemand,
+        )?;
+        let frame = win32_best_frame(&decoder, width.max(height) as u32)?;
+        let source: IWICBitmapSource = if crop_to_square {
+            let (mut frame_width, mut frame_height) = (0, 0);
+            frame.GetSize(&mut frame_width, &mut frame_height)?;
+            let side = frame_width.min(frame_height);
+            let clipper = factory.CreateBitmapClipper()?;
+            clipper.Initialize(
+                &frame,
+                &WICRect {
+                    X: ((frame_width - side) / 2) as i32,
+                    Y: ((frame_height - side) / 2) as i32,
+                    Width: side as i32,
+                    Height: side as i32,
+                },
+            )?;
+            clipper.cast()?
+        } else {
+            frame.cast()?
+        };
+
+        let scaler = factory.CreateBitmapScaler()?;
+        scaler.Initialize(
+            &source,
+            width as u32,
+            height as u32,
+            WICBitmapInterpolationModeFant,
+        )?;
+        let converter = factory.CreateFormatConverter()?;
+        converter.Initialize(
+            &scaler,
+            format,
+            WICBitmapDitherTypeNone,
+            None,
+            0.0,
+            WICBitmapPaletteTypeCustom,
+        )?;
+
+        let stride = width as u32 * 4;
+        let mut pixels = vec![0u8; (stride * height as u32) as usize];
+        converter.CopyPixels(std::ptr::null(), stride, &mut pixels)?;
+        pixels
+    };
+
+    Ok(pixels)
+}
+
+fn win32_create_dib(pixels: &[u8], width: i32, height: i32) -> Result<HBITMAP> {
+    let info = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width,
+            // Negative for top-down rows, the order WIC copies them in
+            biHeight: -height,
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut bits = std::ptr::null_mut();
+    let bitmap = unsafe { CreateDIBSection(None, &info, DIB_RGB_COLORS, &mut bits, None, 0) }
+        .map_err(|e| anyhow!("Failed to create bitmap: {}", e.message()))?;
+    unsafe { std::ptr::copy_nonoverlapping(pixels.as_ptr(), bits as *mut u8, pixels.len()) };
+    Ok(bitmap)
+}
+
+// An icon of size x size pixels from a PNG, ICO or any other format WIC has a codec
+// for. Icons take straight alpha, the mask is only there because CreateIconIndirect
+// wants one. The caller destroys it.
+pub(crate) fn win32_load_icon(path: &Path, size: i32) -> Result<HICON> {
+    let pixels = win32_decode_image(path, size, size, &GUID_WICPixelFormat32bppBGRA, false)
+        .map_err(|e| anyhow!("Failed to load {}: {}", path.display(), e))?;
+    let color = win32_create_dib(&pixels, size, size)?;
+    let mask = unsafe { CreateBitmap(size, size, 1, 1, None) };
+
+    let icon_info = ICONINFO {
+        fIcon: BOOL(1),
+        hbmMask: mask,
+        hbmColor: color,
+        ..Default::default()
+    };
+    let icon = unsafe { CreateIconIndirect(&icon_info) }
+        .map_err(|e| anyhow!("Failed to create icon: {}", e.message()));
+
+    // The icon has copies of both
+    unsafe {
+        DeleteObject(color);
+        DeleteObject(mask);
+    }
+
+    icon
+}
+
+// The middle square of an image cut to a circle of size pixels across, e.g. for a user
+// picture. Premultiplied for AlphaBlend, with the edge antialiased. The caller deletes
+// it.
+pub(crate) fn win32_load_round_bitmap(path: &Path, size: i32) -> Result<HBITMAP> {
+    let mut pixels = win32_decode_image(path, size, size, &GUID_WICPixelFormat32bppPBGRA, true)
+        .map_err(|e| anyhow!("Failed to load {}: {}", path.display(), e))?;
+
+    // How much of each pixel the circle covers, by its center's distance to the edge
+    let radius = size as f32 / 2.0;
+    for (index, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+        let x = (index as i32 % size) as f32 + 0.5 - radius;
+        let y = (index as i32 / size) as f32 + 0.5 - radius;
+        let coverage = (radius - x.hypot(y) + 0.5).clamp(0.0, 1.0);
+        for channel in pixel {
+            *channel = (*channel as f32 * coverage).round() as u8;
+        }
+    }
+
+    win32_create_dib(&pixels, size, size)
+}
@@ -0,0 +1,117 @@
+use crate::{
+    dpi::{win32_icon_title_font, win32_window_dpi},
+    wide::win32_from_wide,
+    win32_dispatch_event, WindowEvent, WindowState,
+};
+use windows::Win32::{
+    Foundation::{HWND, LPARAM, LRESULT, POINT, RECT, WPARAM},
+    UI::{
+        Input::Ime::{
+            ImmGetCompositionStringW, ImmGetContext, ImmReleaseContext, ImmSetCandidateWindow,
+            ImmSetCompositionFontW, ImmSetCompositionWindow, CANDIDATEFORM, CFS_EXCLUDE, CFS_RECT,
+            COMPOSITIONFORM, GCS_RESULTSTR,
+        },
+        WindowsAndMessaging::{
+            DefWindowProcW, WM_CHAR, WM_IME_COMPOSITION, WM_IME_STARTCOMPOSITION,
+        },
+    },
+};
+
+// Puts the composition window over the text field, in the caption font, and keeps
+// the candidate list from covering it
+pub(crate) fn win32_position_ime_windows(handle: HWND, rect: &RECT) {
+    let context = unsafe { ImmGetContext(handle) };
+    if context.0 == 0 {
+        return;
+    }
+
+    let top_left = POINT {
+        x: rect.left,
+        y: rect.top,
+    };
+    let composition_form = COMPOSITIONFORM {
+        dwStyle: CFS_RECT,
+        ptCurrentPos: top_left,
+        rcArea: *rect,
+    };
+    let candidate_form = CANDIDATEFORM {
+        dwIndex: 0,
+        dwStyle: CFS_EXCLUDE,
+        ptCurrentPos: POINT {
+            x: rect.left,
+            y: rect.bottom,
+        },
+        rcArea: *rect,
+    };
+
+    unsafe {
+        ImmSetCompositionWindow(context, &composition_form);
+        ImmSetCandidateWindow(context, &candidate_form);
+        if let Some(logical_font) = win32_icon_title_font(win32_window_dpi(handle)) {
+            ImmSetCompositionFontW(context, &logical_font);
+        }
+        ImmReleaseContext(handle, context);
+    }
+}
+
+// Typing while a text field in the caption has the focus, see
+// Window::set_caption_text_input. Finished compositions and typed characters become
+// WindowEvent::TextInput.
+pub(crate) fn win32_caption_text_input(
+    handle: HWND,
+    state: &mut WindowState,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> Option<LRESULT> {
+    let rect = state.caption_text_input?;
+
+    match message {
+        WM_IME_STARTCOMPOSITION => {
+            win32_position_ime_windows(handle, &rect);
+            None
+        }
+        WM_IME_COMPOSITION if l_param.0 as u32 & GCS_RESULTSTR.0 != 0 => {
+            let context = unsafe { ImmGetContext(handle) };
+            if context.0 == 0 {
+                return None;
+            }
+
+            // The length is in bytes
+            let size = unsafe { ImmGetCompositionStringW(context, GCS_RESULTSTR, None, 0) };
+            let mut buffer = vec![0u16; size.max(0) as usize / 2];
+            if !buffer.is_empty() {
+                unsafe {
+                    ImmGetCompositionStringW(
+                        context,
+                        GCS_RESULTSTR,
+                        Some(buffer.as_mut_ptr() as _),
+                        size as u32,
+                    )
+                };
+            }
+            unsafe { ImmReleaseContext(handle, context) };
+
+            // Without the result string DefWindowProc would send it again as WM_IME_CHAR
+            let flags = l_param.0 & !(GCS_RESULTSTR.0 as isize);
+            win32_dispatch_event(handle, WindowEvent::TextInput(win32_from_wide(&buffer)));
+            Some(unsafe { DefWindowProcW(handle, message, w_param, LPARAM(flags)) })
+        }
+        WM_CHAR => {
+            let unit = w_param.0 as u16;
+            // Characters outside the BMP come as two WM_CHAR
+            let units = match state.pending_high_surrogate.take() {
+                Some(high) => vec![high, unit],
+                None if (0xD800..0xDC00).contains(&unit) => {
+                    state.pending_high_surrogate = Some(unit);
+                    return Some(LRESULT(0));
+                }
+                None => vec![unit],
+            };
+
+            win32_dispatch_event(handle, WindowEvent::TextInput(win32_from_wide(&units)));
+            Some(LRESULT(0))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,328 @@
+use crate::{
+    wide::{win32_from_wide, win32_os_from_wide, win32_os_to_wide, win32_to_wide},
+    Window,
+};
+use anyhow::{anyhow, Result};
+use std::{ffi::OsString, mem::size_of, path::PathBuf, thread, time::Duration};
+use windows::{
+    core::HSTRING,
+    Win32::{
+        Foundation::{
+            CloseHandle, GetLastError, BOOL, ERROR_ALREADY_EXISTS, HANDLE, HWND, LPARAM, WPARAM,
+        },
+        System::{DataExchange::COPYDATASTRUCT, Threading::CreateMutexW},
+        UI::WindowsAndMessaging::{
+            AllowSetForegroundWindow, EnumWindows, GetPropW, GetWindowThreadProcessId,
+            SendMessageTimeoutW, SetPropW, SMTO_ABORTIFHUNG, WM_COPYDATA,
+        },
+    },
+};
+
+// WM_COPYDATA sent by a second instance, the data is its command line
+pub(crate) const WIN32_COPYDATA_COMMAND_LINE: usize = 0x5747_0001;
+// WM_COPYDATA carrying a RemoteCommand, one dwData for each kind
+const WIN32_COPYDATA_OPEN_FILE: usize = 0x5747_0002;
+const WIN32_COPYDATA_SET_THEME: usize = 0x5747_0003;
+const WIN32_COPYDATA_FOCUS: usize = 0x5747_0004;
+const WIN32_COPYDATA_CUSTOM: usize = 0x5747_0005;
+
+// How long a second instance waits for the first one to show its window, and for it
+// to take the command line
+const WIN32_FIND_WINDOW_ATTEMPTS: u32 = 10;
+const WIN32_FIND_WINDOW_INTERVAL: Duration = Duration::from_millis(100);
+const WIN32_SEND_TIMEOUT_MS: u32 = 5000;
+
+// Held by the first instance of the application for as long as it runs. Later ones
+// find out through SingleInstance::acquire and hand over to it.
+pub struct SingleInstance {
+    mutex: HANDLE,
+    property: HSTRING,
+}
+
+impl SingleInstance {
+    // name tells applications apart, per user session. None when an instance already
+    // runs: it got this process' command line as WindowEvent::CommandLine and was
+    // brought to the front, so this one should just exit.
+    pub fn acquire(name: &str) -> Result<Option<SingleInstance>> {
+        let mutex_name = HSTRING::from(format!("Local\\windowsgui single instance {}", name));
+        let property = HSTRING::from(format!("windowsgui single instance {}", name));
+        let mutex = unsafe { CreateMutexW(None, false, &mutex_name) }
+            .map_err(|e| anyhow!("Failed to create single instance mutex: {}", e.message()))?;
+
+        if unsafe { GetLastError() } != ERROR_ALREADY_EXISTS {
+            return Ok(Some(SingleInstance { mutex, property }));
+        }
+
+        if let Err(e) = unsafe { CloseHandle(mutex) } {
+            eprintln!("Failed to close mutex: {}", e.message());
+        }
+
+        // The first instance may still be starting up
+        for _ in 0..WIN32_FIND_WINDOW_ATTEMPTS {
+            if let Some(handle) = win32_find_instance_window(&property) {
+                win32_forward_command_line(handle);
+                return Ok(None);
+            }
+
+            thread::sleep(WIN32_FIND_WINDOW_INTERVAL);
+        }
+
+        Err(anyhow!(
+            "Another instance is running, but has no window to activate"
+        ))
+    }
+
+    // The window later instances bring to the front and send their command lines to,
+    // usually the main window
+    pub fn set_window(&self, window: Window) -> Result<()> {
+        unsafe {
+            SetPropW(window.handle(), &self.property, HANDLE(1))
+                .map_err(|e| anyhow!("Failed to mark instance window: {}", e.message()))?
+        };
+
+        crate::win32_with_window_state(window.handle(), |state| {
+            state.instance_property = Some(self.property.clone());
+        });
+
+        Ok(())
+    }
+}
+
+impl Drop for SingleInstance {
+    fn drop(&mut self) {
+        if let Err(e) = unsafe { CloseHandle(self.mutex) } {
+            eprintln!("Failed to close mutex: {}", e.message());
+        }
+    }
+}
+
+// What another process can tell a running window, arriving as
+// WindowEvent::RemoteCommand
+#[derive(Clone, Debug, PartialEq)]
+pub enum RemoteCommand {
+    OpenFile(PathBuf),
+    // The application decides what the names mean
+    SetTheme(String),
+    // Also brings the window to the front before the event
+    Focus,
+    // Anything else, an application defined id and its data
+    Custom(u32, Vec<u8>),
+}
+
+impl RemoteCommand {
+    // To the window a SingleInstance with this name was given, from a second instance
+    // or another tool
+    pub fn send_to_instance(&self, name: &str) -> Result<()> {
+        let property = HSTRING::from(format!("windowsgui single instance {}", name));
+        let handle = win32_find_instance_window(&property)
+            .ok_or_else(|| anyhow!("No running instance of {}", name))?;
+        self.send_to_window(handle)
+    }
+
+    pub fn send_to_window(&self, handle: HWND) -> Result<()> {
+        let (tag, data) = self.win32_copy_data();
+        win32_send_copy_data(handle, tag, &data)
+    }
+
+    // The dwData and the bytes win32_received_remote_command reads back
+    fn win32_copy_data(&self) -> (usize, Vec<u8>) {
+        match self {
+            Self::OpenFile(path) => (
+                WIN32_COPYDATA_OPEN_FILE,
+                win32_utf16_bytes(&win32_os_to_wide(path.as_os_str())),
+            ),
+            Self::SetTheme(theme) => (
+                WIN32_COPYDATA_SET_THEME,
+                win32_utf16_bytes(&win32_to_wide(theme)),
+            ),
+            Self::Focus => (WIN32_COPYDATA_FOCUS, Vec::new()),
+            Self::Custom(id, data) => {
+                let mut bytes = id.to_le_bytes().to_vec();
+                bytes.extend_from_slice(data);
+                (WIN32_COPYDATA_CUSTOM, bytes)
+            }
+        }
+    }
+}
+
+fn win32_utf16_bytes(text: &[u16]) -> Vec<u8> {
+    text.iter().copied().flat_map(u16::to_le_bytes).collect()
+}
+
+fn win32_utf16_from_bytes(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+fn win32_find_instance_window(property: &HSTRING) -> Option<HWND> {
+    struct Search<'a> {
+        property: &'a HSTRING,
+        found: Option<HWND>,
+    }
+
+    unsafe extern "system" fn enum_window(handle: HWND, l_param: LPARAM) -> BOOL {
+        let search = &mut *(l_param.0 as *mut Search);
+        if GetPropW(handle, search.property).0 != 0 {
+            search.found = Some(handle);
+            return false.into();
+        }
+
+        true.into()
+    }
+
+    let mut search = Search {
+        property,
+        found: None,
+    };
+
+    // Fails when the callback stops it early, which is finding the window
+    let _ = unsafe { EnumWindows(Some(enum_window), LPARAM(&mut search as *mut Search as _)) };
+    search.found
+}
+
+fn win32_send_copy_data(handle: HWND, tag: usize, data: &[u8]) -> Result<()> {
+    // Only the process the user just started may take the foreground, it lends that
+    // to the window in case the command brings it up
+    let mut process_id = 0;
+    unsafe { GetWindowThreadProcessId(handle, Some(&mut process_id)) };
+    if let Err(e) = unsafe { AllowSetForegroundWindow(process_id) } {
+        eprintln!("Failed to allow set foreground window: {}", e.message());
+    }
+
+    // Copied into the receiving process, which only reads it
+    let copy_data = COPYDATASTRUCT {
+        dwData: tag,
+        cbData: data.len() as u32,
+        lpData: data.as_ptr() as _,
+    };
+    let result = unsafe {
+        SendMessageTimeoutW(
+            handle,
+            WM_COPYDATA,
+            WPARAM(0),
+            LPARAM(&copy_data as *const COPYDATASTRUCT as _),
+            SMTO_ABORTIFHUNG,
+            WIN32_SEND_TIMEOUT_MS,
+            None,
+        )
+    };
+
+    if result.0 == 0 {
+        return Err(anyhow!("The window didn't take the command"));
+    }
+
+    Ok(())
+}
+
+// Arguments without the executable
+fn win32_forward_command_line(handle: HWND) {
+    let data = win32_command_line_bytes(std::env::args_os().skip(1));
+    if let Err(e) = win32_send_copy_data(handle, WIN32_COPYDATA_COMMAND_LINE, &data) {
+        eprintln!("Failed to forward the command line:\n{}", e);
+    }
+}
+
+// Each argument null terminated, empty ones included
+fn win32_command_line_bytes(arguments: impl IntoIterator<Item = OsString>) -> Vec<u8> {
+    arguments
+        .into_iter()
+        .flat_map(|argument| win32_utf16_bytes(&win32_os_to_wide(&argument)))
+        .collect()
+}
+
+fn win32_copy_data_bytes(copy_data: &COPYDATASTRUCT) -> &[u8] {
+    if copy_data.lpData.is_null() {
+        return &[];
+    }
+
+    unsafe { std::slice::from_raw_parts(copy_data.lpData as *const u8, copy_data.cbData as usize) }
+}
+
+// The command line sent by win32_forward_command_line
+pub(crate) fn win32_received_command_line(copy_data: &COPYDATASTRUCT) -> Vec<String> {
+    let data = win32_utf16_from_bytes(win32_copy_data_bytes(copy_data));
+    // An empty argument is only its terminator, what follows the last terminator isn't
+    // one
+    let mut arguments: Vec<&[u16]> = data.split(|&unit| unit == 0).collect();
+    arguments.pop();
+    arguments.into_iter().map(win32_from_wide).collect()
+}
+
+// None when the WM_COPYDATA isn't a RemoteCommand
+pub(crate) fn win32_received_remote_command(copy_data: &COPYDATASTRUCT) -> Option<RemoteCommand> {
+    let data = win32_copy_data_bytes(copy_data);
+    match copy_data.dwData {
+        WIN32_COPYDATA_OPEN_FILE => Some(RemoteCommand::OpenFile(PathBuf::from(
+            win32_os_from_wide(&win32_utf16_from_bytes(data)),
+        ))),
+        WIN32_COPYDATA_SET_THEME => Some(RemoteCommand::SetTheme(win32_from_wide(
+            &win32_utf16_from_bytes(data),
+        ))),
+        WIN32_COPYDATA_FOCUS => Some(RemoteCommand::Focus),
+        WIN32_COPYDATA_CUSTOM if data.len() >= size_of::<u32>() => {
+            let (id, data) = data.split_at(size_of::<u32>());
+            Some(RemoteCommand::Custom(
+                u32::from_le_bytes([id[0], id[1], id[2], id[3]]),
+                data.to_vec(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_copy_data(tag: usize, data: &[u8]) -> COPYDATASTRUCT {
+        COPYDATASTRUCT {
+            dwData: tag,
+            cbData: data.len() as u32,
+            lpData: data.as_ptr() as _,
+        }
+    }
+
+    #[test]
+    fn command_line_keeps_empty_arguments() {
+        let arguments = ["--name", "", "file.txt", ""].map(OsString::from);
+        let data = win32_command_line_bytes(arguments);
+        let copy_data = make_copy_data(WIN32_COPYDATA_COMMAND_LINE, &data);
+        assert_eq!(
+            win32_received_command_line(&copy_data),
+            ["--name", "", "file.txt", ""]
+        );
+    }
+
+    #[test]
+    fn remote_commands_round_trip() {
+        for command in [
+            RemoteCommand::OpenFile(PathBuf::from("C:\\Users\\me\\notes ünïcode.txt")),
+            RemoteCommand::OpenFile(PathBuf::new()),
+            RemoteCommand::SetTheme("dark".to_string()),
+            RemoteCommand::Focus,
+            RemoteCommand::Custom(7, vec![0, 1, 2, 255]),
+            RemoteCommand::Custom(u32::MAX, Vec::new()),
+        ] {
+            let (tag, data) = command.win32_copy_data();
+            let copy_data = make_copy_data(tag, &data);
+            assert_eq!(win32_received_remote_command(&copy_data), Some(command));
+        }
+    }
+
+    #[test]
+    fn unknown_copy_data_is_no_remote_command() {
+        let copy_data = make_copy_data(WIN32_COPYDATA_COMMAND_LINE, &[]);
+        assert_eq!(win32_received_remote_command(&copy_data), None);
+        // Too short for the id of a custom command
+        let copy_data = make_copy_data(WIN32_COPYDATA_CUSTOM, &[1, 2]);
+        assert_eq!(win32_received_remote_command(&copy_data), None);
+    }
+
+    #[test]
+    fn command_line_without_arguments() {
+        let copy_data = make_copy_data(WIN32_COPYDATA_COMMAND_LINE, &[]);
+        assert!(win32_received_command_line(&copy_data).is_empty());
+    }
+}
@@ -0,0 +1,6716 @@
+mod animation;
+mod avatar;
+mod badge;
+mod breadcrumb;
+mod caption_buttons;
+mod caption_label;
+mod caption_layout;
+mod caption_tooltips;
+mod context_help;
+mod deferred;
+mod dpi;
+mod focus;
+mod fonts;
+mod images;
+mod ime;
+mod instance;
+mod menu_bar;
+mod menu_button;
+mod message_box;
+mod names;
+mod placement;
+mod registry;
+mod restart;
+mod search_box;
+mod snapping;
+mod splash;
+mod status_led;
+mod strings;
+mod tabs;
+mod tiling;
+mod tooltip;
+mod vector_icon;
+mod wide;
+
+use animation::{Transition, ANIMATION_FRAME_INTERVAL_MS};
+use anyhow::{anyhow, Result};
+use avatar::{win32_open_avatar_flyout, win32_paint_avatar, AvatarButton};
+use badge::{win32_badges_input, win32_is_over_badge, win32_paint_badges, Badges};
+pub use badge::{Badge, BadgeAnchor};
+use breadcrumb::{
+    win32_breadcrumb_input, win32_invalidate_breadcrumb, win32_is_over_breadcrumb,
+    win32_paint_breadcrumb, Breadcrumb,
+};
+use caption_buttons::{
+    win32_caption_button_layout, win32_caption_buttons_input, win32_click_caption_button,
+    win32_invalidate_added_caption_buttons, win32_invalidate_caption_button_rects,
+    win32_is_over_caption_button, win32_paint_caption_buttons, win32_relayout_caption,
+    win32_set_focused_caption_button, CaptionButtons, Win32GlyphPainter, WIN32_TITLE_MIN_WIDTH,
+};
+pub use caption_buttons::{
+    CaptionButton, CaptionButtonId, CaptionButtonPlacement, CaptionGlyph, CaptionGlyphButton,
+};
+use caption_label::win32_paint_caption_label;
+pub use caption_label::CaptionLabel;
+pub use caption_layout::{CaptionAlign, CaptionItem};
+use caption_tooltips::{
+    win32_caption_tooltip_text, win32_caption_tooltips_input, win32_clear_caption_tool_rects,
+    win32_set_caption_tooltip, CaptionTooltips,
+};
+pub use caption_tooltips::{CaptionTooltip, CaptionWidget};
+use context_help::{win32_context_help_input, win32_enter_context_help};
+pub use context_help::{HelpRequest, HelpTarget};
+use deferred::{win32_defer, StateHold};
+pub use dpi::enable_dpi_awareness;
+use dpi::{win32_system_metrics_for_dpi, win32_user32_proc, win32_window_dpi, Win32Proc};
+pub use focus::FocusRegion;
+use focus::FocusRegions;
+use fonts::{
+    win32_affects_caption_font, win32_caption_font, win32_clear_caption_fonts, win32_measure_text,
+    win32_subtitle_font,
+};
+use images::win32_load_icon;
+use ime::{win32_caption_text_input, win32_position_ime_windows};
+use instance::{
+    win32_received_command_line, win32_received_remote_command, WIN32_COPYDATA_COMMAND_LINE,
+};
+pub use instance::{RemoteCommand, SingleInstance};
+use menu_bar::{
+    win32_invalidate_menu_bar, win32_is_over_menu_bar, win32_menu_bar_focus_changed,
+    win32_menu_bar_input, win32_menu_bar_right, win32_paint_menu_bar, win32_track_client_leave,
+    MenuBar,
+};
+pub use menu_button::MenuButtonContent;
+use menu_button::{win32_open_menu_button, MenuButton, WIN32_MENU_GLYPH};
+pub use message_box::{message_box, MessageBoxButtons, MessageBoxResult};
+use message_box::{
+    win32_fit_message_box, win32_message_box_input, win32_paint_message_box, MessageBoxContent,
+};
+pub use names::CaptionButtonName;
+use names::CaptionButtonNames;
+use placement::{win32_fit_placement_to_monitor, win32_load_placement, win32_save_placement};
+use registry::{win32_register_window, win32_unregister_window};
+use restart::win32_update_restart_command_line;
+pub use restart::{
+    register_application_restart, restarted_session, RestartedSession, RestartedWindow,
+};
+use search_box::{
+    win32_focus_search_box, win32_paint_search_field, win32_search_box_input, SearchBox,
+};
+use snapping::{win32_snap_moving_rect, win32_snap_sizing_rect};
+pub use splash::{create_splash_window, SplashOptions, SplashWindow};
+pub use status_led::StatusLed;
+use status_led::{
+    win32_invalidate_status_led, win32_paint_status_led, win32_status_led_rect,
+    WIN32_STATUS_LED_TOOL_ID,
+};
+use std::{
+    cell::RefCell,
+    mem::{size_of, transmute},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::OnceLock,
+    time::Duration,
+};
+pub use strings::{localized_string, register_strings};
+use tabs::{
+    win32_invalidate_tab_strip, win32_is_over_tab, win32_paint_tab_strip, win32_tab_strip_input,
+    TabStrip,
+};
+use tiling::win32_tile_windows;
+pub use tiling::TileLayout;
+use tooltip::{
+    win32_add_tool, win32_create_tooltip, win32_hide_tooltip, win32_init_tooltips,
+    win32_relay_mouse_move, win32_remove_tool, win32_set_tool_rect,
+};
+pub use vector_icon::VectorIcon;
+use wide::{win32_window_text, win32_window_title};
+use windows::{
+    core::{s, w, HSTRING, PCWSTR, PWSTR},
+    Wdk::System::SystemServices::RtlGetVersion,
+    Win32::{
+        Foundation::{BOOL, COLORREF, HWND, LPARAM, LRESULT, POINT, RECT, SIZE, WPARAM},
+        Graphics::{
+            Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED},
+            Gdi::{
+                BeginPaint, ClientToScreen, CreateFontIndirectW, CreatePen, CreateSolidBrush,
+                DeleteObject, DrawFocusRect, DrawTextW, Ellipse, EndPaint, EnumFontFamiliesExW,
+                FillRect, GetDC, GetMonitorInfoW, GetStockObject, GetTextExtentPoint32W,
+                InflateRect, IntersectClipRect, InvalidateRect, LineTo, MonitorFromPoint,
+                MonitorFromRect, MonitorFromWindow, MoveToEx, PtInRect, Rectangle, ReleaseDC,
+                RestoreDC, SaveDC, ScreenToClient, SelectObject, SetBkColor, SetBkMode,
+                SetTextColor, UnionRect, DEFAULT_CHARSET, DT_BOTTOM, DT_CENTER, DT_NOCLIP,
+                DT_NOPREFIX, DT_SINGLELINE, DT_TOP, DT_VCENTER, DT_WORD_ELLIPSIS, HDC, HFONT,
+                HMONITOR, HOLLOW_BRUSH, HPEN, LOGFONTW, MONITORINFO, MONITOR_DEFAULTTONEAREST,
+                MONITOR_DEFAULTTOPRIMARY, NULL_PEN, PAINTSTRUCT, PS_INSIDEFRAME, RGBQUAD,
+                TEXTMETRICW, TRANSPARENT,
+            },
+        },
+        Media::Audio::{PlaySoundW, SND_ALIAS, SND_ASYNC, SND_NODEFAULT, SND_SYSTEM},
+        System::{
+            DataExchange::COPYDATASTRUCT,
+            LibraryLoader::GetModuleHandleW,
+            Power::{
+                GetSystemPowerStatus, RegisterPowerSettingNotification,
+                UnregisterPowerSettingNotification, HPOWERNOTIFY, POWERBROADCAST_SETTING,
+                SYSTEM_POWER_STATUS,
+            },
+            Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD},
+            Shutdown::{ShutdownBlockReasonCreate, ShutdownBlockReasonDestroy},
+            SystemInformation::OSVERSIONINFOW,
+            SystemServices::GUID_POWER_SAVING_STATUS,
+        },
+        UI::{
+            Accessibility::NotifyWinEvent,
+            Controls::{
+                BeginBufferedPaint, BufferedPaintInit, BufferedPaintSetAlpha, BufferedPaintUnInit,
+                CloseThemeData, DrawThemeTextEx, EndBufferedPaint, GetBufferedPaintBits,
+                GetThemePartSize, OpenThemeData, BPBF_COMPATIBLEBITMAP, BPBF_TOPDOWNDIB,
+                BP_PAINTPARAMS, CS_ACTIVE, DTTOPTS, DTT_COMPOSITED, DTT_TEXTCOLOR, NMHDR,
+                NMTTDISPINFOW, STATE_SYSTEM_FOCUSABLE, STATE_SYSTEM_INVISIBLE,
+                STATE_SYSTEM_PRESSED, TS_TRUE, TTN_GETDISPINFOW, WM_MOUSELEAVE, WP_CAPTION,
+                WP_SMALLCAPTION,
+            },
+            HiDpi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI},
+            Input::KeyboardAndMouse::{
+                EnableWindow, GetDoubleClickTime, GetFocus, GetKeyState, ReleaseCapture,
+                SetActiveWindow, SetCapture, TrackMouseEvent, TME_LEAVE, TME_NONCLIENT,
+                TRACKMOUSEEVENT, VIRTUAL_KEY, VK_CONTROL, VK_ESCAPE, VK_F10, VK_F6, VK_G, VK_LEFT,
+                VK_MENU, VK_RETURN, VK_RIGHT, VK_SHIFT, VK_SPACE, VK_T,
+            },
+            Input::Pointer::GetPointerType,
+            WindowsAndMessaging::{
+                AppendMenuW, CheckMenuItem, CreateWindowExW, DefWindowProcW, DeleteMenu,
+                DestroyIcon, DestroyWindow, DispatchMessageW, DrawIconEx, EnableMenuItem,
+                GetClassLongPtrW, GetClientRect, GetCursorPos, GetLastActivePopup,
+                GetMenuItemCount, GetMessageExtraInfo, GetMessageW, GetSystemMenu,
+                GetSystemMetrics, GetWindow, GetWindowLongPtrW, GetWindowPlacement, GetWindowRect,
+                InsertMenuW, IsChild, IsIconic, IsWindow, IsWindowVisible, IsZoomed, KillTimer,
+                LoadCursorW, LoadImageW, PostMessageW, PostQuitMessage, RegisterClassExW,
+                RemovePropW, SendMessageW, SetCursor, SetForegroundWindow, SetMenuDefaultItem,
+                SetTimer, SetWindowLongPtrW, SetWindowPlacement, SetWindowPos, SetWindowTextW,
+                ShowWindow, SystemParametersInfoW, TrackPopupMenu, TranslateMessage,
+                WindowFromPoint, CREATESTRUCTW, CS_HREDRAW, CS_VREDRAW, CW_USEDEFAULT,
+                DEVICE_NOTIFY_WINDOW_HANDLE, DI_NORMAL, ENDSESSION_CLOSEAPP, ENDSESSION_CRITICAL,
+                ENDSESSION_LOGOFF, EVENT_OBJECT_FOCUS, EVENT_OBJECT_NAMECHANGE,
+                EVENT_OBJECT_STATECHANGE, GCLP_HICONSM, GWLP_USERDATA, GWL_EXSTYLE, GWL_STYLE,
+                GW_OWNER, HCURSOR, HICON, HMENU, HTBOTTOM, HTBOTTOMLEFT, HTBOTTOMRIGHT, HTCAPTION,
+                HTCLIENT, HTLEFT, HTMAXBUTTON, HTNOWHERE, HTRIGHT, HTSYSMENU, HTTOP, HTTOPLEFT,
+                HTTOPRIGHT, HWND_NOTOPMOST, HWND_TOP, HWND_TOPMOST, ICON_BIG, ICON_SMALL,
+                ICON_SMALL2, IDC_ARROW, IMAGE_ICON, LR_SHARED, MF_BYCOMMAND, MF_BYPOSITION,
+                MF_CHECKED, MF_ENABLED, MF_GRAYED, MF_SEPARATOR, MF_STRING, MF_UNCHECKED,
+                MINMAXINFO, MSG, NCCALCSIZE_PARAMS, OBJID_TITLEBAR, PBT_POWERSETTINGCHANGE,
+                POINTER_INPUT_TYPE, PT_PEN, PT_TOUCH, SC_CLOSE, SC_KEYMENU, SC_MAXIMIZE,
+                SC_MINIMIZE, SC_MOVE, SC_RESTORE, SC_SIZE, SM_CONVERTIBLESLATEMODE, SM_CXFRAME,
+                SM_CXICON, SM_CXPADDEDBORDER, SM_CXSMICON, SM_CYFRAME, SM_MAXIMUMTOUCHES,
+                SM_SYSTEMDOCKED, SM_TABLETPC, SPI_GETCLIENTAREAANIMATION,
+                SPI_SETCLIENTAREAANIMATION, STATE_SYSTEM_FOCUSED, STATE_SYSTEM_HOTTRACKED,
+                STYLESTRUCT, SWP_FRAMECHANGED, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOOWNERZORDER,
+                SWP_NOSIZE, SWP_NOZORDER, SW_MAXIMIZE, SW_MINIMIZE, SW_NORMAL, SW_RESTORE, SW_SHOW,
+                SW_SHOWMAXIMIZED, SW_SHOWMINIMIZED, SW_SHOWNORMAL, SYSTEM_METRICS_INDEX,
+                SYSTEM_PARAMETERS_INFO_ACTION, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS, TITLEBARINFOEX,
+                TPM_RETURNCMD, TPM_RIGHTBUTTON, WINDOWPLACEMENT, WINDOW_EX_STYLE, WINDOW_STYLE,
+                WM_ACTIVATE, WM_CAPTURECHANGED, WM_CLOSE, WM_COPYDATA, WM_CREATE, WM_DESTROY,
+                WM_DPICHANGED, WM_ENDSESSION, WM_EXITSIZEMOVE, WM_GETICON, WM_GETMINMAXINFO,
+                WM_GETTITLEBARINFOEX, WM_KEYDOWN, WM_KILLFOCUS, WM_LBUTTONDOWN, WM_LBUTTONUP,
+                WM_MBUTTONDOWN, WM_MOUSEMOVE, WM_MOVING, WM_NCCALCSIZE, WM_NCCREATE, WM_NCDESTROY,
+                WM_NCHITTEST, WM_NCLBUTTONDBLCLK, WM_NCLBUTTONDOWN, WM_NCLBUTTONUP,
+                WM_NCMBUTTONDOWN, WM_NCMBUTTONUP, WM_NCMOUSELEAVE, WM_NCMOUSEMOVE,
+                WM_NCPOINTERDOWN, WM_NCPOINTERUP, WM_NCPOINTERUPDATE, WM_NCRBUTTONDOWN,
+                WM_NCRBUTTONUP, WM_NCXBUTTONDOWN, WM_NCXBUTTONUP, WM_NOTIFY, WM_PAINT,
+                WM_POINTERCAPTURECHANGED, WM_POINTERDOWN, WM_POINTERUP, WM_POINTERUPDATE,
+                WM_POWERBROADCAST, WM_QUERYENDSESSION, WM_RBUTTONDOWN, WM_SETCURSOR, WM_SETICON,
+                WM_SETTEXT, WM_SETTINGCHANGE, WM_SIZING, WM_STYLECHANGED, WM_SYSCHAR,
+                WM_SYSCOMMAND, WM_SYSKEYDOWN, WM_TIMER, WM_WINDOWPOSCHANGED, WM_XBUTTONDOWN,
+                WM_XBUTTONUP, WNDCLASSEXW, WPF_RESTORETOMAXIMIZED, WS_CLIPCHILDREN,
+                WS_EX_APPWINDOW, WS_EX_TOOLWINDOW, WS_EX_TOPMOST, WS_MAXIMIZEBOX, WS_MINIMIZEBOX,
+                WS_SYSMENU, WS_THICKFRAME, XBUTTON1, XBUTTON2,
+            },
+        },
+    },
+};
+
+// Registers the window class (once per process) and creates a window with the custom title bar
+pub fn create_window(title: PCWSTR, options: WindowOptions) -> Result<Window> {
+    win32_create_window(
+        title,
+        options,
+        Position::Default,
+        WIN32_DEFAULT_WINDOW_SIZE,
+        false,
+        HWND(0),
+        None,
+    )
+}
+
+// Where a new window opens. A saved placement wins over it, see
+// WindowOptions::forget_placement.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Position {
+    // Wherever Windows puts new windows (CW_USEDEFAULT)
+    #[default]
+    Default,
+    // Centered in the work area of the primary monitor
+    Centered,
+    // Centered in the work area of the monitor the cursor is on, for people working on
+    // more than one monitor
+    OnCursorMonitor,
+    // Centered over the owner, see WindowBuilder::owner. Like Centered without one.
+    OverOwner,
+}
+
+// Default window size at 96 dpi
+const WIN32_DEFAULT_WINDOW_SIZE: SIZE = SIZE { cx: 800, cy: 600 };
+
+// create_window with the less common settings
+pub struct WindowBuilder {
+    title: HSTRING,
+    options: WindowOptions,
+    position: Position,
+    size: SIZE,
+    maximized: bool,
+    owner: HWND,
+    placement: Option<WINDOWPLACEMENT>,
+}
+
+impl WindowBuilder {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: HSTRING::from(title),
+            options: WindowOptions::default(),
+            position: Position::default(),
+            size: WIN32_DEFAULT_WINDOW_SIZE,
+            maximized: false,
+            owner: HWND(0),
+            placement: None,
+        }
+    }
+
+    pub fn options(mut self, options: WindowOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn position(mut self, position: Position) -> Self {
+        self.position = position;
+        self
+    }
+
+    // Window size at 96 dpi, scaled for the monitor the window opens on
+    pub fn size(mut self, width: i32, height: i32) -> Self {
+        self.size = SIZE {
+            cx: width,
+            cy: height,
+        };
+        self
+    }
+
+    // Opens maximized, the size and position are where the restore button goes
+    pub fn maximized(mut self, maximized: bool) -> Self {
+        self.maximized = maximized;
+        self
+    }
+
+    // Owned windows stay in front of their owner and go away with it
+    pub fn owner(mut self, owner: Window) -> Self {
+        self.owner = owner.handle;
+        self
+    }
+
+    // Opens the window exactly like this instead of at its saved placement, e.g. the
+    // one of a RestartedWindow
+    pub fn placement(mut self, placement: WINDOWPLACEMENT) -> Self {
+        self.placement = Some(placement);
+        self
+    }
+
+    pub fn build(self) -> Result<Window> {
+        win32_create_window(
+            PCWSTR(self.title.as_ptr()),
+            self.options,
+            self.position,
+            self.size,
+            self.maximized,
+            self.owner,
+            self.placement,
+        )
+    }
+
+    // Opens the window as a WindowKind::Dialog of owner and only returns once it is
+    // closed. The owner is disabled meanwhile, its messages keep being dispatched.
+    // setup runs before the dialog takes input, to hook up its event handler. Without
+    // a position the dialog is centered over the owner.
+    pub fn run_modal(mut self, owner: Window, setup: impl FnOnce(Window)) -> Result<()> {
+        self.options.kind = WindowKind::Dialog;
+        self.owner = owner.handle;
+        if self.position == Position::Default {
+            self.position = Position::OverOwner;
+        }
+
+        let dialog = self.build()?;
+        win32_with_window_state(dialog.handle, |state| {
+            state.modal_owner = Some(owner.handle);
+        });
+
+        unsafe { EnableWindow(owner.handle, false) };
+        setup(dialog);
+        win32_run_modal_loop(dialog.handle);
+
+        // Normally done in WM_CLOSE already, DestroyWindow skips that
+        unsafe { EnableWindow(owner.handle, true) };
+        Ok(())
+    }
+}
+
+// Dispatches messages for every window on this thread until handle is destroyed
+fn win32_run_modal_loop(handle: HWND) {
+    let mut message: MSG = MSG::default();
+    while unsafe { IsWindow(handle) }.as_bool() {
+        match unsafe { GetMessageW(&mut message, None, 0, 0).0 } {
+            // WM_QUIT belongs to run_message_loop, put back for it
+            0 => {
+                unsafe { PostQuitMessage(message.wParam.0 as i32) };
+                break;
+            }
+            -1 => break,
+            _ => {
+                unsafe { TranslateMessage(&message) };
+                unsafe { DispatchMessageW(&message) };
+            }
+        }
+    }
+}
+
+// The monitor position picks and the area to center in, its work area or the owner
+fn win32_position_monitor(position: Position, owner: HWND) -> (HMONITOR, RECT) {
+    // The caption is part of the client area, which is all of the owner that can be seen
+    if position == Position::OverOwner && owner != HWND(0) {
+        let mut client_rect = RECT::default();
+        if let Err(e) = unsafe { GetClientRect(owner, &mut client_rect) } {
+            eprintln!("Failed to get owner client rect: {}", e.message());
+        }
+
+        let mut top_left = POINT {
+            x: client_rect.left,
+            y: client_rect.top,
+        };
+        let mut bottom_right = POINT {
+            x: client_rect.right,
+            y: client_rect.bottom,
+        };
+        unsafe {
+            ClientToScreen(owner, &mut top_left);
+            ClientToScreen(owner, &mut bottom_right);
+        }
+
+        let monitor = unsafe { MonitorFromWindow(owner, MONITOR_DEFAULTTONEAREST) };
+        let owner_rect = RECT {
+            left: top_left.x,
+            top: top_left.y,
+            right: bottom_right.x,
+            bottom: bottom_right.y,
+        };
+        return (monitor, owner_rect);
+    }
+
+    let monitor = match position {
+        Position::OnCursorMonitor => {
+            let mut cursor_point = POINT::default();
+            if let Err(e) = unsafe { GetCursorPos(&mut cursor_point) } {
+                eprintln!("Failed to get cursor position: {}", e.message());
+            }
+            unsafe { MonitorFromPoint(cursor_point, MONITOR_DEFAULTTONEAREST) }
+        }
+        // The origin is always on the primary monitor
+        Position::Default | Position::Centered | Position::OverOwner => unsafe {
+            MonitorFromPoint(POINT::default(), MONITOR_DEFAULTTOPRIMARY)
+        },
+    };
+    let mut monitor_info = MONITORINFO {
+        cbSize: size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    if !unsafe { GetMonitorInfoW(monitor, &mut monitor_info) }.as_bool() {
+        eprintln!("Failed to get monitor info");
+    }
+
+    (monitor, monitor_info.rcWork)
+}
+
+// Dpi of windows on monitor, before there is a window to ask
+fn win32_monitor_dpi(monitor: HMONITOR) -> u32 {
+    let (mut dpi_x, mut dpi_y) = (0, 0);
+    match unsafe { GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) } {
+        Ok(()) => dpi_x,
+        Err(e) => {
+            eprintln!("Failed to get monitor dpi: {}", e.message());
+            DEFAULT_DPI as u32
+        }
+    }
+}
+
+// Top left corner and size of a new window. The size is scaled for the dpi of the
+// monitor it opens on, the window gets WM_DPICHANGED if it ends up elsewhere.
+fn win32_initial_window_bounds(position: Position, size: SIZE, owner: HWND) -> (POINT, SIZE) {
+    let (monitor, work_area) = win32_position_monitor(position, owner);
+    let dpi = win32_monitor_dpi(monitor);
+
+    let size = SIZE {
+        cx: win32_dpi_scale(size.cx, dpi),
+        cy: win32_dpi_scale(size.cy, dpi),
+    };
+
+    let origin = match position {
+        Position::Default => POINT {
+            x: CW_USEDEFAULT,
+            y: CW_USEDEFAULT,
+        },
+        Position::Centered | Position::OnCursorMonitor | Position::OverOwner => {
+            // Centers what can be seen, the side and bottom resize borders are invisible
+            let border_x = win32_system_metrics_for_dpi(SM_CXFRAME, dpi)
+                + win32_system_metrics_for_dpi(SM_CXPADDEDBORDER, dpi);
+            let border_y = win32_system_metrics_for_dpi(SM_CYFRAME, dpi)
+                + win32_system_metrics_for_dpi(SM_CXPADDEDBORDER, dpi);
+            let visible_width = size.cx - border_x * 2;
+            let visible_height = size.cy - border_y;
+            POINT {
+                x: work_area.left + (work_area.right - work_area.left - visible_width) / 2
+                    - border_x,
+                y: work_area.top + (work_area.bottom - work_area.top - visible_height) / 2,
+            }
+        }
+    };
+
+    (origin, size)
+}
+
+// Window icons loaded from an image file at the sizes for the window's dpi, loaded
+// again when it changes
+struct FileIcons {
+    path: PathBuf,
+    small: HICON,
+    big: HICON,
+}
+
+impl Drop for FileIcons {
+    fn drop(&mut self) {
+        for icon in [self.small, self.big] {
+            if let Err(e) = unsafe { DestroyIcon(icon) } {
+                eprintln!("Failed to destroy icon: {}", e.message());
+            }
+        }
+    }
+}
+
+fn win32_load_file_icons(handle: HWND, path: &Path) -> Result<()> {
+    let dpi = win32_window_dpi(handle);
+    let small = win32_load_icon(path, win32_system_metrics_for_dpi(SM_CXSMICON, dpi))?;
+    let big = match win32_load_icon(path, win32_system_metrics_for_dpi(SM_CXICON, dpi)) {
+        Ok(big) => big,
+        Err(e) => {
+            unsafe { DestroyIcon(small).ok() };
+            return Err(e);
+        }
+    };
+
+    for (size, icon) in [(ICON_SMALL, small), (ICON_BIG, big)] {
+        unsafe { SendMessageW(handle, WM_SETICON, WPARAM(size as _), LPARAM(icon.0)) };
+    }
+
+    // The old icons are only destroyed once the window stopped using them
+    let file_icons = FileIcons {
+        path: path.to_owned(),
+        small,
+        big,
+    };
+    win32_with_window_state(handle, |state| state.file_icons = Some(file_icons));
+    Ok(())
+}
+
+// Icon resource of the executable that build.rs embeds
+const WIN32_APP_ICON_RESOURCE_ID: u16 = 1;
+
+// The application icon at the size of size_metric, the null icon when the executable
+// has none and Windows shows its generic one. Shared, it is never destroyed.
+fn win32_resource_icon(size_metric: SYSTEM_METRICS_INDEX) -> HICON {
+    let Ok(module) = (unsafe { GetModuleHandleW(None) }) else {
+        return HICON(0);
+    };
+
+    let size = unsafe { GetSystemMetrics(size_metric) };
+    unsafe {
+        LoadImageW(
+            module,
+            PCWSTR(WIN32_APP_ICON_RESOURCE_ID as usize as _),
+            IMAGE_ICON,
+            size,
+            size,
+            LR_SHARED,
+        )
+    }
+    .map_or(HICON(0), |icon| HICON(icon.0))
+}
+
+fn win32_create_window(
+    title: PCWSTR,
+    options: WindowOptions,
+    position: Position,
+    size: SIZE,
+    maximized: bool,
+    owner: HWND,
+    placement: Option<WINDOWPLACEMENT>,
+) -> Result<Window> {
+    let window_class_name = w!("Tremind Window Class");
+    let window_class = WNDCLASSEXW {
+        cbSize: size_of::<WNDCLASSEXW>() as u32,
+        lpszClassName: window_class_name,
+        lpfnWndProc: Some(window_proc),
+        style: CS_HREDRAW | CS_VREDRAW,
+        hIcon: win32_resource_icon(SM_CXICON),
+        hIconSm: win32_resource_icon(SM_CXSMICON),
+        ..Default::default()
+    };
+
+    // Fails with ERROR_CLASS_ALREADY_EXISTS for every window after the first, which is fine
+    unsafe { RegisterClassExW(&window_class) };
+    win32_init_tooltips();
+
+    // Shown once the saved placement is applied, so it doesn't appear somewhere first
+    let (window_style, window_ex_style) = match options.kind {
+        WindowKind::Main => (
+            WS_THICKFRAME | WS_SYSMENU | WS_MAXIMIZEBOX | WS_MINIMIZEBOX,
+            WS_EX_APPWINDOW,
+        ),
+        WindowKind::Tool => (WS_THICKFRAME | WS_SYSMENU, WS_EX_TOOLWINDOW),
+        // Owned, which already keeps it off the taskbar
+        WindowKind::Dialog => (WS_THICKFRAME | WS_SYSMENU, WINDOW_EX_STYLE::default()),
+        WindowKind::Document => (
+            WS_THICKFRAME | WS_SYSMENU | WS_MAXIMIZEBOX,
+            WINDOW_EX_STYLE::default(),
+        ),
+    };
+
+    // Windows are told apart by their title. Dialogs open over their owner every time,
+    // documents come and go with what the user opens.
+    let placement_name = if options.forget_placement
+        || matches!(options.kind, WindowKind::Dialog | WindowKind::Document)
+    {
+        None
+    } else {
+        unsafe { title.to_string() }.ok()
+    };
+    let saved_placement =
+        placement.or_else(|| placement_name.as_deref().and_then(win32_load_placement));
+
+    // The reference is handed to the window in WM_NCCREATE and dropped in WM_NCDESTROY
+    let window_state = Rc::into_raw(Rc::new(RefCell::new(WindowState {
+        options,
+        placement_name,
+        window_style,
+        ..Default::default()
+    })));
+
+    let (origin, size) = win32_initial_window_bounds(position, size, owner);
+    let handle = unsafe {
+        CreateWindowExW(
+            window_ex_style,
+            window_class_name,
+            title,
+            window_style,
+            origin.x,
+            origin.y,
+            size.cx,
+            size.cy,
+            owner,
+            None,
+            None,
+            Some(window_state as _),
+        )
+    };
+
+    if handle == HWND(0) {
+        return Err(anyhow!(
+            "Failed to create window: {}",
+            windows::core::Error::from_win32().message()
+        ));
+    }
+
+    win32_register_window(handle);
+
+    let restored = saved_placement.is_some_and(|mut placement| {
+        // Closed while minimized, coming back minimized would look like a failed launch.
+        // It reopens the way it would have been restored, maximized or not.
+        if placement.showCmd == SW_SHOWMINIMIZED.0 as u32 {
+            placement.showCmd = if placement.flags.contains(WPF_RESTORETOMAXIMIZED) {
+                SW_SHOWMAXIMIZED.0 as u32
+            } else {
+                SW_SHOWNORMAL.0 as u32
+            };
+        }
+
+        // Reopening maximized keeps the recorded normal rect for the restore button
+        win32_fit_placement_to_monitor(&mut placement);
+
+        match unsafe { SetWindowPlacement(handle, &placement) } {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("Failed to restore window placement: {}", e.message());
+                false
+            }
+        }
+    });
+
+    if !restored {
+        let show_command = if maximized { SW_SHOWMAXIMIZED } else { SW_SHOW };
+        unsafe { ShowWindow(handle, show_command) };
+    }
+
+    if win32_window_kind(handle) == WindowKind::Document {
+        win32_with_window_state(owner, |owner_state| {
+            owner_state.documents.push(handle);
+        });
+    }
+
+    Ok(Window { handle })
+}
+
+// A window made by create_window. Only use it on the thread that created the window,
+// its state is owned by the window procedure.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Window {
+    handle: HWND,
+}
+
+impl Window {
+    pub fn handle(&self) -> HWND {
+        self.handle
+    }
+
+    // Shaking the window by its caption minimizes every other window. It comes with
+    // the system move loop, so turning it off also gives up dragging into Snap.
+    pub fn set_aero_shake(&self, enabled: bool) {
+        win32_with_window_state(self.handle, |state| {
+            state.options.disable_aero_shake = !enabled;
+        });
+    }
+
+    // Asked first in WM_NCHITTEST with the cursor in client coordinates. Returning an
+    // HT* code (e.g. HTCAPTION to drag by a client region, HTLEFT for a custom resize
+    // zone) skips the built-in hit testing, None falls through to it.
+    pub fn set_hit_test_override(&self, hit_test: impl FnMut(POINT) -> Option<u32> + 'static) {
+        win32_with_window_state(self.handle, |state| {
+            state.hit_test_override = Some(Box::new(hit_test));
+        });
+    }
+
+    pub fn clear_hit_test_override(&self) {
+        win32_with_window_state(self.handle, |state| {
+            state.hit_test_override = None;
+        });
+    }
+
+    // Borderless fullscreen without the caption, covering WindowOptions::fullscreen_bounds.
+    // Leaving it restores the previous placement, maximized or not.
+    pub fn set_fullscreen(&self, enabled: bool) -> Result<()> {
+        win32_set_fullscreen(self.handle, enabled)
+    }
+
+    // A text field the application draws in the caption (usually in a region added with
+    // add_caption_interactive_region) got the keyboard focus, rect in client
+    // coordinates. Typing becomes WindowEvent::TextInput and the input method windows
+    // of CJK languages open at the field. None when it loses the focus.
+    pub fn set_caption_text_input(&self, rect: Option<RECT>) {
+        win32_with_window_state(self.handle, |state| {
+            state.caption_text_input = rect;
+            state.pending_high_surrogate = None;
+            // Follows a field that moves while a composition is open
+            if let Some(rect) = rect {
+                win32_position_ime_windows(self.handle, &rect);
+            }
+        });
+    }
+
+    // Swaps the caption and taskbar icon, e.g. for one showing a status. The icon
+    // isn't copied, keep it until it is replaced or the window is destroyed.
+    pub fn set_icon(&self, icon: HICON) {
+        for size in [ICON_SMALL, ICON_BIG] {
+            unsafe { SendMessageW(self.handle, WM_SETICON, WPARAM(size as _), LPARAM(icon.0)) };
+        }
+
+        win32_with_window_state(self.handle, |state| {
+            state.file_icons = None;
+        });
+    }
+
+    // set_icon with a PNG, ICO or other image file, decoded at the small and big icon
+    // sizes for the window's dpi and again whenever that changes. The window owns them.
+    pub fn set_icon_from_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        win32_load_file_icons(self.handle, path.as_ref())
+    }
+
+    // Repaints only the title, see WM_SETTEXT
+    pub fn set_title(&self, title: &str) -> Result<()> {
+        unsafe { SetWindowTextW(self.handle, &HSTRING::from(title)) }
+            .map_err(|e| anyhow!("Failed to set window title: {}", e.message()))
+    }
+
+    // Marks the window as having unsaved changes with a dot before the title. With
+    // WindowOptions::confirm_close_when_modified closing it asks first.
+    pub fn set_modified(&self, modified: bool) {
+        win32_with_window_state(self.handle, |state| {
+            if state.modified != modified {
+                state.modified = modified;
+                if state.options.modified_close_dot {
+                    win32_invalidate_caption_buttons(self.handle, state);
+                } else {
+                    let title_rect = state.title_rect;
+                    win32_invalidate_rect(self.handle, state, &title_rect);
+                }
+            }
+        });
+    }
+
+    pub fn is_modified(&self) -> bool {
+        win32_with_window_state(self.handle, |state| state.modified).unwrap_or_default()
+    }
+
+    // Tints the caption from the left edge up to fraction of its width, like download
+    // managers show how far along they are. None takes it away.
+    pub fn set_caption_progress(&self, fraction: Option<f32>) {
+        win32_with_window_state(self.handle, |state| {
+            let fraction = fraction.map(|fraction| fraction.clamp(0.0, 1.0));
+            if state.caption_progress != fraction {
+                state.caption_progress = fraction;
+                if let Ok(title_bar_rect) = win32_titlebar_rect(self.handle, state) {
+                    win32_invalidate_rect(self.handle, state, &title_bar_rect);
+                }
+            }
+        });
+    }
+
+    // The tint of set_caption_progress, e.g. red once the download failed
+    pub fn set_caption_progress_state(&self, progress_state: CaptionProgressState) {
+        win32_with_window_state(self.handle, |state| {
+            if state.caption_progress_state != progress_state {
+                state.caption_progress_state = progress_state;
+                if state.caption_progress.is_some() {
+                    if let Ok(title_bar_rect) = win32_titlebar_rect(self.handle, state) {
+                        win32_invalidate_rect(self.handle, state, &title_bar_rect);
+                    }
+                }
+            }
+        });
+    }
+
+    // A smaller line under the title, like the path of the document or the connection
+    // status. Adding or removing it changes the caption height.
+    pub fn set_subtitle(&self, subtitle: Option<&str>) {
+        win32_with_window_state(self.handle, |state| {
+            let changes_height = state.subtitle.is_some() != subtitle.is_some();
+            state.subtitle = subtitle.map(|subtitle| subtitle.encode_utf16().collect());
+            if changes_height {
+                unsafe { InvalidateRect(self.handle, None, false) };
+            } else {
+                let title_rect = state.title_rect;
+                win32_invalidate_rect(self.handle, state, &title_rect);
+            }
+        });
+    }
+
+    // Whether the user allows translucent surfaces. The caption is filled opaque, an
+    // application painting translucent content should fall back to opaque fills when
+    // this is off and follow WindowEvent::TransparencyEffectsChanged.
+    pub fn transparency_effects_enabled(&self) -> bool {
+        win32_with_window_state(self.handle, |state| state.transparency_effects)
+            .unwrap_or_else(win32_transparency_effects_enabled)
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        win32_with_window_state(self.handle, |state| state.fullscreen_restore.is_some())
+            .unwrap_or_default()
+    }
+
+    // Keeps the window above all windows that aren't, the pin button and the system
+    // menu checkmark follow along
+    pub fn set_always_on_top(&self, enabled: bool) -> Result<()> {
+        win32_set_always_on_top(self.handle, enabled)
+    }
+
+    pub fn is_always_on_top(&self) -> bool {
+        win32_is_always_on_top(self.handle)
+    }
+
+    // Shows or hides the pin button after the fact, see WindowOptions::pin_button
+    pub fn set_pin_button(&self, shown: bool) {
+        win32_with_window_state(self.handle, |state| {
+            if shown {
+                win32_add_pin_button(self.handle, state);
+            } else if let Some(id) = state.pin_button.take() {
+                win32_remove_caption_button(self.handle, state, id);
+            }
+        });
+    }
+
+    // Rolls the window up to just its caption, or back down. Maximizing or going
+    // fullscreen rolls it back down first.
+    pub fn set_shaded(&self, shaded: bool) -> Result<()> {
+        win32_set_shaded(self.handle, shaded)
+    }
+
+    // Shrinks the window into an always on top corner of its monitor with the caption
+    // only shown while hovered, for video or monitoring. Leaving it puts the window
+    // back where it was.
+    pub fn set_picture_in_picture(&self, enabled: bool) -> Result<()> {
+        win32_set_picture_in_picture(self.handle, enabled)
+    }
+
+    pub fn is_picture_in_picture(&self) -> bool {
+        win32_with_window_state(self.handle, |state| {
+            state.picture_in_picture_restore.is_some()
+        })
+        .unwrap_or_default()
+    }
+
+    // Side by side or in a grid on the work area of this window's monitor, together
+    // with the application's other main and document windows
+    pub fn tile_windows(&self, layout: TileLayout) -> Result<()> {
+        win32_tile_windows(self.handle, layout)
+    }
+
+    // The WindowKind::Document windows this one owns, in the order they were opened
+    pub fn documents(&self) -> Vec<Window> {
+        win32_with_window_state(self.handle, |state| {
+            state
+                .documents
+                .iter()
+                .map(|&handle| Window { handle })
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+
+    pub fn is_shaded(&self) -> bool {
+        win32_with_window_state(self.handle, |state| state.shade_restore_height.is_some())
+            .unwrap_or_default()
+    }
+
+    // Replaces the tooltip text of a caption button, which otherwise comes in the
+    // user's UI language
+    pub fn set_caption_button_name(&self, name: CaptionButtonName, text: &str) {
+        win32_with_window_state(self.handle, |state| {
+            state.caption_button_names.set(name, text);
+        });
+
+        let button = match name {
+            CaptionButtonName::Minimize => CustomTitleBarHoveredButton::Minimize,
+            CaptionButtonName::Maximize | CaptionButtonName::Restore => {
+                CustomTitleBarHoveredButton::Maximize
+            }
+            CaptionButtonName::Close => CustomTitleBarHoveredButton::Close,
+            _ => return,
+        };
+        win32_notify_caption_button(self.handle, EVENT_OBJECT_NAMECHANGE, button);
+    }
+
+    // Smallest client area interactive resizing allows, in pixels at 96 dpi so it
+    // scales with the monitor. None removes the limit.
+    pub fn set_min_size(&self, size: Option<SIZE>) {
+        win32_with_window_state(self.handle, |state| {
+            state.min_size = size;
+        });
+    }
+
+    // Largest client area resizing allows, at 96 dpi. Maximizing is limited by it too.
+    pub fn set_max_size(&self, size: Option<SIZE>) {
+        win32_with_window_state(self.handle, |state| {
+            state.max_size = size;
+        });
+    }
+
+    // Regions F6 / Shift+F6 cycle through, in order. Content is always included.
+    pub fn set_focus_regions(&self, regions: &[FocusRegion]) {
+        win32_with_window_state(self.handle, |state| {
+            state.focus_regions.set_order(regions);
+        });
+    }
+
+    pub fn focus_region(&self) -> FocusRegion {
+        win32_with_window_state(self.handle, |state| state.focus_regions.current())
+            .unwrap_or(FocusRegion::Content)
+    }
+
+    // Receives the WindowEvents of this window, replacing the previous handler
+    pub fn set_event_handler(&self, handler: impl FnMut(WindowEvent) + 'static) {
+        win32_with_window_state(self.handle, |state| {
+            state.event_handler = Some(Box::new(handler));
+        });
+    }
+
+    // Asked when the user logs off or shuts down, and when an installer wants the
+    // application closed. Returning false vetoes it, except for critical ones. Without a
+    // handler, the session ends unless a shutdown block reason is set.
+    pub fn set_session_ending_handler(&self, handler: impl FnMut(SessionEnding) -> bool + 'static) {
+        win32_with_window_state(self.handle, |state| {
+            state.session_ending_handler = Some(Box::new(handler));
+        });
+    }
+
+    // Why the session can't end right now, e.g. "Unsaved changes in 2 documents".
+    // Windows lists it on the screen shown while logging off waits for the application.
+    // None once the work is saved.
+    pub fn set_shutdown_block_reason(&self, reason: Option<&str>) -> Result<()> {
+        win32_with_window_state(self.handle, |state| {
+            unsafe {
+                match reason {
+                    Some(reason) => ShutdownBlockReasonCreate(self.handle, &HSTRING::from(reason)),
+                    None if state.blocks_shutdown => ShutdownBlockReasonDestroy(self.handle),
+                    None => Ok(()),
+                }
+                .map_err(|e| anyhow!("Failed to set shutdown block reason: {}", e.message()))?
+            };
+
+            state.blocks_shutdown = reason.is_some();
+            Ok(())
+        })
+        .unwrap_or(Ok(()))
+    }
+
+    // Cursor shown over the parts of the window that hit test as hit_test (HTCLIENT,
+    // HTCAPTION, or a code from the hit test override), instead of the default one
+    pub fn set_region_cursor(&self, hit_test: u32, cursor: HCURSOR) {
+        win32_with_window_state(self.handle, |state| {
+            state
+                .region_cursors
+                .retain(|&(region, _)| region != hit_test);
+            state.region_cursors.push((hit_test, cursor));
+        });
+    }
+
+    pub fn clear_region_cursor(&self, hit_test: u32) {
+        win32_with_window_state(self.handle, |state| {
+            state
+                .region_cursors
+                .retain(|&(region, _)| region != hit_test);
+        });
+    }
+
+    // Marks part of the caption (client coordinates) as client area, so widgets drawn
+    // there like search boxes or tabs get normal mouse input instead of dragging the
+    // window
+    pub fn add_caption_interactive_region(&self, rect: RECT) {
+        win32_with_window_state(self.handle, |state| {
+            state.caption_interactive_regions.push(rect);
+        });
+    }
+
+    // Room in the caption for widgets of the application's own, laid out along with the
+    // window's: leading items after the leading caption buttons, centered ones next to
+    // the search box, trailing ones before the caption buttons. The title makes room,
+    // and the items take mouse input like add_caption_interactive_region regions.
+    // WindowEvent::CaptionItemsMoved says where they are whenever that changes.
+    pub fn set_caption_items(&self, items: &[CaptionItem]) {
+        win32_with_window_state(self.handle, |state| {
+            state.caption_items = items.to_vec();
+            win32_invalidate_added_caption_buttons(self.handle, state);
+        });
+    }
+
+    // How much room text takes in the caption font at 96 dpi, rounded up, to size
+    // CaptionItems to their content. The window scales it along with the item.
+    pub fn measure_caption_text(&self, text: &str) -> SIZE {
+        let dpi = win32_window_dpi(self.handle);
+        let text: Vec<u16> = text.encode_utf16().collect();
+        let size = win32_measure_text(win32_caption_font(dpi), &[&text], DT_NOPREFIX)
+            .pop()
+            .unwrap_or_default();
+        let unscale = |value: i32| (value * 96 + dpi as i32 - 1) / dpi as i32;
+        SIZE {
+            cx: unscale(size.cx),
+            cy: unscale(size.cy),
+        }
+    }
+
+    // Caption height and where the system buttons are right now, changes are reported as
+    // WindowEvent::CaptionOverlayChanged
+    pub fn caption_overlay(&self) -> CaptionOverlay {
+        win32_with_window_state(self.handle, |state| {
+            win32_caption_overlay(self.handle, state)
+        })
+        .unwrap_or_default()
+    }
+
+    // Where the items are right now, see set_caption_items
+    pub fn caption_item_rects(&self) -> Vec<RECT> {
+        win32_with_window_state(self.handle, |state| {
+            let title_bar_rect = win32_titlebar_rect(self.handle, state).ok()?;
+            Some(win32_caption_button_layout(self.handle, state, &title_bar_rect).items)
+        })
+        .flatten()
+        .unwrap_or_default()
+    }
+
+    // Removes a region added with the same rect
+    pub fn remove_caption_interactive_region(&self, rect: RECT) {
+        win32_with_window_state(self.handle, |state| {
+            state
+                .caption_interactive_regions
+                .retain(|&region| region != rect);
+        });
+    }
+
+    // Appends a tab to the caption, returning its index. Tabs replace the title, with
+    // a new tab button after the last one. See WindowEvent::TabSelected and friends.
+    pub fn add_tab(&self, title: &str) -> usize {
+        win32_with_window_state(self.handle, |state| {
+            let index = state.tabs.add(title);
+            win32_invalidate_tab_strip(self.handle, state);
+            index
+        })
+        .unwrap_or(0)
+    }
+
+    // The tabs after it move down by one. Removing the last tab brings the title back.
+    pub fn remove_tab(&self, index: usize) {
+        win32_with_window_state(self.handle, |state| {
+            state.tabs.remove(index);
+            win32_invalidate_tab_strip(self.handle, state);
+        });
+    }
+
+    pub fn set_tab_title(&self, index: usize, title: &str) {
+        win32_with_window_state(self.handle, |state| {
+            state.tabs.set_title(index, title);
+            win32_invalidate_tab_strip(self.handle, state);
+        });
+    }
+
+    // Doesn't send WindowEvent::TabSelected, that is for the user's choices
+    pub fn set_active_tab(&self, index: usize) {
+        win32_with_window_state(self.handle, |state| {
+            state.tabs.set_active(index);
+            win32_invalidate_tab_strip(self.handle, state);
+        });
+    }
+
+    // None without tabs
+    pub fn active_tab(&self) -> Option<usize> {
+        win32_with_window_state(self.handle, |state| state.tabs.active()).flatten()
+    }
+
+    // Shows a path in the caption in place of the title, one clickable segment for each
+    // part. Segments that don't fit go into a menu behind a chevron on the left. See
+    // WindowEvent::BreadcrumbSelected. No segments brings the title back.
+    pub fn set_breadcrumb(&self, segments: &[&str]) {
+        win32_with_window_state(self.handle, |state| {
+            state.breadcrumb.set(segments);
+            win32_invalidate_breadcrumb(self.handle, state);
+        });
+    }
+
+    // Adds a button of the application's to the caption. The title, tabs and breadcrumb
+    // make room for it, leading buttons push the icon to the right.
+    pub fn add_caption_button(&self, button: CaptionButton) -> Option<CaptionButtonId> {
+        win32_with_window_state(self.handle, |state| {
+            win32_add_caption_button(self.handle, state, button)
+        })
+    }
+
+    pub fn remove_caption_button(&self, id: CaptionButtonId) {
+        win32_with_window_state(self.handle, |state| {
+            win32_remove_caption_button(self.handle, state, id);
+        });
+    }
+
+    // Adds a ? button to the caption, like WS_EX_CONTEXTHELP gives standard windows.
+    // Clicking it switches to the help cursor, and the next click on the window goes to
+    // handler instead of what was clicked. Escape or a click elsewhere cancels.
+    pub fn set_help_handler(&self, handler: impl FnMut(HelpRequest) + 'static) {
+        win32_with_window_state(self.handle, |state| {
+            state.help_handler = Some(Box::new(handler));
+            if state.help_button.is_some() {
+                return;
+            }
+
+            let icon = match VectorIcon::stroked(WIN32_HELP_GLYPH) {
+                Ok(icon) => icon,
+                Err(e) => {
+                    eprintln!("Failed to create help glyph: {}", e);
+                    return;
+                }
+            };
+            let handle = self.handle;
+            let button =
+                CaptionButton::new(icon, &localized_string(strings::CAPTION_HELP), move || {
+                    win32_enter_context_help(handle)
+                });
+            state.help_button = Some(win32_add_caption_button(self.handle, state, button));
+        });
+    }
+
+    // Removes the ? button again
+    pub fn clear_help_handler(&self) {
+        win32_with_window_state(self.handle, |state| {
+            state.help_handler = None;
+            if let Some(id) = state.help_button.take() {
+                win32_remove_caption_button(self.handle, state, id);
+            }
+        });
+    }
+
+    // A hamburger button at the left edge of the caption, before the icon, that opens
+    // content. None removes it again.
+    pub fn set_menu_button(&self, content: Option<MenuButtonContent>) {
+        win32_with_window_state(self.handle, |state| {
+            let Some(content) = content else {
+                if let Some(menu_button) = state.menu_button.take() {
+                    win32_remove_caption_button(self.handle, state, menu_button.id);
+                }
+                return;
+            };
+
+            if let Some(menu_button) = &mut state.menu_button {
+                menu_button.content = Some(content);
+                return;
+            }
+
+            let icon = match VectorIcon::stroked(WIN32_MENU_GLYPH) {
+                Ok(icon) => icon,
+                Err(e) => {
+                    eprintln!("Failed to create menu glyph: {}", e);
+                    return;
+                }
+            };
+            let handle = self.handle;
+            let button =
+                CaptionButton::new(icon, &localized_string(strings::CAPTION_MENU), move || {
+                    win32_open_menu_button(handle)
+                })
+                .placement(CaptionButtonPlacement::Leading);
+            let id = win32_add_caption_button(self.handle, state, button);
+            state.menu_button = Some(MenuButton {
+                id,
+                content: Some(content),
+            });
+        });
+    }
+
+    // A back arrow at the very left of the caption, like the Settings app has. on_back
+    // runs when it is clicked, replacing the previous one.
+    pub fn set_back_button(&self, on_back: impl FnMut() + 'static) {
+        win32_with_window_state(self.handle, |state| {
+            let old_button = state.back_button.take();
+            let icon = match VectorIcon::stroked(WIN32_BACK_GLYPH) {
+                Ok(icon) => icon,
+                Err(e) => {
+                    eprintln!("Failed to create back glyph: {}", e);
+                    return;
+                }
+            };
+            let button =
+                CaptionButton::new(icon, &localized_string(strings::CAPTION_BACK), on_back)
+                    .placement(CaptionButtonPlacement::Leading);
+            let id = state.caption_buttons.add_first(button);
+            if state.tooltip != HWND(0) {
+                win32_add_tool(state.tooltip, self.handle, id.tool_id());
+            }
+            state.back_button = Some(id);
+
+            // Keeps whether it was enabled
+            if let Some(old_id) = old_button {
+                let enabled = state.caption_buttons.is_enabled(old_id);
+                state.caption_buttons.set_enabled(id, enabled);
+                win32_remove_caption_button(self.handle, state, old_id);
+            } else {
+                win32_invalidate_added_caption_buttons(self.handle, state);
+            }
+        });
+    }
+
+    pub fn remove_back_button(&self) {
+        win32_with_window_state(self.handle, |state| {
+            if let Some(id) = state.back_button.take() {
+                win32_remove_caption_button(self.handle, state, id);
+            }
+        });
+    }
+
+    // Greyed out while there is nowhere to go back to
+    pub fn set_back_enabled(&self, enabled: bool) {
+        win32_with_window_state(self.handle, |state| {
+            if let Some(id) = state.back_button {
+                if state.caption_buttons.set_enabled(id, enabled) {
+                    win32_invalidate_added_caption_buttons(self.handle, state);
+                }
+            }
+        });
+    }
+
+    // Disabled buttons are dimmed and ignore clicks, tooltips still show
+    pub fn set_caption_button_enabled(&self, id: CaptionButtonId, enabled: bool) {
+        win32_with_window_state(self.handle, |state| {
+            if state.caption_buttons.set_enabled(id, enabled) {
+                win32_invalidate_added_caption_buttons(self.handle, state);
+            }
+        });
+    }
+
+    // Hides or shows one of the window's own caption buttons, the others close up.
+    // Showing one the window kind or options leave out does nothing.
+    pub fn set_button_visible(&self, button: SystemButton, visible: bool) {
+        win32_with_window_state(self.handle, |state| {
+            let hovered_button = button.hovered_button();
+            if win32_set_listed(&mut state.hidden_buttons, hovered_button, !visible) {
+                win32_system_button_changed(self.handle, state, hovered_button);
+            }
+        });
+    }
+
+    // Disabled buttons are dimmed, take no hover, clicks or keyboard focus and drag the
+    // window like the rest of the caption
+    pub fn set_button_enabled(&self, button: SystemButton, enabled: bool) {
+        win32_with_window_state(self.handle, |state| {
+            let hovered_button = button.hovered_button();
+            if win32_set_listed(&mut state.disabled_buttons, hovered_button, !enabled) {
+                win32_system_button_changed(self.handle, state, hovered_button);
+            }
+        });
+    }
+
+    // A round user picture left of the system buttons, like Office and Edge show. A
+    // click calls on_open with the screen rect of the button, to open an account
+    // flyout hanging from it. Calling it again swaps picture, tooltip and handler.
+    pub fn set_avatar_button(
+        &self,
+        picture: impl AsRef<Path>,
+        tooltip: &str,
+        on_open: impl FnMut(RECT) + 'static,
+    ) -> Result<()> {
+        win32_with_window_state(self.handle, |state| {
+            // No glyph, the picture is painted over the button
+            let handle = self.handle;
+            let button = CaptionButton::new(VectorIcon::stroked("")?, tooltip, move || {
+                win32_open_avatar_flyout(handle)
+            });
+            let id = state.caption_buttons.add_first(button);
+            let avatar = match AvatarButton::win32_load(
+                self.handle,
+                id,
+                picture.as_ref(),
+                Box::new(on_open),
+            ) {
+                Ok(avatar) => avatar,
+                Err(e) => {
+                    state.caption_buttons.remove(id);
+                    return Err(e);
+                }
+            };
+
+            if state.tooltip != HWND(0) {
+                win32_add_tool(state.tooltip, self.handle, id.tool_id());
+            }
+            if let Some(old_avatar) = state.avatar.replace(avatar) {
+                win32_remove_caption_button(self.handle, state, old_avatar.id);
+            } else {
+                win32_invalidate_added_caption_buttons(self.handle, state);
+            }
+
+            Ok(())
+        })
+        .unwrap_or(Ok(()))
+    }
+
+    pub fn remove_avatar_button(&self) {
+        win32_with_window_state(self.handle, |state| {
+            if let Some(avatar) = state.avatar.take() {
+                win32_remove_caption_button(self.handle, state, avatar.id);
+            }
+        });
+    }
+
+    // A File/Edit/View menu bar drawn in the caption after the icon, with the title
+    // moving right to make room. Each top level item drops down its submenu, items
+    // that don't fit go behind a chevron. Alt, F10 and the mnemonics work like in a
+    // classic menu bar. Commands come as WindowEvent::MenuCommand, the application
+    // keeps owning the menu and calls this again after changing its top level items.
+    pub fn set_menu_bar(&self, menu: Option<HMENU>) {
+        win32_with_window_state(self.handle, |state| {
+            state.menu_bar.set(menu);
+            if state.menu_bar.is_empty() {
+                state.focus_regions.remove(FocusRegion::MenuBar);
+            } else {
+                state.focus_regions.add(FocusRegion::MenuBar);
+            }
+            win32_invalidate_menu_bar(self.handle, state);
+        });
+    }
+
+    // A search field in the middle of the caption, like browsers and Explorer have.
+    // on_search gets the text as it is typed, Escape clears it and then goes back to
+    // the window. Calling it again only swaps the handler.
+    pub fn set_search_box(&self, on_search: impl FnMut(&str) + 'static) -> Result<()> {
+        let on_search: Box<dyn FnMut(&str)> = Box::new(on_search);
+        let Some(Some(on_search)) =
+            win32_with_window_state(self.handle, |state| match &mut state.search_box {
+                Some(search_box) => {
+                    search_box.on_search = Some(on_search);
+                    None
+                }
+                None => Some(on_search),
+            })
+        else {
+            return Ok(());
+        };
+
+        // Creating the EDIT and changing the style send the window messages
+        let search_box = SearchBox::win32_create(self.handle, on_search)?;
+        // The caption is painted around the EDIT, not over it
+        unsafe {
+            let style = GetWindowLongPtrW(self.handle, GWL_STYLE);
+            SetWindowLongPtrW(self.handle, GWL_STYLE, style | WS_CLIPCHILDREN.0 as isize);
+        }
+        win32_with_window_state(self.handle, |state| {
+            state.search_box = Some(search_box);
+            win32_invalidate_added_caption_buttons(self.handle, state);
+        });
+        Ok(())
+    }
+
+    pub fn remove_search_box(&self) {
+        // Destroyed after the state is let go of, the EDIT going away sends the window
+        // messages
+        let _search_box = win32_with_window_state(self.handle, |state| {
+            let search_box = state.search_box.take();
+            if search_box.is_some() {
+                win32_invalidate_added_caption_buttons(self.handle, state);
+            }
+            search_box
+        });
+    }
+
+    // E.g. for Ctrl+E or Ctrl+F
+    pub fn focus_search_box(&self) {
+        win32_with_window_state(self.handle, win32_focus_search_box);
+    }
+
+    // What the ? button does, e.g. for Shift+F1. Needs a help handler.
+    pub fn enter_context_help(&self) {
+        win32_enter_context_help(self.handle);
+    }
+
+    // Shows badge next to the title or on an added caption button, replacing the one
+    // there. None takes it away.
+    pub fn set_badge(&self, anchor: BadgeAnchor, badge: Option<Badge>) {
+        win32_with_window_state(self.handle, |state| {
+            if state.badges.set(anchor, badge) {
+                if let Ok(title_bar_rect) = win32_titlebar_rect(self.handle, state) {
+                    win32_invalidate_rect(self.handle, state, &title_bar_rect);
+                }
+            }
+        });
+    }
+
+    // Shows led in the caption before the application's trailing buttons, replacing the
+    // one there. None takes it away.
+    pub fn set_status_led(&self, led: Option<StatusLed>) {
+        win32_with_window_state(self.handle, |state| {
+            if state.tooltip != HWND(0) {
+                match (&state.status_led, &led) {
+                    (None, Some(_)) => {
+                        win32_add_tool(state.tooltip, self.handle, WIN32_STATUS_LED_TOOL_ID)
+                    }
+                    (Some(_), None) => {
+                        win32_remove_tool(state.tooltip, self.handle, WIN32_STATUS_LED_TOOL_ID)
+                    }
+                    _ => {}
+                }
+            }
+            state.status_led = led;
+
+            win32_invalidate_added_caption_buttons(self.handle, state);
+            win32_start_animation_timer(self.handle, state);
+        });
+    }
+
+    // Shows label in the caption, replacing the one there. None takes it away.
+    pub fn set_caption_label(&self, label: Option<CaptionLabel>) {
+        win32_with_window_state(self.handle, |state| {
+            state.caption_label = label;
+            win32_invalidate_added_caption_buttons(self.handle, state);
+        });
+    }
+
+    // Just the text of the label, e.g. for a clock once a second
+    pub fn set_caption_label_text(&self, text: &str) {
+        win32_with_window_state(self.handle, |state| {
+            if state
+                .caption_label
+                .as_mut()
+                .is_some_and(|label| label.set_text(text))
+            {
+                win32_invalidate_added_caption_buttons(self.handle, state);
+            }
+        });
+    }
+
+    // Gives widget a tooltip, replacing the one it had. None takes it away, the led and
+    // the added buttons go back to their own then.
+    pub fn set_caption_tooltip(&self, widget: CaptionWidget, tooltip: Option<CaptionTooltip>) {
+        win32_with_window_state(self.handle, |state| {
+            win32_set_caption_tooltip(self.handle, state, widget, tooltip);
+        });
+    }
+
+    // Just the count of the badge at anchor, keeping its colors and click handler
+    pub fn set_badge_count(&self, anchor: BadgeAnchor, count: u32) {
+        win32_with_window_state(self.handle, |state| {
+            if state.badges.set_count(anchor, count) {
+                if let Ok(title_bar_rect) = win32_titlebar_rect(self.handle, state) {
+                    win32_invalidate_rect(self.handle, state, &title_bar_rect);
+                }
+            }
+        });
+    }
+
+    // To show what the button does next, e.g. a different glyph while toggled on
+    pub fn set_caption_button_icon(&self, id: CaptionButtonId, icon: VectorIcon) {
+        win32_with_window_state(self.handle, |state| {
+            if state.caption_buttons.set_icon(id, icon) {
+                win32_invalidate_added_caption_buttons(self.handle, state);
+            }
+        });
+    }
+
+    // Draws the glyph of the system and added caption buttons the application wants to
+    // draw itself, returning false leaves a button to the window. Layout, hovering and
+    // clicks stay with the window. Runs while the caption paints, the Window's methods
+    // do nothing in there. None goes back to the window's own glyphs.
+    pub fn set_caption_glyph_painter(
+        &self,
+        glyph_painter: Option<impl FnMut(&CaptionGlyph) -> bool + 'static>,
+    ) {
+        win32_with_window_state(self.handle, |state| {
+            state.glyph_painter =
+                glyph_painter.map(|painter| Box::new(painter) as Win32GlyphPainter);
+            if let Ok(title_bar_rect) = win32_titlebar_rect(self.handle, state) {
+                win32_invalidate_rect(self.handle, state, &title_bar_rect);
+            }
+        });
+    }
+}
+
+// An arrow pointing left, see Window::set_back_button
+const WIN32_BACK_GLYPH: &str = "M13 8 H3 M7.5 3.5 L3 8 L7.5 12.5";
+// A round head on a needle, see WindowOptions::pin_button. The head is filled while
+// the window is pinned.
+const WIN32_PIN_GLYPH: &str = "M5 6 C5 4.34 6.34 3 8 3 C9.66 3 11 4.34 11 6 C11 7.66 9.66 9 8 9 \
+    C6.34 9 5 7.66 5 6 Z M8 9 V13";
+const WIN32_PINNED_GLYPH: &str =
+    "M5 6 C5 4.34 6.34 3 8 3 C9.66 3 11 4.34 11 6 C11 7.66 9.66 9 8 9 \
+    C6.34 9 5 7.66 5 6 Z M7.5 9 H8.5 V13 H7.5 Z";
+// A question mark with its dot, see Window::set_help_handler
+const WIN32_HELP_GLYPH: &str = "M5.5 6 C5.5 2.5 10.5 2.5 10.5 6 C10.5 8 8 8.5 8 10.5 M8 12.5 V13.5";
+
+// Adds or removes button, false when it already was or wasn't listed
+fn win32_set_listed(
+    list: &mut Vec<CustomTitleBarHoveredButton>,
+    button: CustomTitleBarHoveredButton,
+    listed: bool,
+) -> bool {
+    if list.contains(&button) == listed {
+        return false;
+    }
+    if listed {
+        list.push(button);
+    } else {
+        list.retain(|&other| other != button);
+    }
+    true
+}
+
+// After a system button was hidden, shown, disabled or enabled: lets go of it and lays
+// the caption out again
+fn win32_system_button_changed(
+    handle: HWND,
+    state: &mut WindowState,
+    button: CustomTitleBarHoveredButton,
+) {
+    if state.hovered_button == button {
+        win32_set_hovered_button(handle, state, CustomTitleBarHoveredButton::None);
+    }
+    if state.pressed_button == button {
+        state.pressed_button = CustomTitleBarHoveredButton::None;
+    }
+    if state.focused_button == button {
+        win32_set_focused_button(handle, state, CustomTitleBarHoveredButton::None);
+    }
+    win32_invalidate_added_caption_buttons(handle, state);
+}
+
+fn win32_pin_icon(pinned: bool) -> Result<VectorIcon> {
+    if pinned {
+        VectorIcon::filled(WIN32_PINNED_GLYPH)
+    } else {
+        VectorIcon::stroked(WIN32_PIN_GLYPH)
+    }
+}
+
+// Puts the pin button right next to the system buttons, see WindowOptions::pin_button.
+// It is an added button that goes by the always on top name.
+fn win32_add_pin_button(handle: HWND, state: &mut WindowState) {
+    if state.pin_button.is_some() {
+        return;
+    }
+
+    let icon = match win32_pin_icon(state.always_on_top) {
+        Ok(icon) => icon,
+        Err(e) => {
+            eprintln!("Failed to create pin glyph: {}", e);
+            return;
+        }
+    };
+    let tooltip = localized_string(strings::CAPTION_ALWAYS_ON_TOP);
+    let button = CaptionButton::new(icon, &tooltip, move || {
+        let window = Window { handle };
+        if let Err(e) = window.set_always_on_top(!window.is_always_on_top()) {
+            eprintln!("Failed to handle pin button click:\n{}", e);
+        }
+    })
+    .name(CaptionButtonName::AlwaysOnTop);
+    let id = state.caption_buttons.add_first(button);
+    if state.tooltip != HWND(0) {
+        win32_add_tool(state.tooltip, handle, id.tool_id());
+    }
+    state.pin_button = Some(id);
+    win32_invalidate_added_caption_buttons(handle, state);
+}
+
+// Shared by Window::add_caption_button and the methods adding a button of their own
+// while they have the state
+fn win32_add_caption_button(
+    handle: HWND,
+    state: &mut WindowState,
+    button: CaptionButton,
+) -> CaptionButtonId {
+    let id = state.caption_buttons.add(button);
+    if state.tooltip != HWND(0) {
+        win32_add_tool(state.tooltip, handle, id.tool_id());
+    }
+
+    win32_invalidate_added_caption_buttons(handle, state);
+    id
+}
+
+fn win32_remove_caption_button(handle: HWND, state: &mut WindowState, id: CaptionButtonId) {
+    if state.caption_buttons.remove(id) {
+        if state.tooltip != HWND(0) {
+            win32_remove_tool(state.tooltip, handle, id.tool_id());
+        }
+        state.badges.set(BadgeAnchor::Button(id), None);
+        win32_set_caption_tooltip(handle, state, CaptionWidget::Button(id), None);
+
+        win32_invalidate_added_caption_buttons(handle, state);
+    }
+}
+
+// None once the window is destroyed. The window keeps one reference in GWLP_USERDATA,
+// see win32_create_window, the state lives on while a caller still has another.
+fn win32_window_state(handle: HWND) -> Option<Rc<RefCell<WindowState>>> {
+    let state = unsafe { GetWindowLongPtrW(handle, GWLP_USERDATA) } as *const RefCell<WindowState>;
+    if state.is_null() {
+        return None;
+    }
+
+    unsafe {
+        Rc::increment_strong_count(state);
+        Some(Rc::from_raw(state))
+    }
+}
+
+// Calls f with the state borrowed. None once the window is destroyed and while the
+// state is already borrowed further up the stack, e.g. by the window procedure when a
+// callback it runs calls back into the Window.
+fn win32_with_window_state<R>(handle: HWND, f: impl FnOnce(&mut WindowState) -> R) -> Option<R> {
+    let state = win32_window_state(handle)?;
+    win32_borrow_window_state(&state, f)
+}
+
+// The calls win32_once_released queued while f ran go after the borrow ends
+fn win32_borrow_window_state<R>(
+    state: &RefCell<WindowState>,
+    f: impl FnOnce(&mut WindowState) -> R,
+) -> Option<R> {
+    let (result, released_calls) = {
+        let mut state = state.try_borrow_mut().ok()?;
+        let result = f(&mut state);
+        (result, std::mem::take(&mut state.released_calls))
+    };
+
+    for call in released_calls {
+        call();
+    }
+    Some(result)
+}
+
+// For calls that send the window messages, like SetWindowPos or SetCapture. The
+// window procedure can't handle those while the state is borrowed, so they wait until
+// it is let go of, right after the current message or Window method is done with it.
+fn win32_once_released(state: &mut WindowState, call: impl FnOnce() + 'static) {
+    state.released_calls.push(Box::new(call));
+}
+
+// win32_with_window_state for functions returning a Result
+fn win32_try_with_window_state<R>(
+    handle: HWND,
+    f: impl FnOnce(&mut WindowState) -> R,
+) -> Result<R> {
+    win32_with_window_state(handle, f).ok_or_else(|| anyhow!("Window is destroyed or busy"))
+}
+
+// Taking and releasing the mouse capture sends WM_CAPTURECHANGED
+fn win32_set_capture(handle: HWND, state: &mut WindowState) {
+    win32_once_released(state, move || {
+        unsafe { SetCapture(handle) };
+    });
+}
+
+fn win32_release_capture(state: &mut WindowState) {
+    win32_once_released(state, || {
+        if let Err(e) = unsafe { ReleaseCapture() } {
+            eprintln!("Failed to release mouse capture: {}", e.message());
+        }
+    });
+}
+
+// Pumps messages for every window on this thread until WM_QUIT
+pub fn run_message_loop() {
+    let mut message: MSG = MSG::default();
+    while unsafe { GetMessageW(&mut message, None, 0, 0).0 > 0 } {
+        unsafe { TranslateMessage(&message) };
+        unsafe { DispatchMessageW(&message) };
+    }
+}
+
+const DEFAULT_DPI: f32 = 96.0;
+fn win32_dpi_scale(value: i32, dpi: u32) -> i32 {
+    win32_mul_div(value, dpi, DEFAULT_DPI as u32)
+}
+
+// value * numerator / denominator rounded half up, the one rounding every scaled
+// size goes through. Truncating here and there made a 47px button 58px at 125% in
+// one place and 59px in another, so neighbours overlapped or left a gap.
+fn win32_mul_div(value: i32, numerator: u32, denominator: u32) -> i32 {
+    let denominator = denominator.max(1) as i64;
+    (value as i64 * numerator as i64 + denominator / 2).div_euclid(denominator) as i32
+}
+
+// 1 pixel border on top and 1 on bottom
+const TOP_N_BOTTOM_BORDERS_SIZE: i32 = 2;
+fn win32_titlebar_rect(handle: HWND, state: &WindowState) -> Result<RECT> {
+    let theme = unsafe { OpenThemeData(handle, w!("WINDOW")) };
+    let dpi = win32_window_dpi(handle);
+    let caption_part = if state.options.kind.has_small_caption() {
+        WP_SMALLCAPTION
+    } else {
+        WP_CAPTION
+    };
+    let titlebar_size = unsafe {
+        GetThemePartSize(theme, None, caption_part.0, CS_ACTIVE.0, None, TS_TRUE)
+            .map_err(|e| anyhow!("{}", e.message()))?
+    };
+
+    unsafe { CloseThemeData(theme).map_err(|e| anyhow!("{}", e.message()))? };
+
+    let mut height = win32_dpi_scale(titlebar_size.cy, dpi) + TOP_N_BOTTOM_BORDERS_SIZE;
+    if state.touch_metrics {
+        height = height.max(win32_dpi_scale(WIN32_TOUCH_CAPTION_HEIGHT, dpi));
+    }
+    if state.subtitle.is_some() {
+        height = height.max(win32_dpi_scale(WIN32_SUBTITLE_CAPTION_HEIGHT, dpi));
+    }
+
+    let mut rect = RECT::default();
+
+    unsafe { GetClientRect(handle, &mut rect).map_err(|e| anyhow!("{}", e.message()))? };
+
+    // In fullscreen the caption slides down over the content from above the window
+    if let Some(reveal) = win32_caption_reveal(state) {
+        rect.top -= (height as f32 * (1.0 - reveal)).round() as i32;
+    }
+
+    rect.bottom = rect.top + height;
+    Ok(rect)
+}
+
+// Caption button width at 96 dpi, and the bigger touch metrics (see
+// WindowState::touch_metrics) that make comfortable finger targets
+const WIN32_BUTTON_WIDTH: i32 = 47;
+const WIN32_TOOL_BUTTON_WIDTH: i32 = 32;
+const WIN32_TOUCH_BUTTON_WIDTH: i32 = 64;
+const WIN32_TOUCH_CAPTION_HEIGHT: i32 = 44;
+// Caption height at 96 dpi with room for the title and the subtitle under it
+const WIN32_SUBTITLE_CAPTION_HEIGHT: i32 = 42;
+// Put before the title of a window with unsaved changes, like editors do
+const WIN32_MODIFIED_MARKER: &str = "\u{25CF} ";
+// How far the subtitle color goes from the title color towards the caption color
+const WIN32_SUBTITLE_DIMMING: f32 = 0.35;
+// The same for the glyphs of disabled caption buttons
+const WIN32_DISABLED_DIMMING: f32 = 0.6;
+
+// Main for a window that is gone
+fn win32_window_kind(handle: HWND) -> WindowKind {
+    win32_with_window_state(handle, |state| state.options.kind).unwrap_or_default()
+}
+
+// How far the caption is slid in while fullscreen or picture in picture, 0 hidden to
+// 1 shown. None when the caption is always shown.
+fn win32_caption_reveal(state: &WindowState) -> Option<f32> {
+    state.hides_caption().then(|| state.caption_reveal.value())
+}
+
+const WIN32_CAPTION_REVEAL_TIMER_ID: usize = 5;
+// How long the cursor has to rest against the top edge before the caption comes back
+const WIN32_CAPTION_REVEAL_DELAY_MS: u32 = 400;
+const WIN32_CAPTION_REVEAL_DURATION: Duration = Duration::from_millis(150);
+// Height of the strip at the top edge that reveals the caption, at 96 dpi
+const WIN32_CAPTION_REVEAL_ZONE: i32 = 2;
+
+// Slides the hidden caption in or out
+fn win32_reveal_caption(handle: HWND, state: &mut WindowState, revealed: bool) {
+    let duration = if state.skips_animations() {
+        Duration::ZERO
+    } else {
+        WIN32_CAPTION_REVEAL_DURATION
+    };
+
+    state
+        .caption_reveal
+        .animate_to(if revealed { 1.0 } else { 0.0 }, duration);
+    win32_start_animation_timer(handle, state);
+    unsafe { InvalidateRect(handle, None, false) };
+}
+
+fn win32_caption_overlay(handle: HWND, state: &WindowState) -> CaptionOverlay {
+    let Ok(title_bar_rect) = win32_titlebar_rect(handle, state) else {
+        return CaptionOverlay::default();
+    };
+    let button_rects =
+        CustomTitleBarButtonRects::win32_get_title_bar_button_rects(handle, state, &title_bar_rect);
+    let buttons_rect = RECT {
+        left: button_rects.shade.left,
+        ..button_rects.close
+    };
+
+    CaptionOverlay {
+        caption_height: title_bar_rect.bottom - title_bar_rect.top,
+        buttons_rect: if buttons_rect.right > buttons_rect.left {
+            buttons_rect
+        } else {
+            RECT::default()
+        },
+    }
+}
+
+// Tells the application when the caption height or the system buttons moved
+fn win32_update_caption_overlay(handle: HWND, state: &mut WindowState) {
+    let overlay = win32_caption_overlay(handle, state);
+    if overlay != state.caption_overlay {
+        state.caption_overlay = overlay;
+        win32_dispatch_event(handle, WindowEvent::CaptionOverlayChanged(overlay));
+    }
+}
+
+// Switches between the mouse and touch caption metrics, relaying out the caption
+fn win32_set_touch_metrics(handle: HWND, state: &mut WindowState, enabled: bool) {
+    if state.touch_metrics == enabled {
+        return;
+    }
+
+    state.touch_metrics = enabled;
+    win32_relayout_caption(handle, state);
+    unsafe { InvalidateRect(handle, None, false) };
+}
+
+// The "Show animations in Windows" setting, on when it can't be read
+fn win32_animations_enabled() -> bool {
+    let mut enabled = BOOL(1);
+    if let Err(e) = unsafe {
+        SystemParametersInfoW(
+            SPI_GETCLIENTAREAANIMATION,
+            0,
+            Some(&mut enabled as *mut BOOL as _),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+    } {
+        eprintln!("Failed to get animation setting: {}", e.message());
+    }
+
+    enabled.as_bool()
+}
+
+// The "Transparency effects" switch in the personalization settings, which has no
+// SystemParametersInfo value. On when it can't be read, like before the switch existed.
+fn win32_transparency_effects_enabled() -> bool {
+    let mut enabled = 1u32;
+    let mut size = size_of::<u32>() as u32;
+    let result = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            w!("Software\\Microsoft\\Windows\\CurrentVersion\\Themes\\Personalize"),
+            w!("EnableTransparency"),
+            RRF_RT_REG_DWORD,
+            None,
+            Some(&mut enabled as *mut u32 as _),
+            Some(&mut size),
+        )
+    };
+
+    result.is_err() || enabled != 0
+}
+
+// Tablet posture of a convertible, or a tablet without keyboard. Plenty of desktops
+// report slate mode too, it only counts on an undocked tablet PC with a touch screen.
+fn win32_is_slate_mode() -> bool {
+    unsafe {
+        GetSystemMetrics(SM_MAXIMUMTOUCHES) > 0
+            && GetSystemMetrics(SM_TABLETPC) != 0
+            && GetSystemMetrics(SM_SYSTEMDOCKED) == 0
+            && GetSystemMetrics(SM_CONVERTIBLESLATEMODE) == 0
+    }
+}
+
+// MI_WP_SIGNATURE, set in the extra info of mouse messages promoted from touch and pen
+const WIN32_PROMOTED_POINTER_SIGNATURE: u32 = 0xFF515700;
+const WIN32_PROMOTED_POINTER_SIGNATURE_MASK: u32 = 0xFFFFFF00;
+
+// For the mouse message being processed
+fn win32_is_promoted_from_pointer() -> bool {
+    let extra_info = unsafe { GetMessageExtraInfo() };
+    extra_info.0 as u32 & WIN32_PROMOTED_POINTER_SIGNATURE_MASK == WIN32_PROMOTED_POINTER_SIGNATURE
+}
+
+// Set this to 0 to remove the fake shadow painting
+const WIN32_FAKE_SHADOW_HEIGHT: i32 = 1;
+// The offset of the 2 rectangles of the maximized window button, scaled by dpi
+const WIN32_MAXIMIZED_BUTTON_OFFSET: i32 = 2;
+// Stroke width of the button glyphs at 96 dpi
+const WIN32_GLYPH_STROKE_WIDTH: i32 = 1;
+
+// Scaled the same way as the icon dimension so glyph strokes and sizes stay in
+// proportion, but never thinner than a physical pixel
+fn win32_glyph_stroke_width(dpi: u32) -> i32 {
+    win32_dpi_scale(WIN32_GLYPH_STROKE_WIDTH, dpi).max(1)
+}
+
+fn win32_fake_shadow_rect(handle: HWND) -> Result<RECT> {
+    let mut rect = RECT::default();
+    unsafe { GetClientRect(handle, &mut rect).map_err(|e| anyhow!("{}", e.message()))? };
+    rect.bottom = rect.top + WIN32_FAKE_SHADOW_HEIGHT;
+    Ok(rect)
+}
+
+struct CustomTitleBarButtonRects {
+    close: RECT,
+    maximize: RECT,
+    minimize: RECT,
+    // Empty at the left edge of minimize while the shade button is hidden
+    shade: RECT,
+    // In hover_transitions order, see Window::set_button_enabled
+    disabled: [bool; CAPTION_BUTTONS.len()],
+}
+
+// One of the window's own caption buttons, see Window::set_button_visible
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SystemButton {
+    Shade,
+    Minimize,
+    Maximize,
+    Close,
+}
+
+impl SystemButton {
+    fn hovered_button(self) -> CustomTitleBarHoveredButton {
+        match self {
+            Self::Shade => CustomTitleBarHoveredButton::Shade,
+            Self::Minimize => CustomTitleBarHoveredButton::Minimize,
+            Self::Maximize => CustomTitleBarHoveredButton::Maximize,
+            Self::Close => CustomTitleBarHoveredButton::Close,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum CustomTitleBarHoveredButton {
+    #[default]
+    None,
+    // Rolls the window up to its caption, see WindowOptions::shade_button
+    Shade,
+    Minimize,
+    Maximize,
+    Close,
+}
+
+// In hover_transitions order, the tooltip tool ids are the index + 1
+const CAPTION_BUTTONS: [CustomTitleBarHoveredButton; 4] = [
+    CustomTitleBarHoveredButton::Shade,
+    CustomTitleBarHoveredButton::Minimize,
+    CustomTitleBarHoveredButton::Maximize,
+    CustomTitleBarHoveredButton::Close,
+];
+
+impl CustomTitleBarHoveredButton {
+    // Position in WindowState::hover_transitions
+    fn index(self) -> Option<usize> {
+        match self {
+            Self::None => None,
+            Self::Shade => Some(0),
+            Self::Minimize => Some(1),
+            Self::Maximize => Some(2),
+            Self::Close => Some(3),
+        }
+    }
+
+    // Child id of the button in the OBJID_TITLEBAR accessible object and index in
+    // TITLEBARINFOEX. Shade has no place there.
+    fn title_bar_child(self) -> Option<usize> {
+        match self {
+            Self::Minimize => Some(2),
+            Self::Maximize => Some(3),
+            Self::Close => Some(5),
+            Self::None | Self::Shade => None,
+        }
+    }
+
+    // The system buttons depend on the window kind, the others are opt in. Any of them
+    // can be hidden with Window::set_button_visible.
+    fn is_shown(self, state: &WindowState) -> bool {
+        let options = &state.options;
+        !state.hidden_buttons.contains(&self)
+            && match self {
+                Self::Shade => options.shade_button,
+                Self::Minimize => options.kind == WindowKind::Main && state.has_window_boxes(),
+                Self::Maximize => {
+                    matches!(options.kind, WindowKind::Main | WindowKind::Document)
+                        && state.has_window_boxes()
+                }
+                Self::Close | Self::None => true,
+            }
+    }
+
+    // See Window::set_button_enabled. Like the system caption, minimize and maximize
+    // without their window style are disabled while the other one is there.
+    fn is_enabled(self, state: &WindowState) -> bool {
+        !state.disabled_buttons.contains(&self)
+            && match self {
+                Self::Minimize => state.window_style.contains(WS_MINIMIZEBOX),
+                Self::Maximize => state.window_style.contains(WS_MAXIMIZEBOX),
+                _ => true,
+            }
+    }
+}
+
+// width centered on the caption and moved into available as far as needed, all of
+// available when it is too narrow
+fn win32_centered_rect(available: &RECT, caption: &RECT, width: i32) -> RECT {
+    let width = width.clamp(0, (available.right - available.left).max(0));
+    let left = ((caption.left + caption.right - width) / 2)
+        .min(available.right - width)
+        .max(available.left);
+    RECT {
+        left,
+        right: left + width,
+        ..*available
+    }
+}
+
+// What to do with a title that doesn't fit between the left padding and the buttons
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum TitleOverflow {
+    #[default]
+    Ellipsis,
+    // Fade the text out towards the caption background at the clip edge
+    Fade,
+    // Slowly scroll the text, repeating it after a gap
+    Marquee,
+}
+
+// The tint of Window::set_caption_progress
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CaptionProgressState {
+    #[default]
+    Normal,
+    Paused,
+    Error,
+}
+
+impl CaptionProgressState {
+    fn color(self) -> COLORREF {
+        COLORREF(match self {
+            Self::Normal => rgb(6, 176, 37),
+            Self::Paused => rgb(218, 165, 32),
+            Self::Error => rgb(218, 38, 38),
+        })
+    }
+}
+
+// How much of the progress color goes into the caption background
+const WIN32_CAPTION_PROGRESS_TINT: f32 = 0.3;
+
+// The caption background over rect, with the part Window::set_caption_progress covers
+// tinted. Anything painting over the caption background repaints it with this.
+fn win32_fill_caption_background(
+    hdc: HDC,
+    state: &WindowState,
+    rect: &RECT,
+    title_bar_rect: &RECT,
+    color: COLORREF,
+) {
+    let progress_right = state.caption_progress.map_or(rect.left, |fraction| {
+        let width = (title_bar_rect.right - title_bar_rect.left) as f32;
+        title_bar_rect.left + (width * fraction).round() as i32
+    });
+    let split = progress_right.clamp(rect.left, rect.right);
+    let tint = win32_blend_color(
+        color,
+        state.caption_progress_state.color(),
+        WIN32_CAPTION_PROGRESS_TINT,
+    );
+
+    for (left, right, fill_color) in [(rect.left, split, tint), (split, rect.right, color)] {
+        if right > left {
+            unsafe {
+                let brush = CreateSolidBrush(fill_color);
+                FillRect(
+                    hdc,
+                    &RECT {
+                        left,
+                        right,
+                        ..*rect
+                    },
+                    brush,
+                );
+                DeleteObject(brush);
+            }
+        }
+    }
+}
+
+// Where the title sits in the room the caption widgets leave it
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum TitleAlignment {
+    #[default]
+    Left,
+    // In the middle of the whole caption like Windows 11 dialogs and macOS, pushed
+    // aside as far as the widgets on either side need
+    Center,
+}
+
+// Behaviors the application opts into when creating the window
+#[derive(Default)]
+pub struct WindowOptions {
+    // Draw the caption button glyphs with Segoe Fluent Icons / Segoe MDL2 Assets
+    // like the system does, falling back to GDI lines when neither is installed
+    pub use_glyph_font: bool,
+    pub title_overflow: TitleOverflow,
+    pub title_alignment: TitleAlignment,
+    // Expanding ripple from the click point while a caption button is held down
+    pub press_ripple: bool,
+    // Middle clicking the title bar closes the window, like browser tabs
+    pub middle_click_closes: bool,
+    // Classic behavior of the caption icon: a single click opens the system menu and a
+    // double click closes the window. When off, the menu opens right away.
+    pub icon_double_click_closes: bool,
+    // Drag the caption ourselves instead of through the system move loop, so shaking
+    // the window doesn't minimize the others. See Window::set_aero_shake.
+    pub disable_aero_shake: bool,
+    // Double clicking the top or bottom resize border stretches the window over the
+    // height of the work area keeping its width, like Explorer. Again to undo.
+    pub border_double_click_maximizes_vertically: bool,
+    // Alt+Enter toggles borderless fullscreen, like games and media players
+    pub alt_enter_fullscreen: bool,
+    pub fullscreen_bounds: FullscreenBounds,
+    // Don't reopen where the window was last closed. The placement is saved per
+    // executable and window title under HKEY_CURRENT_USER otherwise.
+    pub forget_placement: bool,
+    // Caption button left of the system buttons that keeps the window above the others,
+    // see Window::set_always_on_top. It is one of the added caption buttons, called
+    // CaptionButtonName::AlwaysOnTop. The system menu has the command either way.
+    pub pin_button: bool,
+    // Double clicking the caption rolls the window up to just its caption and back,
+    // instead of maximizing it. See Window::set_shaded.
+    pub caption_double_click_shades: bool,
+    // Caption button left of the others that rolls the window up and back
+    pub shade_button: bool,
+    pub kind: WindowKind,
+    // Ctrl+Alt+T tiles the application's windows side by side and Ctrl+Alt+G in a
+    // grid, see Window::tile_windows
+    pub tiling_shortcuts: bool,
+    // Moving or resizing the window, its edges stick to the work area edges and to the
+    // application's other windows once they come this close, at 96 dpi. 0 turns it off.
+    pub snap_distance: i32,
+    // Closing the window while it is marked modified asks first, see Window::set_modified
+    pub confirm_close_when_modified: bool,
+    // While modified the close button shows a dot instead of the X until the mouse or
+    // the keyboard focus gets to it, like macOS. The title goes without its dot then.
+    pub modified_close_dot: bool,
+    // Leave the window icon out of the caption, the title moves to the left edge. The
+    // system menu still opens with Alt+Space or a right click on the caption.
+    pub hide_caption_icon: bool,
+    // Gap left and right of the caption icon at 96 dpi, None for the default of 10
+    pub caption_icon_padding: Option<i32>,
+    // Width of the caption buttons at 96 dpi, None for the default of 47, or 32 in the
+    // short caption of tools and dialogs. Touch metrics still make them 64 wide.
+    pub caption_button_width: Option<i32>,
+    // Space between the window's own caption buttons at 96 dpi, it drags the window
+    // like the rest of the caption
+    pub caption_button_gap: i32,
+    // Size of the window's own caption button glyphs at 96 dpi, None for the default
+    // of 10
+    pub caption_glyph_size: Option<i32>,
+    // Don't play the Minimize, Maximize, Restore Down and Close system sounds when the
+    // caption buttons are used. Windows plays them for the standard caption.
+    pub mute_caption_sounds: bool,
+}
+
+// What the window is for, decides its caption and taskbar presence
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum WindowKind {
+    // An application window with the full caption and a taskbar button
+    #[default]
+    Main,
+    // Palettes and inspectors: a short caption with only a close button and no
+    // taskbar button. Closing one doesn't end the message loop.
+    Tool,
+    // Made by WindowBuilder::run_modal: the full caption height, but only a close
+    // button and no icon. Escape closes it.
+    Dialog,
+    // One of the open documents of a main window, given with WindowBuilder::owner. A
+    // short caption with maximize and close buttons, listed in the owner's window menu
+    // to bring it to the front.
+    Document,
+}
+
+impl WindowKind {
+    fn has_small_caption(self) -> bool {
+        matches!(self, Self::Tool | Self::Document)
+    }
+}
+
+// What the window reports to the handler given to Window::set_event_handler
+#[derive(Clone, Debug, PartialEq)]
+pub enum WindowEvent {
+    // Keyboard focus moved to another region. Moving into Content is the application's
+    // cue to focus its own controls, leaving it to drop their focus.
+    FocusRegionChanged(FocusRegion),
+    // A key the window itself has no use for was pressed
+    KeyDown(VIRTUAL_KEY),
+    // The side buttons of the mouse, anywhere over the window including the caption
+    XButtonDown(MouseXButton),
+    XButtonUp(MouseXButton),
+    // A caption tab was clicked or reached with Ctrl+Tab, it is the active one now
+    TabSelected(usize),
+    // The close button of a tab, or a middle click on it. The tab stays until
+    // Window::remove_tab, so the application can ask about unsaved changes first.
+    TabCloseRequested(usize),
+    // The button right of the caption tabs
+    NewTabRequested,
+    // Another instance was started with these arguments and exited, see
+    // SingleInstance. The window was brought to the front already.
+    CommandLine(Vec<String>),
+    // Sent by another process with RemoteCommand::send_to_instance or send_to_window
+    RemoteCommand(RemoteCommand),
+    // Nobody vetoed, the process is terminated soon after the handler returns. The
+    // last chance to save.
+    SessionEnded,
+    // The user switched transparency effects on or off, see
+    // Window::transparency_effects_enabled
+    TransparencyEffectsChanged(bool),
+    // A breadcrumb segment was clicked or picked from the overflow menu, the index is
+    // into the segments given to Window::set_breadcrumb
+    BreadcrumbSelected(usize),
+    // Typed or composed with an input method while a caption text field has the focus,
+    // see Window::set_caption_text_input. Control characters like backspace included.
+    TextInput(String),
+    // Picked from the menu of the caption's menu button, see MenuButtonContent::Menu,
+    // or from the menu bar, see Window::set_menu_bar
+    MenuCommand(usize),
+    // Where the items given to Window::set_caption_items are now, in client coordinates
+    // and in the same order. Empty rects for the ones that didn't fit.
+    CaptionItemsMoved(Vec<RECT>),
+    // The caption height or the system buttons changed, see Window::caption_overlay
+    CaptionOverlayChanged(CaptionOverlay),
+}
+
+// The part of the caption content drawn into the client area has to keep clear of,
+// like Electron's titleBarOverlay. In client coordinates.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CaptionOverlay {
+    pub caption_height: i32,
+    // The window's own caption buttons together, empty when none is shown
+    pub buttons_rect: RECT,
+}
+
+// Why the session ends, given to the handler of Window::set_session_ending_handler
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SessionEnding {
+    // Only the user logs off, the system keeps running
+    pub logoff: bool,
+    // An installer or update asked for the application to close through the Restart
+    // Manager, see register_application_restart
+    pub close_app: bool,
+    // Forced, the session ends whatever the handler returns
+    pub critical: bool,
+}
+
+// Side buttons of a mouse, usually bound to back and forward navigation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MouseXButton {
+    // XBUTTON1
+    Back,
+    // XBUTTON2
+    Forward,
+}
+
+// What a fullscreen window covers
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum FullscreenBounds {
+    // The whole monitor, over the taskbar
+    #[default]
+    Monitor,
+    // The monitor without the taskbar and docked toolbars
+    WorkArea,
+}
+
+// Per window state, a reference to it lives in GWLP_USERDATA
+#[derive(Default)]
+struct WindowState {
+    options: WindowOptions,
+    // Resolved once in WM_CREATE when options.use_glyph_font is set
+    glyph_font_face: Option<PCWSTR>,
+    // Of that face at the dpi it was created for, see win32_glyph_font
+    glyph_font: Option<(u32, HFONT)>,
+    hovered_button: CustomTitleBarHoveredButton,
+    // How much of the hover highlight each button shows, 0 to 1
+    hover_transitions: [Transition; 4],
+    // Button the left mouse button went down on, drawn pressed while still hovered
+    pressed_button: CustomTitleBarHoveredButton,
+    // Client coordinates of the press, where the ripple grows from
+    press_origin: POINT,
+    press_ripple: Transition,
+    // TrackMouseEvent was asked for a WM_NCMOUSELEAVE that hasn't arrived yet
+    tracking_nonclient_leave: bool,
+    tooltip: HWND,
+    caption_button_names: CaptionButtonNames,
+    // The maximize button reports HTMAXBUTTON so Windows 11 shows Snap Layouts
+    snap_layouts: bool,
+    // Battery saver is on, see GUID_POWER_SAVING_STATUS
+    power_saving: bool,
+    // Minimized, hidden or cloaked by DWM (e.g. on another virtual desktop)
+    occluded: bool,
+    // Union of the rects invalidated while throttled, flushed by WIN32_REPAINT_TIMER_ID
+    pending_invalidation: Option<RECT>,
+    power_notification: HPOWERNOTIFY,
+    // The window text without terminator, kept by WM_SETTEXT so painting doesn't ask for it
+    title: Vec<u16>,
+    // Smaller second line under the title, see Window::set_subtitle
+    subtitle: Option<Vec<u16>>,
+    // Unsaved changes, marked before the title. See Window::set_modified.
+    modified: bool,
+    // Loaded by Window::set_icon_from_file, owned by the window
+    file_icons: Option<FileIcons>,
+    // Where the title and subtitle were last painted, all a new title needs repainted
+    title_rect: RECT,
+    // Title text rect while the marquee is scrolling
+    marquee_rect: Option<RECT>,
+    marquee_offset: i32,
+    // Where the caption was grabbed relative to the window origin while dragging it
+    // without the system move loop
+    caption_drag_offset: Option<POINT>,
+    hit_test_override: Option<Box<dyn FnMut(POINT) -> Option<u32>>>,
+    // See win32_once_released
+    released_calls: Vec<Box<dyn FnOnce()>>,
+    // Parts of the caption that hit test as HTCLIENT, in client coordinates
+    caption_interactive_regions: Vec<RECT>,
+    // Registry value the placement is saved to on close, None to forget it
+    placement_name: Option<String>,
+    // Client size limits at 96 dpi, see Window::set_min_size
+    min_size: Option<SIZE>,
+    max_size: Option<SIZE>,
+    // Set while fullscreen, see win32_set_fullscreen
+    fullscreen_restore: Option<FullscreenRestore>,
+    // The fullscreen caption is hidden until the cursor rests at the top edge
+    caption_reveal: Transition,
+    // Rect to go back to after a vertical maximize, see
+    // WindowOptions::border_double_click_maximizes_vertically
+    vertical_restore_rect: Option<RECT>,
+    // Window height to go back to while rolled up to the caption, see win32_set_shaded
+    shade_restore_height: Option<i32>,
+    // Owner disabled by WindowBuilder::run_modal, enabled again before the dialog goes
+    // away so it gets the activation back
+    modal_owner: Option<HWND>,
+    // Set while in picture in picture, see win32_set_picture_in_picture
+    picture_in_picture_restore: Option<PictureInPictureRestore>,
+    // TrackMouseEvent was asked for a WM_MOUSELEAVE, picture in picture hides its
+    // caption with it
+    tracking_client_leave: bool,
+    // Drawn in the caption instead of the title once Window::add_tab was called
+    tabs: TabStrip,
+    // Drawn instead of the title while it has segments, see Window::set_breadcrumb
+    breadcrumb: Breadcrumb,
+    // The application's own, see Window::add_caption_button
+    caption_buttons: CaptionButtons,
+    // See Window::set_badge
+    badges: Badges,
+    // See Window::set_status_led
+    status_led: Option<StatusLed>,
+    // See Window::set_caption_label
+    caption_label: Option<CaptionLabel>,
+    // See Window::set_caption_tooltip
+    caption_tooltips: CaptionTooltips,
+    // The window's own ones hidden or disabled by the application, see
+    // Window::set_button_visible and Window::set_button_enabled
+    hidden_buttons: Vec<CustomTitleBarHoveredButton>,
+    disabled_buttons: Vec<CustomTitleBarHoveredButton>,
+    // GWL_STYLE as of the last WM_STYLECHANGED, its minimize and maximize boxes decide
+    // about the buttons
+    window_style: WINDOW_STYLE,
+    // Draws the glyphs of any caption button instead of the window, see
+    // Window::set_caption_glyph_painter. Taken out while painting.
+    glyph_painter: Option<Win32GlyphPainter>,
+    // The application's widgets, see Window::set_caption_items, and where they were
+    // last reported to be
+    caption_items: Vec<CaptionItem>,
+    caption_item_rects: Vec<RECT>,
+    // Last reported with WindowEvent::CaptionOverlayChanged
+    caption_overlay: CaptionOverlay,
+    // See Window::set_caption_progress, between 0 and 1
+    caption_progress: Option<f32>,
+    caption_progress_state: CaptionProgressState,
+    // Drawn between the icon and the title, see Window::set_menu_bar
+    menu_bar: MenuBar,
+    // Set with Window::set_help_handler, along with the ? button it added
+    help_handler: Option<Box<dyn FnMut(HelpRequest)>>,
+    help_button: Option<CaptionButtonId>,
+    // The hamburger button at the left edge, see Window::set_menu_button
+    menu_button: Option<MenuButton>,
+    // See Window::set_back_button
+    back_button: Option<CaptionButtonId>,
+    // See WindowOptions::pin_button and Window::set_pin_button
+    pin_button: Option<CaptionButtonId>,
+    // See Window::set_avatar_button
+    avatar: Option<AvatarButton>,
+    // See Window::set_search_box
+    search_box: Option<SearchBox>,
+    // Waiting for the click to ask for help about, the mouse is captured until then
+    context_help: bool,
+    // Text and buttons of a window opened by message_box
+    message_box: Option<MessageBoxContent>,
+    // Window property later instances find the window by, see SingleInstance
+    instance_property: Option<HSTRING>,
+    // WindowKind::Document windows owned by this one, in the order they were opened
+    documents: Vec<HWND>,
+    // How many of them the window menu listed the last time, see
+    // win32_update_document_menu_items
+    document_menu_items: usize,
+    // Hit test code and the cursor set with Window::set_region_cursor
+    region_cursors: Vec<(u32, HCURSOR)>,
+    // Id of the touch or pen contact holding pressed_button down
+    pointer_press: Option<u32>,
+    // Caption button with the keyboard focus ring, None while the focus is elsewhere
+    focused_button: CustomTitleBarHoveredButton,
+    focus_regions: FocusRegions,
+    event_handler: Option<Box<dyn FnMut(WindowEvent)>>,
+    session_ending_handler: Option<Box<dyn FnMut(SessionEnding) -> bool>>,
+    // Set with Window::set_shutdown_block_reason
+    blocks_shutdown: bool,
+    // Bigger caption and buttons while the last input came from touch (or in
+    // tablet posture), back to normal on the next mouse click
+    touch_metrics: bool,
+    // As of the last "ConvertibleSlateMode" WM_SETTINGCHANGE, see win32_is_slate_mode
+    slate_mode: bool,
+    // What the pixel sizes kept in here were measured at, see WM_DPICHANGED
+    dpi: u32,
+    // Last seen in WM_WINDOWPOSCHANGED, to tell assistive technology about the switch
+    maximized: bool,
+    // Last seen in WM_WINDOWPOSCHANGED, other programs (e.g. PowerToys' Always On Top)
+    // pin and unpin windows too and the pin button has to follow
+    always_on_top: bool,
+    // "Show animations in Windows" is off, everything jumps to its end state
+    reduced_motion: bool,
+    // The "Transparency effects" personalization setting
+    transparency_effects: bool,
+    // Client rect of the caption text field with the keyboard focus, see
+    // Window::set_caption_text_input
+    caption_text_input: Option<RECT>,
+    // First half of a character outside the BMP typed into caption_text_input
+    pending_high_surrogate: Option<u16>,
+}
+
+impl WindowState {
+    // Without both WS_MINIMIZEBOX and WS_MAXIMIZEBOX neither button is shown
+    fn has_window_boxes(&self) -> bool {
+        self.window_style.contains(WS_MINIMIZEBOX) || self.window_style.contains(WS_MAXIMIZEBOX)
+    }
+
+    // Skip animations and batch repaints when nobody is looking or energy is scarce
+    fn is_throttled(&self) -> bool {
+        self.power_saving || self.occluded
+    }
+
+    // Hover fades, the press ripple and the sliding caption
+    fn skips_animations(&self) -> bool {
+        self.is_throttled() || self.reduced_motion
+    }
+
+    // Fullscreen and picture in picture only slide the caption in on demand
+    fn hides_caption(&self) -> bool {
+        self.fullscreen_restore.is_some() || self.picture_in_picture_restore.is_some()
+    }
+
+    fn is_animating(&self) -> bool {
+        self.hover_transitions.iter().any(Transition::is_running)
+            || self.caption_buttons.is_animating()
+            || self.press_ripple.is_running()
+            || self.caption_reveal.is_running()
+            || (self.status_led.as_ref().is_some_and(StatusLed::is_blinking)
+                && !self.skips_animations())
+    }
+}
+
+const WIN32_REPAINT_TIMER_ID: usize = 1;
+// ~30 repaints per second at most while throttled
+const WIN32_COALESCED_REPAINT_INTERVAL_MS: u32 = 33;
+
+// Invalidates right away normally, but while throttled the rects are merged and
+// painted once per WIN32_COALESCED_REPAINT_INTERVAL_MS, or not at all until the
+// window becomes visible again.
+fn win32_invalidate_rect(handle: HWND, state: &mut WindowState, rect: &RECT) {
+    if !state.is_throttled() {
+        unsafe { InvalidateRect(handle, Some(rect), false) };
+        return;
+    }
+
+    let had_pending = state.pending_invalidation.is_some();
+    let pending = state.pending_invalidation.get_or_insert(*rect);
+    let previous = *pending;
+    unsafe { UnionRect(pending, &previous, rect) };
+
+    if !had_pending && !state.occluded {
+        unsafe {
+            SetTimer(
+                handle,
+                WIN32_REPAINT_TIMER_ID,
+                WIN32_COALESCED_REPAINT_INTERVAL_MS,
+                None,
+            )
+        };
+    }
+}
+
+fn win32_flush_pending_invalidation(handle: HWND, state: &mut WindowState) {
+    if let Some(rect) = state.pending_invalidation.take() {
+        unsafe { InvalidateRect(handle, Some(&rect), false) };
+    }
+}
+
+fn win32_is_power_saving() -> bool {
+    let mut status = SYSTEM_POWER_STATUS::default();
+    if let Err(e) = unsafe { GetSystemPowerStatus(&mut status) } {
+        eprintln!("Failed to get system power status: {}", e.message());
+        return false;
+    }
+
+    // 1 = battery saver is on
+    status.SystemStatusFlag == 1
+}
+
+fn win32_window_is_occluded(handle: HWND) -> bool {
+    if unsafe { IsIconic(handle) }.as_bool() || !unsafe { IsWindowVisible(handle) }.as_bool() {
+        return true;
+    }
+
+    let mut cloaked = 0u32;
+    let result = unsafe {
+        DwmGetWindowAttribute(
+            handle,
+            DWMWA_CLOAKED,
+            &mut cloaked as *mut u32 as _,
+            size_of::<u32>() as u32,
+        )
+    };
+
+    matches!(result, Ok(())) && cloaked != 0
+}
+
+// Re-evaluates the throttling inputs and flushes or stops the coalescing timer
+fn win32_update_throttling(handle: HWND, state: &mut WindowState) {
+    let was_throttled = state.is_throttled();
+    state.occluded = win32_window_is_occluded(handle);
+
+    if state.occluded {
+        // Nothing to paint into, pending rects wait until the window is back
+        let _ = unsafe { KillTimer(handle, WIN32_REPAINT_TIMER_ID) };
+    } else if was_throttled && !state.is_throttled() {
+        let _ = unsafe { KillTimer(handle, WIN32_REPAINT_TIMER_ID) };
+        win32_flush_pending_invalidation(handle, state);
+    } else if state.pending_invalidation.is_some() {
+        unsafe {
+            SetTimer(
+                handle,
+                WIN32_REPAINT_TIMER_ID,
+                WIN32_COALESCED_REPAINT_INTERVAL_MS,
+                None,
+            )
+        };
+    }
+
+    if state.is_throttled() {
+        for transition in &mut state.hover_transitions {
+            transition.finish();
+        }
+        state.caption_buttons.finish_animations();
+        state.press_ripple.finish();
+    }
+
+    // The marquee is an animation too, pause it while throttled
+    if state.marquee_rect.is_some() {
+        if state.is_throttled() {
+            let _ = unsafe { KillTimer(handle, WIN32_MARQUEE_TIMER_ID) };
+        } else {
+            unsafe {
+                SetTimer(
+                    handle,
+                    WIN32_MARQUEE_TIMER_ID,
+                    WIN32_MARQUEE_INTERVAL_MS,
+                    None,
+                )
+            };
+        }
+    }
+}
+
+const WIN32_ANIMATION_TIMER_ID: usize = 3;
+const WIN32_HOVER_FADE_DURATION: Duration = Duration::from_millis(100);
+
+// Moves the hover highlight, fading it in and out unless repaints are throttled
+fn win32_set_hovered_button(
+    handle: HWND,
+    state: &mut WindowState,
+    hovered_button: CustomTitleBarHoveredButton,
+) {
+    let duration = if state.skips_animations() {
+        Duration::ZERO
+    } else {
+        WIN32_HOVER_FADE_DURATION
+    };
+
+    for (button, target) in [(state.hovered_button, 0.0), (hovered_button, 1.0)] {
+        if let Some(index) = button.index() {
+            state.hover_transitions[index].animate_to(target, duration);
+        }
+    }
+
+    if state.hovered_button != hovered_button {
+        win32_notify_caption_button(handle, EVENT_OBJECT_STATECHANGE, state.hovered_button);
+        win32_notify_caption_button(handle, EVENT_OBJECT_STATECHANGE, hovered_button);
+    }
+
+    state.hovered_button = hovered_button;
+    win32_start_animation_timer(handle, state);
+}
+
+fn win32_start_animation_timer(handle: HWND, state: &WindowState) {
+    if state.is_animating() {
+        unsafe {
+            SetTimer(
+                handle,
+                WIN32_ANIMATION_TIMER_ID,
+                ANIMATION_FRAME_INTERVAL_MS,
+                None,
+            )
+        };
+    }
+}
+
+const WIN32_PRESS_RIPPLE_DURATION: Duration = Duration::from_millis(350);
+
+// Hover fades towards hover_color, holding the mouse button down shows pressed_color
+// with the optional ripple on top. Returns the fill so glyphs can cut into it.
+fn win32_paint_caption_button_background(
+    hdc: HDC,
+    state: &WindowState,
+    button: CustomTitleBarHoveredButton,
+    rect: &RECT,
+    titlebar_color: COLORREF,
+    hover_color: COLORREF,
+    pressed_color: COLORREF,
+) -> COLORREF {
+    let is_pressed = state.pressed_button == button && state.hovered_button == button;
+    let amount = button
+        .index()
+        .map_or(0.0, |index| state.hover_transitions[index].value());
+    let fill_color = if is_pressed {
+        pressed_color
+    } else {
+        win32_blend_color(titlebar_color, hover_color, amount)
+    };
+
+    // At rest the caption background shows through, caption progress included
+    if is_pressed || amount > 0.0 {
+        let fill_brush = unsafe { CreateSolidBrush(fill_color) };
+        unsafe {
+            FillRect(hdc, rect, fill_brush);
+            DeleteObject(fill_brush);
+        }
+    }
+
+    if is_pressed && state.options.press_ripple && state.press_ripple.is_running() {
+        win32_paint_press_ripple(
+            hdc,
+            rect,
+            state.press_origin,
+            state.press_ripple.value(),
+            fill_color,
+        );
+    }
+
+    fill_color
+}
+
+// A lighter circle growing from the press point and fading out as it grows
+fn win32_paint_press_ripple(
+    hdc: HDC,
+    rect: &RECT,
+    origin: POINT,
+    progress: f32,
+    fill_color: COLORREF,
+) {
+    let farthest_x = (origin.x - rect.left).max(rect.right - origin.x) as f32;
+    let farthest_y = (origin.y - rect.top).max(rect.bottom - origin.y) as f32;
+    let radius = (farthest_x.hypot(farthest_y) * progress).round() as i32;
+    let ripple_color = win32_blend_color(
+        fill_color,
+        COLORREF(rgb(255, 255, 255)),
+        0.3 * (1.0 - progress),
+    );
+
+    unsafe {
+        let saved_dc = SaveDC(hdc);
+        IntersectClipRect(hdc, rect.left, rect.top, rect.right, rect.bottom);
+
+        let ripple_brush = CreateSolidBrush(ripple_color);
+        let old_brush = SelectObject(hdc, ripple_brush);
+        let old_pen = SelectObject(hdc, GetStockObject(NULL_PEN));
+        Ellipse(
+            hdc,
+            origin.x - radius,
+            origin.y - radius,
+            origin.x + radius,
+            origin.y + radius,
+        );
+        SelectObject(hdc, old_pen);
+        SelectObject(hdc, old_brush);
+        DeleteObject(ripple_brush);
+
+        RestoreDC(hdc, saved_dc);
+    }
+}
+
+fn win32_get_button_rects(handle: HWND, state: &WindowState) -> Result<CustomTitleBarButtonRects> {
+    let title_bar_rect = win32_titlebar_rect(handle, state)?;
+    Ok(CustomTitleBarButtonRects::win32_get_title_bar_button_rects(
+        handle,
+        state,
+        &title_bar_rect,
+    ))
+}
+
+// point is in client coordinates
+fn win32_caption_button_at(
+    handle: HWND,
+    state: &WindowState,
+    point: POINT,
+) -> CustomTitleBarHoveredButton {
+    match win32_get_button_rects(handle, state) {
+        Ok(button_rects) => button_rects.button_at(point),
+        Err(e) => {
+            eprintln!("Failed to get title bar rect:\n{}", e);
+            CustomTitleBarHoveredButton::None
+        }
+    }
+}
+
+// Shows button as held down, origin (client coordinates) is where the ripple starts.
+// Whoever calls this tracks the release, by mouse capture or pointer id.
+fn win32_begin_caption_button_press(
+    handle: HWND,
+    state: &mut WindowState,
+    button: CustomTitleBarHoveredButton,
+    origin: POINT,
+) {
+    if state.tooltip != HWND(0) {
+        win32_hide_tooltip(state.tooltip);
+    }
+
+    if state.hovered_button != button {
+        win32_set_hovered_button(handle, state, button);
+    }
+
+    win32_set_pressed_button(handle, state, button);
+    state.press_origin = origin;
+
+    state.press_ripple = Transition::settled(0.0);
+    if state.options.press_ripple && !state.skips_animations() {
+        state
+            .press_ripple
+            .animate_to(1.0, WIN32_PRESS_RIPPLE_DURATION);
+        win32_start_animation_timer(handle, state);
+    }
+
+    win32_invalidate_caption_buttons(handle, state);
+}
+
+// Expects state.pressed_button to be cleared already. Only a release on the same
+// button counts, anything else cancels the press.
+fn win32_end_caption_button_press(
+    handle: HWND,
+    state: &mut WindowState,
+    pressed_button: CustomTitleBarHoveredButton,
+    released_on: CustomTitleBarHoveredButton,
+) {
+    win32_invalidate_caption_buttons(handle, state);
+
+    if released_on == pressed_button {
+        win32_caption_button_clicked(handle, state, pressed_button);
+    }
+}
+
+// GET_POINTERID_WPARAM
+const fn win32_pointer_id(w_param: WPARAM) -> u32 {
+    (w_param.0 & 0xffff) as u32
+}
+
+fn win32_is_touch_or_pen(w_param: WPARAM) -> bool {
+    let mut pointer_type = POINTER_INPUT_TYPE::default();
+    if let Err(e) = unsafe { GetPointerType(win32_pointer_id(w_param), &mut pointer_type) } {
+        eprintln!("Failed to get pointer type: {}", e.message());
+        return false;
+    }
+
+    matches!(pointer_type, PT_TOUCH | PT_PEN)
+}
+
+fn win32_invalidate_caption_buttons(handle: HWND, state: &WindowState) {
+    match win32_get_button_rects(handle, state) {
+        Ok(button_rects) => unsafe {
+            InvalidateRect(handle, Some(&button_rects.shade), false);
+            InvalidateRect(handle, Some(&button_rects.minimize), false);
+            InvalidateRect(handle, Some(&button_rects.maximize), false);
+            InvalidateRect(handle, Some(&button_rects.close), false);
+        },
+        Err(e) => eprintln!("Failed to get title bar rect:\n{}", e),
+    }
+}
+
+fn win32_blend_color(from: COLORREF, to: COLORREF, amount: f32) -> COLORREF {
+    let amount = (amount.clamp(0.0, 1.0) * 255.0).round() as u32;
+    COLORREF(rgb(
+        win32_blend_channel(get_r_value(from.0), get_r_value(to.0), amount),
+        win32_blend_channel(get_g_value(from.0), get_g_value(to.0), amount),
+        win32_blend_channel(get_b_value(from.0), get_b_value(to.0), amount),
+    ))
+}
+
+const WIN32_MARQUEE_TIMER_ID: usize = 2;
+const WIN32_MARQUEE_INTERVAL_MS: u32 = 30;
+// Blank space between the end of the title and its next repetition, at 96 dpi
+const WIN32_MARQUEE_GAP: i32 = 40;
+// Width of the gradient at the clip edge of a faded title, at 96 dpi
+const WIN32_TITLE_FADE_WIDTH: i32 = 24;
+
+// Starts the marquee timer when the title starts scrolling and resets it once it fits again
+fn win32_update_marquee(handle: HWND, state: &mut WindowState, rect: Option<RECT>) {
+    let was_scrolling = state.marquee_rect.is_some();
+    state.marquee_rect = rect;
+
+    if !was_scrolling && rect.is_some() && !state.is_throttled() {
+        unsafe {
+            SetTimer(
+                handle,
+                WIN32_MARQUEE_TIMER_ID,
+                WIN32_MARQUEE_INTERVAL_MS,
+                None,
+            )
+        };
+    } else if was_scrolling && rect.is_none() {
+        let _ = unsafe { KillTimer(handle, WIN32_MARQUEE_TIMER_ID) };
+        state.marquee_offset = 0;
+    }
+}
+
+const fn win32_blend_channel(from: u8, to: u8, amount: u32) -> u8 {
+    ((from as u32 * (255 - amount) + to as u32 * amount) / 255) as u8
+}
+
+// Blends the last fade_width columns of a buffered paint towards color, so an
+// overlong title dissolves into the caption instead of being cut off
+fn win32_fade_out_buffer_edge(
+    buffered_paint: isize,
+    rect: &RECT,
+    fade_width: i32,
+    color: COLORREF,
+) {
+    let mut bits: *mut RGBQUAD = std::ptr::null_mut();
+    let mut row_width = 0;
+    if let Err(e) = unsafe { GetBufferedPaintBits(buffered_paint, &mut bits, &mut row_width) } {
+        eprintln!("Failed to get buffered paint bits: {}", e.message());
+        return;
+    }
+
+    let width = (rect.right - rect.left).min(row_width);
+    let height = rect.bottom - rect.top;
+    let fade_width = fade_width.min(width);
+    if fade_width <= 0 {
+        return;
+    }
+
+    let fade_start = width - fade_width;
+    for y in 0..height {
+        for x in fade_start..width {
+            // Reaches the full background color on the last column
+            let amount = ((x - fade_start + 1) * 255 / fade_width) as u32;
+            let pixel = unsafe { &mut *bits.add((y * row_width + x) as usize) };
+            pixel.rgbRed = win32_blend_channel(pixel.rgbRed, get_r_value(color.0), amount);
+            pixel.rgbGreen = win32_blend_channel(pixel.rgbGreen, get_g_value(color.0), amount);
+            pixel.rgbBlue = win32_blend_channel(pixel.rgbBlue, get_b_value(color.0), amount);
+        }
+    }
+}
+
+fn win32_button_width(handle: HWND, state: &WindowState) -> i32 {
+    let dpi = win32_window_dpi(handle);
+    if state.touch_metrics {
+        win32_dpi_scale(WIN32_TOUCH_BUTTON_WIDTH, dpi)
+    } else if let Some(width) = state.options.caption_button_width {
+        win32_dpi_scale(width.max(0), dpi)
+    } else if state.options.kind.has_small_caption() {
+        win32_dpi_scale(WIN32_TOOL_BUTTON_WIDTH, dpi)
+    } else {
+        win32_dpi_scale(WIN32_BUTTON_WIDTH, dpi)
+    }
+}
+
+// See WindowOptions::caption_button_gap, at the window's dpi
+fn win32_button_gap(handle: HWND, state: &WindowState) -> i32 {
+    let gap = state.options.caption_button_gap;
+    win32_dpi_scale(gap.max(0), win32_window_dpi(handle))
+}
+
+// See WindowOptions::caption_glyph_size, at 96 dpi
+fn win32_glyph_size(state: &WindowState) -> i32 {
+    state
+        .options
+        .caption_glyph_size
+        .map_or(WIN32_GLYPH_SIZE, |size| size.max(1))
+}
+
+impl CustomTitleBarButtonRects {
+    fn win32_get_title_bar_button_rects(
+        handle: HWND,
+        state: &WindowState,
+        title_bar_rect: &RECT,
+    ) -> Self {
+        let button_width = win32_button_width(handle, state);
+        let button_gap = win32_button_gap(handle, state);
+
+        // Maximized, the buttons reach all the way up so the top row of the screen
+        // still hits them and flinging the cursor into the corner lands on Close
+        let top = if win32_window_is_maximized(handle) {
+            title_bar_rect.top
+        } else {
+            title_bar_rect.top + WIN32_FAKE_SHADOW_HEIGHT
+        };
+
+        // Hidden buttons take no space at all
+        let width_of = |button: CustomTitleBarHoveredButton| {
+            if button.is_shown(state) {
+                button_width
+            } else {
+                0
+            }
+        };
+
+        // modify original c code a bit to make it more idiomatic
+        let close = RECT {
+            top,
+            left: title_bar_rect.right - width_of(CustomTitleBarHoveredButton::Close),
+            ..*title_bar_rect
+        };
+
+        // The gap goes between shown buttons, the first one shown needs none
+        let left_of = |rect: &RECT, button: CustomTitleBarHoveredButton| {
+            let right = if rect.left < title_bar_rect.right && width_of(button) > 0 {
+                rect.left - button_gap
+            } else {
+                rect.left
+            };
+            RECT {
+                left: right - width_of(button),
+                right,
+                ..*rect
+            }
+        };
+        let maximize = left_of(&close, CustomTitleBarHoveredButton::Maximize);
+        let minimize = left_of(&maximize, CustomTitleBarHoveredButton::Minimize);
+        let shade = left_of(&minimize, CustomTitleBarHoveredButton::Shade);
+
+        Self {
+            close,
+            maximize,
+            minimize,
+            shade,
+            disabled: CAPTION_BUTTONS.map(|button| !button.is_enabled(state)),
+        }
+    }
+
+    // point is in client coordinates. Disabled buttons are part of the caption.
+    fn button_at(&self, point: POINT) -> CustomTitleBarHoveredButton {
+        let button = self.button_under(point);
+        match button.index() {
+            Some(index) if self.disabled[index] => CustomTitleBarHoveredButton::None,
+            _ => button,
+        }
+    }
+
+    fn button_under(&self, point: POINT) -> CustomTitleBarHoveredButton {
+        if unsafe { PtInRect(&self.shade, point) }.as_bool() {
+            CustomTitleBarHoveredButton::Shade
+        } else if unsafe { PtInRect(&self.minimize, point) }.as_bool() {
+            CustomTitleBarHoveredButton::Minimize
+        } else if unsafe { PtInRect(&self.maximize, point) }.as_bool() {
+            CustomTitleBarHoveredButton::Maximize
+        } else if unsafe { PtInRect(&self.close, point) }.as_bool() {
+            CustomTitleBarHoveredButton::Close
+        } else {
+            CustomTitleBarHoveredButton::None
+        }
+    }
+}
+
+// Stretches the window over the height of its monitor's work area, or puts it back
+// when it already is
+fn win32_toggle_vertical_maximize(handle: HWND) -> Result<()> {
+    win32_set_shaded(handle, false)?;
+    let mut window_rect = RECT::default();
+    unsafe { GetWindowRect(handle, &mut window_rect).map_err(|e| anyhow!("{}", e.message()))? };
+
+    let monitor = unsafe { MonitorFromWindow(handle, MONITOR_DEFAULTTONEAREST) };
+    let mut monitor_info = MONITORINFO {
+        cbSize: size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    if !unsafe { GetMonitorInfoW(monitor, &mut monitor_info) }.as_bool() {
+        return Err(anyhow!("Failed to get monitor info"));
+    }
+
+    // The bottom resize border is invisible and may hang below the work area, the top
+    // one is part of the caption
+    let dpi = win32_window_dpi(handle);
+    let bottom_border = win32_system_metrics_for_dpi(SM_CYFRAME, dpi)
+        + win32_system_metrics_for_dpi(SM_CXPADDEDBORDER, dpi);
+    let work_area = monitor_info.rcWork;
+    let stretched_rect = RECT {
+        top: work_area.top,
+        bottom: work_area.bottom + bottom_border,
+        ..window_rect
+    };
+
+    let target_rect =
+        win32_try_with_window_state(handle, |state| match state.vertical_restore_rect.take() {
+            Some(restore_rect) if window_rect == stretched_rect => RECT {
+                left: window_rect.left,
+                right: window_rect.right,
+                ..restore_rect
+            },
+            _ => {
+                state.vertical_restore_rect = Some(window_rect);
+                stretched_rect
+            }
+        })?;
+
+    unsafe {
+        SetWindowPos(
+            handle,
+            None,
+            target_rect.left,
+            target_rect.top,
+            target_rect.right - target_rect.left,
+            target_rect.bottom - target_rect.top,
+            SWP_NOZORDER | SWP_NOACTIVATE,
+        )
+        .map_err(|e| anyhow!("{}", e.message()))
+    }
+}
+
+// What leaving fullscreen goes back to
+struct FullscreenRestore {
+    placement: WINDOWPLACEMENT,
+    style: isize,
+}
+
+// Borderless fullscreen: the frame styles come off, the caption is hidden and the
+// window covers its monitor. WM_NCCALCSIZE leaves the whole window to the client
+// area meanwhile.
+fn win32_set_fullscreen(handle: HWND, enabled: bool) -> Result<()> {
+    if enabled == win32_try_with_window_state(handle, |state| state.fullscreen_restore.is_some())? {
+        return Ok(());
+    }
+
+    if !enabled {
+        let restore = win32_try_with_window_state(handle, |state| {
+            state.caption_reveal = Transition::settled(0.0);
+            state.fullscreen_restore.take().unwrap()
+        })?;
+        let _ = unsafe { KillTimer(handle, WIN32_CAPTION_REVEAL_TIMER_ID) };
+        unsafe {
+            SetWindowLongPtrW(handle, GWL_STYLE, restore.style);
+            SetWindowPlacement(handle, &restore.placement)
+                .map_err(|e| anyhow!("{}", e.message()))?;
+            return SetWindowPos(
+                handle,
+                None,
+                0,
+                0,
+                0,
+                0,
+                SWP_NOMOVE | SWP_NOSIZE | SWP_NOZORDER | SWP_NOOWNERZORDER | SWP_FRAMECHANGED,
+            )
+            .map_err(|e| anyhow!("{}", e.message()));
+        }
+    }
+
+    win32_set_shaded(handle, false)?;
+    win32_set_picture_in_picture(handle, false)?;
+    let mut placement = WINDOWPLACEMENT {
+        length: size_of::<WINDOWPLACEMENT>() as u32,
+        ..Default::default()
+    };
+    unsafe { GetWindowPlacement(handle, &mut placement).map_err(|e| anyhow!("{}", e.message()))? };
+
+    let monitor = unsafe { MonitorFromWindow(handle, MONITOR_DEFAULTTONEAREST) };
+    let mut monitor_info = MONITORINFO {
+        cbSize: size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    if !unsafe { GetMonitorInfoW(monitor, &mut monitor_info) }.as_bool() {
+        return Err(anyhow!("Failed to get monitor info"));
+    }
+
+    let style = unsafe { GetWindowLongPtrW(handle, GWL_STYLE) };
+    let bounds = win32_try_with_window_state(handle, |state| {
+        state.fullscreen_restore = Some(FullscreenRestore { placement, style });
+        state.caption_reveal = Transition::settled(0.0);
+        match state.options.fullscreen_bounds {
+            FullscreenBounds::Monitor => monitor_info.rcMonitor,
+            FullscreenBounds::WorkArea => monitor_info.rcWork,
+        }
+    })?;
+    unsafe {
+        SetWindowLongPtrW(handle, GWL_STYLE, style & !(WS_THICKFRAME.0 as isize));
+        SetWindowPos(
+            handle,
+            HWND_TOP,
+            bounds.left,
+            bounds.top,
+            bounds.right - bounds.left,
+            bounds.bottom - bounds.top,
+            SWP_NOOWNERZORDER | SWP_FRAMECHANGED,
+        )
+        .map_err(|e| anyhow!("{}", e.message()))
+    }
+}
+
+// What leaving picture in picture goes back to
+struct PictureInPictureRestore {
+    placement: WINDOWPLACEMENT,
+    always_on_top: bool,
+}
+
+// Client size of a picture in picture window at 96 dpi, 16:9 for video
+const WIN32_PICTURE_IN_PICTURE_SIZE: SIZE = SIZE { cx: 320, cy: 180 };
+// Gap to the corner of the work area at 96 dpi
+const WIN32_PICTURE_IN_PICTURE_MARGIN: i32 = 16;
+
+// A small always on top window in the bottom right corner of its work area, with the
+// caption hidden until the cursor is over the window. Leaving it restores the previous
+// placement and z order.
+fn win32_set_picture_in_picture(handle: HWND, enabled: bool) -> Result<()> {
+    let is_picture_in_picture =
+        win32_try_with_window_state(handle, |state| state.picture_in_picture_restore.is_some())?;
+    if enabled == is_picture_in_picture {
+        return Ok(());
+    }
+
+    if !enabled {
+        let restore = win32_try_with_window_state(handle, |state| {
+            state.caption_reveal = Transition::settled(0.0);
+            state.picture_in_picture_restore.take().unwrap()
+        })?;
+        let _ = unsafe { KillTimer(handle, WIN32_CAPTION_REVEAL_TIMER_ID) };
+        unsafe {
+            SetWindowPlacement(handle, &restore.placement)
+                .map_err(|e| anyhow!("{}", e.message()))?
+        };
+        return win32_set_always_on_top(handle, restore.always_on_top);
+    }
+
+    win32_set_fullscreen(handle, false)?;
+    win32_set_shaded(handle, false)?;
+    let mut placement = WINDOWPLACEMENT {
+        length: size_of::<WINDOWPLACEMENT>() as u32,
+        ..Default::default()
+    };
+    unsafe { GetWindowPlacement(handle, &mut placement).map_err(|e| anyhow!("{}", e.message()))? };
+
+    let monitor = unsafe { MonitorFromWindow(handle, MONITOR_DEFAULTTONEAREST) };
+    let mut monitor_info = MONITORINFO {
+        cbSize: size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    if !unsafe { GetMonitorInfoW(monitor, &mut monitor_info) }.as_bool() {
+        return Err(anyhow!("Failed to get monitor info"));
+    }
+
+    let always_on_top = win32_is_always_on_top(handle);
+    win32_try_with_window_state(handle, |state| {
+        state.picture_in_picture_restore = Some(PictureInPictureRestore {
+            placement,
+            always_on_top,
+        });
+        state.caption_reveal = Transition::settled(0.0);
+    })?;
+
+    // The side and bottom resize borders are invisible, the visible window goes into
+    // the corner
+    let dpi = win32_window_dpi(handle);
+    let size = win32_window_size_for_client(handle, WIN32_PICTURE_IN_PICTURE_SIZE);
+    let frame_size = win32_frame_size(handle);
+    let margin = win32_dpi_scale(WIN32_PICTURE_IN_PICTURE_MARGIN, dpi);
+    let work_area = monitor_info.rcWork;
+    let left = work_area.right - margin - size.x + frame_size.cx / 2;
+    let top = work_area.bottom - margin - size.y + frame_size.cy;
+
+    // Through the placement so a maximized window comes out restored
+    let compact_placement = WINDOWPLACEMENT {
+        showCmd: SW_SHOWNORMAL.0 as u32,
+        rcNormalPosition: RECT {
+            left,
+            top,
+            right: left + size.x,
+            bottom: top + size.y,
+        },
+        ..placement
+    };
+    unsafe {
+        SetWindowPlacement(handle, &compact_placement).map_err(|e| anyhow!("{}", e.message()))?
+    };
+    win32_set_always_on_top(handle, true)
+}
+
+// Rolls a restored window up to just its caption, or back down to the height it had.
+// While shaded WM_NCCALCSIZE drops the bottom resize border and WM_GETMINMAXINFO
+// pins the height, only the width can be resized.
+fn win32_set_shaded(handle: HWND, shaded: bool) -> Result<()> {
+    let (is_shaded, hides_caption) = win32_try_with_window_state(handle, |state| {
+        (state.shade_restore_height.is_some(), state.hides_caption())
+    })?;
+    if shaded == is_shaded {
+        return Ok(());
+    }
+
+    if shaded && (win32_window_is_maximized(handle) || hides_caption) {
+        return Err(anyhow!("Only restored windows can be shaded"));
+    }
+
+    let mut window_rect = RECT::default();
+    unsafe { GetWindowRect(handle, &mut window_rect).map_err(|e| anyhow!("{}", e.message()))? };
+
+    // Updated before resizing, the size messages it sends must see the new state
+    let height = win32_try_with_window_state(handle, |state| {
+        if !shaded {
+            return Ok(state.shade_restore_height.take().unwrap());
+        }
+
+        let title_bar_rect = win32_titlebar_rect(handle, state)?;
+        state.shade_restore_height = Some(window_rect.bottom - window_rect.top);
+        Ok::<_, anyhow::Error>(title_bar_rect.bottom - title_bar_rect.top)
+    })??;
+
+    unsafe {
+        SetWindowPos(
+            handle,
+            None,
+            0,
+            0,
+            window_rect.right - window_rect.left,
+            height,
+            SWP_NOMOVE | SWP_NOZORDER | SWP_NOACTIVATE | SWP_FRAMECHANGED,
+        )
+        .map_err(|e| anyhow!("{}", e.message()))?
+    };
+
+    win32_with_window_state(handle, |state| {
+        win32_invalidate_caption_buttons(handle, state)
+    });
+    Ok(())
+}
+
+// Space the resize borders take around the client area, WM_NCCALCSIZE leaves the
+// left, right and bottom ones
+fn win32_frame_size(handle: HWND) -> SIZE {
+    win32_frame_size_for_dpi(win32_window_dpi(handle))
+}
+
+fn win32_frame_size_for_dpi(dpi: u32) -> SIZE {
+    let frame_x = win32_system_metrics_for_dpi(SM_CXFRAME, dpi);
+    let frame_y = win32_system_metrics_for_dpi(SM_CYFRAME, dpi);
+    let padding = win32_system_metrics_for_dpi(SM_CXPADDEDBORDER, dpi);
+    SIZE {
+        cx: (frame_x + padding) * 2,
+        cy: frame_y + padding,
+    }
+}
+
+// The smallest window that still fits the caption buttons next to the icon, whatever
+// Window::set_min_size says
+fn win32_caption_min_window_size(handle: HWND, state: &WindowState) -> POINT {
+    let dpi = win32_window_dpi(handle);
+    let frame_size = win32_frame_size(handle);
+    let icon_space = win32_dpi_scale(WIN32_CAPTION_ICON_PADDING, dpi) * 2
+        + win32_system_metrics_for_dpi(SM_CXSMICON, dpi);
+    let caption_height =
+        win32_titlebar_rect(handle, state).map_or(0, |rect| rect.bottom - rect.top);
+    let button_count = CAPTION_BUTTONS
+        .into_iter()
+        .filter(|&button| button.is_shown(state))
+        .count() as i32;
+    let buttons_width = button_count * win32_button_width(handle, state)
+        + (button_count - 1).max(0) * win32_button_gap(handle, state);
+
+    POINT {
+        x: buttons_width + icon_space + frame_size.cx,
+        y: caption_height + frame_size.cy,
+    }
+}
+
+// A client size at 96 dpi as the window size at the window's dpi
+fn win32_window_size_for_client(handle: HWND, size: SIZE) -> POINT {
+    let dpi = win32_window_dpi(handle);
+    let frame_size = win32_frame_size(handle);
+    POINT {
+        x: win32_dpi_scale(size.cx, dpi) + frame_size.cx,
+        y: win32_dpi_scale(size.cy, dpi) + frame_size.cy,
+    }
+}
+
+// Held down look of a caption button, None when released or the press was cancelled
+fn win32_set_pressed_button(
+    handle: HWND,
+    state: &mut WindowState,
+    pressed_button: CustomTitleBarHoveredButton,
+) {
+    if state.pressed_button != pressed_button {
+        win32_notify_caption_button(handle, EVENT_OBJECT_STATECHANGE, state.pressed_button);
+        win32_notify_caption_button(handle, EVENT_OBJECT_STATECHANGE, pressed_button);
+        state.pressed_button = pressed_button;
+    }
+}
+
+// Gap between a focused caption button's edge and its focus ring at 96 dpi
+const WIN32_FOCUS_RING_INSET: i32 = 3;
+
+// Dotted like the system draws it, in the caption's item color on its background
+fn win32_draw_focus_ring(
+    hdc: HDC,
+    button_rect: &RECT,
+    dpi: u32,
+    item_color: COLORREF,
+    titlebar_color: COLORREF,
+) {
+    let inset = win32_dpi_scale(WIN32_FOCUS_RING_INSET, dpi);
+    let mut focus_rect = *button_rect;
+    unsafe {
+        InflateRect(&mut focus_rect, -inset, -inset);
+        SetTextColor(hdc, item_color);
+        SetBkColor(hdc, titlebar_color);
+        DrawFocusRect(hdc, &focus_rect);
+    }
+}
+
+// Moves the keyboard focus ring between the caption buttons, None takes it off the caption
+fn win32_set_focused_button(
+    handle: HWND,
+    state: &mut WindowState,
+    focused_button: CustomTitleBarHoveredButton,
+) {
+    if state.focused_button != focused_button {
+        state.focused_button = focused_button;
+        win32_notify_caption_button(handle, EVENT_OBJECT_FOCUS, focused_button);
+        win32_invalidate_caption_buttons(handle, state);
+    }
+}
+
+// Calls the application's event handler once the state is let go of, taken out while
+// it runs so it can call back into the Window
+fn win32_dispatch_event(handle: HWND, event: WindowEvent) {
+    win32_defer(move || {
+        let Some(mut handler) =
+            win32_with_window_state(handle, |state| state.event_handler.take()).flatten()
+        else {
+            return;
+        };
+
+        handler(event);
+        win32_with_window_state(handle, |state| {
+            if state.event_handler.is_none() {
+                state.event_handler = Some(handler);
+            }
+        });
+    });
+}
+
+// The application's callbacks that have to answer the message right away. They run
+// before the window procedure borrows the state, so they can call back into the Window.
+unsafe fn win32_answering_callbacks(
+    handle: HWND,
+    message: u32,
+    l_param: LPARAM,
+) -> Option<LRESULT> {
+    match message {
+        WM_NCHITTEST => {
+            let mut hit_test =
+                win32_with_window_state(handle, |state| state.hit_test_override.take())??;
+            let client_point = win32_screen_to_client(handle, l_param);
+
+            let hit = hit_test(client_point);
+            win32_with_window_state(handle, |state| {
+                if state.hit_test_override.is_none() {
+                    state.hit_test_override = Some(hit_test);
+                }
+            });
+
+            hit.map(|hit| LRESULT(hit as _))
+        }
+        WM_QUERYENDSESSION => {
+            if let Err(e) = win32_update_restart_command_line() {
+                eprintln!("{}", e);
+            }
+
+            let flags = l_param.0 as u32;
+            let session_ending = SessionEnding {
+                logoff: flags & ENDSESSION_LOGOFF != 0,
+                close_app: flags & ENDSESSION_CLOSEAPP != 0,
+                critical: flags & ENDSESSION_CRITICAL != 0,
+            };
+
+            let (handler, blocks_shutdown) = win32_with_window_state(handle, |state| {
+                (state.session_ending_handler.take(), state.blocks_shutdown)
+            })?;
+            let allow = match handler {
+                Some(mut handler) => {
+                    let allow = handler(session_ending);
+                    win32_with_window_state(handle, |state| {
+                        if state.session_ending_handler.is_none() {
+                            state.session_ending_handler = Some(handler);
+                        }
+                    });
+
+                    allow
+                }
+                None => !blocks_shutdown,
+            };
+
+            Some(LRESULT((allow || session_ending.critical) as isize))
+        }
+        WM_NOTIFY => {
+            let header = l_param.0 as *const NMHDR;
+            let tooltip = win32_with_window_state(handle, |state| state.tooltip)?;
+            if header.is_null()
+                || (*header).hwndFrom != tooltip
+                || (*header).code != TTN_GETDISPINFOW
+            {
+                return None;
+            }
+
+            let text = win32_caption_tooltip_text(handle, (*header).idFrom)?;
+            let display_info = l_param.0 as *mut NMTTDISPINFOW;
+            (*display_info).lpszText = PWSTR(text as _);
+            Some(LRESULT(0))
+        }
+        _ => None,
+    }
+}
+
+// Moves the keyboard focus to region. Entering the caption focuses its first button.
+fn win32_set_focus_region(handle: HWND, state: &mut WindowState, region: FocusRegion) {
+    if state.focus_regions.current() == region || !state.focus_regions.contains(region) {
+        return;
+    }
+
+    state.focus_regions.set_current(region);
+    let focus = if region == FocusRegion::Caption {
+        win32_caption_focus_stops(handle, state).first().copied()
+    } else {
+        None
+    };
+    win32_set_caption_focus(handle, state, focus);
+    win32_menu_bar_focus_changed(handle, state, region);
+
+    // The message box buttons show their focus ring only while in the content
+    if state.message_box.is_some() {
+        unsafe { InvalidateRect(handle, None, false) };
+    }
+
+    win32_dispatch_event(handle, WindowEvent::FocusRegionChanged(region));
+}
+
+// Where the arrow keys stop in the caption, the added buttons and the system buttons
+#[derive(Clone, Copy, PartialEq)]
+enum CaptionFocusStop {
+    Added(CaptionButtonId),
+    System(CustomTitleBarHoveredButton),
+}
+
+// Left to right: the added buttons where they are laid out, then the system buttons.
+// Hidden and disabled ones are skipped, so are added ones behind the » button.
+fn win32_caption_focus_stops(handle: HWND, state: &WindowState) -> Vec<CaptionFocusStop> {
+    let mut added = match win32_titlebar_rect(handle, state) {
+        Ok(title_bar_rect) => win32_caption_button_layout(handle, state, &title_bar_rect).buttons,
+        Err(_) => Vec::new(),
+    };
+    added.retain(|(id, rect)| rect.right > rect.left && state.caption_buttons.is_enabled(*id));
+    added.sort_by_key(|(_, rect)| rect.left);
+
+    let system = CAPTION_BUTTONS
+        .into_iter()
+        .filter(|button| button.is_shown(state) && button.is_enabled(state));
+    added
+        .into_iter()
+        .map(|(id, _)| CaptionFocusStop::Added(id))
+        .chain(system.map(CaptionFocusStop::System))
+        .collect()
+}
+
+fn win32_caption_focus(state: &WindowState) -> Option<CaptionFocusStop> {
+    match state.caption_buttons.focused() {
+        Some(id) => Some(CaptionFocusStop::Added(id)),
+        None if state.focused_button != CustomTitleBarHoveredButton::None => {
+            Some(CaptionFocusStop::System(state.focused_button))
+        }
+        None => None,
+    }
+}
+
+// Moves the focus ring to focus, None takes it off the caption
+fn win32_set_caption_focus(handle: HWND, state: &mut WindowState, focus: Option<CaptionFocusStop>) {
+    let (added, system) = match focus {
+        Some(CaptionFocusStop::Added(id)) => (Some(id), CustomTitleBarHoveredButton::None),
+        Some(CaptionFocusStop::System(button)) => (None, button),
+        None => (None, CustomTitleBarHoveredButton::None),
+    };
+    win32_set_focused_caption_button(handle, state, added);
+    win32_set_focused_button(handle, state, system);
+}
+
+// The focus stop next to the focused one in direction (-1 or 1), wrapping around. The
+// first one when the focused one is gone, e.g. behind the » button.
+fn win32_neighbor_focus_stop(
+    handle: HWND,
+    state: &WindowState,
+    direction: isize,
+) -> Option<CaptionFocusStop> {
+    let focus = win32_caption_focus(state);
+    let stops = win32_caption_focus_stops(handle, state);
+    let next = match stops.iter().position(|&stop| Some(stop) == focus) {
+        Some(index) => (index as isize + direction).rem_euclid(stops.len() as isize) as usize,
+        None => 0,
+    };
+    stops.get(next).copied().or(focus)
+}
+
+// DefWindowProc plays these for the system commands, the caption buttons act on the
+// window directly. The sound scheme in the Control Panel decides what they sound like.
+fn win32_play_caption_sound(state: &WindowState, alias: PCWSTR) {
+    if !state.options.mute_caption_sounds {
+        unsafe {
+            PlaySoundW(
+                alias,
+                None,
+                SND_ALIAS | SND_ASYNC | SND_NODEFAULT | SND_SYSTEM,
+            )
+        };
+    }
+}
+
+// Plays the button's sound, what the click does waits until the state is let go of
+fn win32_caption_button_clicked(
+    handle: HWND,
+    state: &mut WindowState,
+    button: CustomTitleBarHoveredButton,
+) {
+    let sound = match button {
+        CustomTitleBarHoveredButton::Close => Some(w!("Close")),
+        CustomTitleBarHoveredButton::Minimize => Some(w!("Minimize")),
+        CustomTitleBarHoveredButton::Maximize
+            if state.hides_caption() || win32_window_is_maximized(handle) =>
+        {
+            Some(w!("RestoreDown"))
+        }
+        CustomTitleBarHoveredButton::Maximize => Some(w!("Maximize")),
+        _ => None,
+    };
+    if let Some(sound) = sound {
+        win32_play_caption_sound(state, sound);
+    }
+
+    win32_once_released(state, move || {
+        if let Err(e) = win32_click_system_button(Window { handle }, button) {
+            eprintln!("Failed to handle caption button click:\n{}", e);
+        }
+    });
+}
+
+// Map button clicks to the right messages for the window
+fn win32_click_system_button(window: Window, button: CustomTitleBarHoveredButton) -> Result<()> {
+    match button {
+        CustomTitleBarHoveredButton::Close => unsafe {
+            PostMessageW(window.handle, WM_CLOSE, WPARAM(0), LPARAM(0))
+                .map_err(|e| anyhow!("{}", e.message()))?
+        },
+        CustomTitleBarHoveredButton::Shade => window.set_shaded(!window.is_shaded())?,
+        CustomTitleBarHoveredButton::Minimize => {
+            unsafe { ShowWindow(window.handle, SW_MINIMIZE) };
+        }
+        // Leaves fullscreen for whatever the window was before
+        CustomTitleBarHoveredButton::Maximize if window.is_fullscreen() => {
+            window.set_fullscreen(false)?;
+        }
+        CustomTitleBarHoveredButton::Maximize if window.is_picture_in_picture() => {
+            window.set_picture_in_picture(false)?;
+        }
+        CustomTitleBarHoveredButton::Maximize => {
+            window.set_shaded(false)?;
+            let mode = if win32_window_is_maximized(window.handle) {
+                SW_NORMAL
+            } else {
+                SW_MAXIMIZE
+            };
+            unsafe { ShowWindow(window.handle, mode) };
+        }
+        CustomTitleBarHoveredButton::None => {}
+    }
+
+    Ok(())
+}
+
+// System menu command toggling always on top. Application commands have to stay below
+// 0xF000 with the low four bits clear, those are masked off in WM_SYSCOMMAND.
+const WIN32_SC_ALWAYS_ON_TOP: u32 = 0x0100;
+
+fn win32_is_always_on_top(handle: HWND) -> bool {
+    let ex_style = unsafe { GetWindowLongPtrW(handle, GWL_EXSTYLE) } as u32;
+    ex_style & WS_EX_TOPMOST.0 != 0
+}
+
+fn win32_set_always_on_top(handle: HWND, enabled: bool) -> Result<()> {
+    let insert_after = if enabled {
+        HWND_TOPMOST
+    } else {
+        HWND_NOTOPMOST
+    };
+    unsafe {
+        SetWindowPos(
+            handle,
+            insert_after,
+            0,
+            0,
+            0,
+            0,
+            SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+        )
+        .map_err(|e| anyhow!("Failed to change always on top: {}", e.message()))?
+    };
+
+    win32_with_window_state(handle, |state| {
+        win32_invalidate_caption_buttons(handle, state)
+    });
+    Ok(())
+}
+
+// System menu commands bringing a document to the front, one for each in
+// WindowState::documents
+const WIN32_SC_DOCUMENT_FIRST: u32 = 0x0200;
+
+fn win32_document_command(state: &WindowState, w_param: WPARAM) -> Option<HWND> {
+    let command = w_param.0 as u32 & 0xfff0;
+    let index = command.checked_sub(WIN32_SC_DOCUMENT_FIRST)? / 0x10;
+    state.documents.get(index as usize).copied()
+}
+
+// Lists the documents at the end of the window menu the way MDI applications do, with
+// the last active one checked. Documents open and close while the menu is kept, so the
+// items from the last time are replaced.
+fn win32_update_document_menu_items(handle: HWND, menu: HMENU) -> Result<()> {
+    win32_with_window_state(handle, |state| {
+        for index in 0..state.document_menu_items as u32 {
+            unsafe {
+                DeleteMenu(menu, WIN32_SC_DOCUMENT_FIRST + index * 0x10, MF_BYCOMMAND)
+                    .map_err(|e| anyhow!("{}", e.message()))?
+            };
+        }
+
+        // The separator in front of them is the last item now
+        if state.document_menu_items > 0 {
+            let item_count = unsafe { GetMenuItemCount(menu) };
+            unsafe {
+                DeleteMenu(menu, (item_count - 1) as u32, MF_BYPOSITION)
+                    .map_err(|e| anyhow!("{}", e.message()))?
+            };
+        }
+
+        state.document_menu_items = 0;
+        if state.documents.is_empty() {
+            return Ok(());
+        }
+
+        unsafe {
+            AppendMenuW(menu, MF_SEPARATOR, 0, None).map_err(|e| anyhow!("{}", e.message()))?
+        };
+
+        let active_document = unsafe { GetLastActivePopup(handle) };
+        for (index, &document) in state.documents.iter().enumerate() {
+            // Numbered like the MDI window menu, the first nine get the number as mnemonic
+            let title = win32_window_title(document);
+            let label = if index < 9 {
+                format!("&{} {}", index + 1, title)
+            } else {
+                format!("{} {}", index + 1, title)
+            };
+            let check = if document == active_document {
+                MF_CHECKED
+            } else {
+                MF_UNCHECKED
+            };
+
+            unsafe {
+                AppendMenuW(
+                    menu,
+                    MF_STRING | check,
+                    (WIN32_SC_DOCUMENT_FIRST + index as u32 * 0x10) as usize,
+                    &HSTRING::from(label),
+                )
+                .map_err(|e| anyhow!("{}", e.message()))?
+            };
+            state.document_menu_items += 1;
+        }
+
+        Ok(())
+    })
+    .unwrap_or(Ok(()))
+}
+
+// Puts the always on top command in front of Close, with the checkmark as its state
+fn win32_add_always_on_top_menu_item(handle: HWND) -> Result<()> {
+    let menu = unsafe { GetSystemMenu(handle, false) };
+    if menu.0 == 0 {
+        return Err(anyhow!("Window has no system menu"));
+    }
+
+    unsafe {
+        InsertMenuW(
+            menu,
+            SC_CLOSE,
+            MF_BYCOMMAND | MF_STRING,
+            WIN32_SC_ALWAYS_ON_TOP as usize,
+            &HSTRING::from(localized_string(strings::MENU_ALWAYS_ON_TOP)),
+        )
+        .map_err(|e| anyhow!("{}", e.message()))?;
+        InsertMenuW(menu, SC_CLOSE, MF_BYCOMMAND | MF_SEPARATOR, 0, None)
+            .map_err(|e| anyhow!("{}", e.message()))?;
+    }
+
+    Ok(())
+}
+
+// The default caption is what normally shows the system menu, with ours it is shown
+// by hand. Items get enabled the way DefWindowProc would for the current state.
+// point is in screen coordinates.
+fn win32_show_system_menu(handle: HWND, point: POINT) -> Result<()> {
+    let menu = unsafe { GetSystemMenu(handle, false) };
+    if menu.0 == 0 {
+        return Err(anyhow!("Window has no system menu"));
+    }
+
+    let is_maximized = win32_window_is_maximized(handle);
+    // Restore is how a snapped window gets its pre-snap rect back
+    let can_restore = is_maximized || win32_window_is_snapped(handle);
+    let set_enabled = |command: u32, enabled: bool| unsafe {
+        EnableMenuItem(
+            menu,
+            command,
+            MF_BYCOMMAND | if enabled { MF_ENABLED } else { MF_GRAYED },
+        )
+    };
+
+    set_enabled(SC_RESTORE, can_restore);
+    set_enabled(SC_MOVE, !is_maximized);
+    set_enabled(SC_SIZE, !is_maximized);
+    set_enabled(SC_MINIMIZE, true);
+    set_enabled(SC_MAXIMIZE, !is_maximized);
+    set_enabled(SC_CLOSE, true);
+    let check = if win32_is_always_on_top(handle) {
+        MF_CHECKED
+    } else {
+        MF_UNCHECKED
+    };
+    unsafe { CheckMenuItem(menu, WIN32_SC_ALWAYS_ON_TOP, (MF_BYCOMMAND | check).0) };
+    win32_update_document_menu_items(handle, menu)?;
+    unsafe { SetMenuDefaultItem(menu, SC_CLOSE, 0).map_err(|e| anyhow!("{}", e.message()))? };
+
+    let command = unsafe {
+        TrackPopupMenu(
+            menu,
+            TPM_RETURNCMD | TPM_RIGHTBUTTON,
+            point.x,
+            point.y,
+            0,
+            handle,
+            None,
+        )
+    };
+
+    if command.0 != 0 {
+        unsafe {
+            PostMessageW(handle, WM_SYSCOMMAND, WPARAM(command.0 as _), LPARAM(0))
+                .map_err(|e| anyhow!("{}", e.message()))?
+        };
+    }
+
+    Ok(())
+}
+
+// Small icon of the window, falling back to the one registered with the class
+fn win32_window_icon(handle: HWND) -> HICON {
+    let icon = unsafe { SendMessageW(handle, WM_GETICON, WPARAM(ICON_SMALL2 as _), LPARAM(0)) };
+    if icon.0 != 0 {
+        return HICON(icon.0);
+    }
+
+    HICON(unsafe { GetClassLongPtrW(handle, GCLP_HICONSM) } as _)
+}
+
+// The gap around the caption icon at 96 dpi, None when the icon is hidden
+fn win32_caption_icon_padding(state: &WindowState) -> Option<i32> {
+    let options = &state.options;
+    (!options.hide_caption_icon).then(|| {
+        options
+            .caption_icon_padding
+            .unwrap_or(WIN32_CAPTION_ICON_PADDING)
+    })
+}
+
+// Where the window icon sits in the caption, None when the window has no icon, hides
+// it or is a tool window or dialog
+fn win32_caption_icon_rect(
+    handle: HWND,
+    state: &WindowState,
+    title_bar_rect: &RECT,
+) -> Option<RECT> {
+    if title_bar_rect.bottom <= 0
+        || state.options.kind != WindowKind::Main
+        || win32_window_icon(handle).is_invalid()
+    {
+        return None;
+    }
+
+    let padding = win32_caption_icon_padding(state)?;
+    let dpi = win32_window_dpi(handle);
+    let icon_size = win32_system_metrics_for_dpi(SM_CXSMICON, dpi);
+    let mut icon_rect = RECT {
+        right: icon_size,
+        bottom: icon_size,
+        ..Default::default()
+    };
+
+    win32_center_rect_in_rect(&mut icon_rect, title_bar_rect);
+    let left = win32_caption_button_layout(handle, state, title_bar_rect).leading_right
+        + win32_dpi_scale(padding, dpi);
+    Some(RECT {
+        left,
+        right: left + icon_size,
+        ..icon_rect
+    })
+}
+
+// Gap between the left edge of the window and the caption icon, and between the icon
+// and the title, at 96 dpi
+const WIN32_CAPTION_ICON_PADDING: i32 = 10;
+const WIN32_SYSTEM_MENU_TIMER_ID: usize = 4;
+
+// Opens the system menu hanging from the bottom left of the caption icon
+fn win32_show_icon_system_menu(handle: HWND, state: &mut WindowState) {
+    let title_bar_rect = match win32_titlebar_rect(handle, state) {
+        Ok(title_bar_rect) => title_bar_rect,
+        Err(e) => {
+            eprintln!("Failed to get title bar rect:\n{}", e);
+            return;
+        }
+    };
+    let icon_rect =
+        win32_caption_icon_rect(handle, state, &title_bar_rect).unwrap_or(title_bar_rect);
+    let mut menu_point = POINT {
+        x: icon_rect.left,
+        y: title_bar_rect.bottom,
+    };
+
+    unsafe { ClientToScreen(handle, &mut menu_point) };
+    win32_show_system_menu_once_released(handle, state, menu_point);
+}
+
+// The menu loop sends the window messages, it waits until the state is let go of
+fn win32_show_system_menu_once_released(handle: HWND, state: &mut WindowState, point: POINT) {
+    win32_once_released(state, move || {
+        if let Err(e) = win32_show_system_menu(handle, point) {
+            eprintln!("Failed to show system menu:\n{}", e);
+        }
+    });
+}
+
+// First Windows 11 build, the one that introduced the Snap Layouts flyout
+const WIN32_SNAP_LAYOUTS_BUILD: u32 = 22000;
+
+// GetVersionEx is capped at Windows 8 without a compatibility manifest, ntdll isn't.
+// On Windows 10 HTMAXBUTTON would only get us the classic tooltip on top of ours.
+fn win32_supports_snap_layouts() -> bool {
+    let mut version = OSVERSIONINFOW {
+        dwOSVersionInfoSize: size_of::<OSVERSIONINFOW>() as u32,
+        ..Default::default()
+    };
+
+    if let Err(e) = unsafe { RtlGetVersion(&mut version) }.ok() {
+        eprintln!("Failed to get Windows version: {}", e.message());
+        return false;
+    }
+
+    version.dwBuildNumber >= WIN32_SNAP_LAYOUTS_BUILD
+}
+
+// Reads WS_MAXIMIZE, which is already set in the WM_NCCALCSIZE of a Win+Up or
+// Win+Down transition. The placement's showCmd isn't updated until afterwards and
+// left the frame one transition behind.
+fn win32_window_is_maximized(handle: HWND) -> bool {
+    unsafe { IsZoomed(handle) }.as_bool()
+}
+
+type Win32IsWindowArranged = unsafe extern "system" fn(HWND) -> BOOL;
+
+static IS_WINDOW_ARRANGED: OnceLock<Option<Win32IsWindowArranged>> = OnceLock::new();
+
+// The client rect WM_NCCALCSIZE asks for in window_rect. The caption is drawn over the
+// top frame, so only the other edges lose theirs, and the bottom only when it has one.
+// Snapped windows get nothing more than normal ones, their top edge stays where the
+// snap put it.
+fn win32_inset_client_rect(
+    window_rect: RECT,
+    frame_x: i32,
+    frame_y: i32,
+    padding: i32,
+    has_bottom_frame: bool,
+) -> RECT {
+    RECT {
+        left: window_rect.left + frame_x + padding,
+        right: window_rect.right - frame_x - padding,
+        bottom: if has_bottom_frame {
+            window_rect.bottom - frame_y - padding
+        } else {
+            window_rect.bottom
+        },
+        ..window_rect
+    }
+}
+
+// Maximized windows hang over the monitor edge by the frame size. What is left for the
+// client area is the work area of the monitor they maximize on, which differs between
+// monitors with the taskbar on another edge or only on the primary one. Without the
+// work area the top padding at least keeps the caption on screen. Snapped windows are
+// placed inside the work area like normal ones and must not get this, or a gap would
+// open above the caption and stay there after restoring from the snap.
+fn win32_clip_maximized_client_rect(
+    client_rect: RECT,
+    work_area: Option<RECT>,
+    padding: i32,
+) -> RECT {
+    match work_area {
+        Some(work_area) => RECT {
+            left: client_rect.left.max(work_area.left),
+            top: client_rect.top.max(work_area.top),
+            right: client_rect.right.min(work_area.right),
+            bottom: client_rect.bottom.min(work_area.bottom),
+        },
+        None => RECT {
+            top: client_rect.top + padding,
+            ..client_rect
+        },
+    }
+}
+
+// The rows at the top of the client area that resize the window instead of belonging
+// to the caption, the first one included
+fn win32_is_in_top_resize_band(y: i32, frame_y: i32, padding: i32, resizes_top: bool) -> bool {
+    resizes_top && y >= 0 && y < frame_y + padding
+}
+
+// Rolled up windows only resize sideways, their top and bottom edges drag them like
+// the rest of the caption
+fn win32_shaded_hit(hit: u32) -> u32 {
+    match hit {
+        HTTOPLEFT | HTBOTTOMLEFT => HTLEFT,
+        HTTOPRIGHT | HTBOTTOMRIGHT => HTRIGHT,
+        HTTOP | HTBOTTOM => HTCAPTION,
+        _ => hit,
+    }
+}
+
+// Snapped with Win+Arrow or by dragging to an edge. The window keeps its normal
+// style (and so the normal frame), only its rect comes from the snap. IsWindowArranged
+// came with Windows 10 1903 and is looked up like the dpi functions, never snapped
+// before that.
+fn win32_window_is_snapped(handle: HWND) -> bool {
+    let is_arranged = IS_WINDOW_ARRANGED.get_or_init(|| {
+        win32_user32_proc(s!("IsWindowArranged"))
+            .map(|proc| unsafe { transmute::<Win32Proc, Win32IsWindowArranged>(proc) })
+    });
+
+    is_arranged.is_some_and(|is_arranged| unsafe { is_arranged(handle) }.as_bool())
+}
+
+// Codepoints of the caption glyphs, shared by Segoe Fluent Icons and Segoe MDL2 Assets
+const WIN32_GLYPH_CHEVRON_DOWN: u16 = 0xE70D;
+const WIN32_GLYPH_CHEVRON_UP: u16 = 0xE70E;
+const WIN32_GLYPH_MINIMIZE: u16 = 0xE921;
+const WIN32_GLYPH_MAXIMIZE: u16 = 0xE922;
+const WIN32_GLYPH_RESTORE: u16 = 0xE923;
+const WIN32_GLYPH_CLOSE: u16 = 0xE8BB;
+// Windows 11 ships Segoe Fluent Icons, Windows 10 only has Segoe MDL2 Assets
+const WIN32_GLYPH_FONT_FACES: [PCWSTR; 2] = [w!("Segoe Fluent Icons"), w!("Segoe MDL2 Assets")];
+
+unsafe extern "system" fn win32_font_family_found(
+    _logical_font: *const LOGFONTW,
+    _text_metric: *const TEXTMETRICW,
+    _font_type: u32,
+    l_param: LPARAM,
+) -> i32 {
+    *(l_param.0 as *mut bool) = true;
+    // Stop enumerating, one match is enough
+    0
+}
+
+fn win32_find_glyph_font_face(handle: HWND) -> Option<PCWSTR> {
+    let hdc = unsafe { GetDC(handle) };
+    let face = WIN32_GLYPH_FONT_FACES.into_iter().find(|face| {
+        let mut logical_font = LOGFONTW {
+            lfCharSet: DEFAULT_CHARSET,
+            ..Default::default()
+        };
+        let name = unsafe { face.as_wide() };
+        logical_font.lfFaceName[..name.len()].copy_from_slice(name);
+
+        let mut found = false;
+        unsafe {
+            EnumFontFamiliesExW(
+                hdc,
+                &logical_font,
+                Some(win32_font_family_found),
+                LPARAM(&mut found as *mut bool as _),
+                0,
+            )
+        };
+        found
+    });
+    unsafe { ReleaseDC(handle, hdc) };
+    face
+}
+
+// The glyphs are designed on a 10px em at 96 dpi, same as the vector icon dimension
+const WIN32_GLYPH_SIZE: i32 = 10;
+
+// size is at 96 dpi, see WindowOptions::caption_glyph_size
+fn win32_create_glyph_font(face: PCWSTR, size: i32, dpi: u32) -> HFONT {
+    let mut logical_font = LOGFONTW {
+        lfHeight: -win32_dpi_scale(size, dpi),
+        lfCharSet: DEFAULT_CHARSET,
+        ..Default::default()
+    };
+    let name = unsafe { face.as_wide() };
+    logical_font.lfFaceName[..name.len()].copy_from_slice(name);
+    unsafe { CreateFontIndirectW(&logical_font) }
+}
+
+// The glyph font at the window's dpi, HFONT(0) without a glyph font face. Kept in the
+// state rather than created for every paint, hover fades repaint many times a second.
+// Owned by the state, don't delete it.
+fn win32_glyph_font(handle: HWND, state: &mut WindowState) -> HFONT {
+    let Some(face) = state.glyph_font_face else {
+        return HFONT(0);
+    };
+
+    let dpi = win32_window_dpi(handle);
+    if let Some((font_dpi, font)) = state.glyph_font {
+        if font_dpi == dpi {
+            return font;
+        }
+    }
+
+    win32_drop_glyph_font(state);
+    let font = win32_create_glyph_font(face, win32_glyph_size(state), dpi);
+    if font != HFONT(0) {
+        state.glyph_font = Some((dpi, font));
+    }
+    font
+}
+
+fn win32_drop_glyph_font(state: &mut WindowState) {
+    if let Some((_, font)) = state.glyph_font.take() {
+        unsafe { DeleteObject(font) };
+    }
+}
+
+// Expects the glyph font to be selected into the dc
+fn win32_draw_caption_glyph(hdc: HDC, glyph: u16, rect: &RECT, color: COLORREF) {
+    let mut text = [glyph];
+    let mut rect = *rect;
+    unsafe {
+        SetBkMode(hdc, TRANSPARENT);
+        SetTextColor(hdc, color);
+        DrawTextW(
+            hdc,
+            &mut text,
+            &mut rect,
+            DT_CENTER | DT_VCENTER | DT_SINGLELINE | DT_NOCLIP,
+        );
+    }
+}
+
+// I think this is for centering icon in the title bar's buttons
+// to center = the rect to center
+// outer_rect = the button rect
+fn win32_center_rect_in_rect(to_center: &mut RECT, outer_rect: &RECT) {
+    let to_width = to_center.right - to_center.left;
+    let to_height = to_center.bottom - to_center.top;
+    let outer_width = outer_rect.right - outer_rect.left;
+    let outer_height = outer_rect.bottom - outer_rect.top;
+
+    let padding_x = (outer_width - to_width) / 2;
+    let padding_y = (outer_height - to_height) / 2;
+
+    to_center.left = outer_rect.left + padding_x;
+    to_center.top = outer_rect.top + padding_y;
+    to_center.right = to_center.left + to_width;
+    to_center.bottom = to_center.top + to_height;
+}
+
+// The screen point of WM_NCHITTEST, the non-client mouse messages and the pointer
+// messages in client coordinates. All of them are physical pixels for per monitor
+// aware windows, so this holds while the window straddles monitors of different dpi.
+fn win32_screen_to_client(handle: HWND, l_param: LPARAM) -> POINT {
+    let mut point = POINT {
+        x: get_x_param(l_param),
+        y: get_y_param(l_param),
+    };
+    unsafe { ScreenToClient(handle, &mut point) };
+    point
+}
+
+fn win32_client_rect_to_screen(handle: HWND, rect: &RECT) -> RECT {
+    let mut origin = POINT::default();
+    unsafe { ClientToScreen(handle, &mut origin) };
+    RECT {
+        left: rect.left + origin.x,
+        top: rect.top + origin.y,
+        right: rect.right + origin.x,
+        bottom: rect.bottom + origin.y,
+    }
+}
+
+// Tells assistive technology a caption button changed, the buttons are painted so
+// nothing else would. Children of OBJID_TITLEBAR, which the system builds from
+// WM_GETTITLEBARINFOEX.
+fn win32_notify_caption_button(handle: HWND, event: u32, button: CustomTitleBarHoveredButton) {
+    if let Some(child) = button.title_bar_child() {
+        unsafe { NotifyWinEvent(event, handle, OBJID_TITLEBAR.0, child as i32) };
+    }
+}
+
+// The custom caption as WM_GETTITLEBARINFOEX describes it: screen rects and the
+// accessibility states of the title bar, minimize, maximize, help and close. Shade and
+// the added buttons have no slot, the structure only knows the standard buttons.
+fn win32_title_bar_info(handle: HWND, state: &WindowState) -> Result<TITLEBARINFOEX> {
+    let title_bar_rect = win32_titlebar_rect(handle, state)?;
+    let button_rects =
+        CustomTitleBarButtonRects::win32_get_title_bar_button_rects(handle, state, &title_bar_rect);
+
+    let mut info = TITLEBARINFOEX {
+        cbSize: size_of::<TITLEBARINFOEX>() as u32,
+        rcTitleBar: win32_client_rect_to_screen(handle, &title_bar_rect),
+        ..Default::default()
+    };
+    info.rgstate[0] = STATE_SYSTEM_FOCUSABLE.0;
+    info.rgrect[0] = info.rcTitleBar;
+    // No help button
+    info.rgstate[4] = STATE_SYSTEM_INVISIBLE.0;
+
+    for (button, rect) in [
+        (CustomTitleBarHoveredButton::Minimize, button_rects.minimize),
+        (CustomTitleBarHoveredButton::Maximize, button_rects.maximize),
+        (CustomTitleBarHoveredButton::Close, button_rects.close),
+    ] {
+        let index = button.title_bar_child().unwrap();
+        let mut button_state = 0;
+        if !button.is_shown(state) {
+            button_state |= STATE_SYSTEM_INVISIBLE.0;
+        }
+        if state.pressed_button == button {
+            button_state |= STATE_SYSTEM_PRESSED.0;
+        }
+        if state.hovered_button == button {
+            button_state |= STATE_SYSTEM_HOTTRACKED;
+        }
+        if state.focused_button == button {
+            button_state |= STATE_SYSTEM_FOCUSED;
+        }
+
+        info.rgstate[index] = button_state;
+        info.rgrect[index] = win32_client_rect_to_screen(handle, &rect);
+    }
+
+    Ok(info)
+}
+
+// Description:
+// 0xffff = 65535, so we take the first 16 bits
+// We need to cast to i16 first in order to maintain the sign (negative or positive) then cast to i32
+const fn get_x_param(l_param: LPARAM) -> i32 {
+    (l_param.0 & 0xffff) as i16 as i32
+}
+
+// 0xffff0000 = 4294901760, so we take the last 16 bits.
+// The last 0000 is as we >> aka shift right by 16
+const fn get_y_param(l_param: LPARAM) -> i32 {
+    ((l_param.0 >> 16) & 0xffff) as i16 as i32
+}
+
+const fn rgb(r: u8, g: u8, b: u8) -> u32 {
+    (r as u32) | ((g as u32) << 8) | ((b as u32) << 16)
+}
+
+const fn get_r_value(rgb: u32) -> u8 {
+    (rgb & 0xff) as u8
+}
+
+const fn get_g_value(rgb: u32) -> u8 {
+    ((rgb >> 8) & 0xff) as u8
+}
+
+const fn get_b_value(rgb: u32) -> u8 {
+    ((rgb >> 16) & 0xff) as u8
+}
+
+// What WM_PAINT began, ended on every way out of it including the early returns
+struct FramePaint {
+    handle: HWND,
+    ps: PAINTSTRUCT,
+    buffered_frame: isize,
+}
+
+impl Drop for FramePaint {
+    fn drop(&mut self) {
+        unsafe {
+            if self.buffered_frame != 0 {
+                if let Err(e) = EndBufferedPaint(self.buffered_frame, true) {
+                    eprintln!("Failed to end buffered paint: {}", e.message());
+                }
+            }
+
+            EndPaint(self.handle, &self.ps);
+        }
+    }
+}
+
+unsafe extern "system" fn window_proc(
+    handle: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    if message == WM_NCCREATE {
+        let create_struct = l_param.0 as *const CREATESTRUCTW;
+        if !create_struct.is_null() {
+            SetWindowLongPtrW(handle, GWLP_USERDATA, (*create_struct).lpCreateParams as _);
+        }
+
+        return DefWindowProcW(handle, message, w_param, l_param);
+    }
+
+    let Some(state) = win32_window_state(handle) else {
+        // Messages like WM_GETMINMAXINFO arrive before WM_NCCREATE
+        return DefWindowProcW(handle, message, w_param, l_param);
+    };
+
+    if message == WM_NCDESTROY {
+        win32_unregister_window(handle);
+        // The window's own reference, the state goes once the calls further up the
+        // stack are done with it too
+        let window_state = GetWindowLongPtrW(handle, GWLP_USERDATA) as *const RefCell<WindowState>;
+        SetWindowLongPtrW(handle, GWLP_USERDATA, 0);
+        drop(Rc::from_raw(window_state));
+        return DefWindowProcW(handle, message, w_param, l_param);
+    }
+
+    if let Some(result) = win32_answering_callbacks(handle, message, l_param) {
+        return result;
+    }
+
+    let result = {
+        // Whatever the handlers defer runs once this is dropped, after the calls that
+        // waited for the state to be let go of
+        let _state_hold = StateHold::new();
+        match win32_borrow_window_state(&state, |state| {
+            win32_handle_message(handle, state, message, w_param, l_param)
+        }) {
+            Some(result) => result,
+            // Sent by a call that should have waited for the state to be let go of.
+            // Painting is left for when it is, the rest gets the default handling.
+            None if message == WM_PAINT => Some(LRESULT(0)),
+            None => None,
+        }
+    };
+
+    result.unwrap_or_else(|| DefWindowProcW(handle, message, w_param, l_param))
+}
+
+// The window procedure with the state borrowed, None leaves the message to
+// DefWindowProcW. Calls in here that send the window messages go through
+// win32_once_released.
+#[allow(clippy::cognitive_complexity)]
+unsafe fn win32_handle_message(
+    handle: HWND,
+    state: &mut WindowState,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> Option<LRESULT> {
+    // Follow the last input device for the caption metrics. Mouse moves aren't used
+    // to go back, the system sends them whenever the window moves under the cursor.
+    match message {
+        WM_POINTERDOWN | WM_NCPOINTERDOWN => {
+            let mut pointer_type = POINTER_INPUT_TYPE::default();
+            if GetPointerType(win32_pointer_id(w_param), &mut pointer_type).is_ok()
+                && pointer_type == PT_TOUCH
+            {
+                win32_set_touch_metrics(handle, state, true);
+            }
+        }
+        WM_LBUTTONDOWN | WM_RBUTTONDOWN | WM_MBUTTONDOWN | WM_NCLBUTTONDOWN | WM_NCRBUTTONDOWN
+        | WM_NCMBUTTONDOWN
+            if !win32_is_promoted_from_pointer() && !state.slate_mode =>
+        {
+            win32_set_touch_metrics(handle, state, false);
+        }
+        _ => {}
+    }
+
+    if let Some(result) = win32_context_help_input(handle, state, message, w_param, l_param) {
+        return Some(result);
+    }
+
+    // Keys only reach the message box buttons while the caption doesn't have the focus
+    if state.message_box.is_some()
+        && (message != WM_KEYDOWN || state.focus_regions.current() == FocusRegion::Content)
+    {
+        if let Some(result) = win32_message_box_input(handle, state, message, w_param, l_param) {
+            return Some(result);
+        }
+    }
+
+    if let Some(result) = win32_menu_bar_input(handle, state, message, w_param, l_param) {
+        return Some(result);
+    }
+
+    if let Some(result) = win32_tab_strip_input(handle, state, message, w_param, l_param) {
+        return Some(result);
+    }
+
+    if let Some(result) = win32_breadcrumb_input(handle, state, message, w_param, l_param) {
+        return Some(result);
+    }
+
+    if let Some(result) = win32_badges_input(handle, state, message, w_param, l_param) {
+        return Some(result);
+    }
+
+    if let Some(result) = win32_caption_tooltips_input(handle, state, message, w_param, l_param) {
+        return Some(result);
+    }
+
+    if let Some(result) = win32_caption_buttons_input(handle, state, message, w_param, l_param) {
+        return Some(result);
+    }
+
+    if let Some(result) = win32_search_box_input(handle, state, message, w_param, l_param) {
+        return Some(result);
+    }
+
+    if let Some(result) = win32_caption_text_input(handle, state, message, w_param, l_param) {
+        return Some(result);
+    }
+
+    let title_bar_hovered_button = state.hovered_button;
+
+    match message {
+        WM_NCCALCSIZE => {
+            if w_param == WPARAM(0) {
+                return None;
+            }
+
+            // No frame at all, the client area is the whole window
+            if state.fullscreen_restore.is_some() {
+                return Some(LRESULT(0));
+            }
+
+            let dpi = win32_window_dpi(handle);
+            let frame_x = win32_system_metrics_for_dpi(SM_CXFRAME, dpi);
+            let frame_y = win32_system_metrics_for_dpi(SM_CYFRAME, dpi);
+            let padding = win32_system_metrics_for_dpi(SM_CXPADDEDBORDER, dpi);
+
+            let params = l_param.0 as *mut NCCALCSIZE_PARAMS;
+            if params.is_null() {
+                return None;
+            }
+
+            let requested_client_rect = &mut (*params).rgrc[0];
+            let proposed_window_rect = *requested_client_rect;
+            let is_maximized = win32_window_is_maximized(handle);
+            // Rolled up, the window ends with the caption. The invisible border would
+            // otherwise swallow clicks meant for whatever is below it.
+            let has_bottom_frame = state.shade_restore_height.is_none() || is_maximized;
+            *requested_client_rect = win32_inset_client_rect(
+                proposed_window_rect,
+                frame_x,
+                frame_y,
+                padding,
+                has_bottom_frame,
+            );
+
+            // Asked for the proposed rect, the window may still be on its old monitor
+            if is_maximized {
+                let monitor = MonitorFromRect(&proposed_window_rect, MONITOR_DEFAULTTONEAREST);
+                let mut monitor_info = MONITORINFO {
+                    cbSize: size_of::<MONITORINFO>() as u32,
+                    ..Default::default()
+                };
+                let work_area = GetMonitorInfoW(monitor, &mut monitor_info)
+                    .as_bool()
+                    .then_some(monitor_info.rcWork);
+                *requested_client_rect =
+                    win32_clip_maximized_client_rect(*requested_client_rect, work_area, padding);
+            }
+
+            return Some(LRESULT(0));
+        }
+        WM_GETMINMAXINFO => {
+            let min_max_info = l_param.0 as *mut MINMAXINFO;
+            if min_max_info.is_null() {
+                return None;
+            }
+
+            let caption_min_size = win32_caption_min_window_size(handle, state);
+            let min_size = state.min_size.map_or(caption_min_size, |min_size| {
+                let min_size = win32_window_size_for_client(handle, min_size);
+                POINT {
+                    x: min_size.x.max(caption_min_size.x),
+                    y: min_size.y.max(caption_min_size.y),
+                }
+            });
+            (*min_max_info).ptMinTrackSize = min_size;
+
+            if let Some(max_size) = state.max_size {
+                let max_track_size = win32_window_size_for_client(handle, max_size);
+                (*min_max_info).ptMaxTrackSize = max_track_size;
+                (*min_max_info).ptMaxSize.x = (*min_max_info).ptMaxSize.x.min(max_track_size.x);
+                (*min_max_info).ptMaxSize.y = (*min_max_info).ptMaxSize.y.min(max_track_size.y);
+            }
+
+            // After the max size, which would let a rolled up window be sized open again
+            if state.shade_restore_height.is_some() {
+                let caption_height =
+                    win32_titlebar_rect(handle, state).map_or(0, |rect| rect.bottom - rect.top);
+                (*min_max_info).ptMinTrackSize.y = caption_height;
+                (*min_max_info).ptMaxTrackSize.y = caption_height;
+            }
+
+            return Some(LRESULT(0));
+        }
+        WM_CREATE => {
+            let mut size_rect = RECT::default();
+            let result = GetWindowRect(handle, &mut size_rect);
+
+            if result.is_err() {
+                eprintln!(
+                    "Failed to get window rect:\n{}",
+                    result.err().unwrap().message()
+                );
+                return None;
+            }
+
+            // WM_NCCALCSIZE has to get the state to give the frame its custom size
+            win32_once_released(state, move || {
+                let result = unsafe {
+                    SetWindowPos(
+                        handle,
+                        None,
+                        size_rect.left,
+                        size_rect.top,
+                        size_rect.right - size_rect.left,
+                        size_rect.bottom - size_rect.top,
+                        SWP_FRAMECHANGED | SWP_NOMOVE | SWP_NOSIZE,
+                    )
+                };
+
+                if result.is_err() {
+                    eprintln!(
+                        "Failed to set window position:\n{}",
+                        result.err().unwrap().message()
+                    );
+                }
+            });
+
+            if state.options.use_glyph_font {
+                state.glyph_font_face = win32_find_glyph_font_face(handle);
+            }
+
+            if let Err(e) = win32_add_always_on_top_menu_item(handle) {
+                eprintln!("Failed to add always on top menu item:\n{}", e);
+            }
+
+            state.caption_button_names = CaptionButtonNames::win32_for_user_language(handle);
+            state.snap_layouts = win32_supports_snap_layouts();
+            state.slate_mode = win32_is_slate_mode();
+            state.touch_metrics = state.slate_mode;
+            state.reduced_motion = !win32_animations_enabled();
+            state.transparency_effects = win32_transparency_effects_enabled();
+            state.dpi = win32_window_dpi(handle);
+            state.title = win32_window_text(handle);
+            let tool_ids = CAPTION_BUTTONS.map(|button| button.index().unwrap() + 1);
+            match win32_create_tooltip(handle, &tool_ids) {
+                Ok(tooltip) => state.tooltip = tooltip,
+                Err(e) => eprintln!("{}", e),
+            }
+            if state.options.pin_button {
+                win32_add_pin_button(handle, state);
+            }
+
+            if let Err(e) = BufferedPaintInit() {
+                eprintln!("Failed to initialize buffered paint: {}", e.message());
+            }
+
+            state.power_saving = win32_is_power_saving();
+            match RegisterPowerSettingNotification(
+                handle,
+                &GUID_POWER_SAVING_STATUS,
+                DEVICE_NOTIFY_WINDOW_HANDLE,
+            ) {
+                Ok(notification) => state.power_notification = notification,
+                Err(e) => eprintln!(
+                    "Failed to register power setting notification: {}",
+                    e.message()
+                ),
+            }
+        }
+        WM_POWERBROADCAST if w_param.0 as u32 == PBT_POWERSETTINGCHANGE => {
+            let setting = l_param.0 as *const POWERBROADCAST_SETTING;
+            if !setting.is_null()
+                && (*setting).PowerSetting == GUID_POWER_SAVING_STATUS
+                && (*setting).DataLength as usize >= size_of::<u32>()
+            {
+                let data = std::ptr::addr_of!((*setting).Data) as *const u32;
+                state.power_saving = data.read_unaligned() != 0;
+                win32_update_throttling(handle, state);
+            }
+
+            return Some(LRESULT(1));
+        }
+        WM_STYLECHANGED if w_param.0 as i32 == GWL_STYLE.0 => {
+            let style = &*(l_param.0 as *const STYLESTRUCT);
+            let boxes = (WS_MINIMIZEBOX | WS_MAXIMIZEBOX).0;
+            state.window_style = WINDOW_STYLE(style.styleNew);
+            if (style.styleOld ^ style.styleNew) & boxes != 0 {
+                for button in [
+                    CustomTitleBarHoveredButton::Minimize,
+                    CustomTitleBarHoveredButton::Maximize,
+                ] {
+                    win32_system_button_changed(handle, state, button);
+                }
+            }
+            return Some(LRESULT(0));
+        }
+        WM_SETTINGCHANGE
+            if win32_affects_caption_font(SYSTEM_PARAMETERS_INFO_ACTION(w_param.0 as u32)) =>
+        {
+            win32_clear_caption_fonts();
+            if let Some(search_box) = &mut state.search_box {
+                search_box.font = HFONT::default();
+            }
+            win32_relayout_caption(handle, state);
+            InvalidateRect(handle, None, true);
+            return None;
+        }
+        WM_SETTINGCHANGE if w_param.0 as u32 == SPI_SETCLIENTAREAANIMATION.0 => {
+            state.reduced_motion = !win32_animations_enabled();
+            if state.reduced_motion {
+                for transition in &mut state.hover_transitions {
+                    transition.finish();
+                }
+                state.caption_buttons.finish_animations();
+                state.press_ripple.finish();
+                state.caption_reveal.finish();
+            }
+
+            // A scrolling title turns into a faded one and back
+            InvalidateRect(handle, None, false);
+            return None;
+        }
+        // Sent for the personalization settings, among them transparency effects
+        WM_SETTINGCHANGE
+            if l_param.0 != 0
+                && PCWSTR(l_param.0 as _)
+                    .to_string()
+                    .is_ok_and(|area| area == "ImmersiveColorSet") =>
+        {
+            let transparency_effects = win32_transparency_effects_enabled();
+            if state.transparency_effects != transparency_effects {
+                state.transparency_effects = transparency_effects;
+                win32_dispatch_event(
+                    handle,
+                    WindowEvent::TransparencyEffectsChanged(transparency_effects),
+                );
+                InvalidateRect(handle, None, false);
+            }
+
+            return None;
+        }
+        // The user switched between laptop and tablet posture
+        WM_SETTINGCHANGE
+            if l_param.0 != 0
+                && PCWSTR(l_param.0 as _)
+                    .to_string()
+                    .is_ok_and(|area| area == "ConvertibleSlateMode") =>
+        {
+            state.slate_mode = win32_is_slate_mode();
+            win32_set_touch_metrics(handle, state, state.slate_mode);
+            return None;
+        }
+        // The caption icon is painted by us, DefWindowProc only stores it
+        WM_SETICON => {
+            let title_bar_rect = win32_titlebar_rect(handle, state).unwrap_or_default();
+            let old_icon_rect = win32_caption_icon_rect(handle, state, &title_bar_rect);
+            let result = DefWindowProcW(handle, message, w_param, l_param);
+
+            // Gaining or losing the icon moves the title along
+            let repaint_rect = match (
+                old_icon_rect,
+                win32_caption_icon_rect(handle, state, &title_bar_rect),
+            ) {
+                (Some(old_icon_rect), Some(icon_rect)) if old_icon_rect == icon_rect => icon_rect,
+                (None, None) => return Some(result),
+                _ => title_bar_rect,
+            };
+            win32_invalidate_rect(handle, state, &repaint_rect);
+            return Some(result);
+        }
+        // The title is painted by us from the copy kept in the state
+        WM_SETTEXT => {
+            let result = DefWindowProcW(handle, message, w_param, l_param);
+            state.title = win32_window_text(handle);
+            // The whole space the title may take, which covers the old text as well
+            let title_rect = state.title_rect;
+            win32_invalidate_rect(handle, state, &title_rect);
+            return Some(result);
+        }
+        // Dragged to or the scale changed on a monitor with another dpi. Caption metrics
+        // and fonts follow win32_window_dpi, what's kept in the state is rescaled here.
+        WM_DPICHANGED => {
+            let dpi = (w_param.0 & 0xFFFF) as u32;
+            let rescale = |value: i32| win32_mul_div(value, dpi, state.dpi);
+            state.shade_restore_height = state.shade_restore_height.map(rescale);
+            state.dpi = dpi;
+            win32_drop_glyph_font(state);
+
+            // Remeasured on the next paint at the new size
+            win32_update_marquee(handle, state, None);
+
+            if let Some(path) = state.file_icons.as_ref().map(|icons| icons.path.clone()) {
+                win32_once_released(state, move || {
+                    if let Err(e) = win32_load_file_icons(handle, &path) {
+                        eprintln!("{}", e);
+                    }
+                });
+            }
+            if let Some(avatar) = &mut state.avatar {
+                let path = avatar.path.clone();
+                if let Err(e) = avatar.win32_reload(handle, &path) {
+                    eprintln!("{}", e);
+                }
+            }
+
+            // Frame changed so WM_NCCALCSIZE picks up the new caption height
+            let suggested_rect = *(l_param.0 as *const RECT);
+            win32_once_released(state, move || {
+                if let Err(e) = unsafe {
+                    SetWindowPos(
+                        handle,
+                        None,
+                        suggested_rect.left,
+                        suggested_rect.top,
+                        suggested_rect.right - suggested_rect.left,
+                        suggested_rect.bottom - suggested_rect.top,
+                        SWP_NOZORDER | SWP_NOACTIVATE | SWP_FRAMECHANGED,
+                    )
+                } {
+                    eprintln!("Failed to set window position: {}", e.message());
+                }
+            });
+
+            // The suggested rect scales the whole window, the text wraps differently
+            if state.message_box.is_some() {
+                win32_defer(move || win32_fit_message_box(handle));
+            }
+
+            // Tool rects are refreshed on the next mouse move, until then they would
+            // cover the buttons at their old size
+            if state.tooltip != HWND(0) {
+                for button in CAPTION_BUTTONS {
+                    let id = button.index().unwrap() + 1;
+                    win32_set_tool_rect(state.tooltip, handle, id, RECT::default());
+                }
+                for id in state.caption_buttons.ids() {
+                    win32_set_tool_rect(state.tooltip, handle, id.tool_id(), RECT::default());
+                }
+                if state.status_led.is_some() {
+                    let id = WIN32_STATUS_LED_TOOL_ID;
+                    win32_set_tool_rect(state.tooltip, handle, id, RECT::default());
+                }
+                win32_clear_caption_tool_rects(handle, state);
+            }
+
+            InvalidateRect(handle, None, true);
+            return Some(LRESULT(0));
+        }
+        // Moved or resized by the user, the restart command line follows
+        WM_EXITSIZEMOVE => {
+            // Asks the application for the state to restart with
+            win32_defer(|| {
+                if let Err(e) = win32_update_restart_command_line() {
+                    eprintln!("{}", e);
+                }
+            });
+
+            return None;
+        }
+        WM_ENDSESSION => {
+            if w_param.0 != 0 {
+                win32_dispatch_event(handle, WindowEvent::SessionEnded);
+            }
+
+            return Some(LRESULT(0));
+        }
+        WM_WINDOWPOSCHANGED => {
+            // Covers minimize/restore, show/hide and cloaking changes
+            win32_update_throttling(handle, state);
+
+            // Maximize became restore or the other way around
+            let is_maximized = win32_window_is_maximized(handle);
+            if state.maximized != is_maximized {
+                state.maximized = is_maximized;
+                let button = CustomTitleBarHoveredButton::Maximize;
+                win32_notify_caption_button(handle, EVENT_OBJECT_NAMECHANGE, button);
+                win32_notify_caption_button(handle, EVENT_OBJECT_STATECHANGE, button);
+            }
+
+            let is_always_on_top = win32_is_always_on_top(handle);
+            if state.always_on_top != is_always_on_top {
+                state.always_on_top = is_always_on_top;
+                if let Some(id) = state.pin_button {
+                    match win32_pin_icon(is_always_on_top) {
+                        Ok(icon) => {
+                            state.caption_buttons.set_icon(id, icon);
+                            win32_invalidate_added_caption_buttons(handle, state);
+                        }
+                        Err(e) => eprintln!("Failed to create pin glyph: {}", e),
+                    }
+                }
+            }
+
+            // Size, dpi and fullscreen all move the search field
+            win32_relayout_caption(handle, state);
+            return None;
+        }
+        WM_TIMER if w_param.0 == WIN32_REPAINT_TIMER_ID => {
+            let _ = KillTimer(handle, WIN32_REPAINT_TIMER_ID);
+            win32_flush_pending_invalidation(handle, state);
+            return Some(LRESULT(0));
+        }
+        WM_TIMER if w_param.0 == WIN32_ANIMATION_TIMER_ID => {
+            let animates = !state.skips_animations();
+            if state
+                .status_led
+                .as_mut()
+                .is_some_and(|led| led.step_blink(animates))
+            {
+                win32_invalidate_status_led(handle, state);
+            }
+
+            if !state.is_animating() {
+                let _ = KillTimer(handle, WIN32_ANIMATION_TIMER_ID);
+            }
+
+            // Repaints once more after the last transition ends so it lands on its final value.
+            // The sliding caption uncovers content as it goes.
+            if state.hides_caption() {
+                InvalidateRect(handle, None, false);
+            } else {
+                win32_invalidate_caption_buttons(handle, state);
+                win32_invalidate_caption_button_rects(handle, state);
+            }
+
+            return Some(LRESULT(0));
+        }
+        WM_TIMER if w_param.0 == WIN32_CAPTION_REVEAL_TIMER_ID => {
+            let _ = KillTimer(handle, WIN32_CAPTION_REVEAL_TIMER_ID);
+            if state.hides_caption() {
+                win32_reveal_caption(handle, state, true);
+            }
+
+            return Some(LRESULT(0));
+        }
+        WM_TIMER if w_param.0 == WIN32_MARQUEE_TIMER_ID => {
+            if let Some(marquee_rect) = state.marquee_rect {
+                let dpi = win32_window_dpi(handle);
+                state.marquee_offset += win32_dpi_scale(1, dpi).max(1);
+                InvalidateRect(handle, Some(&marquee_rect), false);
+            }
+
+            return Some(LRESULT(0));
+        }
+        WM_ACTIVATE => {
+            let result = win32_titlebar_rect(handle, state);
+            if result.is_err() {
+                eprintln!("Failed to get title bar rect:\n{}", result.err().unwrap());
+                return None;
+            }
+
+            let title_bar_rect = result.unwrap();
+            InvalidateRect(handle, Some(&title_bar_rect), false);
+
+            return None;
+        }
+        WM_NCHITTEST => {
+            // The hit test override had its go in win32_answering_callbacks
+            let hit = DefWindowProcW(handle, message, w_param, l_param);
+            if state.shade_restore_height.is_some() {
+                let shaded_hit = win32_shaded_hit(hit.0 as u32);
+                if shaded_hit != hit.0 as u32 {
+                    return Some(LRESULT(shaded_hit as _));
+                }
+            }
+
+            match hit.0 as u32 {
+                HTNOWHERE | HTRIGHT | HTLEFT | HTTOPLEFT | HTTOP | HTTOPRIGHT | HTBOTTOMRIGHT
+                | HTBOTTOM | HTBOTTOMLEFT => {
+                    return Some(hit);
+                }
+                _ => {}
+            }
+
+            let dpi = win32_window_dpi(handle);
+            let frame_y = win32_system_metrics_for_dpi(SM_CYFRAME, dpi);
+            let padding = win32_system_metrics_for_dpi(SM_CXPADDEDBORDER, dpi);
+            let cursor_point = win32_screen_to_client(handle, l_param);
+
+            // A maximized window can't be resized, the top rows belong to the caption
+            // (and the buttons) instead
+            let is_maximized =
+                win32_window_is_maximized(handle) || state.fullscreen_restore.is_some();
+            let is_shaded = state.shade_restore_height.is_some();
+            let resizes_top = !is_maximized && !is_shaded;
+            if win32_is_in_top_resize_band(cursor_point.y, frame_y, padding, resizes_top) {
+                return Some(LRESULT(HTTOP as _));
+            }
+
+            let result = win32_titlebar_rect(handle, state);
+            if result.is_err() {
+                eprintln!("Failed to get title bar rect:\n{}", result.err().unwrap());
+                return Some(hit);
+            }
+
+            let title_bar_rect = result.unwrap();
+            let caption_button_layout = win32_caption_button_layout(handle, state, &title_bar_rect);
+
+            if state
+                .caption_interactive_regions
+                .iter()
+                .chain(&caption_button_layout.items)
+                .any(|region| PtInRect(region, cursor_point).as_bool())
+            {
+                return Some(LRESULT(HTCLIENT as _));
+            }
+
+            if win32_is_over_tab(handle, state, cursor_point)
+                || win32_is_over_menu_bar(handle, state, cursor_point)
+                || win32_is_over_breadcrumb(handle, state, cursor_point)
+                || win32_is_over_caption_button(&caption_button_layout, cursor_point)
+                || win32_is_over_badge(&state.badges, cursor_point)
+                || caption_button_layout
+                    .search_field
+                    .into_iter()
+                    .chain(caption_button_layout.caption_label)
+                    .any(|rect| PtInRect(&rect, cursor_point).as_bool())
+            {
+                return Some(LRESULT(HTCLIENT as _));
+            }
+
+            // Windows 11 opens the Snap Layouts flyout when hovering HTMAXBUTTON. Decided
+            // from the geometry rather than the hover state, which is only updated by the
+            // WM_NCMOUSEMOVE that follows this hit test.
+            if state.snap_layouts {
+                let button_rects = CustomTitleBarButtonRects::win32_get_title_bar_button_rects(
+                    handle,
+                    state,
+                    &title_bar_rect,
+                );
+                if button_rects.button_at(cursor_point) == CustomTitleBarHoveredButton::Maximize {
+                    return Some(LRESULT(HTMAXBUTTON as _));
+                }
+            }
+
+            if let Some(icon_rect) = win32_caption_icon_rect(handle, state, &title_bar_rect) {
+                if PtInRect(&icon_rect, cursor_point).as_bool() {
+                    return Some(LRESULT(HTSYSMENU as _));
+                }
+            }
+
+            if cursor_point.y < title_bar_rect.bottom {
+                return Some(LRESULT(HTCAPTION as _));
+            }
+
+            return Some(LRESULT(HTCLIENT as _));
+        }
+        // Magnifiers and assistive technology ask where the caption buttons are,
+        // DefWindowProc would describe the standard caption that isn't painted
+        WM_GETTITLEBARINFOEX => {
+            let info = l_param.0 as *mut TITLEBARINFOEX;
+            if info.is_null() || (*info).cbSize as usize != size_of::<TITLEBARINFOEX>() {
+                return None;
+            }
+
+            match win32_title_bar_info(handle, state) {
+                Ok(title_bar_info) => *info = title_bar_info,
+                Err(e) => {
+                    eprintln!("Failed to get title bar info:\n{}", e);
+                    return None;
+                }
+            }
+
+            return Some(LRESULT(0));
+        }
+        WM_PAINT => {
+            let has_focus = GetFocus() == handle;
+            // Focus on a child, e.g. the search box, keeps the caption looking active
+            let is_active = has_focus || IsChild(handle, GetFocus()).as_bool();
+            let mut ps = PAINTSTRUCT::default();
+            let paint_hdc = BeginPaint(handle, &mut ps);
+
+            // Compose the frame off-screen so hover fades don't flicker
+            let mut hdc = HDC::default();
+            let buffered_frame = BeginBufferedPaint(
+                paint_hdc,
+                &ps.rcPaint,
+                BPBF_COMPATIBLEBITMAP,
+                None,
+                &mut hdc,
+            );
+            if buffered_frame == 0 {
+                hdc = paint_hdc;
+            }
+            let _frame_paint = FramePaint {
+                handle,
+                ps,
+                buffered_frame,
+            };
+
+            let result = win32_titlebar_rect(handle, state);
+            if result.is_err() {
+                eprintln!("Failed to get title bar rect:\n{}", result.err().unwrap());
+                return None;
+            }
+
+            let title_bar_rect = result.unwrap();
+
+            // Paint background
+            let bg_color = COLORREF(rgb(200, 250, 230));
+            let bg_brush = CreateSolidBrush(bg_color);
+            FillRect(hdc, &ps.rcPaint, bg_brush);
+            DeleteObject(bg_brush);
+
+            if let Some(content) = &state.message_box {
+                let show_focus = has_focus && state.focus_regions.current() == FocusRegion::Content;
+                win32_paint_message_box(hdc, handle, content, &title_bar_rect, show_focus);
+            }
+
+            // Paint title bar
+            let titlebar_color = COLORREF(rgb(150, 200, 180));
+            let titlebar_hover_color = COLORREF(rgb(130, 180, 160));
+            let titlebar_pressed_color = COLORREF(rgb(110, 160, 140));
+
+            let theme = OpenThemeData(handle, w!("WINDOW"));
+
+            // Title Bar Background
+            win32_fill_caption_background(
+                hdc,
+                state,
+                &title_bar_rect,
+                &title_bar_rect,
+                titlebar_color,
+            );
+
+            let titlebar_item_color = COLORREF(if is_active {
+                rgb(33, 33, 33)
+            } else {
+                rgb(127, 127, 127)
+            });
+
+            let button_icon_brush = CreateSolidBrush(titlebar_item_color);
+            let dpi = win32_window_dpi(handle);
+            let glyph_size = win32_glyph_size(state);
+            let icon_dimension = win32_dpi_scale(glyph_size, dpi);
+            let stroke_width = win32_glyph_stroke_width(dpi);
+            let maximized_button_offset = win32_dpi_scale(WIN32_MAXIMIZED_BUTTON_OFFSET, dpi);
+
+            // PS_INSIDEFRAME keeps thick strokes inside the rectangle of the maximize glyph
+            let button_icon_pen = CreatePen(PS_INSIDEFRAME, stroke_width, titlebar_item_color);
+
+            // Disabled buttons have their glyph dimmed like the added ones
+            let disabled_item_color =
+                win32_blend_color(titlebar_item_color, titlebar_color, WIN32_DISABLED_DIMMING);
+            let disabled_icon_brush = CreateSolidBrush(disabled_item_color);
+            let disabled_icon_pen = CreatePen(PS_INSIDEFRAME, stroke_width, disabled_item_color);
+
+            let button_rects = CustomTitleBarButtonRects::win32_get_title_bar_button_rects(
+                handle,
+                state,
+                &title_bar_rect,
+            );
+
+            let glyph_font = win32_glyph_font(handle, state);
+
+            // The application's glyphs go first, where it draws one the window doesn't
+            let mut glyph_painter = state.glyph_painter.take();
+            let is_maximized = win32_window_is_maximized(handle);
+            let is_shaded = state.shade_restore_height.is_some();
+            let pressed_button = state.pressed_button;
+            let mut paint_custom_glyph =
+                |button: CustomTitleBarHoveredButton, rect: &RECT, color: COLORREF| {
+                    let Some(name) = CaptionButtonName::for_button(button, is_maximized, is_shaded)
+                    else {
+                        return false;
+                    };
+                    let hovered = title_bar_hovered_button == button;
+                    let glyph = CaptionGlyph {
+                        button: CaptionGlyphButton::System(name),
+                        hdc,
+                        rect: *rect,
+                        dpi,
+                        color,
+                        hovered,
+                        pressed: hovered && pressed_button == button,
+                        enabled: button.is_enabled(state),
+                    };
+                    glyph_painter
+                        .as_mut()
+                        .is_some_and(|glyph_painter| glyph_painter(&glyph))
+                };
+
+            let icon_tools = |button: CustomTitleBarHoveredButton| {
+                if button.is_enabled(state) {
+                    (titlebar_item_color, button_icon_pen, button_icon_brush)
+                } else {
+                    (disabled_item_color, disabled_icon_pen, disabled_icon_brush)
+                }
+            };
+            let old_glyph_font = if glyph_font != HFONT(0) {
+                HFONT(SelectObject(hdc, glyph_font).0)
+            } else {
+                HFONT(0)
+            };
+
+            // Shade Button
+            if CustomTitleBarHoveredButton::Shade.is_shown(state) {
+                let (icon_color, icon_pen, _) = icon_tools(CustomTitleBarHoveredButton::Shade);
+                win32_paint_caption_button_background(
+                    hdc,
+                    state,
+                    CustomTitleBarHoveredButton::Shade,
+                    &button_rects.shade,
+                    titlebar_color,
+                    titlebar_hover_color,
+                    titlebar_pressed_color,
+                );
+
+                if paint_custom_glyph(
+                    CustomTitleBarHoveredButton::Shade,
+                    &button_rects.shade,
+                    icon_color,
+                ) {
+                    // Drawn by the application
+                } else if glyph_font != HFONT(0) {
+                    let glyph = if is_shaded {
+                        WIN32_GLYPH_CHEVRON_DOWN
+                    } else {
+                        WIN32_GLYPH_CHEVRON_UP
+                    };
+                    win32_draw_caption_glyph(hdc, glyph, &button_rects.shade, icon_color);
+                } else {
+                    let mut icon_rect = RECT {
+                        right: icon_dimension,
+                        bottom: icon_dimension / 2,
+                        ..Default::default()
+                    };
+
+                    // Points up to roll up, down to roll back down
+                    win32_center_rect_in_rect(&mut icon_rect, &button_rects.shade);
+                    let (tip_y, ends_y) = if is_shaded {
+                        (icon_rect.bottom, icon_rect.top)
+                    } else {
+                        (icon_rect.top, icon_rect.bottom)
+                    };
+                    let center_x = (icon_rect.left + icon_rect.right) / 2;
+                    SelectObject(hdc, icon_pen);
+                    MoveToEx(hdc, icon_rect.left, ends_y, None);
+                    LineTo(hdc, center_x, tip_y);
+                    LineTo(hdc, icon_rect.right, ends_y);
+                }
+            }
+
+            // Tool windows and dialogs only have the close button, documents can't minimize
+            if CustomTitleBarHoveredButton::Minimize.is_shown(state) {
+                // Minimize Button
+                let (icon_color, _, icon_brush) = icon_tools(CustomTitleBarHoveredButton::Minimize);
+                win32_paint_caption_button_background(
+                    hdc,
+                    state,
+                    CustomTitleBarHoveredButton::Minimize,
+                    &button_rects.minimize,
+                    titlebar_color,
+                    titlebar_hover_color,
+                    titlebar_pressed_color,
+                );
+
+                if paint_custom_glyph(
+                    CustomTitleBarHoveredButton::Minimize,
+                    &button_rects.minimize,
+                    icon_color,
+                ) {
+                    // Drawn by the application
+                } else if glyph_font != HFONT(0) {
+                    win32_draw_caption_glyph(
+                        hdc,
+                        WIN32_GLYPH_MINIMIZE,
+                        &button_rects.minimize,
+                        icon_color,
+                    );
+                } else {
+                    let mut icon_rect = RECT {
+                        right: icon_dimension,
+                        bottom: stroke_width,
+                        ..Default::default()
+                    };
+
+                    win32_center_rect_in_rect(&mut icon_rect, &button_rects.minimize);
+                    FillRect(hdc, &icon_rect, icon_brush);
+                }
+            }
+
+            if CustomTitleBarHoveredButton::Maximize.is_shown(state) {
+                // Maximize Button
+                let (icon_color, icon_pen, _) = icon_tools(CustomTitleBarHoveredButton::Maximize);
+                let maximize_fill = win32_paint_caption_button_background(
+                    hdc,
+                    state,
+                    CustomTitleBarHoveredButton::Maximize,
+                    &button_rects.maximize,
+                    titlebar_color,
+                    titlebar_hover_color,
+                    titlebar_pressed_color,
+                );
+
+                if paint_custom_glyph(
+                    CustomTitleBarHoveredButton::Maximize,
+                    &button_rects.maximize,
+                    icon_color,
+                ) {
+                    // Drawn by the application
+                } else if glyph_font != HFONT(0) {
+                    let glyph = if is_maximized {
+                        WIN32_GLYPH_RESTORE
+                    } else {
+                        WIN32_GLYPH_MAXIMIZE
+                    };
+                    win32_draw_caption_glyph(hdc, glyph, &button_rects.maximize, icon_color);
+                } else {
+                    let mut icon_rect = RECT {
+                        right: icon_dimension,
+                        bottom: icon_dimension,
+                        ..Default::default()
+                    };
+
+                    win32_center_rect_in_rect(&mut icon_rect, &button_rects.maximize);
+                    SelectObject(hdc, icon_pen);
+                    SelectObject(hdc, GetStockObject(HOLLOW_BRUSH));
+                    if win32_window_is_maximized(handle) {
+                        Rectangle(
+                            hdc,
+                            icon_rect.left + maximized_button_offset,
+                            icon_rect.top - maximized_button_offset,
+                            icon_rect.right + maximized_button_offset,
+                            icon_rect.bottom - maximized_button_offset,
+                        );
+
+                        let maximize_brush = CreateSolidBrush(maximize_fill);
+                        FillRect(hdc, &icon_rect, maximize_brush);
+                        DeleteObject(maximize_brush);
+                    }
+
+                    Rectangle(
+                        hdc,
+                        icon_rect.left,
+                        icon_rect.top,
+                        icon_rect.right,
+                        icon_rect.bottom,
+                    );
+                }
+            }
+
+            if CustomTitleBarHoveredButton::Close.is_shown(state) {
+                // Close button
+                let (icon_color, icon_pen, icon_brush) =
+                    icon_tools(CustomTitleBarHoveredButton::Close);
+                win32_paint_caption_button_background(
+                    hdc,
+                    state,
+                    CustomTitleBarHoveredButton::Close,
+                    &button_rects.close,
+                    titlebar_color,
+                    COLORREF(rgb(255, 0, 0)), // aka red color!!
+                    COLORREF(rgb(200, 0, 0)),
+                );
+
+                let close_amount = if state.pressed_button == CustomTitleBarHoveredButton::Close
+                    && title_bar_hovered_button == CustomTitleBarHoveredButton::Close
+                {
+                    1.0
+                } else {
+                    CustomTitleBarHoveredButton::Close
+                        .index()
+                        .map_or(0.0, |index| state.hover_transitions[index].value())
+                };
+
+                let close_color =
+                    win32_blend_color(icon_color, COLORREF(rgb(255, 255, 255)), close_amount);
+                if paint_custom_glyph(
+                    CustomTitleBarHoveredButton::Close,
+                    &button_rects.close,
+                    close_color,
+                ) {
+                    // Drawn by the application
+                } else if state.options.modified_close_dot
+                    && state.modified
+                    && close_amount == 0.0
+                    && state.focused_button != CustomTitleBarHoveredButton::Close
+                {
+                    let mut dot_rect = RECT {
+                        right: icon_dimension * 3 / 5,
+                        bottom: icon_dimension * 3 / 5,
+                        ..Default::default()
+                    };
+                    win32_center_rect_in_rect(&mut dot_rect, &button_rects.close);
+                    SelectObject(hdc, icon_pen);
+                    SelectObject(hdc, icon_brush);
+                    Ellipse(
+                        hdc,
+                        dot_rect.left,
+                        dot_rect.top,
+                        dot_rect.right,
+                        dot_rect.bottom,
+                    );
+                } else if glyph_font != HFONT(0) {
+                    win32_draw_caption_glyph(
+                        hdc,
+                        WIN32_GLYPH_CLOSE,
+                        &button_rects.close,
+                        close_color,
+                    );
+                } else {
+                    SelectObject(hdc, icon_pen);
+                    let mut custom_pen = HPEN(0);
+                    if close_amount > 0.0 {
+                        custom_pen = CreatePen(PS_INSIDEFRAME, stroke_width, close_color);
+                        SelectObject(hdc, custom_pen);
+                    }
+
+                    let mut icon_rect = RECT {
+                        right: icon_dimension,
+                        bottom: icon_dimension,
+                        ..Default::default()
+                    };
+
+                    win32_center_rect_in_rect(&mut icon_rect, &button_rects.close);
+                    MoveToEx(hdc, icon_rect.left, icon_rect.top, None);
+                    LineTo(hdc, icon_rect.right + 1, icon_rect.bottom + 1);
+                    MoveToEx(hdc, icon_rect.left, icon_rect.bottom, None);
+                    LineTo(hdc, icon_rect.right + 1, icon_rect.top - 1);
+                    if custom_pen != HPEN(0) {
+                        DeleteObject(custom_pen);
+                    }
+                }
+            }
+
+            if glyph_font != HFONT(0) {
+                SelectObject(hdc, old_glyph_font);
+            }
+
+            // Keyboard focus ring, only while the window itself has focus
+            if has_focus {
+                let focus_rect = match state.focused_button {
+                    CustomTitleBarHoveredButton::Shade => Some(button_rects.shade),
+                    CustomTitleBarHoveredButton::Minimize => Some(button_rects.minimize),
+                    CustomTitleBarHoveredButton::Maximize => Some(button_rects.maximize),
+                    CustomTitleBarHoveredButton::Close => Some(button_rects.close),
+                    CustomTitleBarHoveredButton::None => None,
+                };
+
+                if let Some(focus_rect) = focus_rect {
+                    win32_draw_focus_ring(
+                        hdc,
+                        &focus_rect,
+                        dpi,
+                        titlebar_item_color,
+                        titlebar_color,
+                    );
+                }
+            }
+
+            DeleteObject(button_icon_brush);
+            DeleteObject(button_icon_pen);
+            DeleteObject(disabled_icon_brush);
+            DeleteObject(disabled_icon_pen);
+
+            // Draw window title
+            let theme_font = win32_caption_font(dpi);
+
+            let mut title_text_buffer = state.title.clone();
+            if state.modified && !state.options.modified_close_dot {
+                title_text_buffer.splice(0..0, WIN32_MODIFIED_MARKER.encode_utf16());
+            }
+
+            // The small icon scaled to the small icon size at this dpi
+            let caption_icon_rect = win32_caption_icon_rect(handle, state, &title_bar_rect);
+            if let Some(icon_rect) = caption_icon_rect {
+                if let Err(e) = DrawIconEx(
+                    hdc,
+                    icon_rect.left,
+                    icon_rect.top,
+                    win32_window_icon(handle),
+                    icon_rect.right - icon_rect.left,
+                    icon_rect.bottom - icon_rect.top,
+                    0,
+                    None,
+                    DI_NORMAL,
+                ) {
+                    eprintln!("Failed to draw caption icon: {}", e.message());
+                }
+            }
+
+            // add padding to the left (title) and right (buttons)
+            let text_padding = 10;
+            let caption_button_layout = win32_caption_button_layout(handle, state, &title_bar_rect);
+            // At the minimum width the title is squeezed out first, an empty rect
+            // draws nothing
+            let text_left = match (
+                win32_menu_bar_right(handle, state, &title_bar_rect),
+                caption_icon_rect,
+            ) {
+                (Some(menu_bar_right), _) => menu_bar_right + text_padding,
+                (None, Some(icon_rect)) => {
+                    icon_rect.right
+                        + win32_dpi_scale(
+                            win32_caption_icon_padding(state).unwrap_or_default(),
+                            dpi,
+                        )
+                }
+                (None, None) => caption_button_layout.leading_right + text_padding,
+            };
+            let mut titlebar_text_rect = RECT {
+                left: text_left,
+                right: (caption_button_layout.content_right - text_padding).max(text_left),
+                ..title_bar_rect
+            };
+
+            // Tabs and the breadcrumb take the title's place. Too narrow for the
+            // title's minimum, it goes instead of showing a sliver.
+            let title_min_width = win32_dpi_scale(WIN32_TITLE_MIN_WIDTH, dpi);
+            if !state.tabs.is_empty()
+                || !state.breadcrumb.is_empty()
+                || !caption_button_layout.title_fits
+                || titlebar_text_rect.right - titlebar_text_rect.left < title_min_width
+            {
+                titlebar_text_rect.right = titlebar_text_rect.left;
+            }
+            state.title_rect = titlebar_text_rect;
+
+            // The title badge goes right after the title, its room comes off the title
+            let title_badge_width = state
+                .badges
+                .title_badge_width(dpi)
+                .filter(|_| titlebar_text_rect.right > titlebar_text_rect.left);
+            if let Some(width) = title_badge_width {
+                titlebar_text_rect.right =
+                    (titlebar_text_rect.right - width).max(titlebar_text_rect.left);
+            }
+
+            // The title goes through a 32bpp buffer primed with an opaque copy of the caption
+            // background. With DTT_COMPOSITED the text keeps a correct alpha channel and its
+            // antialiasing blends against the real background color, GDI drawing straight on
+            // the custom brush is what produced the color fringes.
+            let paint_params = BP_PAINTPARAMS {
+                cbSize: size_of::<BP_PAINTPARAMS>() as u32,
+                ..Default::default()
+            };
+            let mut buffer_hdc = HDC::default();
+            let buffered_paint = BeginBufferedPaint(
+                hdc,
+                &titlebar_text_rect,
+                BPBF_TOPDOWNDIB,
+                Some(&paint_params),
+                &mut buffer_hdc,
+            );
+
+            let text_hdc = if buffered_paint != 0 {
+                win32_fill_caption_background(
+                    buffer_hdc,
+                    state,
+                    &titlebar_text_rect,
+                    &title_bar_rect,
+                    titlebar_color,
+                );
+
+                // GDI leaves the alpha at 0, the backdrop itself is opaque
+                if let Err(e) = BufferedPaintSetAlpha(buffered_paint, None, 255) {
+                    eprintln!("Failed to set buffered paint alpha: {}", e.message());
+                }
+
+                buffer_hdc
+            } else {
+                hdc
+            };
+
+            let old_font = if theme_font != HFONT(0) {
+                HFONT(SelectObject(text_hdc, theme_font).0)
+            } else {
+                HFONT(0)
+            };
+
+            // With a subtitle the title sits on the middle line, the subtitle hangs below
+            // it and each is cut off on its own
+            let subtitle_rect = state.subtitle.is_some().then(|| {
+                let middle = (titlebar_text_rect.top + titlebar_text_rect.bottom) / 2;
+                let subtitle_rect = RECT {
+                    top: middle,
+                    ..titlebar_text_rect
+                };
+                titlebar_text_rect.bottom = middle;
+                subtitle_rect
+            });
+            let title_line = if subtitle_rect.is_some() {
+                DT_BOTTOM
+            } else {
+                DT_VCENTER
+            };
+
+            let title_text = &title_text_buffer[..];
+            let mut title_size = SIZE::default();
+            GetTextExtentPoint32W(text_hdc, title_text, &mut title_size);
+
+            // Fading and scrolling work on the buffer, plain GDI keeps the ellipsis
+            let title_overflow = if buffered_paint != 0
+                && titlebar_text_rect.right > titlebar_text_rect.left
+                && title_size.cx > titlebar_text_rect.right - titlebar_text_rect.left
+            {
+                match state.options.title_overflow {
+                    // Held still, the faded edge still says there is more
+                    TitleOverflow::Marquee if state.reduced_motion => TitleOverflow::Fade,
+                    title_overflow => title_overflow,
+                }
+            } else {
+                TitleOverflow::Ellipsis
+            };
+
+            // Only a title that fits is centered, one that doesn't is cut off the same
+            // either way
+            let centers_title = state.options.title_alignment == TitleAlignment::Center;
+            if centers_title && title_overflow == TitleOverflow::Ellipsis {
+                titlebar_text_rect =
+                    win32_centered_rect(&titlebar_text_rect, &title_bar_rect, title_size.cx);
+            }
+
+            let draw_theme_options = DTTOPTS {
+                dwSize: size_of::<DTTOPTS>() as u32,
+                dwFlags: if buffered_paint != 0 {
+                    DTT_TEXTCOLOR | DTT_COMPOSITED
+                } else {
+                    DTT_TEXTCOLOR
+                },
+                crText: titlebar_item_color,
+                ..Default::default()
+            };
+
+            let draw_title = |rect: &mut RECT, format| {
+                if let Err(e) = DrawThemeTextEx(
+                    theme,
+                    text_hdc,
+                    WP_CAPTION.0,
+                    CS_ACTIVE.0,
+                    title_text,
+                    format,
+                    rect,
+                    Some(&draw_theme_options),
+                ) {
+                    eprintln!("Failed to draw theme text: {}", e.message());
+                };
+            };
+
+            // Draw title text
+            match title_overflow {
+                TitleOverflow::Ellipsis => draw_title(
+                    &mut titlebar_text_rect,
+                    title_line | DT_SINGLELINE | DT_WORD_ELLIPSIS,
+                ),
+                TitleOverflow::Fade => {
+                    draw_title(&mut titlebar_text_rect, title_line | DT_SINGLELINE);
+                    win32_fade_out_buffer_edge(
+                        buffered_paint,
+                        &titlebar_text_rect,
+                        win32_dpi_scale(WIN32_TITLE_FADE_WIDTH, dpi),
+                        titlebar_color,
+                    );
+                }
+                TitleOverflow::Marquee => {
+                    let period = title_size.cx + win32_dpi_scale(WIN32_MARQUEE_GAP, dpi);
+                    state.marquee_offset %= period;
+
+                    // The buffer clips whatever scrolls out of the text rect
+                    for repetition in 0..2 {
+                        let left =
+                            titlebar_text_rect.left - state.marquee_offset + repetition * period;
+                        let mut repetition_rect = RECT {
+                            left,
+                            right: left + title_size.cx,
+                            ..titlebar_text_rect
+                        };
+                        draw_title(&mut repetition_rect, title_line | DT_SINGLELINE);
+                    }
+                }
+            }
+
+            if let (Some(mut subtitle_rect), Some(subtitle)) = (subtitle_rect, &state.subtitle) {
+                let subtitle_font = win32_subtitle_font(dpi);
+                let previous_font = if subtitle_font != HFONT(0) {
+                    HFONT(SelectObject(text_hdc, subtitle_font).0)
+                } else {
+                    HFONT(0)
+                };
+
+                if centers_title {
+                    let mut subtitle_size = SIZE::default();
+                    GetTextExtentPoint32W(text_hdc, subtitle, &mut subtitle_size);
+                    subtitle_rect =
+                        win32_centered_rect(&subtitle_rect, &title_bar_rect, subtitle_size.cx);
+                }
+
+                let subtitle_options = DTTOPTS {
+                    crText: win32_blend_color(
+                        titlebar_item_color,
+                        titlebar_color,
+                        WIN32_SUBTITLE_DIMMING,
+                    ),
+                    ..draw_theme_options
+                };
+                if let Err(e) = DrawThemeTextEx(
+                    theme,
+                    text_hdc,
+                    WP_CAPTION.0,
+                    CS_ACTIVE.0,
+                    subtitle,
+                    DT_TOP | DT_SINGLELINE | DT_WORD_ELLIPSIS,
+                    &mut subtitle_rect,
+                    Some(&subtitle_options),
+                ) {
+                    eprintln!("Failed to draw theme text: {}", e.message());
+                }
+
+                if previous_font != HFONT(0) {
+                    SelectObject(text_hdc, previous_font);
+                }
+            }
+
+            let title_end = title_badge_width.map(|_| RECT {
+                left: (titlebar_text_rect.left + title_size.cx).min(titlebar_text_rect.right),
+                ..titlebar_text_rect
+            });
+
+            win32_update_marquee(
+                handle,
+                state,
+                (title_overflow == TitleOverflow::Marquee).then_some(titlebar_text_rect),
+            );
+
+            if old_font != HFONT(0) {
+                SelectObject(text_hdc, old_font);
+            }
+
+            if buffered_paint != 0 {
+                if let Err(e) = EndBufferedPaint(buffered_paint, true) {
+                    eprintln!("Failed to end buffered paint: {}", e.message());
+                }
+            }
+
+            win32_paint_tab_strip(hdc, handle, state, &title_bar_rect, titlebar_item_color);
+            win32_paint_breadcrumb(hdc, handle, state, &title_bar_rect, titlebar_item_color);
+            win32_paint_menu_bar(hdc, handle, state, &title_bar_rect, titlebar_item_color);
+            win32_paint_caption_buttons(
+                hdc,
+                handle,
+                state,
+                &caption_button_layout,
+                titlebar_color,
+                titlebar_item_color,
+                titlebar_hover_color,
+                titlebar_pressed_color,
+                disabled_item_color,
+                glyph_painter.as_mut(),
+            );
+            // Unless the painter was replaced meanwhile
+            if state.glyph_painter.is_none() {
+                state.glyph_painter = glyph_painter;
+            }
+            if let Some(avatar) = &state.avatar {
+                win32_paint_avatar(hdc, avatar, &caption_button_layout);
+            }
+            if let (Some(search_box), Some(field)) =
+                (&state.search_box, caption_button_layout.search_field)
+            {
+                win32_paint_search_field(hdc, handle, search_box, &field, titlebar_item_color);
+            }
+            if let (Some(label), Some(label_rect)) =
+                (&state.caption_label, caption_button_layout.caption_label)
+            {
+                win32_paint_caption_label(hdc, handle, label, &label_rect, titlebar_item_color);
+            }
+            if let (Some(led), Some(led_rect)) =
+                (&state.status_led, caption_button_layout.status_led)
+            {
+                win32_paint_status_led(hdc, handle, led, &led_rect, titlebar_color);
+            }
+            win32_paint_badges(
+                hdc,
+                handle,
+                &mut state.badges,
+                &caption_button_layout,
+                title_end,
+            );
+
+            if let Err(e) = CloseThemeData(theme) {
+                eprintln!("Failed to close theme data: {}", e.message());
+            };
+
+            // Paint fake top shadow. Original is missing because of the client rect extension.
+            // You might need to tweak the colors here based on the color scheme of your app
+            // or just remove it if you decide it is not worth it.
+            let shadow_color = COLORREF(rgb(100, 100, 100));
+            let fake_top_shadow_color = if is_active {
+                shadow_color
+            } else {
+                let titlebar_color_value = titlebar_color.0;
+                let shadow_color_value = shadow_color.0;
+                COLORREF(rgb(
+                    ((get_r_value(titlebar_color_value) as u32
+                        + get_r_value(shadow_color_value) as u32)
+                        / 2) as u8,
+                    ((get_g_value(titlebar_color_value) as u32
+                        + get_g_value(shadow_color_value) as u32)
+                        / 2) as u8,
+                    ((get_b_value(titlebar_color_value) as u32
+                        + get_b_value(shadow_color_value) as u32)
+                        / 2) as u8,
+                ))
+            };
+
+            let result = win32_fake_shadow_rect(handle);
+            if result.is_err() {
+                eprintln!("Failed to get fake shadow rect:\n{}", result.err().unwrap());
+                return None;
+            }
+
+            let fake_top_shadow_rect = result.unwrap();
+            let fake_top_shadow_brush = CreateSolidBrush(fake_top_shadow_color);
+            if !state.hides_caption() {
+                FillRect(hdc, &fake_top_shadow_rect, fake_top_shadow_brush);
+            }
+            DeleteObject(fake_top_shadow_brush);
+        }
+        // Track when mouse hovers each of the title bar buttons to draw the highlight correctly
+        WM_NCMOUSEMOVE => {
+            // Moving straight off the window from the title bar (e.g. to a monitor above)
+            // doesn't send WM_MOUSEMOVE, only a WM_NCMOUSELEAVE when asked for it
+            if !state.tracking_nonclient_leave {
+                let mut track_mouse_event = TRACKMOUSEEVENT {
+                    cbSize: size_of::<TRACKMOUSEEVENT>() as u32,
+                    dwFlags: TME_NONCLIENT | TME_LEAVE,
+                    hwndTrack: handle,
+                    ..Default::default()
+                };
+
+                match TrackMouseEvent(&mut track_mouse_event) {
+                    Ok(()) => state.tracking_nonclient_leave = true,
+                    Err(e) => eprintln!("Failed to track mouse leave: {}", e.message()),
+                }
+            }
+
+            // Where the message says, the cursor may already be on another monitor
+            let cursor_point = win32_screen_to_client(handle, l_param);
+
+            let result = win32_titlebar_rect(handle, state);
+            if result.is_err() {
+                eprintln!("Failed to get title bar rect:\n{}", result.err().unwrap());
+                return None;
+            }
+
+            let title_bar_rect = result.unwrap();
+            let button_rects = CustomTitleBarButtonRects::win32_get_title_bar_button_rects(
+                handle,
+                state,
+                &title_bar_rect,
+            );
+            let new_hovered_button = button_rects.button_at(cursor_point);
+
+            if state.tooltip != HWND(0) {
+                for (button, rect) in [
+                    (CustomTitleBarHoveredButton::Shade, button_rects.shade),
+                    (CustomTitleBarHoveredButton::Minimize, button_rects.minimize),
+                    (CustomTitleBarHoveredButton::Maximize, button_rects.maximize),
+                    (CustomTitleBarHoveredButton::Close, button_rects.close),
+                ] {
+                    // The flyout takes the place of the tooltip
+                    let rect =
+                        if state.snap_layouts && button == CustomTitleBarHoveredButton::Maximize {
+                            RECT::default()
+                        } else {
+                            rect
+                        };
+
+                    let id = button.index().unwrap() + 1;
+                    win32_set_tool_rect(state.tooltip, handle, id, rect);
+                }
+                if state.status_led.is_some() {
+                    let rect = win32_status_led_rect(handle, state).unwrap_or_default();
+                    win32_set_tool_rect(state.tooltip, handle, WIN32_STATUS_LED_TOOL_ID, rect);
+                }
+
+                win32_relay_mouse_move(state.tooltip, handle, cursor_point);
+            }
+
+            if title_bar_hovered_button != new_hovered_button {
+                // You could do tighter invalidation here but probably doesn't matter
+                win32_invalidate_rect(handle, state, &button_rects.close);
+                win32_invalidate_rect(handle, state, &button_rects.minimize);
+                win32_invalidate_rect(handle, state, &button_rects.maximize);
+                win32_invalidate_rect(handle, state, &button_rects.shade);
+
+                win32_set_hovered_button(handle, state, new_hovered_button);
+            }
+
+            return None;
+        }
+        // The tooltip asks for the text every time so Maximize/Restore is always current
+        WM_NOTIFY => {
+            let header = l_param.0 as *const NMHDR;
+            if header.is_null()
+                || (*header).hwndFrom != state.tooltip
+                || (*header).code != TTN_GETDISPINFOW
+            {
+                return None;
+            }
+
+            // Caption tooltips were answered in win32_answering_callbacks
+            let display_info = l_param.0 as *mut NMTTDISPINFOW;
+            if let (WIN32_STATUS_LED_TOOL_ID, Some(led)) = ((*header).idFrom, &state.status_led) {
+                (*display_info).lpszText = PWSTR(led.tooltip_text().as_ptr() as _);
+                return Some(LRESULT(0));
+            }
+            if let Some(text) = state
+                .caption_buttons
+                .tooltip((*header).idFrom, &state.caption_button_names)
+            {
+                (*display_info).lpszText = PWSTR(text.as_ptr() as _);
+                return Some(LRESULT(0));
+            }
+
+            let button = CAPTION_BUTTONS
+                .get((*header).idFrom.wrapping_sub(1))
+                .copied()
+                .unwrap_or_default();
+            let is_maximized = win32_window_is_maximized(handle);
+            let is_shaded = state.shade_restore_height.is_some();
+            (*display_info).lpszText = PWSTR(
+                state
+                    .caption_button_names
+                    .get(button, is_maximized, is_shaded)
+                    .as_ptr() as _,
+            );
+
+            return Some(LRESULT(0));
+        }
+        WM_NCMOUSELEAVE => {
+            state.tracking_nonclient_leave = false;
+            // Left the caption for another monitor, WM_MOUSEMOVE won't hide it
+            if state.hides_caption()
+                && state.pressed_button == CustomTitleBarHoveredButton::None
+                && state.caption_reveal.target() > 0.0
+            {
+                let mut cursor_point = POINT::default();
+                let _ = GetCursorPos(&mut cursor_point);
+                if WindowFromPoint(cursor_point) != handle {
+                    win32_reveal_caption(handle, state, false);
+                }
+            }
+
+            if state.tooltip != HWND(0) {
+                win32_hide_tooltip(state.tooltip);
+            }
+
+            // Capturing the mouse for a press also makes it leave the non client area,
+            // hovering is tracked through WM_MOUSEMOVE until the release
+            if state.pressed_button != CustomTitleBarHoveredButton::None {
+                return None;
+            }
+
+            if title_bar_hovered_button != CustomTitleBarHoveredButton::None {
+                win32_set_hovered_button(handle, state, CustomTitleBarHoveredButton::None);
+                win32_invalidate_caption_buttons(handle, state);
+            }
+
+            return None;
+        }
+        // Sticks the window to nearby monitor and window edges while moving or sizing
+        WM_MOVING if state.options.snap_distance > 0 => {
+            let moving_rect = &mut *(l_param.0 as *mut RECT);
+            win32_snap_moving_rect(handle, moving_rect, state.options.snap_distance);
+            return Some(LRESULT(1));
+        }
+        WM_SIZING if state.options.snap_distance > 0 => {
+            let sizing_rect = &mut *(l_param.0 as *mut RECT);
+            win32_snap_sizing_rect(
+                handle,
+                sizing_rect,
+                w_param.0 as u32,
+                state.options.snap_distance,
+            );
+            return Some(LRESULT(1));
+        }
+        // Dragging the caption without the system move loop, see caption_drag_offset
+        WM_MOUSEMOVE if state.caption_drag_offset.is_some() => {
+            let drag_offset = state.caption_drag_offset.unwrap();
+            let mut cursor_point = POINT::default();
+            if let Err(e) = GetCursorPos(&mut cursor_point) {
+                eprintln!("Failed to get cursor position: {}", e.message());
+                return Some(LRESULT(0));
+            }
+
+            let mut window_rect = RECT::default();
+            if let Err(e) = GetWindowRect(handle, &mut window_rect) {
+                eprintln!("Failed to get window rect: {}", e.message());
+                return Some(LRESULT(0));
+            }
+
+            // No WM_MOVING without the system move loop, the snapping is done here
+            let mut moved_rect = RECT {
+                left: cursor_point.x - drag_offset.x,
+                top: cursor_point.y - drag_offset.y,
+                right: cursor_point.x - drag_offset.x + window_rect.right - window_rect.left,
+                bottom: cursor_point.y - drag_offset.y + window_rect.bottom - window_rect.top,
+            };
+            if state.options.snap_distance > 0 {
+                win32_snap_moving_rect(handle, &mut moved_rect, state.options.snap_distance);
+            }
+
+            win32_once_released(state, move || {
+                if let Err(e) = unsafe {
+                    SetWindowPos(
+                        handle,
+                        None,
+                        moved_rect.left,
+                        moved_rect.top,
+                        0,
+                        0,
+                        SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE,
+                    )
+                } {
+                    eprintln!("Failed to set window position:\n{}", e.message());
+                }
+            });
+
+            return Some(LRESULT(0));
+        }
+        WM_LBUTTONUP if state.caption_drag_offset.is_some() => {
+            state.caption_drag_offset = None;
+            win32_release_capture(state);
+
+            return Some(LRESULT(0));
+        }
+        WM_CAPTURECHANGED if state.caption_drag_offset.is_some() => {
+            state.caption_drag_offset = None;
+            return Some(LRESULT(0));
+        }
+        // Picture in picture shows its caption while the cursor is anywhere over the window
+        WM_MOUSEMOVE
+            if state.picture_in_picture_restore.is_some()
+                && state.pressed_button == CustomTitleBarHoveredButton::None =>
+        {
+            win32_track_client_leave(handle, state);
+
+            if state.caption_reveal.target() == 0.0 {
+                win32_reveal_caption(handle, state, true);
+            }
+
+            if title_bar_hovered_button != CustomTitleBarHoveredButton::None {
+                win32_set_hovered_button(handle, state, CustomTitleBarHoveredButton::None);
+                win32_invalidate_caption_buttons(handle, state);
+            }
+
+            return None;
+        }
+        // Also sent when the cursor moves from the content onto the caption
+        WM_MOUSELEAVE => {
+            state.tracking_client_leave = false;
+            if state.picture_in_picture_restore.is_some()
+                && state.pressed_button == CustomTitleBarHoveredButton::None
+            {
+                let mut cursor_point = POINT::default();
+                let _ = GetCursorPos(&mut cursor_point);
+                if WindowFromPoint(cursor_point) != handle {
+                    win32_reveal_caption(handle, state, false);
+                }
+            }
+
+            return Some(LRESULT(0));
+        }
+        // The fullscreen caption comes back after resting at the top edge and goes away
+        // again once the cursor moves down into the content
+        WM_MOUSEMOVE
+            if state.fullscreen_restore.is_some()
+                && state.pressed_button == CustomTitleBarHoveredButton::None =>
+        {
+            let reveal_zone = win32_dpi_scale(WIN32_CAPTION_REVEAL_ZONE, win32_window_dpi(handle));
+            let is_revealing = state.caption_reveal.target() > 0.0;
+            if get_y_param(l_param) < reveal_zone.max(1) {
+                if !is_revealing {
+                    SetTimer(
+                        handle,
+                        WIN32_CAPTION_REVEAL_TIMER_ID,
+                        WIN32_CAPTION_REVEAL_DELAY_MS,
+                        None,
+                    );
+                }
+            } else {
+                let _ = KillTimer(handle, WIN32_CAPTION_REVEAL_TIMER_ID);
+                if is_revealing {
+                    win32_reveal_caption(handle, state, false);
+                }
+            }
+
+            if title_bar_hovered_button != CustomTitleBarHoveredButton::None {
+                win32_set_hovered_button(handle, state, CustomTitleBarHoveredButton::None);
+                win32_invalidate_caption_buttons(handle, state);
+            }
+
+            return None;
+        }
+        // If the mouse gets into the client area then no title bar buttons are hovered
+        // so need to reset the hover state
+        WM_MOUSEMOVE => {
+            // While a caption button is pressed the mouse is captured, keep tracking
+            // whether the cursor is still over it so sliding off shows it released
+            if state.pressed_button != CustomTitleBarHoveredButton::None {
+                let cursor_point = POINT {
+                    x: get_x_param(l_param),
+                    y: get_y_param(l_param),
+                };
+
+                match win32_get_button_rects(handle, state) {
+                    Ok(button_rects) => {
+                        let new_hovered_button = button_rects.button_at(cursor_point);
+                        if title_bar_hovered_button != new_hovered_button {
+                            win32_set_hovered_button(handle, state, new_hovered_button);
+                            win32_invalidate_caption_buttons(handle, state);
+                        }
+                    }
+                    Err(e) => eprintln!("Failed to get title bar rect:\n{}", e),
+                }
+
+                return None;
+            }
+
+            if title_bar_hovered_button != CustomTitleBarHoveredButton::None {
+                let result = win32_titlebar_rect(handle, state);
+                if result.is_err() {
+                    eprintln!("Failed to get title bar rect:\n{}", result.err().unwrap());
+                    return None;
+                }
+
+                let title_bar_rect = result.unwrap();
+                // You could do tighter invalidation here but probably doesn't matter
+                win32_invalidate_rect(handle, state, &title_bar_rect);
+                win32_set_hovered_button(handle, state, CustomTitleBarHoveredButton::None);
+            }
+
+            return None;
+        }
+        WM_NCLBUTTONDOWN if w_param.0 as u32 == HTSYSMENU => {
+            if state.options.icon_double_click_closes {
+                // Wait and see whether this becomes a double click before opening the menu
+                SetTimer(
+                    handle,
+                    WIN32_SYSTEM_MENU_TIMER_ID,
+                    GetDoubleClickTime(),
+                    None,
+                );
+            } else {
+                win32_show_icon_system_menu(handle, state);
+            }
+
+            return Some(LRESULT(0));
+        }
+        WM_NCLBUTTONDBLCLK
+            if state.options.border_double_click_maximizes_vertically
+                && matches!(w_param.0 as u32, HTTOP | HTBOTTOM) =>
+        {
+            win32_once_released(state, move || {
+                if let Err(e) = win32_toggle_vertical_maximize(handle) {
+                    eprintln!("Failed to maximize vertically:\n{}", e);
+                }
+            });
+
+            return Some(LRESULT(0));
+        }
+        WM_NCLBUTTONDBLCLK
+            if state.options.caption_double_click_shades
+                && w_param.0 as u32 == HTCAPTION
+                && title_bar_hovered_button == CustomTitleBarHoveredButton::None
+                && !win32_window_is_maximized(handle)
+                && state.fullscreen_restore.is_none() =>
+        {
+            let shaded = state.shade_restore_height.is_none();
+            win32_once_released(state, move || {
+                if let Err(e) = win32_set_shaded(handle, shaded) {
+                    eprintln!("Failed to roll the window up:\n{}", e);
+                }
+            });
+
+            return Some(LRESULT(0));
+        }
+        WM_NCLBUTTONDBLCLK if w_param.0 as u32 == HTSYSMENU => {
+            if state.options.icon_double_click_closes {
+                let _ = KillTimer(handle, WIN32_SYSTEM_MENU_TIMER_ID);
+                if let Err(e) =
+                    PostMessageW(handle, WM_SYSCOMMAND, WPARAM(SC_CLOSE as _), LPARAM(0))
+                {
+                    eprintln!("Failed to post message: {}", e.message());
+                }
+            }
+
+            return Some(LRESULT(0));
+        }
+        WM_TIMER if w_param.0 == WIN32_SYSTEM_MENU_TIMER_ID => {
+            let _ = KillTimer(handle, WIN32_SYSTEM_MENU_TIMER_ID);
+            win32_show_icon_system_menu(handle, state);
+
+            return Some(LRESULT(0));
+        }
+        WM_NCLBUTTONDOWN
+            if state.options.disable_aero_shake
+                && w_param.0 as u32 == HTCAPTION
+                && title_bar_hovered_button == CustomTitleBarHoveredButton::None
+                && !win32_window_is_maximized(handle) =>
+        {
+            // Restoring a maximized window by dragging it still needs the system loop
+            let mut window_rect = RECT::default();
+            if let Err(e) = GetWindowRect(handle, &mut window_rect) {
+                eprintln!("Failed to get window rect:\n{}", e.message());
+                return None;
+            }
+
+            state.caption_drag_offset = Some(POINT {
+                x: get_x_param(l_param) - window_rect.left,
+                y: get_y_param(l_param) - window_rect.top,
+            });
+            win32_set_capture(handle, state);
+            return Some(LRESULT(0));
+        }
+        // A double click on a button is just a second press, DefWindowProc would
+        // maximize the window for it like on the rest of the caption
+        WM_NCLBUTTONDOWN | WM_NCLBUTTONDBLCLK => {
+            // Clicks on buttons will be handled on button up, but we still need
+            // to remove default handling of the click to avoid it counting as drag.
+            //
+            // The mouse is captured until then, so releasing it anywhere (even outside
+            // the window) reaches us as WM_LBUTTONUP and the press can be cancelled
+            // when the cursor moved off the button.
+            //
+            // HTMAXBUTTON must never reach DefWindowProc either, it would run the
+            // classic button tracking loop and paint an old style button over ours.
+            let pressed_button = if w_param.0 as u32 == HTMAXBUTTON {
+                CustomTitleBarHoveredButton::Maximize
+            } else {
+                title_bar_hovered_button
+            };
+
+            if pressed_button != CustomTitleBarHoveredButton::None {
+                let press_origin = win32_screen_to_client(handle, l_param);
+
+                win32_begin_caption_button_press(handle, state, pressed_button, press_origin);
+                win32_set_capture(handle, state);
+                return Some(LRESULT(0));
+            }
+
+            // Default handling allows for dragging and double click to maximize
+            return None;
+        }
+        WM_LBUTTONUP if state.pressed_button != CustomTitleBarHoveredButton::None => {
+            let pressed_button = state.pressed_button;
+            // Cleared first so the WM_CAPTURECHANGED from releasing isn't taken as a cancel
+            win32_set_pressed_button(handle, state, CustomTitleBarHoveredButton::None);
+            win32_release_capture(state);
+
+            let cursor_point = POINT {
+                x: get_x_param(l_param),
+                y: get_y_param(l_param),
+            };
+
+            let released_on = win32_caption_button_at(handle, state, cursor_point);
+            win32_set_hovered_button(handle, state, released_on);
+            win32_end_caption_button_press(handle, state, pressed_button, released_on);
+            return Some(LRESULT(0));
+        }
+        // Touch and pen. Without these, a tap arrives as a WM_NCLBUTTONDOWN with no
+        // WM_NCMOUSEMOVE before it, nothing is hovered yet and the tap becomes a drag.
+        // Mouse input never gets here, it isn't routed through pointer messages.
+        WM_NCPOINTERDOWN | WM_POINTERDOWN if win32_is_touch_or_pen(w_param) => {
+            let client_point = win32_screen_to_client(handle, l_param);
+
+            let button = win32_caption_button_at(handle, state, client_point);
+            if button == CustomTitleBarHoveredButton::None
+                || state.pressed_button != CustomTitleBarHoveredButton::None
+            {
+                // Promoted to mouse messages, which drag the caption as usual
+                return None;
+            }
+
+            state.pointer_press = Some(win32_pointer_id(w_param));
+            win32_begin_caption_button_press(handle, state, button, client_point);
+            return Some(LRESULT(0));
+        }
+        // Contacts are captured implicitly, the updates keep coming after sliding off
+        WM_NCPOINTERUPDATE | WM_POINTERUPDATE
+            if state.pointer_press == Some(win32_pointer_id(w_param)) =>
+        {
+            let client_point = win32_screen_to_client(handle, l_param);
+
+            let new_hovered_button = win32_caption_button_at(handle, state, client_point);
+            if title_bar_hovered_button != new_hovered_button {
+                win32_set_hovered_button(handle, state, new_hovered_button);
+                win32_invalidate_caption_buttons(handle, state);
+            }
+
+            return Some(LRESULT(0));
+        }
+        WM_NCPOINTERUP | WM_POINTERUP if state.pointer_press == Some(win32_pointer_id(w_param)) => {
+            let pressed_button = state.pressed_button;
+            state.pointer_press = None;
+            win32_set_pressed_button(handle, state, CustomTitleBarHoveredButton::None);
+
+            let client_point = win32_screen_to_client(handle, l_param);
+
+            // Fingers don't hover, nothing stays highlighted after lifting
+            let released_on = win32_caption_button_at(handle, state, client_point);
+            win32_set_hovered_button(handle, state, CustomTitleBarHoveredButton::None);
+            win32_end_caption_button_press(handle, state, pressed_button, released_on);
+            return Some(LRESULT(0));
+        }
+        WM_POINTERCAPTURECHANGED if state.pointer_press.is_some() => {
+            state.pointer_press = None;
+            win32_set_pressed_button(handle, state, CustomTitleBarHoveredButton::None);
+            win32_set_hovered_button(handle, state, CustomTitleBarHoveredButton::None);
+            win32_invalidate_caption_buttons(handle, state);
+            return Some(LRESULT(0));
+        }
+        WM_CAPTURECHANGED if state.pressed_button != CustomTitleBarHoveredButton::None => {
+            // Something else took the mouse (a menu, Alt+Tab...), the press is cancelled
+            win32_set_pressed_button(handle, state, CustomTitleBarHoveredButton::None);
+            win32_invalidate_caption_buttons(handle, state);
+            return Some(LRESULT(0));
+        }
+        // The system buttons hit test as HTCAPTION too, only the bare caption closes
+        WM_NCMBUTTONUP
+            if state.options.middle_click_closes
+                && w_param.0 as u32 == HTCAPTION
+                && state.hovered_button == CustomTitleBarHoveredButton::None =>
+        {
+            if let Err(e) = PostMessageW(handle, WM_CLOSE, WPARAM(0), LPARAM(0)) {
+                eprintln!("Failed to post message: {}", e.message());
+                return None;
+            }
+
+            return Some(LRESULT(0));
+        }
+        // Alt+Space, DefWindowProc would pop the menu where the default caption was
+        WM_SYSCOMMAND if (w_param.0 as u32 & 0xfff0) == SC_KEYMENU && l_param.0 == ' ' as isize => {
+            let result = win32_titlebar_rect(handle, state);
+            if result.is_err() {
+                eprintln!("Failed to get title bar rect:\n{}", result.err().unwrap());
+                return None;
+            }
+
+            // Right under our caption, aligned with the left edge of the window
+            let title_bar_rect = result.unwrap();
+            let mut menu_point = POINT {
+                x: title_bar_rect.left,
+                y: title_bar_rect.bottom,
+            };
+            ClientToScreen(handle, &mut menu_point);
+
+            win32_show_system_menu_once_released(handle, state, menu_point);
+            return Some(LRESULT(0));
+        }
+        WM_NCRBUTTONUP if matches!(w_param.0 as u32, HTCAPTION | HTSYSMENU) => {
+            let cursor_point = POINT {
+                x: get_x_param(l_param),
+                y: get_y_param(l_param),
+            };
+
+            win32_show_system_menu_once_released(handle, state, cursor_point);
+            return Some(LRESULT(0));
+        }
+        // Without the capture (e.g. it was taken away) a button up still has to be a
+        // release on the button the press started on
+        WM_NCLBUTTONUP => {
+            let pressed_button = state.pressed_button;
+            if pressed_button != CustomTitleBarHoveredButton::None {
+                win32_set_pressed_button(handle, state, CustomTitleBarHoveredButton::None);
+                win32_invalidate_caption_buttons(handle, state);
+            }
+
+            if pressed_button == CustomTitleBarHoveredButton::None
+                || pressed_button != title_bar_hovered_button
+            {
+                if w_param.0 as u32 == HTMAXBUTTON {
+                    return Some(LRESULT(0));
+                }
+
+                return None;
+            }
+
+            win32_caption_button_clicked(handle, state, pressed_button);
+            return Some(LRESULT(0));
+        }
+        // Only for our own window, children pick their cursor themselves
+        WM_SETCURSOR if HWND(w_param.0 as _) == handle => {
+            let hit = (l_param.0 & 0xffff) as u32;
+            if let Some(&(_, cursor)) = state
+                .region_cursors
+                .iter()
+                .find(|&&(region, _)| region == hit)
+            {
+                SetCursor(cursor);
+                return Some(LRESULT(1));
+            }
+
+            // The borders keep the sizing cursors DefWindowProc picks for them
+            if hit != HTCLIENT {
+                return None;
+            }
+
+            // Show an arrow instead of the busy cursor
+            let result = LoadCursorW(None, IDC_ARROW);
+            if result.is_err() {
+                eprintln!("Failed to load cursor: {}", result.err().unwrap().message());
+                return None;
+            }
+
+            let cursor = result.unwrap();
+            SetCursor(cursor);
+            return Some(LRESULT(1));
+        }
+        WM_XBUTTONDOWN | WM_XBUTTONUP | WM_NCXBUTTONDOWN | WM_NCXBUTTONUP => {
+            let button = match ((w_param.0 >> 16) & 0xffff) as u16 {
+                XBUTTON1 => MouseXButton::Back,
+                XBUTTON2 => MouseXButton::Forward,
+                _ => return None,
+            };
+
+            let event = if matches!(message, WM_XBUTTONDOWN | WM_NCXBUTTONDOWN) {
+                WindowEvent::XButtonDown(button)
+            } else {
+                WindowEvent::XButtonUp(button)
+            };
+            win32_dispatch_event(handle, event);
+
+            // Handled, DefWindowProc would turn it into a WM_APPCOMMAND as well
+            return Some(LRESULT(1));
+        }
+        // F6 / Shift+F6 cycle the focus regions. In the caption, arrows pick a button,
+        // Enter or Space presses it and Escape goes back to the content.
+        WM_KEYDOWN => {
+            let key = VIRTUAL_KEY(w_param.0 as u16);
+            let focus = win32_caption_focus(state);
+            match key {
+                VK_T | VK_G
+                    if state.options.tiling_shortcuts
+                        && GetKeyState(VK_CONTROL.0 as i32) < 0
+                        && GetKeyState(VK_MENU.0 as i32) < 0 =>
+                {
+                    let layout = if key == VK_T {
+                        TileLayout::SideBySide
+                    } else {
+                        TileLayout::Grid
+                    };
+                    // Moves this window too, so after the state is let go of
+                    win32_defer(move || {
+                        if let Err(e) = win32_tile_windows(handle, layout) {
+                            eprintln!("Failed to tile windows:\n{}", e);
+                        }
+                    });
+                }
+                VK_F6 => {
+                    let direction = if GetKeyState(VK_SHIFT.0 as i32) < 0 {
+                        -1
+                    } else {
+                        1
+                    };
+                    let region = state.focus_regions.next(direction);
+                    win32_set_focus_region(handle, state, region);
+                }
+                VK_ESCAPE if state.focus_regions.current() != FocusRegion::Content => {
+                    win32_set_focus_region(handle, state, FocusRegion::Content);
+                }
+                VK_ESCAPE if state.options.kind == WindowKind::Dialog => {
+                    if let Err(e) = PostMessageW(handle, WM_CLOSE, WPARAM(0), LPARAM(0)) {
+                        eprintln!("Failed to post message: {}", e.message());
+                    }
+                }
+                VK_LEFT | VK_RIGHT if focus.is_some() => {
+                    let direction = if key == VK_LEFT { -1 } else { 1 };
+                    let next = win32_neighbor_focus_stop(handle, state, direction);
+                    win32_set_caption_focus(handle, state, next);
+                }
+                VK_RETURN | VK_SPACE if focus.is_some() => match focus.unwrap() {
+                    CaptionFocusStop::System(button) => {
+                        win32_caption_button_clicked(handle, state, button);
+                    }
+                    CaptionFocusStop::Added(id) => win32_click_caption_button(handle, id),
+                },
+                _ => {
+                    win32_dispatch_event(handle, WindowEvent::KeyDown(key));
+                    return None;
+                }
+            }
+
+            return Some(LRESULT(0));
+        }
+        WM_SYSKEYDOWN
+            if state.options.alt_enter_fullscreen
+                && VIRTUAL_KEY(w_param.0 as u16) == VK_RETURN
+                // Bit 30, ignore the repeats of a held down key
+                && l_param.0 & (1 << 30) == 0 =>
+        {
+            let enabled = state.fullscreen_restore.is_none();
+            win32_once_released(state, move || {
+                if let Err(e) = win32_set_fullscreen(handle, enabled) {
+                    eprintln!("Failed to toggle fullscreen:\n{}", e);
+                }
+            });
+
+            return Some(LRESULT(0));
+        }
+        WM_SYSCOMMAND if (w_param.0 as u32 & 0xfff0) == WIN32_SC_ALWAYS_ON_TOP => {
+            win32_once_released(state, move || {
+                if let Err(e) = win32_set_always_on_top(handle, !win32_is_always_on_top(handle)) {
+                    eprintln!("{}", e);
+                }
+            });
+
+            return Some(LRESULT(0));
+        }
+        WM_SYSCOMMAND if win32_document_command(state, w_param).is_some() => {
+            let document = win32_document_command(state, w_param).unwrap();
+            win32_once_released(state, move || {
+                unsafe { SetActiveWindow(document) };
+            });
+
+            return Some(LRESULT(0));
+        }
+        WM_COPYDATA => {
+            let copy_data = &*(l_param.0 as *const COPYDATASTRUCT);
+            let event = if copy_data.dwData == WIN32_COPYDATA_COMMAND_LINE {
+                WindowEvent::CommandLine(win32_received_command_line(copy_data))
+            } else if let Some(command) = win32_received_remote_command(copy_data) {
+                WindowEvent::RemoteCommand(command)
+            } else {
+                return None;
+            };
+
+            // The sender lent its foreground right for these
+            if matches!(
+                event,
+                WindowEvent::CommandLine(_) | WindowEvent::RemoteCommand(RemoteCommand::Focus)
+            ) {
+                win32_once_released(state, move || unsafe {
+                    if IsIconic(handle).as_bool() {
+                        ShowWindow(handle, SW_RESTORE);
+                    }
+
+                    if !SetForegroundWindow(handle).as_bool() {
+                        eprintln!("Failed to bring the window to the front");
+                    }
+                });
+            }
+
+            win32_dispatch_event(handle, event);
+            return Some(LRESULT(1));
+        }
+        // Keeps DefWindowProc from beeping at the Alt+Enter character
+        WM_SYSCHAR if state.options.alt_enter_fullscreen && w_param.0 == '\r' as usize => {
+            return Some(LRESULT(0));
+        }
+        // Maximizing from the system menu or the keyboard, the window comes back down first
+        WM_SYSCOMMAND
+            if state.shade_restore_height.is_some()
+                && (w_param.0 as u32 & 0xfff0) == SC_MAXIMIZE =>
+        {
+            // Before DefWindowProc maximizes it
+            win32_once_released(state, move || {
+                if let Err(e) = win32_set_shaded(handle, false) {
+                    eprintln!("Failed to roll the window down:\n{}", e);
+                }
+            });
+
+            return None;
+        }
+        // Maximizing leaves picture in picture for the previous placement instead
+        WM_SYSCOMMAND
+            if state.picture_in_picture_restore.is_some()
+                && (w_param.0 as u32 & 0xfff0) == SC_MAXIMIZE =>
+        {
+            win32_once_released(state, move || {
+                if let Err(e) = win32_set_picture_in_picture(handle, false) {
+                    eprintln!("Failed to leave picture in picture:\n{}", e);
+                }
+            });
+
+            return Some(LRESULT(0));
+        }
+        // Fullscreen windows stay put, dragging or maximizing them goes through here
+        WM_SYSCOMMAND
+            if state.fullscreen_restore.is_some()
+                && matches!(w_param.0 as u32 & 0xfff0, SC_MOVE | SC_SIZE | SC_MAXIMIZE) =>
+        {
+            return Some(LRESULT(0));
+        }
+        // F10 toggles the menu bar like in classic menus, the system menu mode it
+        // would enter otherwise has no visible menu bar to work with
+        WM_SYSKEYDOWN
+            if VIRTUAL_KEY(w_param.0 as u16) == VK_F10
+                && state.focus_regions.contains(FocusRegion::MenuBar) =>
+        {
+            let region = if state.focus_regions.current() == FocusRegion::MenuBar {
+                FocusRegion::Content
+            } else {
+                FocusRegion::MenuBar
+            };
+            win32_set_focus_region(handle, state, region);
+            return Some(LRESULT(0));
+        }
+        WM_KILLFOCUS => {
+            // Coming back starts in the content again
+            win32_set_focus_region(handle, state, FocusRegion::Content);
+            return None;
+        }
+        WM_CLOSE if state.modal_owner.is_some() => {
+            EnableWindow(state.modal_owner.unwrap(), true);
+            return None;
+        }
+        WM_CLOSE if state.modified && state.options.confirm_close_when_modified => {
+            // The prompt runs a modal loop, it waits until the state is let go of.
+            // Closing is then what DefWindowProc would have done.
+            win32_defer(move || {
+                let answer = message_box(
+                    Window { handle },
+                    &win32_window_title(handle),
+                    &localized_string(strings::CLOSE_MODIFIED_PROMPT),
+                    MessageBoxButtons::YesNo,
+                );
+                match answer {
+                    Ok(MessageBoxResult::Yes) => {
+                        if let Err(e) = unsafe { DestroyWindow(handle) } {
+                            eprintln!("Failed to close window: {}", e.message());
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Failed to ask about unsaved changes:\n{}", e),
+                }
+            });
+            return Some(LRESULT(0));
+        }
+        WM_DESTROY => {
+            win32_drop_glyph_font(state);
+
+            if let Some(property) = &state.instance_property {
+                if let Err(e) = RemovePropW(handle, property) {
+                    eprintln!("Failed to remove window property: {}", e.message());
+                }
+            }
+
+            if state.options.kind == WindowKind::Document {
+                let owner = GetWindow(handle, GW_OWNER);
+                win32_with_window_state(owner, |owner_state| {
+                    owner_state.documents.retain(|&document| document != handle);
+                });
+            }
+
+            if let Some(placement_name) = &state.placement_name {
+                // Fullscreen and picture in picture are temporary, what gets saved is
+                // the window underneath
+                let placement = match (&state.fullscreen_restore, &state.picture_in_picture_restore)
+                {
+                    (Some(restore), _) => Ok(restore.placement),
+                    (None, Some(restore)) => Ok(restore.placement),
+                    (None, None) => {
+                        let mut placement = WINDOWPLACEMENT {
+                            length: size_of::<WINDOWPLACEMENT>() as u32,
+                            ..Default::default()
+                        };
+                        // Reopens rolled down
+                        GetWindowPlacement(handle, &mut placement).map(|()| {
+                            if let Some(height) = state.shade_restore_height {
+                                let normal_rect = &mut placement.rcNormalPosition;
+                                normal_rect.bottom = normal_rect.top + height;
+                            }
+                            placement
+                        })
+                    }
+                };
+
+                match placement {
+                    Ok(placement) => {
+                        if let Err(e) = win32_save_placement(placement_name, &placement) {
+                            eprintln!("{}", e);
+                        }
+                    }
+                    Err(e) => eprintln!("Failed to get window placement: {}", e.message()),
+                }
+            }
+
+            let _ = KillTimer(handle, WIN32_REPAINT_TIMER_ID);
+            let _ = KillTimer(handle, WIN32_MARQUEE_TIMER_ID);
+            let _ = KillTimer(handle, WIN32_ANIMATION_TIMER_ID);
+            let _ = KillTimer(handle, WIN32_SYSTEM_MENU_TIMER_ID);
+            let _ = KillTimer(handle, WIN32_CAPTION_REVEAL_TIMER_ID);
+            if let Err(e) = BufferedPaintUnInit() {
+                eprintln!("Failed to uninitialize buffered paint: {}", e.message());
+            }
+
+            if !state.power_notification.is_invalid() {
+                if let Err(e) = UnregisterPowerSettingNotification(state.power_notification) {
+                    eprintln!(
+                        "Failed to unregister power setting notification: {}",
+                        e.message()
+                    );
+                }
+            }
+
+            if state.options.kind == WindowKind::Main {
+                PostQuitMessage(0);
+            }
+            return Some(LRESULT(0));
+        }
+        _ => {}
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW_RECT: RECT = RECT {
+        left: 100,
+        top: 50,
+        right: 900,
+        bottom: 650,
+    };
+
+    #[test]
+    fn snapped_client_rect_keeps_top_edge() {
+        let client_rect = win32_inset_client_rect(WINDOW_RECT, 8, 8, 4, true);
+        assert_eq!(client_rect.top, WINDOW_RECT.top);
+        assert_eq!(client_rect.left, WINDOW_RECT.left + 12);
+        assert_eq!(client_rect.right, WINDOW_RECT.right - 12);
+        assert_eq!(client_rect.bottom, WINDOW_RECT.bottom - 12);
+    }
+
+    #[test]
+    fn shaded_client_rect_keeps_bottom_edge() {
+        let client_rect = win32_inset_client_rect(WINDOW_RECT, 8, 8, 4, false);
+        assert_eq!(client_rect.bottom, WINDOW_RECT.bottom);
+    }
+
+    #[test]
+    fn maximized_client_rect_is_clipped_to_work_area() {
+        let maximized_rect = RECT {
+            left: -12,
+            top: -12,
+            right: 1932,
+            bottom: 1092,
+        };
+        let work_area = RECT {
+            left: 0,
+            top: 0,
+            right: 1920,
+            bottom: 1040,
+        };
+        let client_rect = win32_inset_client_rect(maximized_rect, 8, 8, 4, true);
+        let client_rect = win32_clip_maximized_client_rect(client_rect, Some(work_area), 4);
+        assert_eq!(client_rect, work_area);
+    }
+
+    #[test]
+    fn maximized_client_rect_without_work_area_gets_top_padding() {
+        let client_rect = win32_clip_maximized_client_rect(WINDOW_RECT, None, 4);
+        assert_eq!(client_rect.top, WINDOW_RECT.top + 4);
+        assert_eq!(client_rect.bottom, WINDOW_RECT.bottom);
+    }
+
+    #[test]
+    fn top_resize_band_spans_frame_and_padding() {
+        assert!(win32_is_in_top_resize_band(0, 8, 4, true));
+        assert!(win32_is_in_top_resize_band(11, 8, 4, true));
+        assert!(!win32_is_in_top_resize_band(12, 8, 4, true));
+        assert!(!win32_is_in_top_resize_band(-1, 8, 4, true));
+    }
+
+    #[test]
+    fn maximized_and_shaded_windows_have_no_top_resize_band() {
+        assert!(!win32_is_in_top_resize_band(0, 8, 4, false));
+        assert!(!win32_is_in_top_resize_band(5, 8, 4, false));
+    }
+
+    fn make_l_param(x: i32, y: i32) -> LPARAM {
+        LPARAM(((x as u16 as u32) | ((y as u16 as u32) << 16)) as _)
+    }
+
+    #[test]
+    fn message_points_left_of_and_above_the_primary_monitor_stay_negative() {
+        let l_param = make_l_param(-1920, -1080);
+        assert_eq!(get_x_param(l_param), -1920);
+        assert_eq!(get_y_param(l_param), -1080);
+
+        let l_param = make_l_param(-1, 2159);
+        assert_eq!(get_x_param(l_param), -1);
+        assert_eq!(get_y_param(l_param), 2159);
+    }
+
+    #[test]
+    fn message_points_at_the_coordinate_limits() {
+        let l_param = make_l_param(i16::MIN as i32, i16::MAX as i32);
+        assert_eq!(get_x_param(l_param), i16::MIN as i32);
+        assert_eq!(get_y_param(l_param), i16::MAX as i32);
+    }
+
+    #[test]
+    fn dpi_scale_at_common_scales() {
+        for (dpi, button_width, frame) in [
+            (96, 47, 1),
+            (120, 59, 1),
+            (144, 71, 2),
+            (168, 82, 2),
+            (192, 94, 2),
+        ] {
+            assert_eq!(win32_dpi_scale(47, dpi), button_width, "47 at {dpi} dpi");
+            assert_eq!(win32_dpi_scale(1, dpi), frame, "1 at {dpi} dpi");
+        }
+    }
+
+    #[test]
+    fn mul_div_rounds_half_up() {
+        // 58.75, 2.5 and 1.75
+        assert_eq!(win32_mul_div(47, 120, 96), 59);
+        assert_eq!(win32_mul_div(2, 120, 96), 3);
+        assert_eq!(win32_mul_div(1, 168, 96), 2);
+        // 0.25 and 0.5
+        assert_eq!(win32_mul_div(1, 24, 96), 0);
+        assert_eq!(win32_mul_div(1, 48, 96), 1);
+    }
+
+    #[test]
+    fn mul_div_rounds_negative_values_half_up() {
+        // -58.75, -1.5 and -0.25
+        assert_eq!(win32_mul_div(-47, 120, 96), -59);
+        assert_eq!(win32_mul_div(-1, 144, 96), -1);
+        assert_eq!(win32_mul_div(-1, 24, 96), 0);
+        assert_eq!(win32_dpi_scale(-2, 144), -3);
+    }
+
+    #[test]
+    fn mul_div_survives_zero_denominator() {
+        assert_eq!(win32_mul_div(47, 96, 0), 47 * 96);
+    }
+
+    #[test]
+    fn shaded_windows_only_resize_sideways() {
+        assert_eq!(win32_shaded_hit(HTTOPLEFT), HTLEFT);
+        assert_eq!(win32_shaded_hit(HTBOTTOMLEFT), HTLEFT);
+        assert_eq!(win32_shaded_hit(HTTOPRIGHT), HTRIGHT);
+        assert_eq!(win32_shaded_hit(HTBOTTOMRIGHT), HTRIGHT);
+        assert_eq!(win32_shaded_hit(HTTOP), HTCAPTION);
+        assert_eq!(win32_shaded_hit(HTBOTTOM), HTCAPTION);
+        assert_eq!(win32_shaded_hit(HTLEFT), HTLEFT);
+        assert_eq!(win32_shaded_hit(HTCLIENT), HTCLIENT);
+    }
+}
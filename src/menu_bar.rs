@@ -0,0 +1,808 @@
+use crate::{
+    caption_buttons::win32_caption_button_layout, deferred::win32_defer, dpi::win32_window_dpi,
+    fonts::win32_measure_text, get_x_param, get_y_param, rgb, win32_caption_font,
+    win32_caption_icon_rect, win32_client_rect_to_screen, win32_dispatch_event, win32_dpi_scale,
+    win32_invalidate_rect, win32_set_focus_region, win32_titlebar_rect, win32_with_window_state,
+    FocusRegion, WindowEvent, WindowState,
+};
+use std::{cell::Cell, mem::size_of};
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{COLORREF, HWND, LPARAM, LRESULT, POINT, RECT, WPARAM},
+        Graphics::Gdi::{
+            CreatePen, CreateSolidBrush, DeleteObject, DrawTextW, FillRect, LineTo, MoveToEx,
+            PtInRect, ScreenToClient, SelectObject, SetBkMode, SetTextColor, UpdateWindow,
+            DT_CENTER, DT_HIDEPREFIX, DT_SINGLELINE, DT_VCENTER, HDC, HFONT, PS_SOLID, TRANSPARENT,
+        },
+        System::Threading::GetCurrentThreadId,
+        UI::{
+            Controls::WM_MOUSELEAVE,
+            Input::KeyboardAndMouse::{
+                TrackMouseEvent, TME_LEAVE, TRACKMOUSEEVENT, VIRTUAL_KEY, VK_DOWN, VK_LEFT,
+                VK_MENU, VK_RETURN, VK_RIGHT, VK_SPACE,
+            },
+            WindowsAndMessaging::{
+                AppendMenuW, CallNextHookEx, CreatePopupMenu, DestroyMenu, EndMenu,
+                GetMenuItemCount, GetMenuItemInfoW, PostMessageW, RemoveMenu, SetWindowsHookExW,
+                TrackPopupMenuEx, UnhookWindowsHookEx, HMENU, MENUITEMINFOW, MFT_SEPARATOR,
+                MF_BYPOSITION, MF_POPUP, MF_STRING, MIIM_FTYPE, MIIM_ID, MIIM_STRING, MIIM_SUBMENU,
+                MSG, MSGF_MENU, SC_KEYMENU, TPMPARAMS, TPM_LEFTALIGN, TPM_RETURNCMD, TPM_TOPALIGN,
+                TPM_VERTICAL, WH_MSGFILTER, WM_CHAR, WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN,
+                WM_MENUSELECT, WM_MOUSEMOVE, WM_SYSCOMMAND, WM_SYSKEYDOWN, WM_SYSKEYUP,
+            },
+        },
+    },
+};
+
+// A classic menu bar drawn in the caption, see Window::set_menu_bar
+#[derive(Default)]
+pub(crate) struct MenuBar {
+    items: Vec<MenuBarItem>,
+    hovered: Option<MenuBarPart>,
+    // Selected with the keyboard while the menu bar region has the focus
+    hot: Option<MenuBarPart>,
+    // Dropped down right now
+    open: Option<MenuBarPart>,
+    // Where the mouse or the arrow keys moved on to while open, and whether its first
+    // item gets selected
+    next: Option<(MenuBarPart, bool)>,
+    // Popup and whether the item selected in it opens another one, from WM_MENUSELECT
+    selection: (HMENU, bool),
+    // Held down, the mnemonics are underlined meanwhile
+    alt_down: bool,
+}
+
+struct MenuBarItem {
+    // With the & in front of the mnemonic
+    title: Vec<u16>,
+    mnemonic: Option<char>,
+    // Items without one are commands right on the bar
+    submenu: HMENU,
+    id: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MenuBarPart {
+    Item(usize),
+    // The chevron with the items that don't fit
+    Overflow,
+}
+
+// The character after a single &, lowercased to compare with what was typed
+fn win32_mnemonic(title: &[u16]) -> Option<char> {
+    let mut chars = char::decode_utf16(title.iter().copied()).filter_map(Result::ok);
+    while let Some(c) = chars.next() {
+        if c == '&' {
+            match chars.next() {
+                Some('&') => {}
+                next => return next.and_then(|next| next.to_lowercase().next()),
+            }
+        }
+    }
+
+    None
+}
+
+// Top level items of the application's menu, separators left out
+fn win32_menu_bar_items(menu: HMENU) -> Vec<MenuBarItem> {
+    let count = unsafe { GetMenuItemCount(menu) };
+    (0..count.max(0) as u32)
+        .filter_map(|position| {
+            let mut info = MENUITEMINFOW {
+                cbSize: size_of::<MENUITEMINFOW>() as u32,
+                fMask: MIIM_STRING | MIIM_SUBMENU | MIIM_ID | MIIM_FTYPE,
+                ..Default::default()
+            };
+            // Asked twice, the first time for the length of the title
+            if let Err(e) = unsafe { GetMenuItemInfoW(menu, position, true, &mut info) } {
+                eprintln!("Failed to get menu item: {}", e.message());
+                return None;
+            }
+            if info.fType.0 & MFT_SEPARATOR.0 != 0 {
+                return None;
+            }
+
+            let mut title = vec![0u16; info.cch as usize + 1];
+            info.dwTypeData = PWSTR(title.as_mut_ptr());
+            info.cch += 1;
+            if let Err(e) = unsafe { GetMenuItemInfoW(menu, position, true, &mut info) } {
+                eprintln!("Failed to get menu item title: {}", e.message());
+                return None;
+            }
+
+            title.truncate(info.cch as usize);
+            Some(MenuBarItem {
+                mnemonic: win32_mnemonic(&title),
+                title,
+                submenu: info.hSubMenu,
+                id: info.wID,
+            })
+        })
+        .collect()
+}
+
+impl MenuBar {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // The items are read once, the application calls Window::set_menu_bar again
+    // after changing them
+    pub(crate) fn set(&mut self, menu: Option<HMENU>) {
+        self.items = menu.map(win32_menu_bar_items).unwrap_or_default();
+        self.hovered = None;
+        self.hot = None;
+    }
+
+    fn item_with_mnemonic(&self, c: char) -> Option<MenuBarPart> {
+        let c = c.to_lowercase().next()?;
+        self.items
+            .iter()
+            .position(|item| item.mnemonic == Some(c))
+            .map(MenuBarPart::Item)
+    }
+
+    fn shows_mnemonics(&self) -> bool {
+        self.alt_down || self.hot.is_some()
+    }
+}
+
+// Sizes at 96 dpi
+const WIN32_MENU_BAR_TOP_MARGIN: i32 = 6;
+const WIN32_MENU_BAR_TEXT_PADDING: i32 = 8;
+const WIN32_MENU_BAR_OVERFLOW_WIDTH: i32 = 24;
+const WIN32_MENU_BAR_GLYPH_SIZE: i32 = 4;
+// Gap to the icon or the window edge on the left and the title on the right
+const WIN32_MENU_BAR_PADDING: i32 = 6;
+
+struct MenuBarLayout {
+    // The items that fit, the first ones of the menu
+    items: Vec<(usize, RECT)>,
+    overflow: Option<RECT>,
+    right: i32,
+}
+
+// Items are laid out from the icon on, the ones that don't fit before the search field
+// or the caption buttons go behind the overflow chevron
+fn win32_menu_bar_layout(
+    handle: HWND,
+    state: &WindowState,
+    title_bar_rect: &RECT,
+) -> MenuBarLayout {
+    let dpi = win32_window_dpi(handle);
+    let padding = win32_dpi_scale(WIN32_MENU_BAR_PADDING, dpi);
+    let text_padding = win32_dpi_scale(WIN32_MENU_BAR_TEXT_PADDING, dpi);
+    let overflow_width = win32_dpi_scale(WIN32_MENU_BAR_OVERFLOW_WIDTH, dpi);
+    let caption_button_layout = win32_caption_button_layout(handle, state, title_bar_rect);
+
+    let left = win32_caption_icon_rect(handle, state, title_bar_rect)
+        .map_or(caption_button_layout.leading_right, |icon_rect| {
+            icon_rect.right
+        })
+        + padding;
+    let right = (caption_button_layout.content_right - padding).max(left);
+    let top = title_bar_rect.top + win32_dpi_scale(WIN32_MENU_BAR_TOP_MARGIN, dpi);
+    let bottom = title_bar_rect.bottom - win32_dpi_scale(WIN32_MENU_BAR_TOP_MARGIN, dpi) / 2;
+
+    // Without the & in front of the mnemonics
+    let titles: Vec<&[u16]> = state
+        .menu_bar
+        .items
+        .iter()
+        .map(|item| item.title.as_slice())
+        .collect();
+    let widths: Vec<i32> = win32_measure_text(win32_caption_font(dpi), &titles, DT_SINGLELINE)
+        .into_iter()
+        .map(|size| size.cx + text_padding * 2)
+        .collect();
+    let mut layout = MenuBarLayout {
+        items: Vec::new(),
+        overflow: None,
+        right: left,
+    };
+    if widths.is_empty() {
+        return layout;
+    }
+
+    let total: i32 = widths.iter().sum();
+    let available = if total > right - left {
+        right - left - overflow_width
+    } else {
+        right - left
+    };
+
+    let mut x = left;
+    for (index, &width) in widths.iter().enumerate() {
+        if x + width - left > available {
+            break;
+        }
+
+        let item_rect = RECT {
+            left: x,
+            top,
+            right: x + width,
+            bottom,
+        };
+        layout.items.push((index, item_rect));
+        x += width;
+    }
+
+    if layout.items.len() < widths.len() {
+        layout.overflow = Some(RECT {
+            left: x,
+            top,
+            right: (x + overflow_width).min(right),
+            bottom,
+        });
+        x = (x + overflow_width).min(right);
+    }
+
+    layout.right = x;
+    layout
+}
+
+// Where the title, tabs or breadcrumb can start, None without a menu bar
+pub(crate) fn win32_menu_bar_right(
+    handle: HWND,
+    state: &WindowState,
+    title_bar_rect: &RECT,
+) -> Option<i32> {
+    (!state.menu_bar.is_empty()).then(|| win32_menu_bar_layout(handle, state, title_bar_rect).right)
+}
+
+fn win32_menu_bar_part_at(handle: HWND, state: &WindowState, point: POINT) -> Option<MenuBarPart> {
+    if state.menu_bar.is_empty() {
+        return None;
+    }
+
+    let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+    let layout = win32_menu_bar_layout(handle, state, &title_bar_rect);
+    let contains = |rect: &RECT| unsafe { PtInRect(rect, point) }.as_bool();
+    if layout.overflow.as_ref().is_some_and(contains) {
+        return Some(MenuBarPart::Overflow);
+    }
+
+    layout
+        .items
+        .iter()
+        .find(|(_, rect)| contains(rect))
+        .map(|&(index, _)| MenuBarPart::Item(index))
+}
+
+// Items behind the chevron drop down from the chevron
+fn win32_menu_bar_part_rect(handle: HWND, state: &WindowState, part: MenuBarPart) -> Option<RECT> {
+    let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+    let layout = win32_menu_bar_layout(handle, state, &title_bar_rect);
+    let item_rect = match part {
+        MenuBarPart::Item(index) => layout
+            .items
+            .iter()
+            .find(|&&(item, _)| item == index)
+            .map(|&(_, rect)| rect),
+        MenuBarPart::Overflow => None,
+    };
+    item_rect.or(layout.overflow)
+}
+
+// The items and the chevron in order, for the arrow keys
+fn win32_menu_bar_parts(handle: HWND, state: &WindowState) -> Vec<MenuBarPart> {
+    let Ok(title_bar_rect) = win32_titlebar_rect(handle, state) else {
+        return Vec::new();
+    };
+    let layout = win32_menu_bar_layout(handle, state, &title_bar_rect);
+    let mut parts: Vec<MenuBarPart> = layout
+        .items
+        .iter()
+        .map(|&(index, _)| MenuBarPart::Item(index))
+        .collect();
+    if layout.overflow.is_some() {
+        parts.push(MenuBarPart::Overflow);
+    }
+    parts
+}
+
+// The part next to part in direction (-1 or 1), wrapping around. Hidden items count as
+// the chevron.
+fn win32_neighbor_part(
+    handle: HWND,
+    state: &WindowState,
+    part: MenuBarPart,
+    direction: isize,
+) -> Option<MenuBarPart> {
+    let parts = win32_menu_bar_parts(handle, state);
+    let count = parts.len() as isize;
+    if count == 0 {
+        return None;
+    }
+
+    let index = parts
+        .iter()
+        .position(|&other| other == part)
+        .unwrap_or(count as usize - 1) as isize;
+    Some(parts[(index + direction).rem_euclid(count) as usize])
+}
+
+// For WM_NCHITTEST, the items take mouse input like the content while the caption
+// around them still drags the window
+pub(crate) fn win32_is_over_menu_bar(handle: HWND, state: &WindowState, point: POINT) -> bool {
+    win32_menu_bar_part_at(handle, state, point).is_some()
+}
+
+// The same hover colors as the caption tabs
+const WIN32_MENU_BAR_HOVER_COLOR: u32 = rgb(170, 220, 200);
+const WIN32_MENU_BAR_PRESSED_COLOR: u32 = rgb(130, 180, 160);
+
+pub(crate) fn win32_paint_menu_bar(
+    hdc: HDC,
+    handle: HWND,
+    state: &WindowState,
+    title_bar_rect: &RECT,
+    item_color: COLORREF,
+) {
+    let menu_bar = &state.menu_bar;
+    if menu_bar.is_empty() {
+        return;
+    }
+
+    let dpi = win32_window_dpi(handle);
+    let layout = win32_menu_bar_layout(handle, state, title_bar_rect);
+    let glyph_size = win32_dpi_scale(WIN32_MENU_BAR_GLYPH_SIZE, dpi);
+    let font = win32_caption_font(dpi);
+    let text_format = if menu_bar.shows_mnemonics() {
+        DT_CENTER | DT_VCENTER | DT_SINGLELINE
+    } else {
+        DT_CENTER | DT_VCENTER | DT_SINGLELINE | DT_HIDEPREFIX
+    };
+
+    let fill_highlighted = |rect: &RECT, part: MenuBarPart| unsafe {
+        let color = if menu_bar.open == Some(part) {
+            WIN32_MENU_BAR_PRESSED_COLOR
+        } else if menu_bar.hovered == Some(part) || menu_bar.hot == Some(part) {
+            WIN32_MENU_BAR_HOVER_COLOR
+        } else {
+            return;
+        };
+
+        let brush = CreateSolidBrush(COLORREF(color));
+        FillRect(hdc, rect, brush);
+        DeleteObject(brush);
+    };
+
+    unsafe {
+        let old_font = if font != HFONT(0) {
+            HFONT(SelectObject(hdc, font).0)
+        } else {
+            HFONT(0)
+        };
+        SetBkMode(hdc, TRANSPARENT);
+        SetTextColor(hdc, item_color);
+
+        for &(index, item_rect) in &layout.items {
+            fill_highlighted(&item_rect, MenuBarPart::Item(index));
+            let mut text_rect = item_rect;
+            let mut text = menu_bar.items[index].title.clone();
+            DrawTextW(hdc, &mut text, &mut text_rect, text_format);
+        }
+
+        // Two chevrons pointing right
+        if let Some(overflow) = &layout.overflow {
+            fill_highlighted(overflow, MenuBarPart::Overflow);
+            let glyph_pen = CreatePen(PS_SOLID, win32_dpi_scale(1, dpi).max(1), item_color);
+            let old_pen = SelectObject(hdc, glyph_pen);
+            let center_y = (overflow.top + overflow.bottom) / 2;
+            let center_x = (overflow.left + overflow.right) / 2;
+            for x in [center_x - glyph_size / 2, center_x + glyph_size / 2] {
+                MoveToEx(hdc, x - glyph_size / 2, center_y - glyph_size, None);
+                LineTo(hdc, x + glyph_size / 2, center_y);
+                LineTo(hdc, x - glyph_size / 2, center_y + glyph_size + 1);
+            }
+            SelectObject(hdc, old_pen);
+            DeleteObject(glyph_pen);
+        }
+
+        if font != HFONT(0) {
+            SelectObject(hdc, old_font);
+        }
+    }
+}
+
+pub(crate) fn win32_invalidate_menu_bar(handle: HWND, state: &mut WindowState) {
+    if let Ok(title_bar_rect) = win32_titlebar_rect(handle, state) {
+        win32_invalidate_rect(handle, state, &title_bar_rect);
+    }
+}
+
+fn win32_set_hovered_menu_bar_part(
+    handle: HWND,
+    state: &mut WindowState,
+    part: Option<MenuBarPart>,
+) {
+    if state.menu_bar.hovered != part {
+        state.menu_bar.hovered = part;
+        win32_invalidate_menu_bar(handle, state);
+    }
+}
+
+// Selects the first item for the menu bar region, and nothing once it's left
+pub(crate) fn win32_menu_bar_focus_changed(
+    handle: HWND,
+    state: &mut WindowState,
+    region: FocusRegion,
+) {
+    let hot = if region == FocusRegion::MenuBar {
+        win32_menu_bar_parts(handle, state).first().copied()
+    } else {
+        None
+    };
+    if state.menu_bar.hot != hot {
+        state.menu_bar.hot = hot;
+        win32_invalidate_menu_bar(handle, state);
+    }
+}
+
+// The items behind the chevron as submenus of one popup. They stay the application's,
+// see win32_destroy_overflow_menu.
+fn win32_create_overflow_menu(handle: HWND, state: &WindowState) -> Option<HMENU> {
+    let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+    let shown = win32_menu_bar_layout(handle, state, &title_bar_rect)
+        .items
+        .len();
+
+    let menu = match unsafe { CreatePopupMenu() } {
+        Ok(menu) => menu,
+        Err(e) => {
+            eprintln!("Failed to create menu bar overflow menu: {}", e.message());
+            return None;
+        }
+    };
+
+    for item in &state.menu_bar.items[shown..] {
+        let title: Vec<u16> = item.title.iter().copied().chain([0]).collect();
+        let result = unsafe {
+            if item.submenu.is_invalid() {
+                AppendMenuW(menu, MF_STRING, item.id as usize, PCWSTR(title.as_ptr()))
+            } else {
+                AppendMenuW(
+                    menu,
+                    MF_STRING | MF_POPUP,
+                    item.submenu.0 as usize,
+                    PCWSTR(title.as_ptr()),
+                )
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to add menu bar overflow item: {}", e.message());
+        }
+    }
+
+    Some(menu)
+}
+
+// DestroyMenu would take the submenus along, they are taken out first
+fn win32_destroy_overflow_menu(menu: HMENU) {
+    unsafe {
+        for position in (0..GetMenuItemCount(menu).max(0) as u32).rev() {
+            if let Err(e) = RemoveMenu(menu, position, MF_BYPOSITION) {
+                eprintln!("Failed to remove menu bar overflow item: {}", e.message());
+            }
+        }
+        if let Err(e) = DestroyMenu(menu) {
+            eprintln!("Failed to destroy menu bar overflow menu: {}", e.message());
+        }
+    }
+}
+
+thread_local! {
+    // The window whose menu bar has a menu open, for the message filter hook
+    static WIN32_MENU_BAR_TRACKING: Cell<HWND> = const { Cell::new(HWND(0)) };
+}
+
+// Runs inside the popup menu's modal loop. Moving the mouse over another item, or the
+// arrow keys at the edge of the menu, close it to open the next one, like a real menu
+// bar does.
+unsafe extern "system" fn win32_menu_bar_filter(
+    code: i32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    let handle = WIN32_MENU_BAR_TRACKING.get();
+    if code != MSGF_MENU as i32 || handle == HWND(0) {
+        return CallNextHookEx(None, code, w_param, l_param);
+    }
+
+    let msg = &*(l_param.0 as *const MSG);
+    let next = win32_with_window_state(handle, |state| {
+        let (Some(open), None) = (state.menu_bar.open, state.menu_bar.next) else {
+            return None;
+        };
+
+        let next = match msg.message {
+            WM_MOUSEMOVE => {
+                let mut point = msg.pt;
+                ScreenToClient(handle, &mut point);
+                win32_menu_bar_part_at(handle, state, point)
+                    .filter(|&part| part != open)
+                    .map(|part| (part, false))
+            }
+            // Left only from the dropped down menu itself, not from a submenu of it
+            WM_KEYDOWN if VIRTUAL_KEY(msg.wParam.0 as u16) == VK_LEFT => {
+                let (popup, _) = state.menu_bar.selection;
+                let top = match open {
+                    MenuBarPart::Item(index) => {
+                        state.menu_bar.items.get(index).map(|item| item.submenu)
+                    }
+                    MenuBarPart::Overflow => None,
+                };
+                (top.is_none() || top == Some(popup))
+                    .then(|| win32_neighbor_part(handle, state, open, -1))
+                    .flatten()
+                    .map(|part| (part, true))
+            }
+            // Right unless it opens the submenu of the selected item
+            WM_KEYDOWN if VIRTUAL_KEY(msg.wParam.0 as u16) == VK_RIGHT => {
+                let (_, opens_submenu) = state.menu_bar.selection;
+                (!opens_submenu)
+                    .then(|| win32_neighbor_part(handle, state, open, 1))
+                    .flatten()
+                    .map(|part| (part, true))
+            }
+            _ => None,
+        };
+        state.menu_bar.next = next;
+        next
+    })
+    .flatten();
+
+    if next.is_none() {
+        return CallNextHookEx(None, code, w_param, l_param);
+    }
+
+    if let Err(e) = EndMenu() {
+        eprintln!("Failed to close menu: {}", e.message());
+    }
+    // The keys are used up, the mouse moves on to the menu loop
+    if msg.message == WM_KEYDOWN {
+        return LRESULT(1);
+    }
+    CallNextHookEx(None, code, w_param, l_param)
+}
+
+// Drops down the menu of part, with its first item selected when opened from the
+// keyboard, once the window procedure lets go of the state
+fn win32_open_menu_bar_part(handle: HWND, part: MenuBarPart, select_first: bool) {
+    win32_defer(move || win32_track_menu_bar(handle, part, select_first));
+}
+
+// Moves on from one menu to the next until one is dismissed or a command is picked.
+// Items without a submenu run their command right away.
+fn win32_track_menu_bar(handle: HWND, mut part: MenuBarPart, mut select_first: bool) {
+    loop {
+        let Some((part_rect, popup, is_overflow)) = win32_with_window_state(handle, |state| {
+            win32_open_menu_bar_menu(handle, state, part)
+        })
+        .flatten() else {
+            return;
+        };
+
+        unsafe { UpdateWindow(handle) };
+
+        // The menu loop picks it up first thing
+        if select_first {
+            if let Err(e) =
+                unsafe { PostMessageW(handle, WM_KEYDOWN, WPARAM(VK_DOWN.0 as usize), LPARAM(0)) }
+            {
+                eprintln!("Failed to post message: {}", e.message());
+            }
+        }
+
+        let screen_rect = win32_client_rect_to_screen(handle, &part_rect);
+        let params = TPMPARAMS {
+            cbSize: size_of::<TPMPARAMS>() as u32,
+            rcExclude: screen_rect,
+        };
+        WIN32_MENU_BAR_TRACKING.set(handle);
+        let hook = unsafe {
+            SetWindowsHookExW(
+                WH_MSGFILTER,
+                Some(win32_menu_bar_filter),
+                None,
+                GetCurrentThreadId(),
+            )
+        };
+        if let Err(e) = &hook {
+            eprintln!("Failed to hook menu messages: {}", e.message());
+        }
+
+        let command = unsafe {
+            TrackPopupMenuEx(
+                popup,
+                (TPM_RETURNCMD | TPM_VERTICAL | TPM_LEFTALIGN | TPM_TOPALIGN).0,
+                screen_rect.left,
+                screen_rect.bottom,
+                handle,
+                Some(&params),
+            )
+        };
+
+        if let Ok(hook) = hook {
+            if let Err(e) = unsafe { UnhookWindowsHookEx(hook) } {
+                eprintln!("Failed to unhook menu messages: {}", e.message());
+            }
+        }
+        WIN32_MENU_BAR_TRACKING.set(HWND(0));
+        if is_overflow {
+            win32_destroy_overflow_menu(popup);
+        }
+
+        let Some((next_part, next_select_first)) = win32_with_window_state(handle, |state| {
+            state.menu_bar.open = None;
+            win32_invalidate_menu_bar(handle, state);
+
+            // 0 when the menu was dismissed
+            if command.0 != 0 {
+                if state.focus_regions.current() == FocusRegion::MenuBar {
+                    win32_set_focus_region(handle, state, FocusRegion::Content);
+                }
+                win32_dispatch_event(handle, WindowEvent::MenuCommand(command.0 as usize));
+                return None;
+            }
+
+            state.menu_bar.next.take()
+        })
+        .flatten() else {
+            return;
+        };
+        part = next_part;
+        select_first = next_select_first;
+    }
+}
+
+// Marks part open and hands back where its menu drops down from. Items without a
+// submenu run their command instead, None then.
+fn win32_open_menu_bar_menu(
+    handle: HWND,
+    state: &mut WindowState,
+    part: MenuBarPart,
+) -> Option<(RECT, HMENU, bool)> {
+    let part_rect = win32_menu_bar_part_rect(handle, state, part)?;
+
+    let (popup, is_overflow) = match part {
+        MenuBarPart::Item(index) => {
+            let item = state.menu_bar.items.get(index)?;
+            if item.submenu.is_invalid() {
+                let id = item.id;
+                if state.focus_regions.current() == FocusRegion::MenuBar {
+                    win32_set_focus_region(handle, state, FocusRegion::Content);
+                }
+                win32_dispatch_event(handle, WindowEvent::MenuCommand(id as usize));
+                return None;
+            }
+            (item.submenu, false)
+        }
+        MenuBarPart::Overflow => (win32_create_overflow_menu(handle, state)?, true),
+    };
+
+    state.menu_bar.open = Some(part);
+    state.menu_bar.next = None;
+    state.menu_bar.selection = (popup, false);
+    if state.menu_bar.hot.is_some() {
+        state.menu_bar.hot = Some(part);
+    }
+    win32_invalidate_menu_bar(handle, state);
+    Some((part_rect, popup, is_overflow))
+}
+
+// Asks for the WM_MOUSELEAVE of the caption items taking mouse input as client area,
+// once until it arrives
+pub(crate) fn win32_track_client_leave(handle: HWND, state: &mut WindowState) {
+    if state.tracking_client_leave {
+        return;
+    }
+
+    let mut track_mouse_event = TRACKMOUSEEVENT {
+        cbSize: size_of::<TRACKMOUSEEVENT>() as u32,
+        dwFlags: TME_LEAVE,
+        hwndTrack: handle,
+        ..Default::default()
+    };
+
+    match unsafe { TrackMouseEvent(&mut track_mouse_event) } {
+        Ok(()) => state.tracking_client_leave = true,
+        Err(e) => eprintln!("Failed to track mouse leave: {}", e.message()),
+    }
+}
+
+// Mouse input over the items, which WM_NCHITTEST makes client area, and the keyboard
+// while the menu bar region has the focus. Alt+letter and Alt on its own come through
+// SC_KEYMENU like for a real menu bar. None leaves the message to the window.
+pub(crate) fn win32_menu_bar_input(
+    handle: HWND,
+    state: &mut WindowState,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> Option<LRESULT> {
+    if state.menu_bar.is_empty() {
+        return None;
+    }
+
+    let client_point = POINT {
+        x: get_x_param(l_param),
+        y: get_y_param(l_param),
+    };
+    let in_menu_bar = state.focus_regions.current() == FocusRegion::MenuBar;
+
+    match message {
+        WM_MOUSEMOVE => {
+            let part = win32_menu_bar_part_at(handle, state, client_point);
+            win32_set_hovered_menu_bar_part(handle, state, part);
+
+            // Moving on to the caption around the items is a WM_MOUSELEAVE
+            if part.is_some() {
+                win32_track_client_leave(handle, state);
+            }
+
+            None
+        }
+        WM_MOUSELEAVE => {
+            win32_set_hovered_menu_bar_part(handle, state, None);
+            None
+        }
+        // Menus drop down on press
+        WM_LBUTTONDOWN => {
+            let part = win32_menu_bar_part_at(handle, state, client_point)?;
+            win32_open_menu_bar_part(handle, part, false);
+            Some(LRESULT(0))
+        }
+        WM_MENUSELECT if state.menu_bar.open.is_some() => {
+            let flags = (w_param.0 >> 16) as u32;
+            state.menu_bar.selection = (HMENU(l_param.0), flags & MF_POPUP.0 != 0);
+            None
+        }
+        WM_SYSKEYDOWN | WM_SYSKEYUP | WM_KEYUP if VIRTUAL_KEY(w_param.0 as u16) == VK_MENU => {
+            let alt_down = message == WM_SYSKEYDOWN;
+            if state.menu_bar.alt_down != alt_down {
+                state.menu_bar.alt_down = alt_down;
+                win32_invalidate_menu_bar(handle, state);
+            }
+            None
+        }
+        // Alt on its own goes in and out of the menu bar, like F10
+        WM_SYSCOMMAND if (w_param.0 as u32 & 0xfff0) == SC_KEYMENU && l_param.0 == 0 => {
+            let region = if in_menu_bar {
+                FocusRegion::Content
+            } else {
+                FocusRegion::MenuBar
+            };
+            win32_set_focus_region(handle, state, region);
+            Some(LRESULT(0))
+        }
+        WM_SYSCOMMAND if (w_param.0 as u32 & 0xfff0) == SC_KEYMENU => {
+            let c = char::from_u32(l_param.0 as u32)?;
+            let part = state.menu_bar.item_with_mnemonic(c)?;
+            win32_open_menu_bar_part(handle, part, true);
+            Some(LRESULT(0))
+        }
+        WM_CHAR if in_menu_bar => {
+            let c = char::from_u32(w_param.0 as u32)?;
+            let part = state.menu_bar.item_with_mnemonic(c)?;
+            win32_open_menu_bar_part(handle, part, true);
+            Some(LRESULT(0))
+        }
+        WM_KEYDOWN if in_menu_bar => {
+            let hot = state.menu_bar.hot?;
+            match VIRTUAL_KEY(w_param.0 as u16) {
+                key @ (VK_LEFT | VK_RIGHT) => {
+                    let direction = if key == VK_LEFT { -1 } else { 1 };
+                    state.menu_bar.hot = win32_neighbor_part(handle, state, hot, direction);
+                    win32_invalidate_menu_bar(handle, state);
+                }
+                VK_DOWN | VK_RETURN | VK_SPACE => win32_open_menu_bar_part(handle, hot, true),
+                _ => return None,
+            }
+            Some(LRESULT(0))
+        }
+        _ => None,
+    }
+}
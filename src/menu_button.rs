@@ -0,0 +1,86 @@
+use crate::{
+    caption_buttons::win32_caption_button_layout, win32_client_rect_to_screen,
+    win32_dispatch_event, win32_titlebar_rect, win32_with_window_state, CaptionButtonId,
+    WindowEvent, WindowState,
+};
+use windows::Win32::{
+    Foundation::{HWND, RECT},
+    UI::WindowsAndMessaging::{TrackPopupMenu, HMENU, TPM_RETURNCMD, TPM_RIGHTBUTTON},
+};
+
+// Three lines, like the navigation button of Windows' own apps
+pub(crate) const WIN32_MENU_GLYPH: &str = "M3 4.5 H13 M3 8 H13 M3 11.5 H13";
+
+// What the menu button at the left edge of the caption opens, see
+// Window::set_menu_button
+pub enum MenuButtonContent {
+    // Dropped down below the button, the command picked comes as
+    // WindowEvent::MenuCommand. The application keeps owning the menu.
+    Menu(HMENU),
+    // Called with the screen rect of the button, to open a popup window of the
+    // application's own hanging from it
+    Flyout(Box<dyn FnMut(RECT)>),
+}
+
+pub(crate) struct MenuButton {
+    pub(crate) id: CaptionButtonId,
+    // Taken out while the menu or the flyout is open
+    pub(crate) content: Option<MenuButtonContent>,
+}
+
+fn win32_menu_button_rect(handle: HWND, state: &WindowState) -> Option<RECT> {
+    let id = state.menu_button.as_ref()?.id;
+    let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+    win32_caption_button_layout(handle, state, &title_bar_rect)
+        .buttons
+        .into_iter()
+        .find(|&(button, _)| button == id)
+        .map(|(_, rect)| win32_client_rect_to_screen(handle, &rect))
+}
+
+// The click handler of the menu button
+pub(crate) fn win32_open_menu_button(handle: HWND) {
+    let Some((button_rect, id, content)) = win32_with_window_state(handle, |state| {
+        let button_rect = win32_menu_button_rect(handle, state)?;
+        let menu_button = state.menu_button.as_mut()?;
+        Some((button_rect, menu_button.id, menu_button.content.take()?))
+    })
+    .flatten() else {
+        return;
+    };
+
+    let content = match content {
+        MenuButtonContent::Menu(menu) => {
+            let command = unsafe {
+                TrackPopupMenu(
+                    menu,
+                    TPM_RETURNCMD | TPM_RIGHTBUTTON,
+                    button_rect.left,
+                    button_rect.bottom,
+                    0,
+                    handle,
+                    None,
+                )
+            };
+
+            // 0 when the menu was dismissed
+            if command.0 != 0 {
+                win32_dispatch_event(handle, WindowEvent::MenuCommand(command.0 as usize));
+            }
+            MenuButtonContent::Menu(menu)
+        }
+        MenuButtonContent::Flyout(mut open) => {
+            open(button_rect);
+            MenuButtonContent::Flyout(open)
+        }
+    };
+
+    // Unless the button was replaced or removed meanwhile
+    win32_with_window_state(handle, |state| {
+        if let Some(menu_button) = &mut state.menu_button {
+            if menu_button.id == id && menu_button.content.is_none() {
+                menu_button.content = Some(content);
+            }
+        }
+    });
+}
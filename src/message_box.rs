@@ -0,0 +1,474 @@
+use crate::{
+    dpi::win32_window_dpi,
+    get_x_param, get_y_param, rgb,
+    strings::{
+        localized_string, MESSAGE_BOX_CANCEL, MESSAGE_BOX_NO, MESSAGE_BOX_OK, MESSAGE_BOX_RETRY,
+        MESSAGE_BOX_YES,
+    },
+    win32_caption_font, win32_dpi_scale, win32_once_released, win32_release_capture,
+    win32_set_capture, win32_titlebar_rect, Window, WindowBuilder, WindowState,
+};
+use anyhow::Result;
+use std::{cell::Cell, rc::Rc};
+use windows::Win32::{
+    Foundation::{COLORREF, HWND, LPARAM, LRESULT, POINT, RECT, SIZE, WPARAM},
+    Graphics::Gdi::{
+        CreatePen, CreateSolidBrush, DeleteObject, DrawFocusRect, DrawTextW, FillRect, GetDC,
+        InflateRect, InvalidateRect, PtInRect, Rectangle, ReleaseDC, SelectObject, SetBkColor,
+        SetBkMode, SetTextColor, DT_CALCRECT, DT_CENTER, DT_NOPREFIX, DT_SINGLELINE, DT_VCENTER,
+        DT_WORDBREAK, HDC, PS_INSIDEFRAME, TRANSPARENT,
+    },
+    UI::{
+        Input::KeyboardAndMouse::{
+            GetKeyState, VIRTUAL_KEY, VK_LEFT, VK_RETURN, VK_RIGHT, VK_SHIFT, VK_SPACE, VK_TAB,
+        },
+        WindowsAndMessaging::{
+            GetClientRect, GetWindowRect, PostMessageW, SetWindowPos, SWP_NOACTIVATE, SWP_NOZORDER,
+            WM_CLOSE, WM_KEYDOWN, WM_KILLFOCUS, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MOUSEMOVE,
+            WM_SETFOCUS,
+        },
+    },
+};
+
+// Buttons of a message_box, in the order they are shown
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageBoxButtons {
+    Ok,
+    OkCancel,
+    YesNo,
+    YesNoCancel,
+    RetryCancel,
+}
+
+// The button a message_box was closed with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageBoxResult {
+    Ok,
+    Cancel,
+    Yes,
+    No,
+    Retry,
+}
+
+impl MessageBoxButtons {
+    fn choices(self) -> &'static [MessageBoxResult] {
+        match self {
+            Self::Ok => &[MessageBoxResult::Ok],
+            Self::OkCancel => &[MessageBoxResult::Ok, MessageBoxResult::Cancel],
+            Self::YesNo => &[MessageBoxResult::Yes, MessageBoxResult::No],
+            Self::YesNoCancel => &[
+                MessageBoxResult::Yes,
+                MessageBoxResult::No,
+                MessageBoxResult::Cancel,
+            ],
+            Self::RetryCancel => &[MessageBoxResult::Retry, MessageBoxResult::Cancel],
+        }
+    }
+
+    // What closing the box with the close button or Escape counts as
+    fn dismissed(self) -> MessageBoxResult {
+        match self {
+            Self::Ok => MessageBoxResult::Ok,
+            Self::YesNo => MessageBoxResult::No,
+            Self::OkCancel | Self::YesNoCancel | Self::RetryCancel => MessageBoxResult::Cancel,
+        }
+    }
+}
+
+impl MessageBoxResult {
+    fn label(self) -> String {
+        localized_string(match self {
+            Self::Ok => MESSAGE_BOX_OK,
+            Self::Cancel => MESSAGE_BOX_CANCEL,
+            Self::Yes => MESSAGE_BOX_YES,
+            Self::No => MESSAGE_BOX_NO,
+            Self::Retry => MESSAGE_BOX_RETRY,
+        })
+    }
+}
+
+// MessageBoxW with the custom frame and caption colored buttons, so it matches the
+// rest of the application. Modal to owner, returns once a button is chosen.
+pub fn message_box(
+    owner: Window,
+    title: &str,
+    text: &str,
+    buttons: MessageBoxButtons,
+) -> Result<MessageBoxResult> {
+    let result = Rc::new(Cell::new(None));
+    let content = MessageBoxContent {
+        text: text.encode_utf16().collect(),
+        choices: buttons.choices(),
+        hovered: None,
+        pressed: None,
+        focused: 0,
+        result: Rc::clone(&result),
+    };
+
+    WindowBuilder::new(title).run_modal(owner, move |dialog| {
+        crate::win32_with_window_state(dialog.handle(), |state| {
+            state.message_box = Some(content);
+        });
+
+        win32_fit_message_box(dialog.handle());
+    })?;
+
+    Ok(result.get().unwrap_or(buttons.dismissed()))
+}
+
+// What a message box window shows below its caption, kept in its WindowState
+pub(crate) struct MessageBoxContent {
+    text: Vec<u16>,
+    choices: &'static [MessageBoxResult],
+    hovered: Option<usize>,
+    // Held down with the mouse, the mouse is captured until it goes up
+    pressed: Option<usize>,
+    // Has the keyboard focus while the window has, starting with the first
+    focused: usize,
+    result: Rc<Cell<Option<MessageBoxResult>>>,
+}
+
+// Sizes at 96 dpi
+const WIN32_MESSAGE_BOX_PADDING: i32 = 24;
+const WIN32_MESSAGE_BOX_TEXT_WIDTH: i32 = 400;
+const WIN32_MESSAGE_BOX_BUTTON_WIDTH: i32 = 88;
+const WIN32_MESSAGE_BOX_BUTTON_HEIGHT: i32 = 28;
+const WIN32_MESSAGE_BOX_BUTTON_GAP: i32 = 8;
+const WIN32_MESSAGE_BOX_FOOTER_PADDING: i32 = 12;
+const WIN32_FOCUS_RING_INSET: i32 = 3;
+
+// Where the parts of the content go for the current client size
+struct MessageBoxLayout {
+    text: RECT,
+    footer: RECT,
+    buttons: Vec<RECT>,
+}
+
+// Height the text needs when wrapped at width
+fn win32_measure_text(hdc: HDC, text: &[u16], width: i32) -> SIZE {
+    let mut rect = RECT {
+        right: width,
+        ..Default::default()
+    };
+    let mut text = text.to_vec();
+    unsafe {
+        DrawTextW(
+            hdc,
+            &mut text,
+            &mut rect,
+            DT_CALCRECT | DT_WORDBREAK | DT_NOPREFIX,
+        )
+    };
+
+    SIZE {
+        cx: rect.right - rect.left,
+        cy: rect.bottom - rect.top,
+    }
+}
+
+fn win32_message_box_layout(
+    hdc: HDC,
+    handle: HWND,
+    content: &MessageBoxContent,
+    title_bar_rect: &RECT,
+) -> MessageBoxLayout {
+    let dpi = win32_window_dpi(handle);
+    let padding = win32_dpi_scale(WIN32_MESSAGE_BOX_PADDING, dpi);
+    let footer_padding = win32_dpi_scale(WIN32_MESSAGE_BOX_FOOTER_PADDING, dpi);
+    let button_width = win32_dpi_scale(WIN32_MESSAGE_BOX_BUTTON_WIDTH, dpi);
+    let button_height = win32_dpi_scale(WIN32_MESSAGE_BOX_BUTTON_HEIGHT, dpi);
+    let button_gap = win32_dpi_scale(WIN32_MESSAGE_BOX_BUTTON_GAP, dpi);
+    let caption_bottom = title_bar_rect.bottom;
+
+    let mut client_rect = RECT::default();
+    if let Err(e) = unsafe { GetClientRect(handle, &mut client_rect) } {
+        eprintln!("Failed to get client rect: {}", e.message());
+    }
+
+    let footer = RECT {
+        top: client_rect.bottom - button_height - footer_padding * 2,
+        ..client_rect
+    };
+
+    let text_left = client_rect.left + padding;
+    let text_right = (client_rect.right - padding).max(text_left);
+    let text_size = win32_measure_text(hdc, &content.text, text_right - text_left);
+    let text = RECT {
+        left: text_left,
+        top: caption_bottom + padding,
+        right: text_right,
+        bottom: caption_bottom + padding + text_size.cy,
+    };
+
+    // Right aligned in the footer, in choice order
+    let mut right = footer.right - footer_padding;
+    let mut buttons = vec![RECT::default(); content.choices.len()];
+    for button in buttons.iter_mut().rev() {
+        *button = RECT {
+            left: right - button_width,
+            top: footer.top + footer_padding,
+            right,
+            bottom: footer.top + footer_padding + button_height,
+        };
+        right -= button_width + button_gap;
+    }
+
+    MessageBoxLayout {
+        text,
+        footer,
+        buttons,
+    }
+}
+
+// Resizes a new message box to fit its text, keeping it centered where it opened
+pub(crate) fn win32_fit_message_box(handle: HWND) {
+    crate::win32_with_window_state(handle, |state| {
+        let Some(content) = &state.message_box else {
+            return;
+        };
+
+        let dpi = win32_window_dpi(handle);
+        let padding = win32_dpi_scale(WIN32_MESSAGE_BOX_PADDING, dpi);
+        let footer_padding = win32_dpi_scale(WIN32_MESSAGE_BOX_FOOTER_PADDING, dpi);
+        let button_count = content.choices.len() as i32;
+        let buttons_width = button_count * win32_dpi_scale(WIN32_MESSAGE_BOX_BUTTON_WIDTH, dpi)
+            + (button_count - 1) * win32_dpi_scale(WIN32_MESSAGE_BOX_BUTTON_GAP, dpi)
+            + footer_padding * 2;
+        let footer_height =
+            win32_dpi_scale(WIN32_MESSAGE_BOX_BUTTON_HEIGHT, dpi) + footer_padding * 2;
+        let caption_height =
+            win32_titlebar_rect(handle, state).map_or(0, |rect| rect.bottom - rect.top);
+
+        let hdc = unsafe { GetDC(handle) };
+        let font = win32_caption_font(dpi);
+        let old_font = unsafe { SelectObject(hdc, font) };
+        let text_size = win32_measure_text(
+            hdc,
+            &content.text,
+            win32_dpi_scale(WIN32_MESSAGE_BOX_TEXT_WIDTH, dpi),
+        );
+        unsafe {
+            SelectObject(hdc, old_font);
+            ReleaseDC(handle, hdc);
+        }
+
+        let client_width = (text_size.cx + padding * 2).max(buttons_width);
+        let client_height = caption_height + padding * 2 + text_size.cy + footer_height;
+
+        let mut window_rect = RECT::default();
+        let mut client_rect = RECT::default();
+        if unsafe { GetWindowRect(handle, &mut window_rect) }.is_err()
+            || unsafe { GetClientRect(handle, &mut client_rect) }.is_err()
+        {
+            return;
+        }
+
+        // The frame around the client area stays what it is
+        let width = window_rect.right - window_rect.left - client_rect.right + client_width;
+        let height = window_rect.bottom - window_rect.top - client_rect.bottom + client_height;
+        let center_x = (window_rect.left + window_rect.right) / 2;
+        let center_y = (window_rect.top + window_rect.bottom) / 2;
+        win32_once_released(state, move || {
+            if let Err(e) = unsafe {
+                SetWindowPos(
+                    handle,
+                    None,
+                    center_x - width / 2,
+                    center_y - height / 2,
+                    width,
+                    height,
+                    SWP_NOZORDER | SWP_NOACTIVATE,
+                )
+            } {
+                eprintln!("Failed to resize message box: {}", e.message());
+            }
+        });
+    });
+}
+
+// The caption's colors, the buttons look like its buttons
+const WIN32_BUTTON_COLOR: u32 = rgb(150, 200, 180);
+const WIN32_BUTTON_HOVER_COLOR: u32 = rgb(130, 180, 160);
+const WIN32_BUTTON_PRESSED_COLOR: u32 = rgb(110, 160, 140);
+const WIN32_FOOTER_COLOR: u32 = rgb(185, 235, 215);
+const WIN32_TEXT_COLOR: u32 = rgb(33, 33, 33);
+
+// Draws the text and the buttons on top of the content background. The focused
+// button gets a focus ring while the keyboard is in the content.
+pub(crate) fn win32_paint_message_box(
+    hdc: HDC,
+    handle: HWND,
+    content: &MessageBoxContent,
+    title_bar_rect: &RECT,
+    show_focus: bool,
+) {
+    let dpi = win32_window_dpi(handle);
+    let font = win32_caption_font(dpi);
+    let old_font = unsafe { SelectObject(hdc, font) };
+    let layout = win32_message_box_layout(hdc, handle, content, title_bar_rect);
+
+    unsafe {
+        let footer_brush = CreateSolidBrush(COLORREF(WIN32_FOOTER_COLOR));
+        FillRect(hdc, &layout.footer, footer_brush);
+        DeleteObject(footer_brush);
+
+        SetBkMode(hdc, TRANSPARENT);
+        SetTextColor(hdc, COLORREF(WIN32_TEXT_COLOR));
+        let mut text_rect = layout.text;
+        DrawTextW(
+            hdc,
+            &mut content.text.clone(),
+            &mut text_rect,
+            DT_WORDBREAK | DT_NOPREFIX,
+        );
+
+        let border_pen = CreatePen(
+            PS_INSIDEFRAME,
+            win32_dpi_scale(1, dpi).max(1),
+            COLORREF(WIN32_BUTTON_PRESSED_COLOR),
+        );
+        let old_pen = SelectObject(hdc, border_pen);
+        for (index, (&choice, button_rect)) in
+            content.choices.iter().zip(&layout.buttons).enumerate()
+        {
+            let fill_color = if content.pressed == Some(index) && content.hovered == Some(index) {
+                WIN32_BUTTON_PRESSED_COLOR
+            } else if content.hovered == Some(index) {
+                WIN32_BUTTON_HOVER_COLOR
+            } else {
+                WIN32_BUTTON_COLOR
+            };
+
+            let fill_brush = CreateSolidBrush(COLORREF(fill_color));
+            let old_brush = SelectObject(hdc, fill_brush);
+            Rectangle(
+                hdc,
+                button_rect.left,
+                button_rect.top,
+                button_rect.right,
+                button_rect.bottom,
+            );
+            SelectObject(hdc, old_brush);
+            DeleteObject(fill_brush);
+
+            let mut label: Vec<u16> = choice.label().encode_utf16().collect();
+            let mut label_rect = *button_rect;
+            DrawTextW(
+                hdc,
+                &mut label,
+                &mut label_rect,
+                DT_CENTER | DT_VCENTER | DT_SINGLELINE | DT_NOPREFIX,
+            );
+
+            if show_focus && content.focused == index {
+                let mut focus_rect = *button_rect;
+                let inset = win32_dpi_scale(WIN32_FOCUS_RING_INSET, dpi);
+                InflateRect(&mut focus_rect, -inset, -inset);
+                SetBkColor(hdc, COLORREF(fill_color));
+                DrawFocusRect(hdc, &focus_rect);
+            }
+        }
+
+        SelectObject(hdc, old_pen);
+        DeleteObject(border_pen);
+        SelectObject(hdc, old_font);
+    }
+}
+
+fn win32_message_box_button_at(
+    handle: HWND,
+    content: &MessageBoxContent,
+    title_bar_rect: &RECT,
+    point: POINT,
+) -> Option<usize> {
+    let hdc = unsafe { GetDC(handle) };
+    let layout = win32_message_box_layout(hdc, handle, content, title_bar_rect);
+    unsafe { ReleaseDC(handle, hdc) };
+    layout
+        .buttons
+        .iter()
+        .position(|rect| unsafe { PtInRect(rect, point) }.as_bool())
+}
+
+// The choice is made, the box closes and message_box returns it
+fn win32_choose(handle: HWND, content: &MessageBoxContent, index: usize) {
+    content.result.set(Some(content.choices[index]));
+    if let Err(e) = unsafe { PostMessageW(handle, WM_CLOSE, WPARAM(0), LPARAM(0)) } {
+        eprintln!("Failed to post message: {}", e.message());
+    }
+}
+
+// Mouse and keyboard input for the buttons. None leaves the message to the window.
+pub(crate) fn win32_message_box_input(
+    handle: HWND,
+    state: &mut WindowState,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> Option<LRESULT> {
+    let title_bar_rect = win32_titlebar_rect(handle, state).unwrap_or_default();
+    let content = state.message_box.as_mut()?;
+    let client_point = POINT {
+        x: get_x_param(l_param),
+        y: get_y_param(l_param),
+    };
+
+    match message {
+        // Not swallowed, the caption still needs it to drop its hover
+        WM_MOUSEMOVE => {
+            let hovered =
+                win32_message_box_button_at(handle, content, &title_bar_rect, client_point);
+            if hovered != content.hovered {
+                content.hovered = hovered;
+                unsafe { InvalidateRect(handle, None, false) };
+            }
+
+            None
+        }
+        WM_LBUTTONDOWN => {
+            let pressed =
+                win32_message_box_button_at(handle, content, &title_bar_rect, client_point)?;
+            content.pressed = Some(pressed);
+            content.focused = pressed;
+            unsafe { InvalidateRect(handle, None, false) };
+            win32_set_capture(handle, state);
+
+            Some(LRESULT(0))
+        }
+        WM_LBUTTONUP => {
+            let pressed = content.pressed.take()?;
+            unsafe { InvalidateRect(handle, None, false) };
+            if win32_message_box_button_at(handle, content, &title_bar_rect, client_point)
+                == Some(pressed)
+            {
+                win32_choose(handle, content, pressed);
+            }
+
+            win32_release_capture(state);
+            Some(LRESULT(0))
+        }
+        WM_KEYDOWN => {
+            let count = content.choices.len();
+            let direction = match VIRTUAL_KEY(w_param.0 as u16) {
+                VK_RETURN | VK_SPACE => {
+                    win32_choose(handle, content, content.focused);
+                    return Some(LRESULT(0));
+                }
+                VK_TAB if unsafe { GetKeyState(VK_SHIFT.0 as i32) } < 0 => count - 1,
+                VK_TAB | VK_RIGHT => 1,
+                VK_LEFT => count - 1,
+                _ => return None,
+            };
+
+            content.focused = (content.focused + direction) % count;
+            unsafe { InvalidateRect(handle, None, false) };
+            Some(LRESULT(0))
+        }
+        // For the focus ring
+        WM_SETFOCUS | WM_KILLFOCUS => {
+            unsafe { InvalidateRect(handle, None, false) };
+            None
+        }
+        _ => None,
+    }
+}
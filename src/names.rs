@@ -0,0 +1,148 @@
+use crate::{
+    strings::{
+        localized_string, win32_user_language_string, CAPTION_ALWAYS_ON_TOP, CAPTION_CLOSE,
+        CAPTION_MAXIMIZE, CAPTION_MINIMIZE, CAPTION_RESTORE, CAPTION_ROLL_DOWN, CAPTION_ROLL_UP,
+    },
+    wide::win32_to_wide,
+    CustomTitleBarHoveredButton,
+};
+use windows::Win32::{
+    Foundation::HWND,
+    UI::WindowsAndMessaging::{
+        GetMenuStringW, GetSystemMenu, MF_BYCOMMAND, SC_CLOSE, SC_MAXIMIZE, SC_MINIMIZE, SC_RESTORE,
+    },
+};
+
+// What the caption buttons are called in their tooltips, see Window::set_caption_button_name
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaptionButtonName {
+    RollUp,
+    RollDown,
+    AlwaysOnTop,
+    Minimize,
+    Maximize,
+    // The maximize button of a maximized window
+    Restore,
+    Close,
+}
+
+const CAPTION_BUTTON_NAME_COUNT: usize = 7;
+
+impl CaptionButtonName {
+    // In CaptionButtonName order
+    const ALL: [Self; CAPTION_BUTTON_NAME_COUNT] = [
+        Self::RollUp,
+        Self::RollDown,
+        Self::AlwaysOnTop,
+        Self::Minimize,
+        Self::Maximize,
+        Self::Restore,
+        Self::Close,
+    ];
+
+    // What button is called in its current state, None for no button
+    pub(crate) fn for_button(
+        button: CustomTitleBarHoveredButton,
+        is_maximized: bool,
+        is_shaded: bool,
+    ) -> Option<Self> {
+        match button {
+            CustomTitleBarHoveredButton::Shade if is_shaded => Some(Self::RollDown),
+            CustomTitleBarHoveredButton::Shade => Some(Self::RollUp),
+            CustomTitleBarHoveredButton::Minimize => Some(Self::Minimize),
+            CustomTitleBarHoveredButton::Maximize if is_maximized => Some(Self::Restore),
+            CustomTitleBarHoveredButton::Maximize => Some(Self::Maximize),
+            CustomTitleBarHoveredButton::Close => Some(Self::Close),
+            CustomTitleBarHoveredButton::None => None,
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Self::RollUp => CAPTION_ROLL_UP,
+            Self::RollDown => CAPTION_ROLL_DOWN,
+            Self::AlwaysOnTop => CAPTION_ALWAYS_ON_TOP,
+            Self::Minimize => CAPTION_MINIMIZE,
+            Self::Maximize => CAPTION_MAXIMIZE,
+            Self::Restore => CAPTION_RESTORE,
+            Self::Close => CAPTION_CLOSE,
+        }
+    }
+}
+
+// Null terminated tooltip texts in the user's UI language, see strings.rs. Languages
+// without strings take the standard buttons from the system menu, which comes in the
+// language of the OS, and the rest in English.
+pub(crate) struct CaptionButtonNames {
+    names: [Vec<u16>; CAPTION_BUTTON_NAME_COUNT],
+}
+
+impl CaptionButtonNames {
+    pub(crate) fn win32_for_user_language(handle: HWND) -> Self {
+        let mut names = Self::default();
+        let menu = unsafe { GetSystemMenu(handle, false) };
+
+        for name in CaptionButtonName::ALL {
+            if let Some(text) = win32_user_language_string(name.key()) {
+                names.set(name, &text);
+                continue;
+            }
+
+            let command = match name {
+                CaptionButtonName::Minimize => SC_MINIMIZE,
+                CaptionButtonName::Maximize => SC_MAXIMIZE,
+                CaptionButtonName::Restore => SC_RESTORE,
+                CaptionButtonName::Close => SC_CLOSE,
+                _ => continue,
+            };
+
+            let mut buffer = [0u16; 128];
+            let length =
+                unsafe { GetMenuStringW(menu, command, Some(&mut buffer), MF_BYCOMMAND) } as usize;
+            if menu.0 == 0 || length == 0 {
+                continue;
+            }
+
+            // "&Close\tAlt+F4" -> "Close"
+            let tab = '\t' as u16;
+            let ampersand = '&' as u16;
+            names.names[name as usize] = buffer[..length]
+                .iter()
+                .copied()
+                .take_while(|&character| character != tab)
+                .filter(|&character| character != ampersand)
+                .chain([0])
+                .collect();
+        }
+
+        names
+    }
+
+    pub(crate) fn set(&mut self, name: CaptionButtonName, text: &str) {
+        self.names[name as usize] = win32_to_wide(text);
+    }
+
+    pub(crate) fn get(
+        &self,
+        button: CustomTitleBarHoveredButton,
+        is_maximized: bool,
+        is_shaded: bool,
+    ) -> &[u16] {
+        match CaptionButtonName::for_button(button, is_maximized, is_shaded) {
+            Some(name) => self.text(name),
+            None => &[0],
+        }
+    }
+
+    pub(crate) fn text(&self, name: CaptionButtonName) -> &[u16] {
+        &self.names[name as usize]
+    }
+}
+
+impl Default for CaptionButtonNames {
+    fn default() -> Self {
+        Self {
+            names: CaptionButtonName::ALL.map(|name| win32_to_wide(&localized_string(name.key()))),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::{anyhow, Result};
+use std::mem::size_of;
+use windows::{
+    core::HSTRING,
+    Win32::{
+        Graphics::Gdi::{GetMonitorInfoW, MonitorFromRect, MONITORINFO, MONITOR_DEFAULTTONEAREST},
+        System::Registry::{
+            RegGetValueW, RegSetKeyValueW, HKEY_CURRENT_USER, REG_BINARY, RRF_RT_REG_BINARY,
+        },
+        UI::WindowsAndMessaging::WINDOWPLACEMENT,
+    },
+};
+
+// HKCU\Software\<executable name>\Window Placement, one binary value per window name.
+// Keyed by the executable so every application using the library gets its own.
+fn win32_placement_key() -> HSTRING {
+    let application = std::env::current_exe()
+        .ok()
+        .and_then(|path| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "windowsgui".to_owned());
+    HSTRING::from(format!("Software\\{}\\Window Placement", application))
+}
+
+pub(crate) fn win32_load_placement(name: &str) -> Option<WINDOWPLACEMENT> {
+    let mut placement = WINDOWPLACEMENT::default();
+    let mut size = size_of::<WINDOWPLACEMENT>() as u32;
+    let result = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            &win32_placement_key(),
+            &HSTRING::from(name),
+            RRF_RT_REG_BINARY,
+            None,
+            Some(&mut placement as *mut WINDOWPLACEMENT as _),
+            Some(&mut size),
+        )
+    };
+
+    // Nothing saved yet, or written by a build with a different layout
+    if result.is_err()
+        || size != size_of::<WINDOWPLACEMENT>() as u32
+        || placement.length != size_of::<WINDOWPLACEMENT>() as u32
+    {
+        return None;
+    }
+
+    Some(placement)
+}
+
+pub(crate) fn win32_save_placement(name: &str, placement: &WINDOWPLACEMENT) -> Result<()> {
+    unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            &win32_placement_key(),
+            &HSTRING::from(name),
+            REG_BINARY.0,
+            Some(placement as *const WINDOWPLACEMENT as _),
+            size_of::<WINDOWPLACEMENT>() as u32,
+        )
+        .ok()
+        .map_err(|e| anyhow!("Failed to save window placement: {}", e.message()))
+    }
+}
+
+// Restored sizes saved on a bigger monitor (or with a different scale) can be larger
+// than the monitor the window comes back on, which would leave the caption out of reach
+// once it is restored from maximized. Shrinks the normal rect to the work area.
+pub(crate) fn win32_fit_placement_to_monitor(placement: &mut WINDOWPLACEMENT) {
+    let normal_rect = &mut placement.rcNormalPosition;
+    let monitor = unsafe { MonitorFromRect(normal_rect, MONITOR_DEFAULTTONEAREST) };
+    let mut monitor_info = MONITORINFO {
+        cbSize: size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    if !unsafe { GetMonitorInfoW(monitor, &mut monitor_info) }.as_bool() {
+        return;
+    }
+
+    let work_area = monitor_info.rcWork;
+    let width = (normal_rect.right - normal_rect.left).min(work_area.right - work_area.left);
+    let height = (normal_rect.bottom - normal_rect.top).min(work_area.bottom - work_area.top);
+    normal_rect.right = normal_rect.left + width;
+    normal_rect.bottom = normal_rect.top + height;
+}
@@ -0,0 +1,25 @@
+use std::sync::Mutex;
+use windows::Win32::Foundation::HWND;
+
+// Every window made by the library that isn't destroyed yet, in creation order, for
+// the features that arrange them relative to each other
+static WINDOWS: Mutex<Vec<HWND>> = Mutex::new(Vec::new());
+
+pub(crate) fn win32_register_window(handle: HWND) {
+    if let Ok(mut windows) = WINDOWS.lock() {
+        windows.push(handle);
+    }
+}
+
+pub(crate) fn win32_unregister_window(handle: HWND) {
+    if let Ok(mut windows) = WINDOWS.lock() {
+        windows.retain(|&window| window != handle);
+    }
+}
+
+pub(crate) fn win32_registered_windows() -> Vec<HWND> {
+    WINDOWS
+        .lock()
+        .map(|windows| windows.clone())
+        .unwrap_or_default()
+}
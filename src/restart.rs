@@ -0,0 +1,171 @@
+use crate::{
+    registry::win32_registered_windows, wide::win32_window_title, win32_window_kind, WindowKind,
+};
+use anyhow::{anyhow, Result};
+use std::{cell::RefCell, mem::size_of};
+use windows::{
+    core::HSTRING,
+    Win32::{
+        Foundation::{HWND, RECT},
+        System::Recovery::{RegisterApplicationRestart, REGISTER_APPLICATION_RESTART_FLAGS},
+        UI::WindowsAndMessaging::{GetWindowPlacement, IsWindowVisible, WINDOWPLACEMENT},
+    },
+};
+
+// Arguments of the restart command line, one --restart-window for each window
+const RESTART_WINDOW_ARGUMENT: &str = "--restart-window=";
+const RESTART_STATE_ARGUMENT: &str = "--restart-state=";
+
+thread_local! {
+    static SAVE_STATE: RefCell<Option<Box<dyn FnMut() -> String>>> = RefCell::new(None);
+}
+
+// Has Windows start the application again after it was closed for an update, or after
+// it crashed or hung (once it has run for a minute). The new process gets the main
+// and document windows back through restarted_session, together with what save_state
+// returns. The command line is capped at 1024 characters, keep the state short.
+pub fn register_application_restart(save_state: impl FnMut() -> String + 'static) -> Result<()> {
+    SAVE_STATE.with(|cell| *cell.borrow_mut() = Some(Box::new(save_state)));
+    win32_update_restart_command_line()
+}
+
+// A window open when the application was closed for the restart
+pub struct RestartedWindow {
+    pub title: String,
+    // For WindowBuilder::placement
+    pub placement: WINDOWPLACEMENT,
+}
+
+#[derive(Default)]
+pub struct RestartedSession {
+    pub windows: Vec<RestartedWindow>,
+    // What the save_state of register_application_restart returned
+    pub state: String,
+}
+
+// None unless this process was started by Windows after register_application_restart
+pub fn restarted_session() -> Option<RestartedSession> {
+    let mut session = None;
+    for argument in std::env::args().skip(1) {
+        if let Some(window) = argument.strip_prefix(RESTART_WINDOW_ARGUMENT) {
+            let session = session.get_or_insert_with(RestartedSession::default);
+            if let Some(window) = win32_parse_restart_window(window) {
+                session.windows.push(window);
+            }
+        } else if let Some(state) = argument.strip_prefix(RESTART_STATE_ARGUMENT) {
+            session.get_or_insert_with(RestartedSession::default).state = state.to_owned();
+        }
+    }
+
+    session
+}
+
+// left,top,right,bottom,show command,title
+fn win32_parse_restart_window(value: &str) -> Option<RestartedWindow> {
+    let mut fields = value.splitn(6, ',');
+    let mut number = || fields.next()?.parse::<i32>().ok();
+    let rect = RECT {
+        left: number()?,
+        top: number()?,
+        right: number()?,
+        bottom: number()?,
+    };
+    let show_command = number()? as u32;
+
+    Some(RestartedWindow {
+        title: fields.next()?.to_owned(),
+        placement: WINDOWPLACEMENT {
+            length: size_of::<WINDOWPLACEMENT>() as u32,
+            showCmd: show_command,
+            rcNormalPosition: rect,
+            ..Default::default()
+        },
+    })
+}
+
+fn win32_restart_window_argument(handle: HWND) -> Option<String> {
+    let mut placement = WINDOWPLACEMENT {
+        length: size_of::<WINDOWPLACEMENT>() as u32,
+        ..Default::default()
+    };
+    unsafe { GetWindowPlacement(handle, &mut placement) }.ok()?;
+
+    let rect = placement.rcNormalPosition;
+    Some(format!(
+        "{}{},{},{},{},{},{}",
+        RESTART_WINDOW_ARGUMENT,
+        rect.left,
+        rect.top,
+        rect.right,
+        rect.bottom,
+        placement.showCmd,
+        win32_window_title(handle)
+    ))
+}
+
+// Quoted the way CommandLineToArgvW and the Rust runtime split them: backslashes are
+// only special in front of a quote
+fn win32_quote_argument(argument: &str) -> String {
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for character in argument.chars() {
+        match character {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                backslashes = 0;
+            }
+        }
+
+        if character != '\\' {
+            quoted.push(character);
+        }
+    }
+
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+// Registers the command line for the windows as they are now. Called again whenever
+// they change in a way worth coming back to, and when the session ends.
+pub(crate) fn win32_update_restart_command_line() -> Result<()> {
+    let Some(state) = SAVE_STATE.with(|cell| {
+        // Taken out while it runs so it can use the library
+        let mut save_state = cell.borrow_mut().take()?;
+        let state = save_state();
+        cell.borrow_mut().get_or_insert(save_state);
+        Some(state)
+    }) else {
+        return Ok(());
+    };
+
+    let mut arguments: Vec<String> = win32_registered_windows()
+        .into_iter()
+        .filter(|&handle| {
+            matches!(
+                win32_window_kind(handle),
+                WindowKind::Main | WindowKind::Document
+            ) && unsafe { IsWindowVisible(handle) }.as_bool()
+        })
+        .filter_map(win32_restart_window_argument)
+        .collect();
+    arguments.push(format!("{}{}", RESTART_STATE_ARGUMENT, state));
+
+    let command_line = arguments
+        .iter()
+        .map(|argument| win32_quote_argument(argument))
+        .collect::<Vec<_>>()
+        .join(" ");
+    unsafe {
+        RegisterApplicationRestart(
+            &HSTRING::from(command_line),
+            REGISTER_APPLICATION_RESTART_FLAGS::default(),
+        )
+        .map_err(|e| anyhow!("Failed to register application restart: {}", e.message()))
+    }
+}
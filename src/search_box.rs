@@ -0,0 +1,412 @@
+use crate::{
+    caption_buttons::{win32_caption_button_layout, win32_invalidate_added_caption_buttons},
+    caption_layout::{CaptionAlign, CaptionItem},
+    deferred::win32_defer,
+    dpi::win32_window_dpi,
+    get_x_param, get_y_param, rgb, strings, win32_caption_font, win32_dpi_scale,
+    win32_once_released, win32_titlebar_rect, win32_with_window_state, VectorIcon, WindowState,
+};
+use anyhow::{anyhow, Result};
+use windows::{
+    core::{w, HSTRING},
+    Win32::{
+        Foundation::{COLORREF, HWND, LPARAM, LRESULT, POINT, RECT, WPARAM},
+        Graphics::Gdi::{
+            CreatePen, CreateSolidBrush, DeleteObject, GetDC, GetStockObject, GetTextMetricsW,
+            PtInRect, ReleaseDC, RoundRect, SelectObject, SetBkColor, SetTextColor, HBRUSH, HDC,
+            HFONT, NULL_PEN, PS_SOLID, TEXTMETRICW,
+        },
+        UI::{
+            Controls::EM_SETCUEBANNER,
+            Input::KeyboardAndMouse::{GetFocus, SetFocus, VK_ESCAPE, VK_F6},
+            WindowsAndMessaging::{
+                CallWindowProcW, CreateWindowExW, DestroyWindow, GetParent, GetWindowLongPtrW,
+                GetWindowTextLengthW, GetWindowTextW, IsChild, IsWindow, SendMessageW,
+                SetWindowLongPtrW, SetWindowPos, SetWindowTextW, ShowWindow, EN_CHANGE,
+                EN_KILLFOCUS, EN_SETFOCUS, ES_AUTOHSCROLL, GWLP_USERDATA, GWLP_WNDPROC, HMENU,
+                SWP_NOACTIVATE, SWP_NOZORDER, SW_HIDE, SW_SHOWNA, WINDOW_EX_STYLE, WINDOW_STYLE,
+                WM_CHAR, WM_COMMAND, WM_CTLCOLOREDIT, WM_KEYDOWN, WM_LBUTTONDOWN, WM_SETFONT,
+                WNDPROC, WS_CHILD, WS_CLIPSIBLINGS, WS_TABSTOP,
+            },
+        },
+    },
+};
+
+// Sizes at 96 dpi. The field shrinks down to the minimum width before it is hidden.
+const WIN32_SEARCH_FIELD_WIDTH: i32 = 320;
+const WIN32_SEARCH_FIELD_MIN_WIDTH: i32 = 140;
+const WIN32_SEARCH_FIELD_HEIGHT: i32 = 26;
+const WIN32_SEARCH_FIELD_RADIUS: i32 = 8;
+const WIN32_SEARCH_FIELD_TEXT_PADDING: i32 = 8;
+// Kept to the title or tabs on the left and the caption buttons on the right
+const WIN32_SEARCH_FIELD_MARGIN: i32 = 16;
+
+// Lighter than the caption, like the content background it leads into
+const WIN32_SEARCH_FIELD_COLOR: u32 = rgb(185, 230, 210);
+
+// A magnifying glass
+const WIN32_SEARCH_GLYPH: &str = "M10 10 L13.5 13.5 M11 6.5 C11 9 9 11 6.5 11 C4 11 2 9 2 6.5 \
+                                  C2 4 4 2 6.5 2 C9 2 11 4 11 6.5 Z";
+
+type Win32SearchHandler = Box<dyn FnMut(&str)>;
+
+// Child id of the EDIT, for its WM_COMMAND notifications
+const WIN32_SEARCH_BOX_ID: usize = 0x5EA;
+
+// The search field of Window::set_search_box. The text is typed into a real EDIT
+// control, the rounded field around it is painted with the caption.
+pub(crate) struct SearchBox {
+    edit: HWND,
+    glyph: VectorIcon,
+    field_brush: HBRUSH,
+    // Last sent with WM_SETFONT, cleared when the caption fonts are made again
+    pub(crate) font: HFONT,
+    // Taken out while it runs
+    pub(crate) on_search: Option<Win32SearchHandler>,
+}
+
+impl Drop for SearchBox {
+    fn drop(&mut self) {
+        unsafe {
+            if IsWindow(self.edit).as_bool() {
+                if let Err(e) = DestroyWindow(self.edit) {
+                    eprintln!("Failed to destroy search box: {}", e.message());
+                }
+            }
+            DeleteObject(self.field_brush);
+        }
+    }
+}
+
+impl SearchBox {
+    pub(crate) fn win32_create(handle: HWND, on_search: Win32SearchHandler) -> Result<Self> {
+        let edit = unsafe {
+            CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                w!("EDIT"),
+                None,
+                WS_CHILD | WS_CLIPSIBLINGS | WS_TABSTOP | WINDOW_STYLE(ES_AUTOHSCROLL as u32),
+                0,
+                0,
+                0,
+                0,
+                handle,
+                HMENU(WIN32_SEARCH_BOX_ID as isize),
+                None,
+                None,
+            )
+        };
+        if edit == HWND(0) {
+            return Err(anyhow!(
+                "Failed to create search box: {}",
+                windows::core::Error::from_win32().message()
+            ));
+        }
+
+        // Subclassed for Escape and F6, the original procedure goes in its user data
+        unsafe {
+            let original = SetWindowLongPtrW(
+                edit,
+                GWLP_WNDPROC,
+                win32_search_box_proc as *const () as isize,
+            );
+            SetWindowLongPtrW(edit, GWLP_USERDATA, original);
+        }
+
+        // Needs common controls 6, older ones just show an empty field
+        let placeholder = HSTRING::from(crate::localized_string(strings::CAPTION_SEARCH));
+        unsafe {
+            SendMessageW(
+                edit,
+                EM_SETCUEBANNER,
+                WPARAM(1),
+                LPARAM(placeholder.as_ptr() as _),
+            )
+        };
+
+        Ok(Self {
+            edit,
+            glyph: VectorIcon::stroked(WIN32_SEARCH_GLYPH)?,
+            field_brush: unsafe { CreateSolidBrush(COLORREF(WIN32_SEARCH_FIELD_COLOR)) },
+            font: HFONT::default(),
+            on_search: Some(on_search),
+        })
+    }
+}
+
+// The window gets WM_KILLFOCUS and the EDIT notifies it, so after the state is let go of
+pub(crate) fn win32_focus_search_box(state: &mut WindowState) {
+    if let Some(search_box) = &state.search_box {
+        let edit = search_box.edit;
+        win32_once_released(state, move || {
+            unsafe { SetFocus(edit) };
+        });
+    }
+}
+
+// The field in the caption layout, margins included: centered, and squeezed down to
+// its minimum width and then hidden before the caption buttons have to give way
+pub(crate) fn win32_search_field_item(dpi: u32) -> CaptionItem {
+    let margins = WIN32_SEARCH_FIELD_MARGIN * 2;
+    CaptionItem::new(CaptionAlign::Center, WIN32_SEARCH_FIELD_WIDTH + margins)
+        .min_width(WIN32_SEARCH_FIELD_MIN_WIDTH + margins)
+        .shrink(2)
+        .scaled(|value| win32_dpi_scale(value, dpi))
+}
+
+// The field inside the span the layout gave it, None when it had to go
+pub(crate) fn win32_search_field_rect(
+    handle: HWND,
+    title_bar_rect: &RECT,
+    (left, right): (i32, i32),
+) -> Option<RECT> {
+    if right <= left {
+        return None;
+    }
+
+    let dpi = win32_window_dpi(handle);
+    let margin = win32_dpi_scale(WIN32_SEARCH_FIELD_MARGIN, dpi);
+    let height = win32_dpi_scale(WIN32_SEARCH_FIELD_HEIGHT, dpi)
+        .min(title_bar_rect.bottom - title_bar_rect.top);
+    let top = (title_bar_rect.top + title_bar_rect.bottom - height) / 2;
+    Some(RECT {
+        left: left + margin,
+        top,
+        right: right - margin,
+        bottom: top + height,
+    })
+}
+
+fn win32_text_height(edit: HWND, font: HFONT) -> i32 {
+    let mut metrics = TEXTMETRICW::default();
+    unsafe {
+        let hdc = GetDC(edit);
+        let old_font = SelectObject(hdc, font);
+        GetTextMetricsW(hdc, &mut metrics);
+        SelectObject(hdc, old_font);
+        ReleaseDC(edit, hdc);
+    }
+    metrics.tmHeight
+}
+
+// Puts the EDIT inside the painted field, after the glyph and one line high in the
+// middle. For size, dpi, font and caption layout changes.
+pub(crate) fn win32_layout_search_box(handle: HWND, state: &mut WindowState) {
+    let field = win32_titlebar_rect(handle, state)
+        .ok()
+        .and_then(|title_bar_rect| {
+            win32_caption_button_layout(handle, state, &title_bar_rect).search_field
+        });
+    let Some(search_box) = &mut state.search_box else {
+        return;
+    };
+    let edit = search_box.edit;
+    let Some(field) = field else {
+        // Hiding the EDIT while it has the focus hands that to the window
+        win32_once_released(state, move || {
+            unsafe { ShowWindow(edit, SW_HIDE) };
+        });
+        return;
+    };
+
+    let dpi = win32_window_dpi(handle);
+    let font = win32_caption_font(dpi);
+    if search_box.font != font {
+        search_box.font = font;
+        unsafe { SendMessageW(search_box.edit, WM_SETFONT, WPARAM(font.0 as _), LPARAM(0)) };
+    }
+
+    let text_height = win32_text_height(edit, font);
+    let left = field.left + (field.bottom - field.top);
+    let right = field.right - win32_dpi_scale(WIN32_SEARCH_FIELD_TEXT_PADDING, dpi);
+    let top = (field.top + field.bottom - text_height) / 2;
+    // The EDIT asks the window for its colors when it repaints
+    win32_once_released(state, move || unsafe {
+        if let Err(e) = SetWindowPos(
+            edit,
+            None,
+            left,
+            top,
+            (right - left).max(0),
+            text_height,
+            SWP_NOZORDER | SWP_NOACTIVATE,
+        ) {
+            eprintln!("Failed to position search box: {}", e.message());
+        }
+        ShowWindow(edit, SW_SHOWNA);
+    });
+}
+
+// The rounded field with the glyph, outlined while the EDIT has the focus
+pub(crate) fn win32_paint_search_field(
+    hdc: HDC,
+    handle: HWND,
+    search_box: &SearchBox,
+    field: &RECT,
+    item_color: COLORREF,
+) {
+    let dpi = win32_window_dpi(handle);
+    let radius = win32_dpi_scale(WIN32_SEARCH_FIELD_RADIUS, dpi);
+    let focused = unsafe { GetFocus() } == search_box.edit;
+
+    unsafe {
+        let pen = if focused {
+            CreatePen(PS_SOLID, win32_dpi_scale(1, dpi).max(1), item_color)
+        } else {
+            Default::default()
+        };
+        let old_pen = if focused {
+            SelectObject(hdc, pen)
+        } else {
+            SelectObject(hdc, GetStockObject(NULL_PEN))
+        };
+        let old_brush = SelectObject(hdc, search_box.field_brush);
+        RoundRect(
+            hdc,
+            field.left,
+            field.top,
+            field.right,
+            field.bottom,
+            radius,
+            radius,
+        );
+        SelectObject(hdc, old_brush);
+        SelectObject(hdc, old_pen);
+        if focused {
+            DeleteObject(pen);
+        }
+    }
+
+    let glyph_rect = RECT {
+        right: field.left + (field.bottom - field.top),
+        ..*field
+    };
+    search_box.glyph.draw(hdc, &glyph_rect, item_color, dpi);
+}
+
+fn win32_search_text(edit: HWND) -> String {
+    let length = unsafe { GetWindowTextLengthW(edit) };
+    let mut buffer = vec![0u16; length as usize + 1];
+    let copied = unsafe { GetWindowTextW(edit, &mut buffer) };
+    String::from_utf16_lossy(&buffer[..copied.max(0) as usize])
+}
+
+// Hands on_search the text as it is now, after the message is handled
+fn win32_search(handle: HWND, state: &WindowState) {
+    let Some(search_box) = &state.search_box else {
+        return;
+    };
+    let text = win32_search_text(search_box.edit);
+
+    win32_defer(move || {
+        let Some(mut on_search) = win32_with_window_state(handle, |state| {
+            state
+                .search_box
+                .as_mut()
+                .and_then(|search_box| search_box.on_search.take())
+        })
+        .flatten() else {
+            return;
+        };
+
+        on_search(&text);
+
+        win32_with_window_state(handle, |state| {
+            if let Some(search_box) = &mut state.search_box {
+                if search_box.on_search.is_none() {
+                    search_box.on_search = Some(on_search);
+                }
+            }
+        });
+    });
+}
+
+// The parent's side of the search box: text changes, its colors, and clicks on the
+// field around the EDIT, which WM_NCHITTEST makes client area
+pub(crate) fn win32_search_box_input(
+    handle: HWND,
+    state: &mut WindowState,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> Option<LRESULT> {
+    let search_box = state.search_box.as_ref()?;
+
+    match message {
+        WM_COMMAND if l_param.0 == search_box.edit.0 && (w_param.0 >> 16) as u32 == EN_CHANGE => {
+            win32_search(handle, state);
+            Some(LRESULT(0))
+        }
+        // For the outline of the focused field
+        WM_COMMAND
+            if l_param.0 == search_box.edit.0
+                && matches!((w_param.0 >> 16) as u32, EN_SETFOCUS | EN_KILLFOCUS) =>
+        {
+            win32_invalidate_added_caption_buttons(handle, state);
+            Some(LRESULT(0))
+        }
+        WM_CTLCOLOREDIT if l_param.0 == search_box.edit.0 => {
+            let hdc = HDC(w_param.0 as isize);
+            let focus = unsafe { GetFocus() };
+            let text_color = if focus == handle || unsafe { IsChild(handle, focus) }.as_bool() {
+                rgb(33, 33, 33)
+            } else {
+                rgb(127, 127, 127)
+            };
+            unsafe {
+                SetTextColor(hdc, COLORREF(text_color));
+                SetBkColor(hdc, COLORREF(WIN32_SEARCH_FIELD_COLOR));
+            }
+            Some(LRESULT(search_box.field_brush.0))
+        }
+        WM_LBUTTONDOWN => {
+            let point = POINT {
+                x: get_x_param(l_param),
+                y: get_y_param(l_param),
+            };
+            let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+            let field = win32_caption_button_layout(handle, state, &title_bar_rect).search_field?;
+            if !unsafe { PtInRect(&field, point) }.as_bool() {
+                return None;
+            }
+
+            win32_focus_search_box(state);
+            Some(LRESULT(0))
+        }
+        _ => None,
+    }
+}
+
+// Escape clears the text, and once it is empty goes back to the window. F6 moves on
+// to the window's next focus region.
+unsafe extern "system" fn win32_search_box_proc(
+    edit: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    let original: WNDPROC = std::mem::transmute(GetWindowLongPtrW(edit, GWLP_USERDATA));
+    let parent = GetParent(edit);
+
+    match message {
+        WM_KEYDOWN if w_param.0 == VK_ESCAPE.0 as usize => {
+            if GetWindowTextLengthW(edit) > 0 {
+                if let Err(e) = SetWindowTextW(edit, w!("")) {
+                    eprintln!("Failed to clear search box: {}", e.message());
+                }
+            } else {
+                SetFocus(parent);
+            }
+            return LRESULT(0);
+        }
+        WM_KEYDOWN if w_param.0 == VK_F6.0 as usize => {
+            SetFocus(parent);
+            return SendMessageW(parent, message, w_param, l_param);
+        }
+        // Single line edits beep on Enter and Escape
+        WM_CHAR if matches!(w_param.0, 0x0D | 0x1B) => return LRESULT(0),
+        _ => {}
+    }
+
+    CallWindowProcW(original, edit, message, w_param, l_param)
+}
@@ -0,0 +1,135 @@
+use crate::{
+    dpi::win32_window_dpi, registry::win32_registered_windows, win32_dpi_scale, win32_frame_size,
+};
+use std::mem::size_of;
+use windows::Win32::{
+    Foundation::{HWND, RECT},
+    Graphics::Gdi::{GetMonitorInfoW, MonitorFromRect, MONITORINFO, MONITOR_DEFAULTTONEAREST},
+    UI::WindowsAndMessaging::{
+        GetWindowRect, IsIconic, IsWindowVisible, IsZoomed, WMSZ_BOTTOM, WMSZ_BOTTOMLEFT,
+        WMSZ_BOTTOMRIGHT, WMSZ_LEFT, WMSZ_RIGHT, WMSZ_TOP, WMSZ_TOPLEFT, WMSZ_TOPRIGHT,
+    },
+};
+
+// What can be seen of a window rect, the side and bottom resize borders are invisible
+fn win32_visible_rect(handle: HWND, rect: &RECT) -> RECT {
+    let frame_size = win32_frame_size(handle);
+    RECT {
+        left: rect.left + frame_size.cx / 2,
+        top: rect.top,
+        right: rect.right - frame_size.cx / 2,
+        bottom: rect.bottom - frame_size.cy,
+    }
+}
+
+// Edges the window sticks to: the work area of its monitor and the other windows of
+// the application it lines up with. Vertical lines first, then horizontal ones.
+fn win32_snap_lines(handle: HWND, visible_rect: &RECT, distance: i32) -> (Vec<i32>, Vec<i32>) {
+    let mut vertical = Vec::new();
+    let mut horizontal = Vec::new();
+
+    let monitor = unsafe { MonitorFromRect(visible_rect, MONITOR_DEFAULTTONEAREST) };
+    let mut monitor_info = MONITORINFO {
+        cbSize: size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    if unsafe { GetMonitorInfoW(monitor, &mut monitor_info) }.as_bool() {
+        let work_area = monitor_info.rcWork;
+        vertical.extend([work_area.left, work_area.right]);
+        horizontal.extend([work_area.top, work_area.bottom]);
+    }
+
+    for other in win32_registered_windows() {
+        if other == handle
+            || !unsafe { IsWindowVisible(other) }.as_bool()
+            || unsafe { IsIconic(other) }.as_bool()
+            || unsafe { IsZoomed(other) }.as_bool()
+        {
+            continue;
+        }
+
+        let mut other_rect = RECT::default();
+        if unsafe { GetWindowRect(other, &mut other_rect) }.is_err() {
+            continue;
+        }
+
+        // Only edges the window could actually touch, not ones far above or beside it
+        let other_rect = win32_visible_rect(other, &other_rect);
+        if visible_rect.top - distance < other_rect.bottom
+            && visible_rect.bottom + distance > other_rect.top
+        {
+            vertical.extend([other_rect.left, other_rect.right]);
+        }
+
+        if visible_rect.left - distance < other_rect.right
+            && visible_rect.right + distance > other_rect.left
+        {
+            horizontal.extend([other_rect.top, other_rect.bottom]);
+        }
+    }
+
+    (vertical, horizontal)
+}
+
+// Smallest move that puts one of edges on one of lines, None if none is close enough
+fn win32_snap_offset(edges: &[i32], lines: &[i32], distance: i32) -> Option<i32> {
+    edges
+        .iter()
+        .flat_map(|&edge| lines.iter().map(move |&line| line - edge))
+        .filter(|offset| offset.abs() <= distance)
+        .min_by_key(|offset| offset.abs())
+}
+
+// Moves the rect given with WM_MOVING (or about to be set while dragging the caption
+// ourselves) so its edges stick to the lines within distance, at 96 dpi
+pub(crate) fn win32_snap_moving_rect(handle: HWND, rect: &mut RECT, distance: i32) {
+    let distance = win32_dpi_scale(distance, win32_window_dpi(handle));
+    let visible_rect = win32_visible_rect(handle, rect);
+    let (vertical, horizontal) = win32_snap_lines(handle, &visible_rect, distance);
+
+    let offset_x = win32_snap_offset(
+        &[visible_rect.left, visible_rect.right],
+        &vertical,
+        distance,
+    )
+    .unwrap_or(0);
+    let offset_y = win32_snap_offset(
+        &[visible_rect.top, visible_rect.bottom],
+        &horizontal,
+        distance,
+    )
+    .unwrap_or(0);
+
+    rect.left += offset_x;
+    rect.right += offset_x;
+    rect.top += offset_y;
+    rect.bottom += offset_y;
+}
+
+// Same for the rect given with WM_SIZING, only the edges being dragged move
+pub(crate) fn win32_snap_sizing_rect(handle: HWND, rect: &mut RECT, edge: u32, distance: i32) {
+    let distance = win32_dpi_scale(distance, win32_window_dpi(handle));
+    let visible_rect = win32_visible_rect(handle, rect);
+    let (vertical, horizontal) = win32_snap_lines(handle, &visible_rect, distance);
+
+    match edge {
+        WMSZ_LEFT | WMSZ_TOPLEFT | WMSZ_BOTTOMLEFT => {
+            rect.left += win32_snap_offset(&[visible_rect.left], &vertical, distance).unwrap_or(0)
+        }
+        WMSZ_RIGHT | WMSZ_TOPRIGHT | WMSZ_BOTTOMRIGHT => {
+            rect.right += win32_snap_offset(&[visible_rect.right], &vertical, distance).unwrap_or(0)
+        }
+        _ => {}
+    }
+
+    match edge {
+        WMSZ_TOP | WMSZ_TOPLEFT | WMSZ_TOPRIGHT => {
+            rect.top += win32_snap_offset(&[visible_rect.top], &horizontal, distance).unwrap_or(0)
+        }
+        WMSZ_BOTTOM | WMSZ_BOTTOMLEFT | WMSZ_BOTTOMRIGHT => {
+            rect.bottom +=
+                win32_snap_offset(&[visible_rect.bottom], &horizontal, distance).unwrap_or(0)
+        }
+        _ => {}
+    }
+}
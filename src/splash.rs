@@ -0,0 +1,180 @@
+use crate::{
+    dpi::{win32_icon_title_font, win32_window_dpi},
+    rgb,
+    wide::win32_window_text,
+    win32_dpi_scale, win32_monitor_dpi, win32_position_monitor, Position,
+};
+use anyhow::{anyhow, Result};
+use std::mem::size_of;
+use windows::{
+    core::{w, PCWSTR},
+    Win32::{
+        Foundation::{COLORREF, HWND, LPARAM, LRESULT, RECT, SIZE, WPARAM},
+        Graphics::Gdi::{
+            BeginPaint, CreateFontIndirectW, CreateSolidBrush, DeleteObject, DrawTextW, EndPaint,
+            FillRect, SelectObject, SetBkMode, SetTextColor, DT_CENTER, DT_SINGLELINE, DT_VCENTER,
+            HFONT, PAINTSTRUCT, TRANSPARENT,
+        },
+        UI::WindowsAndMessaging::{
+            CreateWindowExW, DefWindowProcW, DestroyWindow, GetClientRect, RegisterClassExW,
+            ShowWindow, CS_DROPSHADOW, SW_SHOWNOACTIVATE, WM_PAINT, WNDCLASSEXW, WS_EX_TOPMOST,
+            WS_POPUP,
+        },
+    },
+};
+
+// Settings of create_splash_window
+pub struct SplashOptions {
+    // At 96 dpi, scaled for the monitor the splash opens on
+    pub size: SIZE,
+    // Position::Default centers too, a splash has no business in a cascade
+    pub position: Position,
+    // The small shadow of menus and tooltips around the frameless window
+    pub drop_shadow: bool,
+}
+
+impl Default for SplashOptions {
+    fn default() -> Self {
+        Self {
+            size: SIZE { cx: 480, cy: 270 },
+            position: Position::Centered,
+            drop_shadow: true,
+        }
+    }
+}
+
+// Frameless window shown while the application starts, without caption, buttons or
+// resize borders. Unlike Window, closing it doesn't end the message loop.
+#[derive(Clone, Copy, PartialEq)]
+pub struct SplashWindow {
+    handle: HWND,
+}
+
+impl SplashWindow {
+    pub fn handle(&self) -> HWND {
+        self.handle
+    }
+
+    // Usually right after the main window is shown
+    pub fn close(&self) {
+        if let Err(e) = unsafe { DestroyWindow(self.handle) } {
+            eprintln!("Failed to close splash window: {}", e.message());
+        }
+    }
+}
+
+// Shows title centered on the content background until SplashWindow::close
+pub fn create_splash_window(title: PCWSTR, options: SplashOptions) -> Result<SplashWindow> {
+    // CS_DROPSHADOW is a class style, so shadowed splashes get a class of their own
+    let (window_class_name, class_style) = if options.drop_shadow {
+        (w!("Tremind Splash Shadow Window Class"), CS_DROPSHADOW)
+    } else {
+        (w!("Tremind Splash Window Class"), Default::default())
+    };
+    let window_class = WNDCLASSEXW {
+        cbSize: size_of::<WNDCLASSEXW>() as u32,
+        lpszClassName: window_class_name,
+        lpfnWndProc: Some(splash_window_proc),
+        style: class_style,
+        ..Default::default()
+    };
+
+    // Fails with ERROR_CLASS_ALREADY_EXISTS for every splash after the first, which is fine
+    unsafe { RegisterClassExW(&window_class) };
+
+    let (monitor, work_area) = win32_position_monitor(options.position, HWND(0));
+    let dpi = win32_monitor_dpi(monitor);
+    let width = win32_dpi_scale(options.size.cx, dpi);
+    let height = win32_dpi_scale(options.size.cy, dpi);
+
+    // No invisible borders to account for, the window is exactly what can be seen
+    let handle = unsafe {
+        CreateWindowExW(
+            WS_EX_TOPMOST,
+            window_class_name,
+            title,
+            WS_POPUP,
+            work_area.left + (work_area.right - work_area.left - width) / 2,
+            work_area.top + (work_area.bottom - work_area.top - height) / 2,
+            width,
+            height,
+            None,
+            None,
+            None,
+            None,
+        )
+    };
+
+    if handle == HWND(0) {
+        return Err(anyhow!(
+            "Failed to create splash window: {}",
+            windows::core::Error::from_win32().message()
+        ));
+    }
+
+    // Doesn't take the focus from whatever the user is doing while the app loads
+    unsafe { ShowWindow(handle, SW_SHOWNOACTIVATE) };
+
+    Ok(SplashWindow { handle })
+}
+
+// Title text size at 96 dpi, bigger than the caption's
+const WIN32_SPLASH_TITLE_HEIGHT: i32 = 28;
+
+unsafe extern "system" fn splash_window_proc(
+    handle: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    if message != WM_PAINT {
+        return DefWindowProcW(handle, message, w_param, l_param);
+    }
+
+    let mut ps = PAINTSTRUCT::default();
+    let hdc = BeginPaint(handle, &mut ps);
+
+    // Same background as the main window's content
+    let bg_brush = CreateSolidBrush(COLORREF(rgb(200, 250, 230)));
+    FillRect(hdc, &ps.rcPaint, bg_brush);
+    DeleteObject(bg_brush);
+
+    let dpi = win32_window_dpi(handle);
+    let title_font = if let Some(mut logical_font) = win32_icon_title_font(dpi) {
+        // Negative heights are the character height without internal leading
+        logical_font.lfHeight = -win32_dpi_scale(WIN32_SPLASH_TITLE_HEIGHT, dpi);
+        CreateFontIndirectW(&logical_font)
+    } else {
+        HFONT(0)
+    };
+
+    let old_font = if title_font != HFONT(0) {
+        HFONT(SelectObject(hdc, title_font).0)
+    } else {
+        HFONT(0)
+    };
+
+    let mut title_text_buffer = win32_window_text(handle);
+
+    let mut client_rect = RECT::default();
+    if let Err(e) = GetClientRect(handle, &mut client_rect) {
+        eprintln!("Failed to get client rect: {}", e.message());
+    }
+
+    SetBkMode(hdc, TRANSPARENT);
+    SetTextColor(hdc, COLORREF(rgb(33, 33, 33)));
+    DrawTextW(
+        hdc,
+        &mut title_text_buffer,
+        &mut client_rect,
+        DT_CENTER | DT_VCENTER | DT_SINGLELINE,
+    );
+
+    if title_font != HFONT(0) {
+        SelectObject(hdc, old_font);
+        DeleteObject(title_font);
+    }
+
+    EndPaint(handle, &ps);
+    LRESULT(0)
+}
@@ -0,0 +1,135 @@
+use crate::{
+    animation::Transition, caption_buttons::win32_caption_button_layout, dpi::win32_window_dpi,
+    rgb, wide::win32_to_wide, win32_blend_color, win32_dpi_scale, win32_titlebar_rect, WindowState,
+};
+use std::time::Duration;
+use windows::Win32::{
+    Foundation::{COLORREF, HWND, RECT},
+    Graphics::Gdi::{
+        CreateSolidBrush, DeleteObject, Ellipse, GetStockObject, InvalidateRect, SelectObject, HDC,
+        NULL_PEN,
+    },
+};
+
+// A small colored dot in the caption, e.g. to tell whether the application is
+// connected or recording, see Window::set_status_led
+pub struct StatusLed {
+    color: COLORREF,
+    // Null terminated for TTN_GETDISPINFOW
+    tooltip: Vec<u16>,
+    blinking: bool,
+    // Between WIN32_STATUS_LED_DIM and 1, how much of the color shows
+    blink: Transition,
+}
+
+const WIN32_STATUS_LED_CONNECTED: u32 = rgb(16, 137, 62);
+const WIN32_STATUS_LED_RECORDING: u32 = rgb(232, 17, 35);
+const WIN32_STATUS_LED_ERROR: u32 = rgb(247, 99, 12);
+// The room it takes in the caption and the dot's diameter at 96 dpi
+pub(crate) const WIN32_STATUS_LED_WIDTH: i32 = 24;
+const WIN32_STATUS_LED_DIAMETER: i32 = 8;
+// Blinking fades to this much of the color and back, each way taking this long
+const WIN32_STATUS_LED_DIM: f32 = 0.2;
+const WIN32_STATUS_LED_BLINK_DURATION: Duration = Duration::from_millis(600);
+pub(crate) const WIN32_STATUS_LED_TOOL_ID: usize = 0xff;
+
+impl StatusLed {
+    // Steady and without a tooltip by default
+    pub fn new(color: COLORREF) -> Self {
+        Self {
+            color,
+            tooltip: win32_to_wide(""),
+            blinking: false,
+            blink: Transition::settled(1.0),
+        }
+    }
+
+    pub fn connected() -> Self {
+        Self::new(COLORREF(WIN32_STATUS_LED_CONNECTED))
+    }
+
+    // Blinks
+    pub fn recording() -> Self {
+        Self::new(COLORREF(WIN32_STATUS_LED_RECORDING)).blinking(true)
+    }
+
+    pub fn error() -> Self {
+        Self::new(COLORREF(WIN32_STATUS_LED_ERROR))
+    }
+
+    pub fn tooltip(mut self, tooltip: &str) -> Self {
+        self.tooltip = win32_to_wide(tooltip);
+        self
+    }
+
+    // Fades in and out, unless animations are off or throttled
+    pub fn blinking(mut self, blinking: bool) -> Self {
+        self.blinking = blinking;
+        self
+    }
+
+    pub(crate) fn is_blinking(&self) -> bool {
+        self.blinking
+    }
+
+    pub(crate) fn tooltip_text(&self) -> &[u16] {
+        &self.tooltip
+    }
+
+    // For every animation frame, turns the fade around at either end. Without
+    // animations the dot rests at its full color. False when it looks as it did.
+    pub(crate) fn step_blink(&mut self, animates: bool) -> bool {
+        if !self.blinking || !animates {
+            let changed = self.blink.value() != 1.0;
+            self.blink = Transition::settled(1.0);
+            return changed;
+        }
+
+        if !self.blink.is_running() {
+            let to = if self.blink.target() == 1.0 {
+                WIN32_STATUS_LED_DIM
+            } else {
+                1.0
+            };
+            self.blink.animate_to(to, WIN32_STATUS_LED_BLINK_DURATION);
+        }
+        true
+    }
+}
+
+pub(crate) fn win32_status_led_rect(handle: HWND, state: &WindowState) -> Option<RECT> {
+    let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+    win32_caption_button_layout(handle, state, &title_bar_rect).status_led
+}
+
+pub(crate) fn win32_invalidate_status_led(handle: HWND, state: &WindowState) {
+    if let Some(rect) = win32_status_led_rect(handle, state) {
+        unsafe { InvalidateRect(handle, Some(&rect), false) };
+    }
+}
+
+// rect is the LED's room in the caption, background what the dot fades into
+pub(crate) fn win32_paint_status_led(
+    hdc: HDC,
+    handle: HWND,
+    led: &StatusLed,
+    rect: &RECT,
+    background: COLORREF,
+) {
+    let dpi = win32_window_dpi(handle);
+    let diameter = win32_dpi_scale(WIN32_STATUS_LED_DIAMETER, dpi);
+    let left = (rect.left + rect.right - diameter) / 2;
+    let top = (rect.top + rect.bottom - diameter) / 2;
+    let color = win32_blend_color(background, led.color, led.blink.value());
+
+    unsafe {
+        let brush = CreateSolidBrush(color);
+        let old_brush = SelectObject(hdc, brush);
+        let old_pen = SelectObject(hdc, GetStockObject(NULL_PEN));
+        // The null pen leaves out the right and bottom edge
+        Ellipse(hdc, left, top, left + diameter + 1, top + diameter + 1);
+        SelectObject(hdc, old_pen);
+        SelectObject(hdc, old_brush);
+        DeleteObject(brush);
+    }
+}
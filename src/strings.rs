@@ -0,0 +1,313 @@
+use std::sync::Mutex;
+use windows::Win32::Globalization::GetUserDefaultUILanguage;
+
+// Keys of the strings the library itself shows. Applications use keys of their own
+// with register_strings, prefixing them keeps them apart from these.
+pub(crate) const CAPTION_ROLL_UP: &str = "caption.roll_up";
+pub(crate) const CAPTION_ROLL_DOWN: &str = "caption.roll_down";
+pub(crate) const CAPTION_ALWAYS_ON_TOP: &str = "caption.always_on_top";
+pub(crate) const CAPTION_MINIMIZE: &str = "caption.minimize";
+pub(crate) const CAPTION_MAXIMIZE: &str = "caption.maximize";
+pub(crate) const CAPTION_RESTORE: &str = "caption.restore";
+pub(crate) const CAPTION_CLOSE: &str = "caption.close";
+pub(crate) const MENU_ALWAYS_ON_TOP: &str = "menu.always_on_top";
+pub(crate) const MESSAGE_BOX_OK: &str = "message_box.ok";
+pub(crate) const MESSAGE_BOX_CANCEL: &str = "message_box.cancel";
+pub(crate) const MESSAGE_BOX_YES: &str = "message_box.yes";
+pub(crate) const MESSAGE_BOX_NO: &str = "message_box.no";
+pub(crate) const MESSAGE_BOX_RETRY: &str = "message_box.retry";
+pub(crate) const CLOSE_MODIFIED_PROMPT: &str = "close.modified_prompt";
+pub(crate) const CAPTION_HELP: &str = "caption.help";
+pub(crate) const CAPTION_MENU: &str = "caption.menu";
+pub(crate) const CAPTION_BACK: &str = "caption.back";
+pub(crate) const CAPTION_SEARCH: &str = "caption.search";
+
+const ENGLISH: u16 = 0x09;
+
+// The library's own strings for each primary language, in the order of the keys above.
+// The wording follows what Windows itself shows for the standard buttons and dialogs.
+const KEYS: [&str; 18] = [
+    CAPTION_ROLL_UP,
+    CAPTION_ROLL_DOWN,
+    CAPTION_ALWAYS_ON_TOP,
+    CAPTION_MINIMIZE,
+    CAPTION_MAXIMIZE,
+    CAPTION_RESTORE,
+    CAPTION_CLOSE,
+    MENU_ALWAYS_ON_TOP,
+    MESSAGE_BOX_OK,
+    MESSAGE_BOX_CANCEL,
+    MESSAGE_BOX_YES,
+    MESSAGE_BOX_NO,
+    MESSAGE_BOX_RETRY,
+    CLOSE_MODIFIED_PROMPT,
+    CAPTION_HELP,
+    CAPTION_MENU,
+    CAPTION_BACK,
+    CAPTION_SEARCH,
+];
+
+const TABLES: [(u16, [&str; 18]); 9] = [
+    (
+        ENGLISH,
+        [
+            "Roll up",
+            "Roll down",
+            "Always on top",
+            "Minimize",
+            "Maximize",
+            "Restore Down",
+            "Close",
+            "Always on &top",
+            "OK",
+            "Cancel",
+            "Yes",
+            "No",
+            "Retry",
+            "Close without saving the changes?",
+            "Help",
+            "Menu",
+            "Back",
+            "Search",
+        ],
+    ),
+    (
+        0x07, // German
+        [
+            "Aufrollen",
+            "Abrollen",
+            "Immer im Vordergrund",
+            "Minimieren",
+            "Maximieren",
+            "Verkleinern",
+            "Schließen",
+            "&Immer im Vordergrund",
+            "OK",
+            "Abbrechen",
+            "Ja",
+            "Nein",
+            "Wiederholen",
+            "Ohne Speichern der Änderungen schließen?",
+            "Hilfe",
+            "Menü",
+            "Zurück",
+            "Suchen",
+        ],
+    ),
+    (
+        0x0c, // French
+        [
+            "Enrouler",
+            "Dérouler",
+            "Toujours au premier plan",
+            "Réduire",
+            "Agrandir",
+            "Niveau inférieur",
+            "Fermer",
+            "&Toujours au premier plan",
+            "OK",
+            "Annuler",
+            "Oui",
+            "Non",
+            "Réessayer",
+            "Fermer sans enregistrer les modifications ?",
+            "Aide",
+            "Menu",
+            "Précédent",
+            "Rechercher",
+        ],
+    ),
+    (
+        0x0a, // Spanish
+        [
+            "Enrollar",
+            "Desenrollar",
+            "Siempre visible",
+            "Minimizar",
+            "Maximizar",
+            "Restaurar",
+            "Cerrar",
+            "&Siempre visible",
+            "Aceptar",
+            "Cancelar",
+            "Sí",
+            "No",
+            "Reintentar",
+            "¿Cerrar sin guardar los cambios?",
+            "Ayuda",
+            "Menú",
+            "Atrás",
+            "Buscar",
+        ],
+    ),
+    (
+        0x10, // Italian
+        [
+            "Arrotola",
+            "Srotola",
+            "Sempre in primo piano",
+            "Riduci a icona",
+            "Ingrandisci",
+            "Ripristina",
+            "Chiudi",
+            "Sempre in &primo piano",
+            "OK",
+            "Annulla",
+            "Sì",
+            "No",
+            "Riprova",
+            "Chiudere senza salvare le modifiche?",
+            "Guida",
+            "Menu",
+            "Indietro",
+            "Cerca",
+        ],
+    ),
+    (
+        0x16, // Portuguese
+        [
+            "Enrolar",
+            "Desenrolar",
+            "Sempre visível",
+            "Minimizar",
+            "Maximizar",
+            "Restaurar",
+            "Fechar",
+            "&Sempre visível",
+            "OK",
+            "Cancelar",
+            "Sim",
+            "Não",
+            "Repetir",
+            "Fechar sem salvar as alterações?",
+            "Ajuda",
+            "Menu",
+            "Voltar",
+            "Pesquisar",
+        ],
+    ),
+    (
+        0x13, // Dutch
+        [
+            "Oprollen",
+            "Afrollen",
+            "Altijd op voorgrond",
+            "Minimaliseren",
+            "Maximaliseren",
+            "Vorige grootte",
+            "Sluiten",
+            "&Altijd op voorgrond",
+            "OK",
+            "Annuleren",
+            "Ja",
+            "Nee",
+            "Opnieuw",
+            "Sluiten zonder de wijzigingen op te slaan?",
+            "Help",
+            "Menu",
+            "Terug",
+            "Zoeken",
+        ],
+    ),
+    (
+        0x11, // Japanese
+        [
+            "折りたたむ",
+            "展開する",
+            "常に手前に表示",
+            "最小化",
+            "最大化",
+            "元に戻す (縮小)",
+            "閉じる",
+            "常に手前に表示(&T)",
+            "OK",
+            "キャンセル",
+            "はい",
+            "いいえ",
+            "再試行",
+            "変更を保存せずに閉じますか?",
+            "ヘルプ",
+            "メニュー",
+            "戻る",
+            "検索",
+        ],
+    ),
+    (
+        0x04, // Chinese
+        [
+            "卷起",
+            "展开",
+            "置于顶层",
+            "最小化",
+            "最大化",
+            "向下还原",
+            "关闭",
+            "置于顶层(&T)",
+            "确定",
+            "取消",
+            "是",
+            "否",
+            "重试",
+            "不保存更改就关闭吗?",
+            "帮助",
+            "菜单",
+            "返回",
+            "搜索",
+        ],
+    ),
+];
+
+// Added by the application, searched before the tables so it can reword the
+// library's strings as well
+static REGISTERED: Mutex<Vec<(u16, String, String)>> = Mutex::new(Vec::new());
+
+// Adds (key, text) pairs for language, a LANGID like 0x0407 for German (Germany) or
+// just the primary language like 0x07 for every German. Registering a key again
+// replaces its text.
+pub fn register_strings(language: u16, strings: &[(&str, &str)]) {
+    let Ok(mut registered) = REGISTERED.lock() else {
+        return;
+    };
+
+    for &(key, text) in strings {
+        registered.retain(|(registered_language, registered_key, _)| {
+            *registered_language != language || registered_key != key
+        });
+        registered.push((language, key.to_owned(), text.to_owned()));
+    }
+}
+
+// The text of key in the user's UI language, falling back to the primary language,
+// then English, then the key itself
+pub fn localized_string(key: &str) -> String {
+    win32_user_language_string(key)
+        .or_else(|| win32_language_string(ENGLISH, key))
+        .unwrap_or_else(|| key.to_owned())
+}
+
+// Only what exists for the user's UI language, without the English fallback
+pub(crate) fn win32_user_language_string(key: &str) -> Option<String> {
+    let language = unsafe { GetUserDefaultUILanguage() };
+    win32_language_string(language, key).or_else(|| win32_language_string(language & 0x3ff, key))
+}
+
+fn win32_language_string(language: u16, key: &str) -> Option<String> {
+    if let Ok(registered) = REGISTERED.lock() {
+        if let Some((_, _, text)) =
+            registered
+                .iter()
+                .find(|(registered_language, registered_key, _)| {
+                    *registered_language == language && registered_key == key
+                })
+        {
+            return Some(text.clone());
+        }
+    }
+
+    // The tables only know primary languages
+    let index = KEYS.iter().position(|&table_key| table_key == key)?;
+    TABLES
+        .iter()
+        .find(|(table_language, _)| *table_language == language)
+        .map(|(_, table)| table[index].to_owned())
+}
@@ -0,0 +1,426 @@
+use crate::{
+    caption_buttons::win32_caption_button_layout,
+    dpi::win32_window_dpi,
+    fonts::win32_measure_text,
+    get_x_param, get_y_param,
+    menu_bar::{win32_menu_bar_right, win32_track_client_leave},
+    rgb, win32_caption_font, win32_caption_icon_rect, win32_dispatch_event, win32_dpi_scale,
+    win32_invalidate_rect, win32_release_capture, win32_set_capture, win32_titlebar_rect,
+    WindowEvent, WindowState,
+};
+use windows::Win32::{
+    Foundation::{COLORREF, HWND, LPARAM, LRESULT, POINT, RECT, WPARAM},
+    Graphics::Gdi::{
+        CreatePen, CreateSolidBrush, DeleteObject, DrawTextW, FillRect, LineTo, MoveToEx, PtInRect,
+        SelectObject, SetBkMode, SetTextColor, DT_END_ELLIPSIS, DT_NOPREFIX, DT_SINGLELINE,
+        DT_VCENTER, HDC, HFONT, PS_SOLID, TRANSPARENT,
+    },
+    UI::{
+        Controls::WM_MOUSELEAVE,
+        Input::KeyboardAndMouse::{GetKeyState, VIRTUAL_KEY, VK_CONTROL, VK_SHIFT, VK_TAB},
+        WindowsAndMessaging::{
+            WM_CAPTURECHANGED, WM_KEYDOWN, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN,
+            WM_MOUSEMOVE,
+        },
+    },
+};
+
+// Tabs drawn in the caption in place of the title, see Window::add_tab
+#[derive(Default)]
+pub(crate) struct TabStrip {
+    titles: Vec<Vec<u16>>,
+    active: usize,
+    hovered: Option<TabPart>,
+    // Close and new tab buttons act on release, the mouse is captured until then
+    pressed: Option<TabPart>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TabPart {
+    Tab(usize),
+    Close(usize),
+    NewTab,
+}
+
+impl TabStrip {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.titles.is_empty()
+    }
+
+    pub(crate) fn active(&self) -> Option<usize> {
+        (!self.titles.is_empty()).then_some(self.active)
+    }
+
+    pub(crate) fn add(&mut self, title: &str) -> usize {
+        self.titles.push(title.encode_utf16().collect());
+        self.titles.len() - 1
+    }
+
+    // The tab after the removed one becomes active if it was, the last one otherwise
+    pub(crate) fn remove(&mut self, index: usize) {
+        if index >= self.titles.len() {
+            return;
+        }
+
+        self.titles.remove(index);
+        if self.active > index || self.active == self.titles.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+
+        self.hovered = None;
+        self.pressed = None;
+    }
+
+    pub(crate) fn set_title(&mut self, index: usize, title: &str) {
+        if let Some(tab_title) = self.titles.get_mut(index) {
+            *tab_title = title.encode_utf16().collect();
+        }
+    }
+
+    pub(crate) fn set_active(&mut self, index: usize) {
+        if index < self.titles.len() {
+            self.active = index;
+        }
+    }
+}
+
+// Sizes at 96 dpi
+const WIN32_TAB_MAX_WIDTH: i32 = 200;
+const WIN32_TAB_TOP_MARGIN: i32 = 6;
+const WIN32_TAB_GAP: i32 = 2;
+const WIN32_TAB_TEXT_PADDING: i32 = 10;
+const WIN32_TAB_CLOSE_SIZE: i32 = 16;
+const WIN32_TAB_GLYPH_SIZE: i32 = 8;
+const WIN32_NEW_TAB_BUTTON_WIDTH: i32 = 32;
+// Gap to the icon or the window edge on the left and the caption buttons on the right
+const WIN32_TAB_STRIP_PADDING: i32 = 10;
+
+struct TabStripLayout {
+    // Tab and its close button
+    tabs: Vec<(RECT, RECT)>,
+    new_tab: RECT,
+}
+
+// Tabs are as wide as their titles up to a maximum, with the new tab button right
+// after the last one. When they don't all fit they share the space between the icon
+// and the caption buttons equally.
+fn win32_tab_strip_layout(
+    handle: HWND,
+    state: &WindowState,
+    title_bar_rect: &RECT,
+) -> TabStripLayout {
+    let tabs = &state.tabs;
+    let dpi = win32_window_dpi(handle);
+    let padding = win32_dpi_scale(WIN32_TAB_STRIP_PADDING, dpi);
+    let gap = win32_dpi_scale(WIN32_TAB_GAP, dpi);
+    let close_size = win32_dpi_scale(WIN32_TAB_CLOSE_SIZE, dpi);
+    let text_padding = win32_dpi_scale(WIN32_TAB_TEXT_PADDING, dpi);
+    let new_tab_width = win32_dpi_scale(WIN32_NEW_TAB_BUTTON_WIDTH, dpi);
+    let max_width = win32_dpi_scale(WIN32_TAB_MAX_WIDTH, dpi);
+    let caption_button_layout = win32_caption_button_layout(handle, state, title_bar_rect);
+
+    let left = win32_menu_bar_right(handle, state, title_bar_rect).unwrap_or_else(|| {
+        win32_caption_icon_rect(handle, state, title_bar_rect)
+            .map_or(caption_button_layout.leading_right, |icon_rect| {
+                icon_rect.right
+            })
+    }) + padding;
+    let right = (caption_button_layout.content_right - padding).max(left);
+    let top = title_bar_rect.top + win32_dpi_scale(WIN32_TAB_TOP_MARGIN, dpi);
+
+    let count = tabs.titles.len() as i32;
+    let titles: Vec<&[u16]> = tabs.titles.iter().map(Vec::as_slice).collect();
+    let mut widths: Vec<i32> = win32_measure_text(win32_caption_font(dpi), &titles, DT_NOPREFIX)
+        .into_iter()
+        .map(|size| (size.cx + text_padding * 2 + close_size).min(max_width))
+        .collect();
+    let available = right - left - new_tab_width - gap * count;
+    if widths.iter().sum::<i32>() > available && count > 0 {
+        widths.fill((available / count).clamp(0, max_width));
+    }
+
+    let mut tab_left = left;
+    let tabs = widths
+        .iter()
+        .map(|&tab_width| {
+            let tab = RECT {
+                left: tab_left,
+                top,
+                right: tab_left + tab_width,
+                bottom: title_bar_rect.bottom,
+            };
+            tab_left += tab_width + gap;
+            let close_top = (tab.top + tab.bottom - close_size) / 2;
+            let close = RECT {
+                left: tab.right - text_padding / 2 - close_size,
+                top: close_top,
+                right: tab.right - text_padding / 2,
+                bottom: close_top + close_size,
+            };
+            (tab, close)
+        })
+        .collect();
+
+    let new_tab = RECT {
+        left: tab_left,
+        top,
+        right: (tab_left + new_tab_width).min(right),
+        bottom: title_bar_rect.bottom,
+    };
+
+    TabStripLayout { tabs, new_tab }
+}
+
+fn win32_tab_part_at(handle: HWND, state: &WindowState, point: POINT) -> Option<TabPart> {
+    if state.tabs.is_empty() {
+        return None;
+    }
+
+    let title_bar_rect = win32_titlebar_rect(handle, state).ok()?;
+    let layout = win32_tab_strip_layout(handle, state, &title_bar_rect);
+    let contains = |rect: &RECT| unsafe { PtInRect(rect, point) }.as_bool();
+    if contains(&layout.new_tab) {
+        return Some(TabPart::NewTab);
+    }
+
+    layout
+        .tabs
+        .iter()
+        .position(|(tab, _)| contains(tab))
+        .map(|index| {
+            if contains(&layout.tabs[index].1) {
+                TabPart::Close(index)
+            } else {
+                TabPart::Tab(index)
+            }
+        })
+}
+
+// For WM_NCHITTEST, the tabs take mouse input like the content while the rest of the
+// strip still drags the window
+pub(crate) fn win32_is_over_tab(handle: HWND, state: &WindowState, point: POINT) -> bool {
+    win32_tab_part_at(handle, state, point).is_some()
+}
+
+// Colors next to the caption's, the active tab takes the content's to join it
+const WIN32_ACTIVE_TAB_COLOR: u32 = rgb(200, 250, 230);
+const WIN32_TAB_HOVER_COLOR: u32 = rgb(170, 220, 200);
+const WIN32_TAB_BUTTON_HOVER_COLOR: u32 = rgb(130, 180, 160);
+const WIN32_TAB_BUTTON_PRESSED_COLOR: u32 = rgb(110, 160, 140);
+
+pub(crate) fn win32_paint_tab_strip(
+    hdc: HDC,
+    handle: HWND,
+    state: &WindowState,
+    title_bar_rect: &RECT,
+    item_color: COLORREF,
+) {
+    let tabs = &state.tabs;
+    if tabs.is_empty() {
+        return;
+    }
+
+    let dpi = win32_window_dpi(handle);
+    let layout = win32_tab_strip_layout(handle, state, title_bar_rect);
+    let text_padding = win32_dpi_scale(WIN32_TAB_TEXT_PADDING, dpi);
+    let glyph_size = win32_dpi_scale(WIN32_TAB_GLYPH_SIZE, dpi);
+    let font = win32_caption_font(dpi);
+
+    let fill = |rect: &RECT, color: u32| unsafe {
+        let brush = CreateSolidBrush(COLORREF(color));
+        FillRect(hdc, rect, brush);
+        DeleteObject(brush);
+    };
+    let button_color = |part: TabPart| {
+        if tabs.pressed == Some(part) && tabs.hovered == Some(part) {
+            Some(WIN32_TAB_BUTTON_PRESSED_COLOR)
+        } else if tabs.hovered == Some(part) {
+            Some(WIN32_TAB_BUTTON_HOVER_COLOR)
+        } else {
+            None
+        }
+    };
+
+    unsafe {
+        let old_font = if font != HFONT(0) {
+            HFONT(SelectObject(hdc, font).0)
+        } else {
+            HFONT(0)
+        };
+        let glyph_pen = CreatePen(PS_SOLID, win32_dpi_scale(1, dpi).max(1), item_color);
+        let old_pen = SelectObject(hdc, glyph_pen);
+        SetBkMode(hdc, TRANSPARENT);
+        SetTextColor(hdc, item_color);
+
+        for (index, (tab_rect, close_rect)) in layout.tabs.iter().enumerate() {
+            if index == tabs.active {
+                fill(tab_rect, WIN32_ACTIVE_TAB_COLOR);
+            } else if tabs
+                .hovered
+                .is_some_and(|part| part == TabPart::Tab(index) || part == TabPart::Close(index))
+            {
+                fill(tab_rect, WIN32_TAB_HOVER_COLOR);
+            }
+
+            let mut text_rect = RECT {
+                left: tab_rect.left + text_padding,
+                right: (close_rect.left - text_padding / 2).max(tab_rect.left + text_padding),
+                ..*tab_rect
+            };
+            let mut title = tabs.titles[index].clone();
+            DrawTextW(
+                hdc,
+                &mut title,
+                &mut text_rect,
+                DT_VCENTER | DT_SINGLELINE | DT_END_ELLIPSIS | DT_NOPREFIX,
+            );
+
+            if let Some(color) = button_color(TabPart::Close(index)) {
+                fill(close_rect, color);
+            }
+
+            // A small cross
+            let center_x = (close_rect.left + close_rect.right) / 2;
+            let center_y = (close_rect.top + close_rect.bottom) / 2;
+            let half = glyph_size / 2;
+            MoveToEx(hdc, center_x - half, center_y - half, None);
+            LineTo(hdc, center_x + half + 1, center_y + half + 1);
+            MoveToEx(hdc, center_x + half, center_y - half, None);
+            LineTo(hdc, center_x - half - 1, center_y + half + 1);
+        }
+
+        if let Some(color) = button_color(TabPart::NewTab) {
+            fill(&layout.new_tab, color);
+        }
+
+        // A plus
+        if layout.new_tab.right > layout.new_tab.left {
+            let center_x = (layout.new_tab.left + layout.new_tab.right) / 2;
+            let center_y = (layout.new_tab.top + layout.new_tab.bottom) / 2;
+            let half = glyph_size / 2 + 1;
+            MoveToEx(hdc, center_x - half, center_y, None);
+            LineTo(hdc, center_x + half + 1, center_y);
+            MoveToEx(hdc, center_x, center_y - half, None);
+            LineTo(hdc, center_x, center_y + half + 1);
+        }
+
+        SelectObject(hdc, old_pen);
+        DeleteObject(glyph_pen);
+        if font != HFONT(0) {
+            SelectObject(hdc, old_font);
+        }
+    }
+}
+
+pub(crate) fn win32_invalidate_tab_strip(handle: HWND, state: &mut WindowState) {
+    if let Ok(title_bar_rect) = win32_titlebar_rect(handle, state) {
+        win32_invalidate_rect(handle, state, &title_bar_rect);
+    }
+}
+
+fn win32_set_hovered_tab_part(handle: HWND, state: &mut WindowState, part: Option<TabPart>) {
+    if state.tabs.hovered != part {
+        state.tabs.hovered = part;
+        win32_invalidate_tab_strip(handle, state);
+    }
+}
+
+fn win32_select_tab(handle: HWND, state: &mut WindowState, index: usize) {
+    if state.tabs.active != index {
+        state.tabs.active = index;
+        win32_invalidate_tab_strip(handle, state);
+        win32_dispatch_event(handle, WindowEvent::TabSelected(index));
+    }
+}
+
+// Mouse input over the tabs, which WM_NCHITTEST makes client area, and Ctrl+Tab to
+// cycle them. None leaves the message to the window.
+pub(crate) fn win32_tab_strip_input(
+    handle: HWND,
+    state: &mut WindowState,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> Option<LRESULT> {
+    if state.tabs.is_empty() {
+        return None;
+    }
+
+    let client_point = POINT {
+        x: get_x_param(l_param),
+        y: get_y_param(l_param),
+    };
+
+    match message {
+        WM_MOUSEMOVE => {
+            let part = win32_tab_part_at(handle, state, client_point);
+            win32_set_hovered_tab_part(handle, state, part);
+
+            // Moving on to the caption around the tabs is a WM_MOUSELEAVE
+            if part.is_some() {
+                win32_track_client_leave(handle, state);
+            }
+
+            None
+        }
+        WM_MOUSELEAVE if state.tabs.pressed.is_none() => {
+            win32_set_hovered_tab_part(handle, state, None);
+            None
+        }
+        // Tabs are selected on press like in browsers, the buttons wait for the release
+        WM_LBUTTONDOWN => {
+            let part = win32_tab_part_at(handle, state, client_point)?;
+            if let TabPart::Tab(index) = part {
+                win32_select_tab(handle, state, index);
+            } else {
+                state.tabs.pressed = Some(part);
+                win32_set_capture(handle, state);
+                win32_invalidate_tab_strip(handle, state);
+            }
+
+            Some(LRESULT(0))
+        }
+        WM_LBUTTONUP => {
+            let pressed = state.tabs.pressed.take()?;
+            win32_release_capture(state);
+
+            win32_invalidate_tab_strip(handle, state);
+            if win32_tab_part_at(handle, state, client_point) == Some(pressed) {
+                let event = match pressed {
+                    TabPart::Close(index) => WindowEvent::TabCloseRequested(index),
+                    _ => WindowEvent::NewTabRequested,
+                };
+                win32_dispatch_event(handle, event);
+            }
+
+            Some(LRESULT(0))
+        }
+        // Middle clicking a tab closes it, as in browsers
+        WM_MBUTTONDOWN => match win32_tab_part_at(handle, state, client_point)? {
+            TabPart::Tab(index) | TabPart::Close(index) => {
+                win32_dispatch_event(handle, WindowEvent::TabCloseRequested(index));
+                Some(LRESULT(0))
+            }
+            TabPart::NewTab => None,
+        },
+        WM_CAPTURECHANGED if state.tabs.pressed.is_some() => {
+            state.tabs.pressed = None;
+            win32_invalidate_tab_strip(handle, state);
+            Some(LRESULT(0))
+        }
+        WM_KEYDOWN
+            if VIRTUAL_KEY(w_param.0 as u16) == VK_TAB
+                && unsafe { GetKeyState(VK_CONTROL.0 as i32) } < 0 =>
+        {
+            let count = state.tabs.titles.len();
+            let step = if unsafe { GetKeyState(VK_SHIFT.0 as i32) } < 0 {
+                count - 1
+            } else {
+                1
+            };
+            win32_select_tab(handle, state, (state.tabs.active + step) % count);
+            Some(LRESULT(0))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{
+    registry::win32_registered_windows, win32_frame_size_for_dpi, win32_monitor_dpi,
+    win32_with_window_state, WindowKind,
+};
+use anyhow::{anyhow, Result};
+use std::mem::size_of;
+use windows::Win32::{
+    Foundation::{HWND, RECT},
+    Graphics::Gdi::{
+        GetMonitorInfoW, MonitorFromWindow, HMONITOR, MONITORINFO, MONITOR_DEFAULTTONEAREST,
+    },
+    UI::WindowsAndMessaging::{
+        IsIconic, IsWindowVisible, IsZoomed, SetWindowPos, ShowWindow, SWP_NOACTIVATE,
+        SWP_NOZORDER, SW_RESTORE,
+    },
+};
+
+// How Window::tile_windows arranges the application's windows
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TileLayout {
+    // One column each, over the full height
+    SideBySide,
+    // As close to square as it gets, the last row shares its width among fewer windows
+    Grid,
+}
+
+// Main and document windows that are shown normally. Tool windows and dialogs float
+// over the others, the temporary modes keep their own placement.
+fn win32_is_tileable(handle: HWND) -> bool {
+    win32_with_window_state(handle, |state| {
+        matches!(state.options.kind, WindowKind::Main | WindowKind::Document)
+            && unsafe { IsWindowVisible(handle) }.as_bool()
+            && !unsafe { IsIconic(handle) }.as_bool()
+            && !state.hides_caption()
+            && state.shade_restore_height.is_none()
+    })
+    .unwrap_or(false)
+}
+
+// Visible rects of count cells filling work_area
+fn win32_tile_rects(layout: TileLayout, work_area: &RECT, count: i32) -> Vec<RECT> {
+    let (columns, rows) = match layout {
+        TileLayout::SideBySide => (count, 1),
+        TileLayout::Grid => {
+            let columns = (count as f32).sqrt().ceil() as i32;
+            (columns, (count + columns - 1) / columns)
+        }
+    };
+
+    let width = work_area.right - work_area.left;
+    let height = work_area.bottom - work_area.top;
+    (0..count)
+        .map(|index| {
+            let row = index / columns;
+            let column = index % columns;
+            let row_columns = if row == rows - 1 {
+                count - row * columns
+            } else {
+                columns
+            };
+
+            // Spread from the edges so the rounding doesn't leave a gap at the end
+            RECT {
+                left: work_area.left + width * column / row_columns,
+                top: work_area.top + height * row / rows,
+                right: work_area.left + width * (column + 1) / row_columns,
+                bottom: work_area.top + height * (row + 1) / rows,
+            }
+        })
+        .collect()
+}
+
+// Arranges the tileable windows in the work area of the monitor handle is on, in the
+// order they were created. Sized for that monitor's dpi, windows coming from another
+// monitor rescale for it on their own.
+pub(crate) fn win32_tile_windows(handle: HWND, layout: TileLayout) -> Result<()> {
+    let monitor = unsafe { MonitorFromWindow(handle, MONITOR_DEFAULTTONEAREST) };
+    let mut monitor_info = MONITORINFO {
+        cbSize: size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    if !unsafe { GetMonitorInfoW(monitor, &mut monitor_info) }.as_bool() {
+        return Err(anyhow!("Failed to get monitor info"));
+    }
+
+    let windows: Vec<HWND> = win32_registered_windows()
+        .into_iter()
+        .filter(|&window| win32_is_tileable(window))
+        .collect();
+    let rects = win32_tile_rects(layout, &monitor_info.rcWork, windows.len() as i32);
+    for (window, rect) in windows.into_iter().zip(rects) {
+        win32_place_tile(window, monitor, &rect)?;
+    }
+
+    Ok(())
+}
+
+fn win32_place_tile(handle: HWND, monitor: HMONITOR, visible_rect: &RECT) -> Result<()> {
+    // Maximized and snapped windows get their normal style back first
+    if unsafe { IsZoomed(handle) }.as_bool() {
+        unsafe { ShowWindow(handle, SW_RESTORE) };
+    }
+
+    // The invisible resize borders go around the cell
+    let frame_size = win32_frame_size_for_dpi(win32_monitor_dpi(monitor));
+    unsafe {
+        SetWindowPos(
+            handle,
+            None,
+            visible_rect.left - frame_size.cx / 2,
+            visible_rect.top,
+            visible_rect.right - visible_rect.left + frame_size.cx,
+            visible_rect.bottom - visible_rect.top + frame_size.cy,
+            SWP_NOZORDER | SWP_NOACTIVATE,
+        )
+        .map_err(|e| anyhow!("Failed to tile window: {}", e.message()))
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::{anyhow, Result};
+use std::mem::size_of;
+use windows::{
+    core::PWSTR,
+    Win32::{
+        Foundation::{HWND, LPARAM, POINT, RECT, WPARAM},
+        UI::{
+            Controls::{
+                InitCommonControlsEx, ICC_BAR_CLASSES, INITCOMMONCONTROLSEX, TOOLTIPS_CLASSW,
+                TTM_ADDTOOLW, TTM_DELTOOLW, TTM_NEWTOOLRECTW, TTM_POP, TTM_RELAYEVENT,
+                TTS_ALWAYSTIP, TTS_NOPREFIX, TTTOOLINFOW,
+            },
+            WindowsAndMessaging::{
+                CreateWindowExW, SendMessageW, CW_USEDEFAULT, MSG, WINDOW_STYLE, WM_MOUSEMOVE,
+                WS_EX_TOPMOST, WS_POPUP,
+            },
+        },
+    },
+};
+
+// LPSTR_TEXTCALLBACKW, the text is asked for with TTN_GETDISPINFOW every time the tip shows
+const WIN32_TOOLTIP_TEXT_CALLBACK: PWSTR = PWSTR(usize::MAX as *mut u16);
+
+// Tooltips live in comctl32, which has to be initialized before the class exists
+pub(crate) fn win32_init_tooltips() {
+    let init = INITCOMMONCONTROLSEX {
+        dwSize: size_of::<INITCOMMONCONTROLSEX>() as u32,
+        dwICC: ICC_BAR_CLASSES,
+    };
+
+    if !unsafe { InitCommonControlsEx(&init) }.as_bool() {
+        eprintln!("Failed to initialize common controls");
+    }
+}
+
+fn win32_tool_info(owner: HWND, id: usize, rect: RECT) -> TTTOOLINFOW {
+    TTTOOLINFOW {
+        cbSize: size_of::<TTTOOLINFOW>() as u32,
+        hwnd: owner,
+        uId: id,
+        rect,
+        lpszText: WIN32_TOOLTIP_TEXT_CALLBACK,
+        ..Default::default()
+    }
+}
+
+// Creates a tooltip owned by owner with one tool per id. The tools start with empty
+// rects, see win32_set_tool_rect.
+pub(crate) fn win32_create_tooltip(owner: HWND, ids: &[usize]) -> Result<HWND> {
+    let tooltip = unsafe {
+        CreateWindowExW(
+            WS_EX_TOPMOST,
+            TOOLTIPS_CLASSW,
+            None,
+            WS_POPUP | WINDOW_STYLE(TTS_ALWAYSTIP | TTS_NOPREFIX),
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            owner,
+            None,
+            None,
+            None,
+        )
+    };
+
+    if tooltip == HWND(0) {
+        return Err(anyhow!(
+            "Failed to create tooltip: {}",
+            windows::core::Error::from_win32().message()
+        ));
+    }
+
+    for &id in ids {
+        win32_add_tool(tooltip, owner, id);
+    }
+
+    Ok(tooltip)
+}
+
+// Another tool with an empty rect, for a button that came after the tooltip
+pub(crate) fn win32_add_tool(tooltip: HWND, owner: HWND, id: usize) {
+    let tool_info = win32_tool_info(owner, id, RECT::default());
+    unsafe {
+        SendMessageW(
+            tooltip,
+            TTM_ADDTOOLW,
+            WPARAM(0),
+            LPARAM(&tool_info as *const TTTOOLINFOW as _),
+        )
+    };
+}
+
+pub(crate) fn win32_remove_tool(tooltip: HWND, owner: HWND, id: usize) {
+    let tool_info = win32_tool_info(owner, id, RECT::default());
+    unsafe {
+        SendMessageW(
+            tooltip,
+            TTM_DELTOOLW,
+            WPARAM(0),
+            LPARAM(&tool_info as *const TTTOOLINFOW as _),
+        )
+    };
+}
+
+// rect is in client coordinates of the owner
+pub(crate) fn win32_set_tool_rect(tooltip: HWND, owner: HWND, id: usize, rect: RECT) {
+    let tool_info = win32_tool_info(owner, id, rect);
+    unsafe {
+        SendMessageW(
+            tooltip,
+            TTM_NEWTOOLRECTW,
+            WPARAM(0),
+            LPARAM(&tool_info as *const TTTOOLINFOW as _),
+        )
+    };
+}
+
+// The tooltip only watches client mouse messages, but the caption is non client area
+// as far as Windows is concerned. Relaying a made up WM_MOUSEMOVE at the client
+// coordinates lets it run its usual hover delay.
+pub(crate) fn win32_relay_mouse_move(tooltip: HWND, owner: HWND, client_point: POINT) {
+    let message = MSG {
+        hwnd: owner,
+        message: WM_MOUSEMOVE,
+        wParam: WPARAM(0),
+        lParam: LPARAM(
+            ((client_point.x as u16 as u32) | ((client_point.y as u16 as u32) << 16)) as _,
+        ),
+        ..Default::default()
+    };
+
+    unsafe {
+        SendMessageW(
+            tooltip,
+            TTM_RELAYEVENT,
+            WPARAM(0),
+            LPARAM(&message as *const MSG as _),
+        )
+    };
+}
+
+pub(crate) fn win32_hide_tooltip(tooltip: HWND) {
+    unsafe { SendMessageW(tooltip, TTM_POP, WPARAM(0), LPARAM(0)) };
+}
@@ -0,0 +1,333 @@
+use crate::win32_dpi_scale;
+use anyhow::{anyhow, Result};
+use windows::Win32::{
+    Foundation::{COLORREF, POINT, RECT},
+    Graphics::Gdi::{
+        BeginPath, CloseFigure, CreatePen, CreateSolidBrush, DeleteObject, EndPath, FillPath,
+        LineTo, MoveToEx, PolyBezierTo, SelectObject, StrokePath, HDC, PS_SOLID,
+    },
+};
+
+// Side of the box icon coordinates are in, like the 16px glyphs of the system buttons
+const VECTOR_ICON_SIZE: f32 = 16.0;
+
+// A glyph for a caption button given as path data, drawn at the size for the dpi in
+// whatever color the caption glyphs have at the time. Understands the M, L, H, V, C
+// and Z commands of SVG paths, absolute and relative, in a 16 x 16 box.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VectorIcon {
+    commands: Vec<PathCommand>,
+    filled: bool,
+}
+
+// Absolute coordinates in the 16 x 16 box
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PathCommand {
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    // Two control points and the end point
+    CubicTo([f32; 6]),
+    Close,
+}
+
+impl VectorIcon {
+    // Outlines drawn with a line as wide as the ones of the system glyphs, e.g.
+    // "M3 8 H13 M8 3 V13" for a plus
+    pub fn stroked(path_data: &str) -> Result<Self> {
+        Ok(Self {
+            commands: parse_path_data(path_data)?,
+            filled: false,
+        })
+    }
+
+    // Closed shapes filled with the glyph color
+    pub fn filled(path_data: &str) -> Result<Self> {
+        Ok(Self {
+            commands: parse_path_data(path_data)?,
+            filled: true,
+        })
+    }
+
+    // Centered in rect at 16px scaled for dpi, snapped to whole pixels so the lines
+    // stay sharp
+    pub fn draw(&self, hdc: HDC, rect: &RECT, color: COLORREF, dpi: u32) {
+        let size = win32_dpi_scale(VECTOR_ICON_SIZE as i32, dpi);
+        let left = rect.left + (rect.right - rect.left - size) / 2;
+        let top = rect.top + (rect.bottom - rect.top - size) / 2;
+        let scale = size as f32 / VECTOR_ICON_SIZE;
+        let point = |x: f32, y: f32| POINT {
+            x: left + (x * scale).round() as i32,
+            y: top + (y * scale).round() as i32,
+        };
+
+        unsafe {
+            BeginPath(hdc);
+            for command in &self.commands {
+                match *command {
+                    PathCommand::MoveTo(x, y) => {
+                        let to = point(x, y);
+                        MoveToEx(hdc, to.x, to.y, None);
+                    }
+                    PathCommand::LineTo(x, y) => {
+                        let to = point(x, y);
+                        LineTo(hdc, to.x, to.y);
+                    }
+                    PathCommand::CubicTo([x1, y1, x2, y2, x, y]) => {
+                        PolyBezierTo(hdc, &[point(x1, y1), point(x2, y2), point(x, y)]);
+                    }
+                    PathCommand::Close => {
+                        CloseFigure(hdc);
+                    }
+                }
+            }
+            EndPath(hdc);
+
+            if self.filled {
+                let brush = CreateSolidBrush(color);
+                let old_brush = SelectObject(hdc, brush);
+                FillPath(hdc);
+                SelectObject(hdc, old_brush);
+                DeleteObject(brush);
+            } else {
+                let pen = CreatePen(PS_SOLID, win32_dpi_scale(1, dpi).max(1), color);
+                let old_pen = SelectObject(hdc, pen);
+                StrokePath(hdc);
+                SelectObject(hdc, old_pen);
+                DeleteObject(pen);
+            }
+        }
+    }
+}
+
+// Numbers and command letters of path data, separated by whitespace, commas or signs.
+// Numbers are written like in SVG, exponents included.
+fn path_tokens(path_data: &str) -> Result<Vec<PathToken>> {
+    let mut tokens = Vec::new();
+    let mut chars = path_data.char_indices().peekable();
+    while let Some(&(start, character)) = chars.peek() {
+        if character.is_whitespace() || character == ',' {
+            chars.next();
+        } else if character.is_ascii_alphabetic() {
+            tokens.push(PathToken::Command(character));
+            chars.next();
+        } else if character.is_ascii_digit() || matches!(character, '-' | '+' | '.') {
+            chars.next();
+            let mut end = start + character.len_utf8();
+            let mut seen_point = character == '.';
+            let mut seen_exponent = false;
+            let mut previous = character;
+            while let Some(&(index, next)) = chars.peek() {
+                // A sign only continues the number right after the e of its exponent
+                let continues = match next {
+                    '0'..='9' => true,
+                    '.' => !seen_point && !seen_exponent,
+                    'e' | 'E' => !seen_exponent,
+                    '-' | '+' => matches!(previous, 'e' | 'E'),
+                    _ => false,
+                };
+                if !continues {
+                    break;
+                }
+
+                seen_point |= next == '.';
+                seen_exponent |= matches!(next, 'e' | 'E');
+                previous = next;
+                end = index + next.len_utf8();
+                chars.next();
+            }
+
+            let number = &path_data[start..end];
+            tokens.push(PathToken::Number(
+                number
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid number {} in path data", number))?,
+            ));
+        } else {
+            return Err(anyhow!("Unexpected {} in path data", character));
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy)]
+enum PathToken {
+    Command(char),
+    Number(f32),
+}
+
+fn parse_path_data(path_data: &str) -> Result<Vec<PathCommand>> {
+    let tokens = path_tokens(path_data)?;
+    let mut commands = Vec::new();
+    let mut index = 0;
+    let mut command = None;
+    let (mut x, mut y) = (0.0, 0.0);
+    let (mut start_x, mut start_y) = (0.0, 0.0);
+
+    let numbers = |index: &mut usize, count: usize| -> Result<Vec<f32>> {
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            match tokens.get(*index) {
+                Some(PathToken::Number(value)) => values.push(*value),
+                _ => return Err(anyhow!("Missing numbers in path data")),
+            }
+            *index += 1;
+        }
+        Ok(values)
+    };
+
+    while index < tokens.len() {
+        // Numbers after a command repeat it, after a move they are lines
+        if let PathToken::Command(letter) = tokens[index] {
+            command = Some(letter);
+            index += 1;
+        } else if command.is_none() {
+            return Err(anyhow!("Numbers without a command in path data"));
+        }
+
+        let letter = command.unwrap_or('M');
+        let relative = letter.is_ascii_lowercase();
+        let (origin_x, origin_y) = if relative { (x, y) } else { (0.0, 0.0) };
+        match letter.to_ascii_uppercase() {
+            'M' => {
+                let values = numbers(&mut index, 2)?;
+                (x, y) = (origin_x + values[0], origin_y + values[1]);
+                (start_x, start_y) = (x, y);
+                commands.push(PathCommand::MoveTo(x, y));
+                command = Some(if relative { 'l' } else { 'L' });
+            }
+            'L' => {
+                let values = numbers(&mut index, 2)?;
+                (x, y) = (origin_x + values[0], origin_y + values[1]);
+                commands.push(PathCommand::LineTo(x, y));
+            }
+            'H' => {
+                x = origin_x + numbers(&mut index, 1)?[0];
+                commands.push(PathCommand::LineTo(x, y));
+            }
+            'V' => {
+                y = origin_y + numbers(&mut index, 1)?[0];
+                commands.push(PathCommand::LineTo(x, y));
+            }
+            'C' => {
+                let values = numbers(&mut index, 6)?;
+                let mut points = [0.0; 6];
+                for (offset, value) in values.iter().enumerate() {
+                    let origin = if offset % 2 == 0 { origin_x } else { origin_y };
+                    points[offset] = origin + value;
+                }
+                (x, y) = (points[4], points[5]);
+                commands.push(PathCommand::CubicTo(points));
+            }
+            'Z' => {
+                (x, y) = (start_x, start_y);
+                commands.push(PathCommand::Close);
+                command = None;
+            }
+            _ => return Err(anyhow!("Unsupported path command {}", letter)),
+        }
+    }
+
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use PathCommand::{Close, CubicTo, LineTo, MoveTo};
+
+    #[test]
+    fn absolute_commands() {
+        assert_eq!(
+            parse_path_data("M3 8 H13 M8 3 V13 L1 2 C1 2 3 4 5 6 Z").unwrap(),
+            [
+                MoveTo(3.0, 8.0),
+                LineTo(13.0, 8.0),
+                MoveTo(8.0, 3.0),
+                LineTo(8.0, 13.0),
+                LineTo(1.0, 2.0),
+                CubicTo([1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+                Close,
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_commands_follow_the_current_point() {
+        assert_eq!(
+            parse_path_data("m2 3 l4 0 h-1 v2 c1 0 1 1 0 1").unwrap(),
+            [
+                MoveTo(2.0, 3.0),
+                LineTo(6.0, 3.0),
+                LineTo(5.0, 3.0),
+                LineTo(5.0, 5.0),
+                CubicTo([6.0, 5.0, 6.0, 6.0, 5.0, 6.0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_after_a_move_are_lines() {
+        assert_eq!(
+            parse_path_data("M1 1 2 2 3 3").unwrap(),
+            [MoveTo(1.0, 1.0), LineTo(2.0, 2.0), LineTo(3.0, 3.0)]
+        );
+        assert_eq!(
+            parse_path_data("m1 1 2 2").unwrap(),
+            [MoveTo(1.0, 1.0), LineTo(3.0, 3.0)]
+        );
+        assert_eq!(
+            parse_path_data("M1 1 H2 3").unwrap(),
+            [MoveTo(1.0, 1.0), LineTo(2.0, 1.0), LineTo(3.0, 1.0)]
+        );
+    }
+
+    #[test]
+    fn relative_move_after_close_starts_at_the_subpath_start() {
+        assert_eq!(
+            parse_path_data("M2 2 L6 2 L6 6 Z m1 1 l2 0").unwrap(),
+            [
+                MoveTo(2.0, 2.0),
+                LineTo(6.0, 2.0),
+                LineTo(6.0, 6.0),
+                Close,
+                MoveTo(3.0, 3.0),
+                LineTo(5.0, 3.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_split_at_commas_signs_and_second_points() {
+        assert_eq!(
+            parse_path_data("M1,2L-3-4.5l.5.5").unwrap(),
+            [MoveTo(1.0, 2.0), LineTo(-3.0, -4.5), LineTo(-2.5, -4.0)]
+        );
+    }
+
+    #[test]
+    fn numbers_with_exponents() {
+        assert_eq!(
+            parse_path_data("M1e1 2E-1 l1e+0,0").unwrap(),
+            [MoveTo(10.0, 0.2), LineTo(11.0, 0.2)]
+        );
+    }
+
+    #[test]
+    fn malformed_path_data_is_an_error() {
+        for path_data in [
+            "1 2",
+            "M1",
+            "M1 2 L3",
+            "M1 2 Z 3 4",
+            "M1 2 A1 1 0 0 1 3 3",
+            "M1 2 #",
+            "M1e 2",
+            "M- 2",
+        ] {
+            assert!(parse_path_data(path_data).is_err(), "{path_data}");
+        }
+        assert!(parse_path_data("").unwrap().is_empty());
+    }
+}
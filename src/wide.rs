@@ -0,0 +1,53 @@
+use std::{
+    ffi::{OsStr, OsString},
+    os::windows::ffi::{OsStrExt, OsStringExt},
+};
+use windows::Win32::{
+    Foundation::HWND,
+    UI::WindowsAndMessaging::{GetWindowTextLengthW, GetWindowTextW},
+};
+
+// Conversions between Rust strings and the UTF-16 of the W functions. Constant
+// strings stay w! literals, these are for text only known at run time.
+
+// Null terminated, for PCWSTR parameters and texts kept for later like tooltips
+pub(crate) fn win32_to_wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain([0]).collect()
+}
+
+// Null terminated. Unlike a str, an OsStr holds any file name Windows accepts.
+pub(crate) fn win32_os_to_wide(text: &OsStr) -> Vec<u16> {
+    text.encode_wide().chain([0]).collect()
+}
+
+// Up to the first null if there is one, unpaired surrogates become U+FFFD
+pub(crate) fn win32_from_wide(text: &[u16]) -> String {
+    String::from_utf16_lossy(win32_until_null(text))
+}
+
+// Up to the first null if there is one, unpaired surrogates are kept
+pub(crate) fn win32_os_from_wide(text: &[u16]) -> OsString {
+    OsString::from_wide(win32_until_null(text))
+}
+
+fn win32_until_null(text: &[u16]) -> &[u16] {
+    let length = text
+        .iter()
+        .position(|&unit| unit == 0)
+        .unwrap_or(text.len());
+    &text[..length]
+}
+
+// The title of a window without terminator, empty if it has none
+pub(crate) fn win32_window_text(handle: HWND) -> Vec<u16> {
+    let text_length = unsafe { GetWindowTextLengthW(handle) };
+    // Room for the terminator GetWindowTextW always writes
+    let mut text = vec![0u16; text_length.max(0) as usize + 1];
+    let copied = unsafe { GetWindowTextW(handle, &mut text) };
+    text.truncate(copied.max(0) as usize);
+    text
+}
+
+pub(crate) fn win32_window_title(handle: HWND) -> String {
+    win32_from_wide(&win32_window_text(handle))
+}